@@ -0,0 +1,181 @@
+//! Organ actions — vetted things an organ's page can be asked to do, with
+//! a JSON answer: "list my unread chats", "mark this chat read".
+//!
+//! A service with actions (see [`SERVICES`]) has a script, run with the
+//! other page-load scripts (see `organs::inject`), that puts named
+//! functions on `window.__lexiconActions`, and an allowlist of the names
+//! that may be called, each with a check of its arguments. `organ_action`
+//! (and the control channel's `organ_action`) runs one: anything not on
+//! the list — other names, JavaScript — is refused before the page sees
+//! it, the checked arguments go in as JSON rather than code, and the
+//! function's value (or what its promise resolves to) comes back through
+//! `organs::ask`, matched to the call by a random token, within
+//! [`TIMEOUT`]. A function that throws or rejects fails the call with its
+//! message. Only the main window may call `organ_action`, so an organ's
+//! page can't reach its own actions.
+//!
+//! WhatsApp's are `listUnreadChats` and `markRead`; see `whatsapp`.
+
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde_json::Value;
+use tauri::Manager;
+
+/// How long an action gets to answer.
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// One function a page may be asked to run.
+#[derive(Debug)]
+pub struct Action {
+    /// Its name on `window.__lexiconActions`.
+    pub name: &'static str,
+    /// Checks the caller's arguments; Ok holds what the function gets.
+    pub args: fn(&Value) -> Result<Value, String>,
+}
+
+/// What one service's pages can do.
+pub struct Actions {
+    pub service: &'static str,
+    /// Registers the functions; safe to run twice.
+    pub script: &'static str,
+    pub allowed: &'static [Action],
+}
+
+const SERVICES: &[Actions] = &[crate::whatsapp::ACTIONS];
+
+fn registered(service: &str) -> Option<&'static Actions> {
+    SERVICES.iter().find(|actions| actions.service == service)
+}
+
+/// The allowed `action` for `service`, or why it isn't one.
+fn find(service: &str, action: &str) -> Result<&'static Action, String> {
+    let actions = registered(service).ok_or_else(|| format!("{service} has no actions"))?;
+    let found = actions.allowed.iter().find(|allowed| allowed.name == action);
+    found.ok_or_else(|| {
+        if action.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            format!("{service} has no action '{action}'")
+        } else {
+            "not an action name".into()
+        }
+    })
+}
+
+/// The call of `name` with `args`, answering through `reply` with
+/// `{ "ok": value }` or `{ "error": why }`.
+fn dispatch_script(name: &str, args: &Value, reply: &str) -> String {
+    format!(
+        r#"(function (name, args, reply) {{
+  function send(answer) {{ reply(JSON.stringify(answer)); }}
+  function failed(e) {{ send({{ error: String((e && e.message) || e) }}); }}
+  var run = window.__lexiconActions && window.__lexiconActions[name];
+  if (typeof run !== 'function') return send({{ error: 'not-ready' }});
+  try {{
+    Promise.resolve(run(args)).then(function (value) {{ send({{ ok: value === undefined ? null : value }}); }}, failed);
+  }} catch (e) {{
+    failed(e);
+  }}
+}})({name}, {args}, {reply});"#,
+        name = serde_json::to_string(name).unwrap_or_default(),
+        args = serde_json::to_string(args).unwrap_or_default(),
+    )
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Answer {
+    Ok(Value),
+    Error(String),
+}
+
+/// What the page answered `action` with.
+fn read(action: &str, answer: &str) -> Result<Value, String> {
+    match serde_json::from_str::<Answer>(answer) {
+        Ok(Answer::Ok(value)) => Ok(value),
+        Ok(Answer::Error(why)) if why == "not-ready" => Err("the page hasn't loaded its actions yet".into()),
+        Ok(Answer::Error(why)) => Err(format!("{action} failed: {why}")),
+        Err(e) => Err(format!("unreadable answer to {action}: {e}")),
+    }
+}
+
+/// Register `id`'s actions in its page, if its service has any.
+pub fn inject(app: &tauri::AppHandle, id: &str) {
+    let manager = app.state::<crate::organs::OrganManager>();
+    let Some(actions) = manager.get(id).and_then(|def| registered(def.service())) else { return };
+    if let Some(window) = app.get_webview_window(&crate::organs::window_label(id)) {
+        let _ = window.eval(actions.script);
+    }
+}
+
+/// Run `action` in organ `id`'s page. Blocks until it answers; call it
+/// off the main thread.
+pub fn run(app: &tauri::AppHandle, id: &str, action: &str, args: &Value) -> Result<Value, String> {
+    let service = app.state::<crate::organs::OrganManager>().get(id).map(|def| def.service().to_string());
+    let action = find(&service.ok_or_else(|| format!("unknown organ: {id}"))?, action)?;
+    let args = (action.args)(args)?;
+    if crate::idle::suspended(id) {
+        return Err(format!("the {id} organ is suspended — resume it first"));
+    }
+    if crate::organs::status(app, id) == "closed" {
+        return Err(format!("the {id} organ is closed"));
+    }
+    let answer = crate::organs::ask(app, id, |reply| dispatch_script(action.name, &args, reply), TIMEOUT)
+        .map_err(|_| format!("{id} did not answer {} within {}s", action.name, TIMEOUT.as_secs()))?;
+    read(action.name, &answer)
+}
+
+/// Run one of an organ's allowed actions (see the module docs) and return
+/// what it answered. `label` is an organ id or window label.
+#[tauri::command]
+pub async fn organ_action(
+    window: tauri::WebviewWindow,
+    app: tauri::AppHandle,
+    label: String,
+    action: String,
+    args: Option<Value>,
+) -> Result<Value, String> {
+    if window.label() != "main" {
+        return Err("only the main window can run organ actions".into());
+    }
+    let id = crate::organs::id_from_label(&label).unwrap_or(&label).to_string();
+    let args = args.unwrap_or(Value::Null);
+    tauri::async_runtime::spawn_blocking(move || run(&app, &id, &action, &args)).await.map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn only_listed_actions_run_and_arguments_stay_data() {
+        assert!(find("whatsapp", "listUnreadChats").is_ok());
+        assert_eq!(find("whatsapp", "deleteChat").unwrap_err(), "whatsapp has no action 'deleteChat'");
+        assert_eq!(find("whatsapp", "alert(document.cookie)").unwrap_err(), "not an action name");
+        assert!(find("telegram", "listUnreadChats").is_err());
+
+        let args = json!({ "chat": "'); alert(1); ('" });
+        let script = dispatch_script("markRead", &args, "done");
+        assert!(script.ends_with(&format!(r#"}})("markRead", {}, done);"#, serde_json::to_string(&args).unwrap())));
+    }
+
+    #[test]
+    fn answers_are_values_or_errors() {
+        assert_eq!(read("markRead", r#"{"ok":{"chat":"Ana"}}"#).unwrap(), json!({ "chat": "Ana" }));
+        assert_eq!(read("markRead", r#"{"ok":null}"#).unwrap(), Value::Null);
+        assert_eq!(read("markRead", r#"{"error":"no-chat"}"#).unwrap_err(), "markRead failed: no-chat");
+        assert!(read("markRead", r#"{"error":"not-ready"}"#).unwrap_err().contains("hasn't loaded"));
+        assert!(read("markRead", "sent").unwrap_err().starts_with("unreadable answer"));
+    }
+
+    #[test]
+    fn whatsapp_action_arguments_are_checked() {
+        let check = |name: &str, args: Value| (find("whatsapp", name).unwrap().args)(&args);
+        assert_eq!(check("listUnreadChats", Value::Null).unwrap(), json!({ "limit": 50 }));
+        assert_eq!(check("listUnreadChats", json!({ "limit": 500 })).unwrap(), json!({ "limit": 100 }));
+        assert!(check("listUnreadChats", json!({ "limit": "all" })).is_err());
+        assert_eq!(check("markRead", json!({ "chat": " Ana " })).unwrap(), json!({ "chat": "Ana" }));
+        assert!(check("markRead", json!({})).is_err());
+        assert!(check("markRead", json!({ "chat": "4915112345678@c.us" })).is_err());
+    }
+}
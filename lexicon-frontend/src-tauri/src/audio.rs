@@ -0,0 +1,329 @@
+//! Audio playback for speech and sounds pushed by the Brain.
+//!
+//! `play_audio` takes a local file or base64 bytes (the canvas forwards
+//! `SPEAK` messages from the Brain WebSocket) and queues them on one
+//! player thread, so speech plays even while every window is hidden.
+//! `audio://started` and `audio://finished` bracket each clip.
+//!
+//! Playback is refused during do-not-disturb, quiet hours and the
+//! presentation guard — speech can be exempted from the first two with
+//! `audio.speech_exempt`. While the microphone is recording, playback is
+//! ducked or paused according to `audio.while_listening`.
+//!
+//! The player is behind the `audio` cargo feature (on by default); without
+//! it the commands exist but report that playback isn't built in.
+
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[serde(rename_all = "snake_case")]
+pub enum ListeningPolicy {
+    /// Keep playing at `duck_volume`.
+    Duck,
+    /// Pause what's playing and turn new clips away.
+    Refuse,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+#[serde(default)]
+pub struct AudioConfig {
+    /// Output volume, 0.0–1.0; set with `set_output_volume`.
+    #[cfg_attr(test, proptest(strategy = "crate::testing::finite_f32()"))]
+    pub volume: f32,
+    /// Local "HH:MM-HH:MM" window with no playback, e.g. "22:00-07:00".
+    /// Empty disables it.
+    pub quiet_hours: String,
+    /// Let speech through during DND and quiet hours.
+    pub speech_exempt: bool,
+    pub while_listening: ListeningPolicy,
+    /// Fraction of `volume` used while ducked.
+    #[cfg_attr(test, proptest(strategy = "crate::testing::finite_f32()"))]
+    pub duck_volume: f32,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            quiet_hours: String::new(),
+            speech_exempt: false,
+            while_listening: ListeningPolicy::Duck,
+            duck_volume: 0.2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioSource {
+    Path(std::path::PathBuf),
+    /// Base64-encoded file contents (mp3, ogg/vorbis or wav).
+    Bytes(String),
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// `f32` bits of the configured output volume.
+static VOLUME: AtomicU32 = AtomicU32::new(0x3f80_0000); // 1.0
+
+/// Whether `now` falls inside a "HH:MM-HH:MM" window, which may wrap
+/// past midnight. Malformed windows never match.
+fn in_quiet_hours(window: &str, now: NaiveTime) -> bool {
+    let Some((start, end)) = window.split_once('-') else { return false };
+    let parse = |s: &str| NaiveTime::parse_from_str(s.trim(), "%H:%M").ok();
+    let (Some(start), Some(end)) = (parse(start), parse(end)) else { return false };
+    if start <= end {
+        start <= now && now < end
+    } else {
+        now >= start || now < end
+    }
+}
+
+/// Why a clip may not play right now, if it may not.
+fn refusal(app: &tauri::AppHandle, speech: bool) -> Option<&'static str> {
+    let config = crate::config::current(app).audio;
+    if crate::presentation::is_active() {
+        return Some("presentation guard is on");
+    }
+    if crate::voice::is_active() && config.while_listening == ListeningPolicy::Refuse {
+        return Some("voice capture is active");
+    }
+    let exempt = speech && config.speech_exempt;
+    if !exempt && crate::dnd::is_enabled() {
+        return Some("do-not-disturb is on");
+    }
+    if !exempt && in_quiet_hours(&config.quiet_hours, chrono::Local::now().time()) {
+        return Some("quiet hours");
+    }
+    None
+}
+
+pub fn init(app: &tauri::AppHandle) {
+    let level = crate::config::current(app).audio.volume.clamp(0.0, 1.0);
+    VOLUME.store(level.to_bits(), Ordering::Relaxed);
+}
+
+// ── Player ─────────────────────────────────────────────────────
+
+#[cfg(feature = "audio")]
+mod player {
+    use std::collections::VecDeque;
+    use std::io::Cursor;
+    use std::sync::atomic::Ordering;
+    use std::sync::mpsc;
+    use std::sync::{Mutex, OnceLock};
+    use std::time::Duration;
+
+    use serde::Serialize;
+    use tauri::Emitter;
+
+    use super::ListeningPolicy;
+
+    pub type Clip = rodio::Decoder<Cursor<Vec<u8>>>;
+
+    /// Clips waiting behind the one playing; more are turned away.
+    const MAX_QUEUED: usize = 8;
+
+    const TICK: Duration = Duration::from_millis(50);
+
+    /// Drop the output device after this long with nothing to play.
+    const IDLE_RELEASE: Duration = Duration::from_secs(5);
+
+    #[derive(Clone, Serialize)]
+    struct Started {
+        id: u64,
+    }
+
+    #[derive(Clone, Serialize)]
+    struct Finished {
+        id: u64,
+        /// "ended" | "stopped" | "error"
+        reason: &'static str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    }
+
+    enum Command {
+        Play(u64, Clip),
+        Stop,
+    }
+
+    static COMMANDS: OnceLock<Mutex<mpsc::Sender<Command>>> = OnceLock::new();
+    static QUEUED: Mutex<usize> = Mutex::new(0);
+
+    pub fn decode(bytes: Vec<u8>) -> Result<Clip, String> {
+        rodio::Decoder::new(Cursor::new(bytes)).map_err(|e| format!("unsupported audio: {e}"))
+    }
+
+    fn send(app: &tauri::AppHandle, command: Command) {
+        let commands = COMMANDS.get_or_init(|| {
+            let (tx, rx) = mpsc::channel();
+            let handle = app.clone();
+            std::thread::spawn(move || run(handle, rx));
+            Mutex::new(tx)
+        });
+        let _ = commands.lock().unwrap_or_else(|e| e.into_inner()).send(command);
+    }
+
+    pub fn enqueue(app: &tauri::AppHandle, id: u64, clip: Clip) -> Result<(), String> {
+        let mut queued = QUEUED.lock().unwrap_or_else(|e| e.into_inner());
+        if *queued > MAX_QUEUED {
+            return Err("audio queue is full".into());
+        }
+        *queued += 1;
+        drop(queued);
+        send(app, Command::Play(id, clip));
+        Ok(())
+    }
+
+    pub fn stop(app: &tauri::AppHandle) {
+        send(app, Command::Stop);
+    }
+
+    fn finished(app: &tauri::AppHandle, id: u64, reason: &'static str, error: Option<String>) {
+        *QUEUED.lock().unwrap_or_else(|e| e.into_inner()) -= 1;
+        let _ = app.emit("audio://finished", Finished { id, reason, error });
+    }
+
+    /// Player thread: owns the (non-`Send`) output device.
+    fn run(app: tauri::AppHandle, commands: mpsc::Receiver<Command>) {
+        let mut pending: VecDeque<(u64, Clip)> = VecDeque::new();
+        let mut output: Option<(rodio::MixerDeviceSink, rodio::Player)> = None;
+        let mut playing: Option<u64> = None;
+        let mut idle = Duration::ZERO;
+
+        loop {
+            match commands.recv_timeout(TICK) {
+                Ok(Command::Play(id, clip)) => pending.push_back((id, clip)),
+                Ok(Command::Stop) => {
+                    if let Some((_, player)) = &output {
+                        player.clear();
+                    }
+                    if let Some(id) = playing.take() {
+                        finished(&app, id, "stopped", None);
+                    }
+                    for (id, _) in pending.drain(..) {
+                        finished(&app, id, "stopped", None);
+                    }
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => return,
+            }
+
+            if let (Some((_, player)), Some(id)) = (&output, playing) {
+                if player.empty() {
+                    playing = None;
+                    finished(&app, id, "ended", None);
+                }
+            }
+
+            if playing.is_none() {
+                if let Some((id, clip)) = pending.pop_front() {
+                    if output.is_none() {
+                        match rodio::DeviceSinkBuilder::open_default_sink() {
+                            Ok(mut sink) => {
+                                sink.log_on_drop(false);
+                                let player = rodio::Player::connect_new(sink.mixer());
+                                output = Some((sink, player));
+                            }
+                            Err(e) => {
+                                tracing::warn!("audio output unavailable: {e}");
+                                finished(&app, id, "error", Some(e.to_string()));
+                                continue;
+                            }
+                        }
+                    }
+                    if let Some((_, player)) = &output {
+                        player.append(clip);
+                        player.play();
+                        playing = Some(id);
+                        let _ = app.emit("audio://started", Started { id });
+                    }
+                }
+            }
+
+            let Some((_, player)) = &output else { continue };
+            if playing.is_some() {
+                idle = Duration::ZERO;
+                let config = crate::config::current(&app).audio;
+                let listening = crate::voice::is_active();
+                let mut level = f32::from_bits(super::VOLUME.load(Ordering::Relaxed));
+                if listening && config.while_listening == ListeningPolicy::Duck {
+                    level *= config.duck_volume.clamp(0.0, 1.0);
+                }
+                player.set_volume(level);
+                if listening && config.while_listening == ListeningPolicy::Refuse {
+                    player.pause();
+                } else if player.is_paused() {
+                    player.play();
+                }
+            } else {
+                idle += TICK;
+                if idle >= IDLE_RELEASE {
+                    output = None;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "audio"))]
+mod player {
+    pub struct Clip;
+
+    pub fn decode(_bytes: Vec<u8>) -> Result<Clip, String> {
+        Err("audio playback is not built in (enable the `audio` feature)".into())
+    }
+
+    pub fn enqueue(_app: &tauri::AppHandle, _id: u64, _clip: Clip) -> Result<(), String> {
+        Err("audio playback is not built in (enable the `audio` feature)".into())
+    }
+
+    pub fn stop(_app: &tauri::AppHandle) {}
+}
+
+/// Stop the current clip and drop everything queued behind it.
+pub fn stop(app: &tauri::AppHandle) {
+    player::stop(app);
+}
+
+// ── Commands ───────────────────────────────────────────────────
+
+/// Queue a clip. Returns its id, as carried by `audio://started` and
+/// `audio://finished`. `speech` defaults to true.
+#[tauri::command]
+pub fn play_audio(app: tauri::AppHandle, source: AudioSource, speech: Option<bool>) -> Result<u64, String> {
+    if let Some(reason) = refusal(&app, speech.unwrap_or(true)) {
+        return Err(format!("not playing audio: {reason}"));
+    }
+    let bytes = match source {
+        AudioSource::Path(path) => std::fs::read(&path).map_err(|e| format!("read {}: {e}", path.display()))?,
+        AudioSource::Bytes(data) => {
+            use base64::Engine;
+            base64::engine::general_purpose::STANDARD.decode(data.trim()).map_err(|e| format!("bad audio data: {e}"))?
+        }
+    };
+    let clip = player::decode(bytes)?;
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    player::enqueue(&app, id, clip)?;
+    Ok(id)
+}
+
+#[tauri::command]
+pub fn stop_audio(app: tauri::AppHandle) {
+    stop(&app);
+}
+
+/// Set and persist the output volume (0.0–1.0).
+#[tauri::command]
+pub fn set_output_volume(app: tauri::AppHandle, volume: f32) -> Result<f32, String> {
+    let level = if volume.is_finite() { volume.clamp(0.0, 1.0) } else { 1.0 };
+    VOLUME.store(level.to_bits(), Ordering::Relaxed);
+    crate::config::update(&app, |config| config.audio.volume = level)?;
+    Ok(level)
+}
@@ -0,0 +1,174 @@
+//! Start on login.
+//!
+//! Linux gets an XDG autostart entry (`~/.config/autostart/lexicon.desktop`),
+//! Windows a `HKCU\…\Run` value. Both point at the current executable —
+//! re-resolved on every enable so AppImage / flatpak relocations are
+//! picked up — optionally with `--hidden`. State is always read back from
+//! the entry itself, so manual edits are reported accurately.
+
+use serde::Serialize;
+
+/// Flag passed by the autostart entry: boot straight into hidden mode.
+pub const HIDDEN_FLAG: &str = "--hidden";
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AutostartState {
+    pub enabled: bool,
+    pub start_hidden: bool,
+}
+
+/// Whether this process was launched with `--hidden`.
+pub fn launched_hidden() -> bool {
+    std::env::args().any(|a| a == HIDDEN_FLAG)
+}
+
+/// The command line a login launch should run.
+#[cfg_attr(not(any(target_os = "linux", windows)), allow(dead_code))]
+fn launch_command(start_hidden: bool) -> Result<String, String> {
+    let mut cmd = if let Ok(app_id) = std::env::var("FLATPAK_ID") {
+        format!("flatpak run {app_id}")
+    } else {
+        // Inside an AppImage current_exe() is the transient mount point;
+        // $APPIMAGE is the file that actually persists.
+        let exe = match std::env::var_os("APPIMAGE") {
+            Some(path) => std::path::PathBuf::from(path),
+            None => std::env::current_exe().map_err(|e| format!("cannot resolve executable: {e}"))?,
+        };
+        format!("\"{}\"", exe.display())
+    };
+    if start_hidden {
+        cmd.push(' ');
+        cmd.push_str(HIDDEN_FLAG);
+    }
+    Ok(cmd)
+}
+
+// ── Linux: XDG autostart ───────────────────────────────────────
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::AutostartState;
+    use std::path::PathBuf;
+
+    fn entry_path() -> Result<PathBuf, String> {
+        let base = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+            .ok_or("cannot locate the XDG config dir")?;
+        Ok(base.join("autostart").join("lexicon.desktop"))
+    }
+
+    pub fn enable(command: &str) -> Result<(), String> {
+        let path = entry_path()?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("create {}: {e}", dir.display()))?;
+        }
+        let entry = format!(
+            "[Desktop Entry]\nType=Application\nName=Lexicon\nExec={command}\nX-GNOME-Autostart-enabled=true\nNoDisplay=true\n"
+        );
+        std::fs::write(&path, entry).map_err(|e| format!("write {}: {e}", path.display()))
+    }
+
+    pub fn disable() -> Result<(), String> {
+        let path = entry_path()?;
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(format!("remove {}: {e}", path.display())),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn read() -> Result<AutostartState, String> {
+        let Ok(text) = std::fs::read_to_string(entry_path()?) else {
+            return Ok(AutostartState::default());
+        };
+        let value = |key: &str| {
+            text.lines()
+                .find_map(|l| l.strip_prefix(key).and_then(|r| r.strip_prefix('=')))
+                .map(str::trim)
+        };
+        let disabled = value("Hidden") == Some("true") || value("X-GNOME-Autostart-enabled") == Some("false");
+        let exec = value("Exec").unwrap_or_default();
+        Ok(AutostartState {
+            enabled: !exec.is_empty() && !disabled,
+            start_hidden: exec.split_whitespace().any(|a| a == super::HIDDEN_FLAG),
+        })
+    }
+}
+
+// ── Windows: HKCU Run key ──────────────────────────────────────
+
+#[cfg(windows)]
+mod platform {
+    use super::AutostartState;
+    use std::process::Command;
+
+    const RUN_KEY: &str = r"HKCU\Software\Microsoft\Windows\CurrentVersion\Run";
+    const VALUE: &str = "Lexicon";
+
+    fn reg(args: &[&str]) -> Result<String, String> {
+        let out = Command::new("reg").args(args).output().map_err(|e| format!("reg: {e}"))?;
+        if out.status.success() {
+            Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+        } else {
+            Err(String::from_utf8_lossy(&out.stderr).trim().to_string())
+        }
+    }
+
+    pub fn enable(command: &str) -> Result<(), String> {
+        reg(&["add", RUN_KEY, "/v", VALUE, "/t", "REG_SZ", "/d", command, "/f"]).map(|_| ())
+    }
+
+    pub fn disable() -> Result<(), String> {
+        match read()?.enabled {
+            true => reg(&["delete", RUN_KEY, "/v", VALUE, "/f"]).map(|_| ()),
+            false => Ok(()),
+        }
+    }
+
+    pub fn read() -> Result<AutostartState, String> {
+        let Ok(out) = reg(&["query", RUN_KEY, "/v", VALUE]) else {
+            return Ok(AutostartState::default());
+        };
+        let command = out
+            .lines()
+            .find_map(|l| l.split_once("REG_SZ").map(|(_, v)| v.trim().to_string()))
+            .unwrap_or_default();
+        Ok(AutostartState {
+            enabled: !command.is_empty(),
+            start_hidden: command.split_whitespace().any(|a| a == super::HIDDEN_FLAG),
+        })
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+mod platform {
+    use super::AutostartState;
+
+    pub fn enable(_command: &str) -> Result<(), String> {
+        Err("autostart is not supported on this platform".into())
+    }
+
+    pub fn disable() -> Result<(), String> {
+        Err("autostart is not supported on this platform".into())
+    }
+
+    pub fn read() -> Result<AutostartState, String> {
+        Ok(AutostartState::default())
+    }
+}
+
+#[tauri::command]
+pub fn set_autostart(enabled: bool, start_hidden: bool) -> Result<AutostartState, String> {
+    if enabled {
+        platform::enable(&launch_command(start_hidden)?)?;
+    } else {
+        platform::disable()?;
+    }
+    tracing::info!("autostart {}", if enabled { "enabled" } else { "disabled" });
+    platform::read()
+}
+
+#[tauri::command]
+pub fn get_autostart() -> Result<AutostartState, String> {
+    platform::read()
+}
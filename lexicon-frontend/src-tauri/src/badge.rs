@@ -0,0 +1,195 @@
+//! Unread badge on the tray icon.
+//!
+//! Organs report unread counts (`organ://badge`); the aggregate is
+//! rendered as a count bubble composited over the base icon. The icon is
+//! only regenerated when what it would show actually changes.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use tauri::image::Image;
+use tauri::{Listener, Manager};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[serde(rename_all = "lowercase")]
+pub enum BadgePolicy {
+    #[default]
+    Count,
+    Dot,
+    Off,
+}
+
+/// Payload of the `organ://badge` event.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrganBadge {
+    pub id: String,
+    pub count: u32,
+}
+
+/// What the icon currently shows, so unchanged counts skip re-rendering.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Rendered {
+    Plain,
+    Dot,
+    Count(String),
+}
+
+#[derive(Default)]
+pub struct BadgeState {
+    counts: Mutex<HashMap<String, u32>>,
+    rendered: Mutex<Option<Rendered>>,
+}
+
+impl BadgeState {
+    pub fn total(&self) -> u32 {
+        self.counts.lock().unwrap_or_else(|e| e.into_inner()).values().sum()
+    }
+}
+
+/// "1" … "99", then "99+".
+fn count_label(count: u32) -> String {
+    if count > 99 {
+        "99+".into()
+    } else {
+        count.to_string()
+    }
+}
+
+pub fn init(app: &tauri::AppHandle) {
+    app.manage(BadgeState::default());
+    let handle = app.clone();
+    app.listen("organ://badge", move |event| {
+        let Ok(badge) = serde_json::from_str::<OrganBadge>(event.payload()) else {
+            return;
+        };
+        let state = handle.state::<BadgeState>();
+        state
+            .counts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(badge.id, badge.count);
+        refresh(&handle);
+    });
+}
+
+/// Re-render the tray icon if the badge it should show has changed.
+pub fn refresh(app: &tauri::AppHandle) {
+    let Some(state) = app.try_state::<BadgeState>() else {
+        return;
+    };
+    let Some(base) = app.default_window_icon() else {
+        return;
+    };
+    let total = state.total();
+    let policy = crate::config::current(app).tray.badge;
+
+    let wanted = if total == 0 || crate::presentation::is_active() {
+        Rendered::Plain
+    } else {
+        match policy {
+            BadgePolicy::Off => Rendered::Plain,
+            BadgePolicy::Dot => Rendered::Dot,
+            BadgePolicy::Count => Rendered::Count(count_label(total)),
+        }
+    };
+
+    let mut rendered = state.rendered.lock().unwrap_or_else(|e| e.into_inner());
+    if rendered.as_ref() == Some(&wanted) {
+        return;
+    }
+
+    let result = match &wanted {
+        Rendered::Plain => crate::tray::set_icon(app, base.clone().to_owned()),
+        Rendered::Dot => crate::tray::set_icon(app, composite(base, None)),
+        Rendered::Count(label) => crate::tray::set_icon(app, composite(base, Some(label))).or_else(|e| {
+            // Some tray hosts choke on frequently changing icons; a dot
+            // still tells the user something is waiting.
+            tracing::warn!("tray count badge failed ({e}) — falling back to dot");
+            crate::tray::set_icon(app, composite(base, None))
+        }),
+    };
+    if result.is_ok() {
+        *rendered = Some(wanted);
+    }
+}
+
+#[tauri::command]
+pub fn set_tray_badge_policy(app: tauri::AppHandle, policy: BadgePolicy) -> Result<(), String> {
+    crate::config::update(&app, |c| c.tray.badge = policy)?;
+    refresh(&app);
+    Ok(())
+}
+
+// ── Rendering ──────────────────────────────────────────────────
+
+/// 3×5 bitmap glyphs for the badge text, one row per byte (low 3 bits).
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        _ => [0; 5],
+    }
+}
+
+/// Draw a red bubble in the top-right corner of `base`, with `label`
+/// in it when given (a plain dot otherwise).
+fn composite(base: &Image<'_>, label: Option<&str>) -> Image<'static> {
+    let (w, h) = (base.width(), base.height());
+    let mut img = RgbaImage::from_raw(w, h, base.rgba().to_vec()).unwrap_or_else(|| RgbaImage::new(w, h));
+
+    let size = w.min(h);
+    let diameter = if label.is_some() { size * 11 / 20 } else { size * 2 / 5 };
+    let radius = diameter as f32 / 2.0;
+    let (cx, cy) = (w as f32 - radius, radius);
+    let red = Rgba([0xe5, 0x39, 0x35, 0xff]);
+    let white = Rgba([0xff, 0xff, 0xff, 0xff]);
+
+    for y in 0..diameter.min(h) {
+        for x in w.saturating_sub(diameter)..w {
+            let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+            if dx * dx + dy * dy <= radius * radius {
+                img.put_pixel(x, y, red);
+            }
+        }
+    }
+
+    if let Some(label) = label {
+        let chars: Vec<char> = label.chars().collect();
+        let cols = chars.len() as u32 * 4 - 1;
+        let scale = (diameter / 2 / 5).min(diameter * 4 / 5 / cols).max(1);
+        let text_w = cols * scale;
+        let text_h = 5 * scale;
+        let left = (cx - text_w as f32 / 2.0).max(0.0) as u32;
+        let top = (cy - text_h as f32 / 2.0).max(0.0) as u32;
+        for (i, c) in chars.iter().enumerate() {
+            for (row, bits) in glyph(*c).iter().enumerate() {
+                for col in 0..3u32 {
+                    if bits & (0b100 >> col) == 0 {
+                        continue;
+                    }
+                    let x0 = left + (i as u32 * 4 + col) * scale;
+                    let y0 = top + row as u32 * scale;
+                    for y in y0..(y0 + scale).min(h) {
+                        for x in x0..(x0 + scale).min(w) {
+                            img.put_pixel(x, y, white);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Image::new_owned(img.into_raw(), w, h)
+}
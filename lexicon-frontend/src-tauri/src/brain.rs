@@ -0,0 +1,476 @@
+//! HTTP access to the Brain (the FastAPI backend).
+//!
+//! The base URL is, in order: `LEXICON_BRAIN_URL` at startup, `brain.url`
+//! in the config, or the address `dev.sh` starts the Brain on. The
+//! settings panel changes it with `set_brain_url`, which saves it to the
+//! config and takes over from the environment for the rest of the run.
+//! An edit of `brain.url` in the file is picked up as it runs (see
+//! `config`), but doesn't take over from the environment.
+//!
+//! A monitor polls `/health` every `brain.health_interval_secs` (and on
+//! every network change) and keeps an online/offline verdict. Flips are
+//! emitted to the main window as `brain://online` / `brain://offline`
+//! with the [`BrainStatus`], and `brain_status` returns it on demand.
+//! Every answered probe is also emitted as `brain://latency`, with how
+//! long `/health` took in `latency_ms`.
+//! While the Brain is offline the relay holds its queue instead of
+//! spending attempts on POSTs that cannot land.
+//!
+//! The Brain can also drive the app, over the WebSocket `control` keeps
+//! open; its state is part of the [`BrainStatus`] too, and its changes
+//! are emitted as `brain://control`.
+
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager};
+
+use crate::host::Host;
+
+pub const DEFAULT_URL: &str = "http://127.0.0.1:8000";
+/// Overrides `brain.url` for one run without touching the config file.
+pub const URL_ENV: &str = "LEXICON_BRAIN_URL";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+#[serde(default)]
+pub struct BrainConfig {
+    pub url: String,
+    /// From [`URL_ENV`] at startup; never saved.
+    #[serde(skip)]
+    #[cfg_attr(test, proptest(value = "None"))]
+    pub env_url: Option<String>,
+    /// How often the monitor polls `/health`; 0 leaves it to network changes.
+    #[cfg_attr(test, proptest(strategy = "crate::testing::toml_u64()"))]
+    pub health_interval_secs: u64,
+    /// Keep the control channel open; see `control`. Off, the Brain can
+    /// only receive.
+    pub control: bool,
+}
+
+impl Default for BrainConfig {
+    fn default() -> Self {
+        Self { url: DEFAULT_URL.into(), env_url: None, health_interval_secs: 10, control: true }
+    }
+}
+
+impl BrainConfig {
+    /// The URL in effect.
+    pub fn effective_url(&self) -> &str {
+        self.env_url.as_deref().unwrap_or(&self.url)
+    }
+
+    /// Settle the URL at startup from the file and the environment.
+    /// Invalid values are logged and passed over.
+    pub fn resolve(&mut self, env: Option<&str>) {
+        match parse_url(&self.url) {
+            Ok(url) => self.url = url,
+            Err(e) => {
+                tracing::warn!("brain.url ignored: {e}");
+                self.url = DEFAULT_URL.into();
+            }
+        }
+        self.env_url = env.and_then(|raw| match parse_url(raw) {
+            Ok(url) => Some(url),
+            Err(e) => {
+                tracing::warn!("{URL_ENV} ignored: {e}");
+                None
+            }
+        });
+    }
+}
+
+/// An http(s) base URL with a host, without the trailing slash.
+pub fn parse_url(raw: &str) -> Result<String, String> {
+    let url = tauri::Url::parse(raw.trim()).map_err(|e| format!("not a URL: '{raw}' ({e})"))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("the Brain URL must be http or https, not {}", url.scheme()));
+    }
+    if url.host_str().is_none_or(str::is_empty) {
+        return Err(format!("'{raw}' has no host"));
+    }
+    if url.query().is_some() || url.fragment().is_some() {
+        return Err("the Brain URL can't have a query or fragment".into());
+    }
+    Ok(url.as_str().trim_end_matches('/').to_string())
+}
+
+/// `{BRAIN_URL}{path}`.
+pub fn endpoint(app: &impl Host, path: &str) -> String {
+    format!("{}{path}", app.config().brain.effective_url().trim_end_matches('/'))
+}
+
+/// Whether the Brain answers `/health`.
+pub fn is_reachable(app: &impl Host) -> bool {
+    ping(app).is_some()
+}
+
+/// How long the Brain took to answer `/health`; `None` if it didn't.
+pub fn ping(app: &impl Host) -> Option<Duration> {
+    let start = std::time::Instant::now();
+    call(app, Request::get("/health").timeout(Duration::from_secs(3))).ok().map(|_| start.elapsed())
+}
+
+/// Send `request` through the app's transport.
+pub fn call(app: &impl Host, request: Request) -> Result<String, TransportError> {
+    let url = endpoint(app, &request.path);
+    app.transport().send(&url, request)
+}
+
+// ── Transport ──────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Get,
+    Post,
+}
+
+pub enum Body {
+    Empty,
+    Bytes(Vec<u8>),
+    /// Sent as it is read, chunked.
+    Stream(Box<dyn std::io::Read + Send>),
+}
+
+/// One call to the Brain; `path` is relative to `brain.url`.
+pub struct Request {
+    pub method: Method,
+    pub path: String,
+    pub content_type: Option<String>,
+    /// Extra headers for a POST, sent after `Content-Type`.
+    pub headers: Vec<(String, String)>,
+    pub body: Body,
+    pub timeout: Duration,
+}
+
+impl Request {
+    pub fn get(path: &str) -> Self {
+        Self { method: Method::Get, path: path.into(), content_type: None, headers: Vec::new(), body: Body::Empty, timeout: Duration::from_secs(60) }
+    }
+
+    pub fn post(path: &str, content_type: &str, body: Body) -> Self {
+        Self { method: Method::Post, content_type: Some(content_type.into()), body, ..Self::get(path) }
+    }
+
+    pub fn timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+
+    pub fn header(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransportError {
+    /// The Brain isn't there (refused, unresolvable, timed out) rather
+    /// than saying no — worth retrying later.
+    Offline(String),
+    /// It answered with an error status.
+    Status(u16),
+    Failed(String),
+}
+
+impl std::fmt::Display for TransportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransportError::Offline(e) => write!(f, "Brain unreachable: {e}"),
+            TransportError::Status(code) => write!(f, "Brain answered {code}"),
+            TransportError::Failed(e) => f.write_str(e),
+        }
+    }
+}
+
+/// How Brain calls go over the wire. Everything that talks to the Brain
+/// goes through the one in [`BrainClient`], so an embedder (or a test)
+/// can swap in its own.
+pub trait BrainTransport: Send + Sync {
+    /// Send `request` to `url` and return the response body.
+    fn send(&self, url: &str, request: Request) -> Result<String, TransportError>;
+}
+
+/// The default transport: one shared ureq agent.
+pub struct UreqTransport(ureq::Agent);
+
+impl Default for UreqTransport {
+    fn default() -> Self {
+        Self(ureq::Agent::new_with_defaults())
+    }
+}
+
+impl UreqTransport {
+    /// An agent that goes through `proxy` instead of the environment's.
+    pub fn with_proxy(proxy: ureq::Proxy) -> Self {
+        Self(ureq::Agent::config_builder().proxy(Some(proxy)).build().into())
+    }
+}
+
+impl BrainTransport for UreqTransport {
+    fn send(&self, url: &str, request: Request) -> Result<String, TransportError> {
+        let sent = match request.method {
+            Method::Get => self.0.get(url).config().timeout_global(Some(request.timeout)).build().call(),
+            Method::Post => {
+                let mut builder = self.0.post(url).config().timeout_global(Some(request.timeout)).build();
+                if let Some(content_type) = &request.content_type {
+                    builder = builder.header("Content-Type", content_type);
+                }
+                for (name, value) in &request.headers {
+                    builder = builder.header(name, value);
+                }
+                match request.body {
+                    Body::Empty => builder.send_empty(),
+                    Body::Bytes(bytes) => builder.send(&bytes[..]),
+                    Body::Stream(reader) => builder.send(ureq::SendBody::from_owned_reader(reader)),
+                }
+            }
+        };
+        let mut response = sent.map_err(|e| match e {
+            ureq::Error::StatusCode(code) => TransportError::Status(code),
+            ureq::Error::Io(_) | ureq::Error::ConnectionFailed | ureq::Error::HostNotFound | ureq::Error::Timeout(_) => {
+                TransportError::Offline(e.to_string())
+            }
+            e => TransportError::Failed(e.to_string()),
+        })?;
+        response.body_mut().read_to_string().map_err(|e| TransportError::Failed(e.to_string()))
+    }
+}
+
+/// Managed state: the transport every Brain call uses. Replacing it
+/// leaves calls already running on the old one.
+pub struct BrainClient(RwLock<Arc<dyn BrainTransport>>);
+
+impl BrainClient {
+    pub fn new(transport: Arc<dyn BrainTransport>) -> Self {
+        Self(RwLock::new(transport))
+    }
+
+    pub fn transport(&self) -> Arc<dyn BrainTransport> {
+        self.0.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn replace(&self, transport: Arc<dyn BrainTransport>) {
+        *self.0.write().unwrap_or_else(|e| e.into_inner()) = transport;
+    }
+}
+
+impl Default for BrainClient {
+    fn default() -> Self {
+        Self::new(Arc::new(UreqTransport::default()))
+    }
+}
+
+// ── Health monitor ─────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BrainState {
+    Online,
+    Offline,
+    /// No probe has finished yet.
+    Unknown,
+}
+
+/// The control channel; see `control`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ControlState {
+    /// Turned off in the config, or not on this platform.
+    Disabled,
+    Connecting,
+    Connected,
+    /// Lost or refused; retrying.
+    Disconnected,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct BrainStatus {
+    pub state: BrainState,
+    /// Unix milliseconds of the last answered probe.
+    pub last_contact_ms: Option<u64>,
+    /// How long the last answered probe took.
+    pub latency_ms: Option<u64>,
+    pub control: ControlState,
+}
+
+impl BrainStatus {
+    /// Record a probe, answered in `latency_ms` or not at all; the new
+    /// state if it changed.
+    fn observe(&mut self, latency_ms: Option<u64>, now_ms: u64) -> Option<BrainState> {
+        let state = if latency_ms.is_some() { BrainState::Online } else { BrainState::Offline };
+        if latency_ms.is_some() {
+            self.last_contact_ms = Some(now_ms);
+            self.latency_ms = latency_ms;
+        }
+        (std::mem::replace(&mut self.state, state) != state).then_some(state)
+    }
+}
+
+static STATUS: Mutex<BrainStatus> =
+    Mutex::new(BrainStatus {
+        state: BrainState::Unknown,
+        last_contact_ms: None,
+        latency_ms: None,
+        control: ControlState::Disabled,
+    });
+
+pub fn status() -> BrainStatus {
+    *STATUS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Take in a probe result (see [`ping`]) from the monitor or `network::probe`.
+pub fn observe(app: &tauri::AppHandle, latency: Option<Duration>) {
+    let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+    let latency_ms = latency.map(|l| l.as_millis() as u64);
+    let (changed, status) = {
+        let mut status = STATUS.lock().unwrap_or_else(|e| e.into_inner());
+        (status.observe(latency_ms, now_ms), *status)
+    };
+    if let Some(latency_ms) = latency_ms {
+        let _ = app.emit_to("main", "brain://latency", serde_json::json!({ "latency_ms": latency_ms }));
+    }
+    let Some(state) = changed else { return };
+    if let Some(delivery) = app.try_state::<crate::relay::Delivery>() {
+        delivery.hold(state == BrainState::Offline);
+    }
+    let event = if state == BrainState::Online { "brain://online" } else { "brain://offline" };
+    tracing::info!("Brain {}", if state == BrainState::Online { "online" } else { "offline" });
+    let _ = app.emit_to("main", event, status);
+    crate::tray::refresh_tooltip(app);
+}
+
+/// Probe the Brain now and open a new control channel, instead of waiting
+/// for the next poll or retry.
+pub fn reconnect(app: &tauri::AppHandle) {
+    tracing::info!("reconnecting to the Brain");
+    #[cfg(desktop)]
+    crate::control::reconnect();
+    let app = app.clone();
+    std::thread::spawn(move || observe(&app, ping(&app)));
+}
+
+/// Record the control channel's state, announcing changes.
+#[cfg_attr(mobile, allow(dead_code))]
+pub fn set_control(app: &tauri::AppHandle, control: ControlState) {
+    let status = {
+        let mut status = STATUS.lock().unwrap_or_else(|e| e.into_inner());
+        if std::mem::replace(&mut status.control, control) == control {
+            return;
+        }
+        *status
+    };
+    let _ = app.emit_to("main", "brain://control", status);
+    crate::tray::refresh_tooltip(app);
+}
+
+/// Poll `/health` on the configured interval for the life of the app.
+pub fn monitor(app: &tauri::AppHandle) {
+    let app = app.clone();
+    std::thread::spawn(move || loop {
+        let interval = crate::config::current(&app).brain.health_interval_secs;
+        if interval > 0 && !crate::power::is_suspended() {
+            observe(&app, ping(&app));
+        }
+        std::thread::sleep(Duration::from_secs(interval.max(1)));
+    });
+}
+
+// ── Commands ───────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BrainUrl {
+    pub url: String,
+    /// Set by `LEXICON_BRAIN_URL` for this run.
+    pub from_env: bool,
+}
+
+impl From<&BrainConfig> for BrainUrl {
+    fn from(config: &BrainConfig) -> Self {
+        Self { url: config.effective_url().to_string(), from_env: config.env_url.is_some() }
+    }
+}
+
+#[tauri::command]
+pub fn brain_status() -> BrainStatus {
+    status()
+}
+
+#[tauri::command]
+pub fn get_brain_url(app: tauri::AppHandle) -> BrainUrl {
+    BrainUrl::from(&crate::config::current(&app).brain)
+}
+
+/// Point the app at another Brain and remember it.
+#[tauri::command]
+pub fn set_brain_url(window: tauri::WebviewWindow, app: tauri::AppHandle, url: String) -> Result<BrainUrl, String> {
+    if window.label() != "main" {
+        return Err("only the main window can change the Brain URL".into());
+    }
+    let url = parse_url(&url)?;
+    crate::config::update(&app, |config| {
+        config.brain.url = url.clone();
+        config.brain.env_url = None;
+    })?;
+    tracing::info!("Brain URL set to {url}");
+    Ok(get_brain_url(app))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{MockBrain, Reply, TestHost};
+
+    #[test]
+    fn urls_are_checked_and_normalized() {
+        assert_eq!(parse_url(" http://192.168.1.20:9000/ ").unwrap(), "http://192.168.1.20:9000");
+        assert_eq!(parse_url("https://brain.lan/api/").unwrap(), "https://brain.lan/api");
+        assert!(parse_url("127.0.0.1:8000").is_err());
+        assert!(parse_url("ftp://brain.lan").is_err());
+        assert!(parse_url("http://brain.lan/?x=1").is_err());
+        assert!(parse_url("not a url").is_err());
+    }
+
+    #[test]
+    fn the_environment_wins_over_the_file_and_bad_values_fall_back() {
+        let mut config = BrainConfig { url: "http://brain.lan:8000/".into(), env_url: None, ..BrainConfig::default() };
+        config.resolve(Some("http://10.0.0.5:8000"));
+        assert_eq!(config.effective_url(), "http://10.0.0.5:8000");
+        assert_eq!(config.url, "http://brain.lan:8000");
+
+        config.resolve(Some("nonsense"));
+        assert_eq!(config.effective_url(), "http://brain.lan:8000");
+
+        let mut config = BrainConfig { url: "brain".into(), env_url: None, ..BrainConfig::default() };
+        config.resolve(None);
+        assert_eq!(config.effective_url(), DEFAULT_URL);
+    }
+
+    #[test]
+    fn only_flips_are_reported_and_contact_is_kept() {
+        let unknown =
+            BrainStatus { state: BrainState::Unknown, last_contact_ms: None, latency_ms: None, control: ControlState::Disabled };
+        let mut status = unknown;
+        assert_eq!(status.observe(Some(12), 1), Some(BrainState::Online));
+        assert_eq!(status.observe(Some(30), 2), None);
+        assert_eq!(status.observe(None, 3), Some(BrainState::Offline));
+        assert_eq!(status.observe(None, 4), None);
+        assert_eq!((status.last_contact_ms, status.latency_ms), (Some(2), Some(30)));
+
+        let mut status = unknown;
+        assert_eq!(status.observe(None, 1), Some(BrainState::Offline));
+    }
+
+    #[test]
+    fn offline_and_refusals_are_told_apart() {
+        let brain = MockBrain::start();
+        brain.always("/ingest/file", Reply::status(500));
+        let host = TestHost::new().with_brain(&brain);
+        assert!(call(&host, Request::get("/health")).unwrap().contains("ok"));
+        assert_eq!(call(&host, Request::get("/ingest/file")), Err(TransportError::Status(500)));
+        brain.stop();
+        drop(brain);
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(matches!(call(&host, Request::get("/health")), Err(TransportError::Offline(_))));
+    }
+}
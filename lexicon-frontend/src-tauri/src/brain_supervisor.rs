@@ -0,0 +1,316 @@
+//! Brain supervisor — the Brain run as a child of the app.
+//!
+//! With `brain_process.command` set (the program and its arguments, run in
+//! `brain_process.dir` with `brain_process.env` added), the app starts the
+//! Brain itself at launch (unless `start_with_app` is off) and stops it on
+//! exit, once the relay has said its goodbyes. Its stdout and stderr go to
+//! the log a line at a time, under the `brain` target, and `brain_logs`
+//! returns the last of them. A Brain that exits while it is wanted is
+//! started again (unless `restart` is off): after a second, then twice as
+//! long each time it didn't keep running for a minute, up to a minute.
+//!
+//! `brain_start`, `brain_stop` and `brain_restart` drive it by hand, and
+//! return its [`SupervisorStatus`]. With no command, the Brain is left to
+//! be started some other way (`dev.sh`, a service), as before.
+//!
+//! ```toml
+//! [brain_process]
+//! command = ["python", "-m", "brain"]
+//! dir = "/home/me/lexicon/brain"
+//! ```
+
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Child, Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// How often the child is looked at.
+const POLL: Duration = Duration::from_secs(1);
+const FIRST_RETRY: Duration = Duration::from_secs(1);
+const MAX_RETRY: Duration = Duration::from_secs(60);
+/// Running this long counts as having started fine.
+const STABLE: Duration = Duration::from_secs(60);
+/// Lines `brain_logs` can go back.
+const LOG_LINES: usize = 1000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+#[serde(default)]
+pub struct SupervisorConfig {
+    /// The program and its arguments; empty leaves the Brain alone.
+    pub command: Vec<String>,
+    /// Working directory; empty is the app's.
+    pub dir: String,
+    /// Added to the app's environment.
+    pub env: BTreeMap<String, String>,
+    pub start_with_app: bool,
+    /// Start it again when it exits.
+    pub restart: bool,
+}
+
+impl Default for SupervisorConfig {
+    fn default() -> Self {
+        Self { command: Vec::new(), dir: String::new(), env: BTreeMap::new(), start_with_app: true, restart: true }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct SupervisorStatus {
+    /// A command is set.
+    pub configured: bool,
+    /// It should be running: started, and not stopped since.
+    pub wanted: bool,
+    pub pid: Option<u32>,
+    /// Times it was started again after exiting.
+    pub restarts: u64,
+}
+
+struct Supervisor {
+    child: Option<Child>,
+    wanted: bool,
+    started: Option<Instant>,
+    /// Exits in a row that came too soon.
+    failures: u32,
+    next_try: Option<Instant>,
+    restarts: u64,
+}
+
+static SUPERVISOR: Mutex<Supervisor> = Mutex::new(Supervisor {
+    child: None,
+    wanted: false,
+    started: None,
+    failures: 0,
+    next_try: None,
+    restarts: 0,
+});
+static LOGS: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+fn keep(line: String) {
+    let mut logs = LOGS.lock().unwrap_or_else(|e| e.into_inner());
+    if logs.len() == LOG_LINES {
+        logs.pop_front();
+    }
+    logs.push_back(line);
+}
+
+/// Log what the Brain writes to `stream`, until it closes.
+fn capture(stream: impl Read + Send + 'static) {
+    std::thread::spawn(move || {
+        for line in BufReader::new(stream).lines().map_while(Result::ok) {
+            tracing::info!(target: "brain", "{line}");
+            keep(line);
+        }
+    });
+}
+
+fn spawn(config: &SupervisorConfig) -> Result<Child, String> {
+    let (program, args) = config.command.split_first().ok_or("no brain_process.command is set")?;
+    let mut command = Command::new(program);
+    command.args(args).envs(&config.env).stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+    if !config.dir.is_empty() {
+        command.current_dir(&config.dir);
+    }
+    let mut child = command.spawn().map_err(|e| format!("could not start the brain ({program}): {e}"))?;
+    if let Some(stdout) = child.stdout.take() {
+        capture(stdout);
+    }
+    if let Some(stderr) = child.stderr.take() {
+        capture(stderr);
+    }
+    tracing::info!("brain started (pid {})", child.id());
+    Ok(child)
+}
+
+/// The wait before starting again after `failures` early exits in a row.
+fn retry_after(failures: u32) -> Duration {
+    FIRST_RETRY.saturating_mul(1 << failures.min(6)).min(MAX_RETRY)
+}
+
+impl Supervisor {
+    fn launch(&mut self, config: &SupervisorConfig, now: Instant) -> Result<(), String> {
+        match spawn(config) {
+            Ok(child) => {
+                self.child = Some(child);
+                self.started = Some(now);
+                self.next_try = None;
+                Ok(())
+            }
+            Err(e) => {
+                self.failures += 1;
+                self.next_try = Some(now + retry_after(self.failures));
+                Err(e)
+            }
+        }
+    }
+
+    /// Reap a child that exited, and start one that is wanted and due.
+    fn tick(&mut self, config: &SupervisorConfig, now: Instant) {
+        if let Some(child) = self.child.as_mut() {
+            match child.try_wait() {
+                Ok(None) => return,
+                Ok(Some(status)) => tracing::warn!("brain exited ({status})"),
+                Err(e) => tracing::warn!("brain lost: {e}"),
+            }
+            self.child = None;
+            let stable = self.started.is_some_and(|at| now.saturating_duration_since(at) >= STABLE);
+            self.failures = if stable { 0 } else { self.failures + 1 };
+            self.wanted &= config.restart;
+            if self.wanted {
+                let wait = retry_after(self.failures);
+                tracing::info!("starting the brain again in {}s", wait.as_secs());
+                self.next_try = Some(now + wait);
+                self.restarts += 1;
+            }
+            return;
+        }
+        if !self.wanted || config.command.is_empty() || self.next_try.is_some_and(|at| now < at) {
+            return;
+        }
+        if let Err(e) = self.launch(config, now) {
+            tracing::warn!("{e}");
+        }
+    }
+
+    fn stop(&mut self) {
+        self.wanted = false;
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+            tracing::info!("brain stopped");
+        }
+    }
+
+    fn status(&self, config: &SupervisorConfig) -> SupervisorStatus {
+        SupervisorStatus {
+            configured: !config.command.is_empty(),
+            wanted: self.wanted,
+            pid: self.child.as_ref().map(Child::id),
+            restarts: self.restarts,
+        }
+    }
+}
+
+fn supervisor() -> std::sync::MutexGuard<'static, Supervisor> {
+    SUPERVISOR.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Start the Brain if it is set up to start with the app, and keep an eye
+/// on it for the life of the app.
+pub fn init(app: &tauri::AppHandle) {
+    let config = crate::config::current(app).brain_process;
+    supervisor().wanted = config.start_with_app && !config.command.is_empty();
+    let app = app.clone();
+    std::thread::spawn(move || loop {
+        let config = crate::config::current(&app).brain_process;
+        supervisor().tick(&config, Instant::now());
+        std::thread::sleep(POLL);
+    });
+}
+
+/// Stop the Brain for good, as the app exits.
+pub fn shutdown() {
+    supervisor().stop();
+}
+
+fn start(app: &tauri::AppHandle) -> Result<SupervisorStatus, String> {
+    let config = crate::config::current(app).brain_process;
+    let mut supervisor = supervisor();
+    supervisor.wanted = true;
+    supervisor.failures = 0;
+    if supervisor.child.is_none() {
+        supervisor.launch(&config, Instant::now())?;
+    }
+    Ok(supervisor.status(&config))
+}
+
+#[tauri::command]
+pub fn brain_start(window: tauri::WebviewWindow, app: tauri::AppHandle) -> Result<SupervisorStatus, String> {
+    if window.label() != "main" {
+        return Err("only the main window can start the brain".into());
+    }
+    start(&app)
+}
+
+#[tauri::command]
+pub fn brain_stop(window: tauri::WebviewWindow, app: tauri::AppHandle) -> Result<SupervisorStatus, String> {
+    if window.label() != "main" {
+        return Err("only the main window can stop the brain".into());
+    }
+    let mut supervisor = supervisor();
+    supervisor.stop();
+    Ok(supervisor.status(&crate::config::current(&app).brain_process))
+}
+
+#[tauri::command]
+pub fn brain_restart(window: tauri::WebviewWindow, app: tauri::AppHandle) -> Result<SupervisorStatus, String> {
+    if window.label() != "main" {
+        return Err("only the main window can restart the brain".into());
+    }
+    supervisor().stop();
+    start(&app)
+}
+
+/// The last `lines` lines the Brain wrote, oldest first.
+#[tauri::command]
+pub fn brain_logs(lines: u32) -> Vec<String> {
+    let logs = LOGS.lock().unwrap_or_else(|e| e.into_inner());
+    logs.iter().skip(logs.len().saturating_sub(lines as usize)).cloned().collect()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn idle() -> Supervisor {
+        Supervisor { child: None, wanted: true, started: None, failures: 0, next_try: None, restarts: 0 }
+    }
+
+    #[test]
+    fn a_brain_that_exits_is_started_again_later() {
+        let config = SupervisorConfig {
+            command: vec!["sh".into(), "-c".into(), "echo supervised brain up; exit 3".into()],
+            ..SupervisorConfig::default()
+        };
+        let mut supervisor = idle();
+        let start = Instant::now();
+        supervisor.tick(&config, start);
+        assert!(supervisor.child.is_some());
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while supervisor.child.is_some() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+            supervisor.tick(&config, start + Duration::from_secs(1));
+        }
+        assert_eq!((supervisor.failures, supervisor.restarts), (1, 1));
+        assert_eq!(supervisor.next_try, Some(start + Duration::from_secs(3)));
+        // Not due yet, then due.
+        supervisor.tick(&config, start + Duration::from_secs(2));
+        assert!(supervisor.child.is_none());
+        supervisor.tick(&config, start + Duration::from_secs(3));
+        assert!(supervisor.status(&config).pid.is_some());
+        supervisor.stop();
+        assert!(!supervisor.status(&config).wanted);
+
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while !brain_logs(u32::MAX).iter().any(|l| l == "supervised brain up") && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert!(brain_logs(u32::MAX).iter().any(|l| l == "supervised brain up"));
+    }
+
+    #[test]
+    fn a_command_that_cannot_run_backs_off() {
+        let config = SupervisorConfig { command: vec!["/nonexistent/brain".into()], ..SupervisorConfig::default() };
+        let mut supervisor = idle();
+        let now = Instant::now();
+        assert!(supervisor.launch(&config, now).unwrap_err().contains("/nonexistent/brain"));
+        assert_eq!(supervisor.next_try, Some(now + Duration::from_secs(2)));
+        // Not again until then.
+        supervisor.tick(&config, now + Duration::from_secs(1));
+        assert_eq!(supervisor.failures, 1);
+        assert_eq!(retry_after(10), MAX_RETRY);
+    }
+}
@@ -0,0 +1,371 @@
+//! `capture_screen` — a screenshot of whatever is behind Lexicon, as
+//! desktop context for the Brain; `capture_window` — one of a single
+//! window, the canvas or an organ.
+//!
+//! Off unless `capture.enabled = true`, and every call must also carry
+//! `consent: true`. Nothing is captured while the presentation guard is on
+//! or the session is locked. The overlay is hidden for the capture and
+//! brought back afterwards, unless it is the window being captured.
+//!
+//! Either can be cut down to a `region`, in physical pixels from the
+//! top-left of what was captured (the desktop, the monitor or the window),
+//! as the overlay's selection gives it. A frame sent to the Brain is
+//! POSTed as a PNG to `capture.brain_path` (`/vision/frame`), with
+//! `X-Lexicon-Window` naming the window for `capture_window`.
+//!
+//! On Linux the shot comes from the xdg-desktop-portal Screenshot API,
+//! which works on Wayland and X11 alike. The portal may show a permission
+//! dialog the first time; a denial is reported as such, and a grant the
+//! user chose to remember is kept by the portal's own permission store.
+//! Windows and macOS capture through GDI / CoreGraphics via `xcap`.
+
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use tauri::Manager;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+#[serde(default)]
+pub struct CaptureConfig {
+    /// Allow `capture_screen` and `capture_window` at all.
+    pub enabled: bool,
+    /// Where on the Brain frames are sent.
+    pub brain_path: String,
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self { enabled: false, brain_path: "/vision/frame".into() }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Capture {
+    pub width: u32,
+    pub height: u32,
+    /// The PNG, when it was kept rather than sent to the Brain.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    /// The Brain's reply, when the PNG was sent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<serde_json::Value>,
+}
+
+/// Give the compositor time to take the overlay off screen.
+const HIDE_SETTLE: Duration = Duration::from_millis(250);
+
+// ── Platform capture ───────────────────────────────────────────
+
+#[cfg(target_os = "linux")]
+fn grab() -> Result<RgbaImage, String> {
+    use std::collections::HashMap;
+    use zbus::zvariant::{OwnedValue, Value};
+
+    let bus = zbus::blocking::Connection::session().map_err(|e| e.to_string())?;
+    let token = format!("lexicon{}", SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis());
+    let sender = bus.unique_name().map(|n| n.trim_start_matches(':').replace('.', "_")).unwrap_or_default();
+    let request_path = format!("/org/freedesktop/portal/desktop/request/{sender}/{token}");
+
+    // Subscribe before asking, so a fast response can't be missed.
+    let request = zbus::blocking::Proxy::new(
+        &bus,
+        "org.freedesktop.portal.Desktop",
+        request_path.as_str(),
+        "org.freedesktop.portal.Request",
+    )
+    .map_err(|e| e.to_string())?;
+    let mut responses = request.receive_signal("Response").map_err(|e| e.to_string())?;
+
+    let mut options: HashMap<&str, Value> = HashMap::new();
+    options.insert("handle_token", token.as_str().into());
+    options.insert("interactive", false.into());
+    bus.call_method(
+        Some("org.freedesktop.portal.Desktop"),
+        "/org/freedesktop/portal/desktop",
+        Some("org.freedesktop.portal.Screenshot"),
+        "Screenshot",
+        &("", options),
+    )
+    .map_err(|e| format!("no screenshot portal: {e}"))?;
+
+    let response = responses.next().ok_or("screenshot portal went away")?;
+    let (code, mut results): (u32, HashMap<String, OwnedValue>) =
+        response.body().deserialize().map_err(|e| e.to_string())?;
+    match code {
+        0 => {}
+        1 => return Err("screen capture was denied".into()),
+        _ => return Err("screen capture failed in the portal".into()),
+    }
+    let uri = results.remove("uri").and_then(|v| String::try_from(v).ok()).ok_or("portal returned no image")?;
+    let path = tauri::Url::parse(&uri)
+        .ok()
+        .and_then(|u| u.to_file_path().ok())
+        .ok_or_else(|| format!("unexpected screenshot uri {uri}"))?;
+    // The portal saves to the user's pictures folder; don't leave it there.
+    let image = image::open(&path).map_err(|e| format!("read screenshot: {e}"));
+    let _ = std::fs::remove_file(&path);
+    Ok(image?.into_rgba8())
+}
+
+#[cfg(any(windows, target_os = "macos"))]
+fn grab() -> Result<RgbaImage, String> {
+    // One image spanning every monitor, laid out by their positions.
+    let monitors = xcap::Monitor::all().map_err(|e| e.to_string())?;
+    let mut shots = Vec::new();
+    for monitor in &monitors {
+        let x = monitor.x().map_err(|e| e.to_string())?;
+        let y = monitor.y().map_err(|e| e.to_string())?;
+        shots.push((x, y, monitor.capture_image().map_err(|e| e.to_string())?));
+    }
+    let left = shots.iter().map(|(x, ..)| *x).min().ok_or("no monitors")?;
+    let top = shots.iter().map(|(_, y, _)| *y).min().ok_or("no monitors")?;
+    let right = shots.iter().map(|(x, _, s)| x + s.width() as i32).max().unwrap_or(left);
+    let bottom = shots.iter().map(|(_, y, s)| y + s.height() as i32).max().unwrap_or(top);
+    let mut desktop = RgbaImage::new((right - left) as u32, (bottom - top) as u32);
+    for (x, y, shot) in &shots {
+        image::imageops::replace(&mut desktop, shot, (x - left) as i64, (y - top) as i64);
+    }
+    Ok(desktop)
+}
+
+#[cfg(not(any(target_os = "linux", windows, target_os = "macos")))]
+fn grab() -> Result<RgbaImage, String> {
+    Err("screen capture is not supported on this platform".into())
+}
+
+/// Whether the desktop session is locked.
+#[cfg(target_os = "linux")]
+fn session_locked() -> bool {
+    let Ok(bus) = zbus::blocking::Connection::system() else { return false };
+    let Ok(session) = zbus::blocking::Proxy::new(
+        &bus,
+        "org.freedesktop.login1",
+        "/org/freedesktop/login1/session/auto",
+        "org.freedesktop.login1.Session",
+    ) else {
+        return false;
+    };
+    session.get_property::<bool>("LockedHint").unwrap_or(false)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn session_locked() -> bool {
+    false
+}
+
+// ── Cropping ───────────────────────────────────────────────────
+
+/// Bounds of monitor `index` inside the full-desktop image.
+fn monitor_bounds(app: &tauri::AppHandle, index: u32) -> Result<Rect, String> {
+    let monitors = app.available_monitors().map_err(|e| e.to_string())?;
+    let left = monitors.iter().map(|m| m.position().x).min().unwrap_or(0);
+    let top = monitors.iter().map(|m| m.position().y).min().unwrap_or(0);
+    let monitor = monitors.get(index as usize).ok_or_else(|| format!("no monitor {index}"))?;
+    Ok(Rect {
+        x: (monitor.position().x - left) as u32,
+        y: (monitor.position().y - top) as u32,
+        width: monitor.size().width,
+        height: monitor.size().height,
+    })
+}
+
+/// Where a window at `position` (`size` big) is in a desktop image whose
+/// top-left is at `origin`, the part off screen left out.
+fn window_rect(origin: (i32, i32), position: (i32, i32), size: (u32, u32)) -> Rect {
+    let x = position.0 - origin.0;
+    let y = position.1 - origin.1;
+    Rect {
+        x: x.max(0) as u32,
+        y: y.max(0) as u32,
+        width: size.0.saturating_sub(x.min(0).unsigned_abs()),
+        height: size.1.saturating_sub(y.min(0).unsigned_abs()),
+    }
+}
+
+/// Bounds of `window` inside the full-desktop image.
+fn window_bounds(app: &tauri::AppHandle, window: &tauri::WebviewWindow) -> Result<Rect, String> {
+    let monitors = app.available_monitors().map_err(|e| e.to_string())?;
+    let left = monitors.iter().map(|m| m.position().x).min().unwrap_or(0);
+    let top = monitors.iter().map(|m| m.position().y).min().unwrap_or(0);
+    let position = window.outer_position().map_err(|e| e.to_string())?;
+    let size = window.outer_size().map_err(|e| e.to_string())?;
+    Ok(window_rect((left, top), (position.x, position.y), (size.width, size.height)))
+}
+
+fn crop(image: &RgbaImage, rect: Rect) -> Result<RgbaImage, String> {
+    let (w, h) = image.dimensions();
+    let x = rect.x.min(w);
+    let y = rect.y.min(h);
+    let width = rect.width.min(w - x);
+    let height = rect.height.min(h - y);
+    if width == 0 || height == 0 {
+        return Err("capture region is outside the screen".into());
+    }
+    Ok(image::imageops::crop_imm(image, x, y, width, height).to_image())
+}
+
+// ── Commands ───────────────────────────────────────────────────
+
+/// What is captured.
+enum Target {
+    /// The desktop, or one monitor of it.
+    Screen(Option<u32>),
+    /// A window, by label.
+    Window(String),
+}
+
+fn capture(app: &tauri::AppHandle, target: Target, region: Option<Rect>, send_to_brain: bool) -> Result<Capture, String> {
+    let main = app.get_webview_window("main");
+    let is_main = matches!(&target, Target::Window(label) if label == "main");
+    let was_visible = !is_main && main.as_ref().is_some_and(|w| w.is_visible().unwrap_or(false));
+    if let Some(main) = main.as_ref().filter(|_| was_visible) {
+        crate::hide_window(main);
+        std::thread::sleep(HIDE_SETTLE);
+    }
+    let shot = grab();
+    // The guard may have come on while we were waiting on the portal.
+    if let Some(main) = main.as_ref().filter(|_| was_visible && !crate::presentation::is_active()) {
+        crate::show_window(main);
+    }
+    let mut image = shot?;
+
+    match &target {
+        Target::Screen(Some(index)) => image = crop(&image, monitor_bounds(app, *index)?)?,
+        Target::Screen(None) => {}
+        Target::Window(label) => {
+            let window = app.get_webview_window(label).ok_or_else(|| format!("{label} closed"))?;
+            image = crop(&image, window_bounds(app, &window)?)?;
+        }
+    }
+    if let Some(rect) = region {
+        image = crop(&image, rect)?;
+    }
+    let (width, height) = image.dimensions();
+
+    let dir = app.path().temp_dir().map_err(|e| e.to_string())?;
+    let path = dir.join(format!(
+        "lexicon-capture-{}.png",
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis()
+    ));
+    image.save(&path).map_err(|e| format!("write {}: {e}", path.display()))?;
+
+    if !send_to_brain {
+        return Ok(Capture { width, height, path: Some(path), response: None });
+    }
+    let sent = std::fs::read(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|png| {
+            let path = crate::config::current(app).capture.brain_path;
+            let mut request = crate::brain::Request::post(&path, "image/png", crate::brain::Body::Bytes(png));
+            if let Target::Window(label) = &target {
+                request = request.header("X-Lexicon-Window", crate::organs::id_from_label(label).unwrap_or(label));
+            }
+            crate::brain::call(app, request).map_err(|e| e.to_string())
+        });
+    let _ = std::fs::remove_file(&path);
+    let text = sent.map_err(|e| format!("could not send screenshot to the Brain: {e}"))?;
+    let response = serde_json::from_str(&text).unwrap_or(serde_json::Value::String(text));
+    Ok(Capture { width, height, path: None, response: Some(response) })
+}
+
+/// Why `window` may not capture now, if it may not.
+fn refused(window: &tauri::WebviewWindow, app: &tauri::AppHandle, consent: bool) -> Result<(), String> {
+    if window.label() != "main" {
+        return Err("screen capture can only be requested by the main window".into());
+    }
+    if !crate::config::current(app).capture.enabled {
+        return Err("screen capture is disabled (set capture.enabled in the config)".into());
+    }
+    if !consent {
+        return Err("screen capture needs explicit consent".into());
+    }
+    if crate::presentation::is_active() {
+        return Err("presentation guard is on".into());
+    }
+    Ok(())
+}
+
+async fn capture_blocking(
+    app: tauri::AppHandle,
+    target: Target,
+    region: Option<Rect>,
+    send_to_brain: bool,
+) -> Result<Capture, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        if session_locked() {
+            return Err("the session is locked".into());
+        }
+        capture(&app, target, region, send_to_brain)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Capture the desktop behind the overlay. `consent` must be true on every
+/// call; with `send_to_brain` the PNG is posted to the Brain and deleted.
+#[tauri::command]
+pub async fn capture_screen(
+    window: tauri::WebviewWindow,
+    app: tauri::AppHandle,
+    monitor: Option<u32>,
+    region: Option<Rect>,
+    consent: bool,
+    send_to_brain: bool,
+) -> Result<Capture, String> {
+    refused(&window, &app, consent)?;
+    capture_blocking(app, Target::Screen(monitor), region, send_to_brain).await
+}
+
+/// Capture window `label` (`main`, an organ id or its window label) as it
+/// is on screen, like `capture_screen`; `region` is within the window.
+#[tauri::command]
+pub async fn capture_window(
+    window: tauri::WebviewWindow,
+    app: tauri::AppHandle,
+    label: String,
+    region: Option<Rect>,
+    consent: bool,
+    send_to_brain: bool,
+) -> Result<Capture, String> {
+    refused(&window, &app, consent)?;
+    let name = match label.as_str() {
+        "main" => label.clone(),
+        other => crate::organs::window_label(crate::organs::id_from_label(other).unwrap_or(other)),
+    };
+    let target = app.get_webview_window(&name).ok_or_else(|| format!("unknown window: {label}"))?;
+    if !target.is_visible().unwrap_or(false) || target.is_minimized().unwrap_or(false) {
+        return Err(format!("{label} is not on screen"));
+    }
+    capture_blocking(app, Target::Window(name), region, send_to_brain).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_window_is_found_in_the_desktop_image() {
+        // A desktop whose left monitor starts at x = -1920.
+        let rect = window_rect((-1920, 0), (100, 50), (800, 600));
+        assert_eq!((rect.x, rect.y, rect.width, rect.height), (2020, 50, 800, 600));
+        // Hanging off the top-left, only what's on screen.
+        let rect = window_rect((0, 0), (-30, -10), (800, 600));
+        assert_eq!((rect.x, rect.y, rect.width, rect.height), (0, 0, 770, 590));
+
+        let desktop = RgbaImage::new(1000, 700);
+        let shot = crop(&desktop, window_rect((0, 0), (600, 400), (800, 600))).unwrap();
+        assert_eq!(shot.dimensions(), (400, 300));
+        assert!(crop(&desktop, window_rect((0, 0), (1200, 0), (100, 100))).is_err());
+    }
+}
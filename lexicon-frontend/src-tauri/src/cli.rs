@@ -0,0 +1,111 @@
+//! Command-line control of a running instance.
+//!
+//! ```text
+//! lexicon                          start the GUI (or toggle it if running)
+//! lexicon toggle | show | hide
+//! lexicon organ <id> [open|close|status]
+//! lexicon status                   print get_health as JSON
+//! lexicon relay-metrics           print organ status relay counts as JSON
+//! lexicon dnd on|off
+//! lexicon --headless               start as a relay only; see `headless`
+//! ```
+//!
+//! Subcommands are parsed before the GUI starts and forwarded over the
+//! single-instance channel. Replies are plain text; a reply starting with
+//! `error:` makes the invoking process exit nonzero.
+
+use tauri::Manager;
+
+pub const USAGE: &str = "usage: lexicon [--headless | toggle | show | hide | status | relay-metrics | dnd on|off | organ <id> [open|close|status]]";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OrganAction {
+    Open,
+    Close,
+    Status,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Toggle,
+    Show,
+    Hide,
+    Organ { id: String, action: OrganAction },
+    Status,
+    RelayMetrics,
+    Dnd(bool),
+}
+
+/// Parse launch arguments. `Ok(None)` means no subcommand: flags such as
+/// `--hidden` and deep-link URLs are left for the GUI path.
+pub fn parse(args: &[String]) -> Result<Option<Command>, String> {
+    let mut words = args.iter().map(String::as_str).filter(|a| !a.starts_with("--") && !a.contains("://"));
+    let Some(sub) = words.next() else {
+        return Ok(None);
+    };
+    let command = match sub {
+        "toggle" => Command::Toggle,
+        "show" => Command::Show,
+        "hide" => Command::Hide,
+        "status" => Command::Status,
+        "relay-metrics" => Command::RelayMetrics,
+        "dnd" => match words.next() {
+            Some("on") => Command::Dnd(true),
+            Some("off") => Command::Dnd(false),
+            _ => return Err("dnd takes 'on' or 'off'".into()),
+        },
+        "organ" => {
+            let id = words.next().ok_or("organ needs an id")?.to_string();
+            let action = match words.next().unwrap_or("open") {
+                "open" => OrganAction::Open,
+                "close" => OrganAction::Close,
+                "status" => OrganAction::Status,
+                other => return Err(format!("unknown organ action '{other}'")),
+            };
+            Command::Organ { id, action }
+        }
+        other => return Err(format!("unknown command '{other}'")),
+    };
+    if let Some(extra) = words.next() {
+        return Err(format!("unexpected argument '{extra}'"));
+    }
+    Ok(Some(command))
+}
+
+/// Run a parsed command in the primary instance.
+pub fn execute(app: &tauri::AppHandle, command: Command) -> Result<String, String> {
+    match command {
+        Command::Toggle => {
+            crate::headless::refuse("toggling the overlay")?;
+            crate::toggle_main(app);
+            Ok("ok".into())
+        }
+        Command::Show | Command::Hide => {
+            crate::headless::refuse("showing or hiding the canvas")?;
+            if crate::presentation::is_active() {
+                return Err("presentation guard is on".into());
+            }
+            let main = app.get_webview_window("main").ok_or("main window missing")?;
+            if command == Command::Show {
+                crate::show_window(&main);
+            } else {
+                crate::hide_window(&main);
+            }
+            crate::tray::refresh_tooltip(app);
+            Ok("ok".into())
+        }
+        Command::Organ { id, action } => match action {
+            OrganAction::Open => crate::organs::open(app, &id).map(|_| "ok".into()),
+            OrganAction::Close => crate::organs::close(app, &id).map(|()| "ok".into()),
+            OrganAction::Status => crate::organs::status_of(app, &id).map(String::from),
+        },
+        Command::Status => serde_json::to_string_pretty(&crate::health::snapshot()).map_err(|e| e.to_string()),
+        Command::RelayMetrics => {
+            serde_json::to_string_pretty(&crate::relay::metrics(app)).map_err(|e| e.to_string())
+        }
+        Command::Dnd(enabled) => {
+            crate::dnd::set_dnd(app.clone(), enabled);
+            Ok("ok".into())
+        }
+    }
+}
@@ -0,0 +1,184 @@
+//! Clipboard commands for the canvas.
+//!
+//! Everything here is initiated by the main window: organ pages are remote
+//! content and get neither these commands nor the clipboard plugin's own
+//! permissions. Pasting into an organ never sends on its own — the image
+//! lands in the organ's attachment preview and the user presses send there.
+
+use std::io::Cursor;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, RgbaImage};
+use serde::{Deserialize, Serialize};
+use tauri::Manager;
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+use crate::deeplink::DeepLink;
+
+const WHATSAPP: &str = "whatsapp";
+
+/// WhatsApp Web's message composer.
+pub const WA_COMPOSER: &str = "footer [contenteditable=\"true\"]";
+
+/// Minimum gap between two pastes into an organ.
+const PASTE_INTERVAL: Duration = Duration::from_secs(3);
+
+static LAST_PASTE: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Forget the last paste, e.g. after a suspend skewed the clock.
+pub fn reset_rate_limit() {
+    *LAST_PASTE.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+#[serde(default)]
+pub struct ClipboardConfig {
+    /// Longest side, in pixels, of an image pasted into an organ.
+    /// Larger images are scaled down before sending.
+    pub max_image_side: u32,
+    /// Relay clipboard changes to the Brain (see `clipboard_watch`).
+    pub watch: bool,
+    /// How long a change must stay on the clipboard to be relayed.
+    #[cfg_attr(test, proptest(strategy = "crate::testing::toml_u64()"))]
+    pub watch_debounce_ms: u64,
+}
+
+impl Default for ClipboardConfig {
+    fn default() -> Self {
+        Self { max_image_side: 2048, watch: false, watch_debounce_ms: 1000 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StagedImage {
+    pub path: PathBuf,
+    pub width: u32,
+    pub height: u32,
+}
+
+fn require_main(window: &tauri::WebviewWindow) -> Result<(), String> {
+    if window.label() == "main" {
+        Ok(())
+    } else {
+        Err("clipboard commands are only available to the main window".into())
+    }
+}
+
+/// Scale `image` down so neither side exceeds `max_side`.
+pub fn fit(image: RgbaImage, max_side: u32) -> RgbaImage {
+    let (w, h) = image.dimensions();
+    if max_side == 0 || w.max(h) <= max_side {
+        return image;
+    }
+    let scale = max_side as f64 / w.max(h) as f64;
+    let (nw, nh) = (((w as f64 * scale).round() as u32).max(1), ((h as f64 * scale).round() as u32).max(1));
+    image::imageops::resize(&image, nw, nh, FilterType::Triangle)
+}
+
+/// Read the clipboard image, downscale it and write it out as a PNG.
+fn stage_clipboard_image(app: &tauri::AppHandle) -> Result<(StagedImage, Vec<u8>), String> {
+    let clip = app.clipboard().read_image().map_err(|_| "there is no image on the clipboard".to_string())?;
+    let image = RgbaImage::from_raw(clip.width(), clip.height(), clip.rgba().to_vec())
+        .ok_or("clipboard image has an unexpected layout")?;
+    let image = fit(image, crate::config::current(app).clipboard.max_image_side);
+    let (width, height) = image.dimensions();
+
+    let mut png = Vec::new();
+    DynamicImage::ImageRgba8(image)
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| format!("encode clipboard image: {e}"))?;
+
+    let dir = app.path().app_cache_dir().map_err(|e| e.to_string())?.join("attachments");
+    std::fs::create_dir_all(&dir).map_err(|e| format!("create {}: {e}", dir.display()))?;
+    let path = dir.join("clipboard.png");
+    std::fs::write(&path, &png).map_err(|e| format!("write {}: {e}", path.display()))?;
+    Ok((StagedImage { path, width, height }, png))
+}
+
+/// Script that hands the PNG to the composer as a paste, which opens the
+/// app's own attachment preview. Retries while the page is still loading.
+fn paste_script(png: &[u8]) -> String {
+    let data = base64::engine::general_purpose::STANDARD.encode(png);
+    format!(
+        r#"(function () {{
+  var bytes = Uint8Array.from(atob("{data}"), function (c) {{ return c.charCodeAt(0); }});
+  var file = new File([bytes], "clipboard.png", {{ type: "image/png" }});
+  var tries = 0;
+  (function attempt() {{
+    var box = document.querySelector({selector});
+    if (!box) {{ if (++tries < 150) setTimeout(attempt, 200); return; }}
+    var items = new DataTransfer();
+    items.items.add(file);
+    box.focus();
+    box.dispatchEvent(new ClipboardEvent("paste", {{ clipboardData: items, bubbles: true, cancelable: true }}));
+  }})();
+}})();"#,
+        selector = serde_json::to_string(WA_COMPOSER).unwrap_or_default(),
+    )
+}
+
+#[tauri::command]
+pub fn copy_to_clipboard(window: tauri::WebviewWindow, app: tauri::AppHandle, text: String) -> Result<(), String> {
+    require_main(&window)?;
+    app.clipboard().write_text(text).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn copy_image_to_clipboard(window: tauri::WebviewWindow, app: tauri::AppHandle, path: PathBuf) -> Result<(), String> {
+    require_main(&window)?;
+    let image = image::open(&path).map_err(|e| format!("read {}: {e}", path.display()))?.into_rgba8();
+    let (width, height) = image.dimensions();
+    let image = tauri::image::Image::new_owned(image.into_raw(), width, height);
+    app.clipboard().write_image(&image).map_err(|e| e.to_string())
+}
+
+/// Paste the clipboard image into a WhatsApp chat. The organ shows its
+/// attachment preview; nothing is sent until the user confirms there.
+#[tauri::command]
+pub fn wa_paste_attachment(
+    window: tauri::WebviewWindow,
+    app: tauri::AppHandle,
+    chat_query: String,
+) -> Result<StagedImage, String> {
+    require_main(&window)?;
+    if crate::presentation::is_active() {
+        return Err("presentation guard is on".into());
+    }
+    {
+        let mut last = LAST_PASTE.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(at) = *last {
+            let wait = PASTE_INTERVAL.saturating_sub(at.elapsed());
+            if !wait.is_zero() {
+                return Err(format!("slow down — try again in {}s", wait.as_secs() + 1));
+            }
+        }
+        *last = Some(Instant::now());
+    }
+
+    let (staged, png) = stage_clipboard_image(&app)?;
+    let fresh = crate::organs::status(&app, WHATSAPP) == "closed";
+    crate::deeplink::dispatch(&app, DeepLink::OpenChat { organ: WHATSAPP.into(), query: chat_query });
+    let organ = app
+        .get_webview_window(&crate::organs::window_label(WHATSAPP))
+        .ok_or("whatsapp organ failed to open")?;
+
+    let script = paste_script(&png);
+    let upload = crate::inhibit::hold(&app, crate::inhibit::Reason::MediaUpload);
+    if fresh {
+        // A new window is still navigating; an early eval would be lost.
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_secs(5));
+            let _ = organ.eval(&script);
+            drop(upload);
+        });
+    } else {
+        organ.eval(&script).map_err(|e| e.to_string())?;
+    }
+    tracing::info!("clipboard image ({}x{}) handed to whatsapp", staged.width, staged.height);
+    Ok(staged)
+}
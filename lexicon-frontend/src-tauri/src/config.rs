@@ -0,0 +1,337 @@
+//! Runtime configuration — `~/.config/lexicon/config.toml`.
+//!
+//! Every field has a default, so a missing file or a partial file is
+//! fine. A file that fails to parse is logged and ignored rather than
+//! keeping the app from starting.
+//!
+//! The file is watched while the app runs: an edit is picked up within
+//! [`WATCH_INTERVAL`] and replaces the config everything reads through
+//! [`current`], so a new `brain.url` takes effect on the next request,
+//! without a restart. `LEXICON_BRAIN_URL` still wins over it, and an edit
+//! that doesn't parse is logged and leaves the running config alone. The
+//! main window hears of each reload as `config://changed`. Settings read
+//! once at startup (the relay workers and queue, the metrics port, the
+//! proxy) still need a restart.
+
+#[cfg(desktop)]
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager};
+
+use crate::audio::AudioConfig;
+use crate::badge::BadgePolicy;
+use crate::brain::BrainConfig;
+#[cfg(desktop)]
+use crate::brain_supervisor::SupervisorConfig;
+use crate::capture::CaptureConfig;
+use crate::clipboard::ClipboardConfig;
+use crate::deeplink::GuardedLinkPolicy;
+use crate::external::ExternalConfig;
+use crate::ingest::IngestConfig;
+use crate::inhibit::InhibitConfig;
+use crate::journal::JournalConfig;
+use crate::loadtest::LoadTestConfig;
+use crate::media::MediaConfig;
+use crate::metrics::MetricsConfig;
+#[cfg(desktop)]
+use crate::monitors::MonitorConfig;
+use crate::notify::NotificationPrefs;
+use crate::proxy::ProxyConfig;
+use crate::relay::RelayConfig;
+#[cfg(desktop)]
+use crate::shortcuts::Binding;
+use crate::startup::StartupConfig;
+use crate::theme::ThemeOverride;
+#[cfg(desktop)]
+use crate::updater::UpdaterConfig;
+use crate::voice::VoiceConfig;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+#[serde(default)]
+pub struct Config {
+    pub tray: TrayConfig,
+    pub deep_links: DeepLinkConfig,
+    pub organs: OrgansConfig,
+    pub clipboard: ClipboardConfig,
+    pub dbus: DbusConfig,
+    pub inhibit: InhibitConfig,
+    pub brain: BrainConfig,
+    pub ingest: IngestConfig,
+    pub media: MediaConfig,
+    pub capture: CaptureConfig,
+    pub voice: VoiceConfig,
+    pub audio: AudioConfig,
+    pub external: ExternalConfig,
+    pub relay: RelayConfig,
+    pub load_test: LoadTestConfig,
+    pub journal: JournalConfig,
+    pub metrics: MetricsConfig,
+    pub notifications: NotificationPrefs,
+    pub proxy: ProxyConfig,
+    pub startup: StartupConfig,
+    /// Closing the main window quits the app instead of hiding it.
+    pub close_exits_app: bool,
+    /// "system" (default), "light" or "dark"; see `theme`.
+    pub theme: ThemeOverride,
+    /// Shortcut overrides by action name; see `shortcuts`.
+    #[cfg(desktop)]
+    pub shortcuts: BTreeMap<String, Binding>,
+    #[cfg(desktop)]
+    pub updater: UpdaterConfig,
+    /// Which display each window goes fullscreen on; see `monitors`.
+    #[cfg(desktop)]
+    pub monitors: MonitorConfig,
+    /// Running the Brain from the app; see `brain_supervisor`.
+    #[cfg(desktop)]
+    pub brain_process: SupervisorConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+#[serde(default)]
+pub struct TrayConfig {
+    /// Set to false for minimal setups that don't want a tray icon.
+    pub enabled: bool,
+    /// How unread counts are drawn on the tray icon.
+    pub badge: BadgePolicy,
+}
+
+impl Default for TrayConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            badge: BadgePolicy::Count,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+#[serde(default)]
+pub struct DeepLinkConfig {
+    /// `queue` or `reject` links that arrive while the presentation guard is on.
+    pub while_guarded: GuardedLinkPolicy,
+    /// Register as the `whatsapp:` handler so wa.me links open the organ;
+    /// see `whatsapp`.
+    pub claim_whatsapp: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+#[serde(default)]
+pub struct OrgansConfig {
+    /// Start the WhatsApp organ in the background at launch, so relaying
+    /// begins before it is first opened.
+    pub autostart_whatsapp: bool,
+    /// Seconds an open organ may go without reporting before it is
+    /// recovered; 0 turns the watchdog off. See `watchdog`.
+    #[cfg_attr(test, proptest(strategy = "crate::testing::toml_u64()"))]
+    pub watchdog_secs: u64,
+    /// More WhatsApp logins, each an organ of its own (`whatsapp-work`
+    /// for "work"), relayed with `"account"`. See `whatsapp`.
+    pub whatsapp_accounts: Vec<String>,
+    /// Minutes a WhatsApp organ may sit in the background without
+    /// relaying a message before it is suspended; 0 never. See `idle`.
+    #[cfg_attr(test, proptest(strategy = "crate::testing::toml_u64()"))]
+    pub suspend_after_mins: u64,
+    /// Most milliseconds a window switch waits for the outgoing windows
+    /// to leave the screen before promoting the incoming one.
+    #[cfg_attr(test, proptest(strategy = "crate::testing::toml_u64()"))]
+    pub switch_settle_ms: u64,
+    /// How organs are shown, by id; unlisted ones are fullscreen. See
+    /// `layout`.
+    #[cfg(desktop)]
+    pub display: BTreeMap<String, crate::layout::OrganDisplay>,
+}
+
+impl Default for OrgansConfig {
+    fn default() -> Self {
+        Self {
+            autostart_whatsapp: false,
+            watchdog_secs: 120,
+            whatsapp_accounts: Vec::new(),
+            suspend_after_mins: 30,
+            switch_settle_ms: 500,
+            #[cfg(desktop)]
+            display: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+#[serde(default)]
+pub struct DbusConfig {
+    /// Expose `org.lexicon.Frontend` on the session bus (Linux only).
+    pub enabled: bool,
+}
+
+impl Default for DbusConfig {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+/// How often the file is checked for edits.
+pub const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// When the file was last read or written here, so our own saves aren't
+/// taken for edits.
+static SEEN: Mutex<Option<SystemTime>> = Mutex::new(None);
+
+/// Managed state wrapper so commands can read and update the config.
+pub struct ConfigState(pub RwLock<Config>);
+
+impl ConfigState {
+    pub fn get(&self) -> Config {
+        self.0.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+/// Location of the config file, if the platform has a config dir.
+pub fn path(app: &tauri::AppHandle) -> Option<PathBuf> {
+    app.path()
+        .config_dir()
+        .ok()
+        .map(|dir| dir.join("lexicon").join("config.toml"))
+}
+
+/// Load the config file, falling back to defaults, and settle the
+/// Brain URL against `LEXICON_BRAIN_URL`.
+pub fn load(app: &tauri::AppHandle) -> Config {
+    let mut config = read(app);
+    config.brain.resolve(std::env::var(crate::brain::URL_ENV).ok().as_deref());
+    config
+}
+
+fn read(app: &tauri::AppHandle) -> Config {
+    let Some(path) = path(app) else {
+        return Config::default();
+    };
+    seen(&path);
+    match std::fs::read_to_string(&path) {
+        Ok(text) => match toml::from_str(&text) {
+            Ok(config) => config,
+            Err(e) => {
+                tracing::warn!("config {} is invalid ({e}) — using defaults", path.display());
+                Config::default()
+            }
+        },
+        Err(_) => Config::default(),
+    }
+}
+
+/// Current config from managed state.
+pub fn current(app: &tauri::AppHandle) -> Config {
+    app.state::<ConfigState>().get()
+}
+
+/// Apply `change` to the managed config and write the result to disk.
+pub fn update(app: &tauri::AppHandle, change: impl FnOnce(&mut Config)) -> Result<(), String> {
+    let state = app.state::<ConfigState>();
+    let mut config = state.0.write().unwrap_or_else(|e| e.into_inner());
+    change(&mut config);
+    save(app, &config)
+}
+
+fn save(app: &tauri::AppHandle, config: &Config) -> Result<(), String> {
+    let path = path(app).ok_or("no config directory on this platform")?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("create {}: {e}", dir.display()))?;
+    }
+    let text = toml::to_string_pretty(config).map_err(|e| format!("serialize config: {e}"))?;
+    std::fs::write(&path, text).map_err(|e| format!("write {}: {e}", path.display()))?;
+    seen(&path);
+    Ok(())
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn seen(path: &Path) {
+    *SEEN.lock().unwrap_or_else(|e| e.into_inner()) = modified(path);
+}
+
+/// The config in `text`, replacing `running`: the URL is settled as at
+/// startup, but an environment override stays in force.
+fn reloaded(text: &str, running: &Config) -> Result<Config, String> {
+    let mut config: Config = toml::from_str(text).map_err(|e| e.to_string())?;
+    config.brain.resolve(None);
+    config.brain.env_url = running.brain.env_url.clone();
+    Ok(config)
+}
+
+fn reload(app: &tauri::AppHandle, path: &Path) {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        // Removed: what is running stays until a file is back.
+        Err(_) => return,
+    };
+    let state = app.state::<ConfigState>();
+    let mut running = state.0.write().unwrap_or_else(|e| e.into_inner());
+    match reloaded(&text, &running) {
+        Ok(config) => {
+            if let Some(limiter) = app.try_state::<crate::ratelimit::RateLimiter>() {
+                limiter.set(config.relay.rate_limits.clone());
+            }
+            *running = config;
+            drop(running);
+            tracing::info!("config {} changed — reloaded", path.display());
+            let _ = app.emit_to("main", "config://changed", ());
+        }
+        Err(e) => tracing::warn!("config {} is invalid ({e}) — keeping the running config", path.display()),
+    }
+}
+
+/// Reload the file whenever it changes, for the life of the app.
+pub fn watch(app: &tauri::AppHandle) {
+    let Some(path) = path(app) else { return };
+    let app = app.clone();
+    std::thread::Builder::new()
+        .name("config-watch".into())
+        .spawn(move || loop {
+            std::thread::sleep(WATCH_INTERVAL);
+            let now = modified(&path);
+            let changed = *SEEN.lock().unwrap_or_else(|e| e.into_inner()) != now;
+            if changed {
+                seen(&path);
+                reload(&app, &path);
+            }
+        })
+        .expect("spawn config watcher");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn config_survives_the_file(config in any::<Config>()) {
+            let text = toml::to_string(&config).unwrap();
+            prop_assert_eq!(toml::from_str::<Config>(&text).unwrap(), config);
+        }
+
+        #[test]
+        fn any_file_parses_or_is_rejected(text in any::<String>()) {
+            let _ = toml::from_str::<Config>(&text);
+        }
+    }
+
+    #[test]
+    fn reloads_keep_the_environment_url() {
+        let mut running = Config::default();
+        running.brain.env_url = Some("http://10.0.0.5:8000".into());
+        let config = reloaded("[brain]\nurl = \"http://brain.lan:9000/\"\n", &running).unwrap();
+        assert_eq!(config.brain.url, "http://brain.lan:9000");
+        assert_eq!(config.brain.effective_url(), "http://10.0.0.5:8000");
+        assert!(reloaded("[brain\n", &running).is_err());
+    }
+}
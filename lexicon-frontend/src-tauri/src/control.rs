@@ -0,0 +1,349 @@
+//! Control channel — the Brain asking the app to do things.
+//!
+//! While `brain.control` is on (the default), a WebSocket to
+//! `{brain url}/control` (`ws://`, or `wss://` for an https Brain) stays
+//! open, reconnecting after a second and then twice as long each time it
+//! fails, up to a minute. The Brain sends JSON text frames:
+//!
+//! ```text
+//! { "id": 7, "command": "open_organ", "args": { "label": "whatsapp" } }
+//! ```
+//!
+//! and gets one reply per command, with the same `id`:
+//! `{ "id": 7, "ok": true, "result": "ok" }` or
+//! `{ "id": 7, "ok": false, "error": "…" }`. Commands are `ping`,
+//! `show_main`, `hide_main`, `open_organ`, `close_organ` and
+//! `resume_organ` (`label`: an organ id or window label; `resume_organ`
+//! brings a suspended organ back in the background, see `idle`), and
+//! `send_message` (`chat`, `text`; see
+//! `whatsapp::wa_send_message`), `organ_action` (`label`, `action`,
+//! `args`; the result is the action's answer as JSON text, see
+//! `actions`) and `notify` (`title`, `body`, and a `label` and `chat` for
+//! one that opens that chat when clicked rather than the canvas; DND and
+//! the presentation guard hold it back, see `notify`). Anything else, or
+//! a frame that doesn't parse, is answered with an error. Commands run on threads of their
+//! own, so a slow send doesn't hold up a ping.
+//!
+//! The channel's state is in `brain_status`. Turning `brain.control` off,
+//! or changing the Brain URL, closes the connection within a second;
+//! [`reconnect`] (the tray's "Reconnect to Brain") drops it, or the wait
+//! before the next try, and connects again at once.
+
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Message, WebSocket};
+
+use crate::brain::ControlState;
+
+/// How long a read waits before the loop looks at replies and config.
+const POLL: Duration = Duration::from_secs(1);
+const FIRST_RETRY: Duration = Duration::from_secs(1);
+const MAX_RETRY: Duration = Duration::from_secs(60);
+
+/// Set by [`reconnect`], taken by the connection or the wait it cuts short.
+static RECONNECT: AtomicBool = AtomicBool::new(false);
+
+/// Start over with a fresh connection, without waiting out the backoff.
+pub fn reconnect() {
+    RECONNECT.store(true, Ordering::Relaxed);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Command {
+    Ping,
+    ShowMain,
+    HideMain,
+    OpenOrgan(String),
+    CloseOrgan(String),
+    ResumeOrgan(String),
+    SendMessage { chat: String, text: String },
+    OrganAction { id: String, action: String, args: serde_json::Value },
+    Notify { title: String, body: String, chat: Option<(String, String)> },
+}
+
+#[derive(Debug, Deserialize)]
+struct Incoming {
+    #[serde(default)]
+    id: serde_json::Value,
+    command: String,
+    #[serde(default)]
+    args: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Serialize)]
+#[cfg_attr(test, derive(PartialEq))]
+struct Reply {
+    id: serde_json::Value,
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Reply {
+    fn new(id: serde_json::Value, outcome: Result<String, String>) -> Self {
+        match outcome {
+            Ok(result) => Self { id, ok: true, result: Some(result), error: None },
+            Err(error) => Self { id, ok: false, result: None, error: Some(error) },
+        }
+    }
+}
+
+/// The WebSocket URL for a Brain at `base`.
+fn control_url(base: &str) -> Result<String, String> {
+    let rest = base
+        .strip_prefix("http://")
+        .map(|rest| format!("ws://{rest}"))
+        .or_else(|| base.strip_prefix("https://").map(|rest| format!("wss://{rest}")))
+        .ok_or_else(|| format!("can't reach {base} over a WebSocket"))?;
+    Ok(format!("{rest}/control"))
+}
+
+/// An organ id from an id or a window label.
+fn organ(args: &serde_json::Map<String, serde_json::Value>) -> Result<String, String> {
+    let label = args.get("label").or_else(|| args.get("id")).and_then(|v| v.as_str()).ok_or("no organ label given")?;
+    Ok(crate::organs::id_from_label(label).unwrap_or(label).to_string())
+}
+
+/// A frame's id and command; an error reply is ready for one that has
+/// none or doesn't parse.
+fn parse(text: &str) -> Result<(serde_json::Value, Command), Reply> {
+    let incoming: Incoming = serde_json::from_str(text)
+        .map_err(|e| Reply::new(serde_json::Value::Null, Err(format!("bad control frame: {e}"))))?;
+    let text_arg = |key: &str| incoming.args.get(key).and_then(|v| v.as_str()).map(String::from);
+    let command = match incoming.command.as_str() {
+        "ping" => Ok(Command::Ping),
+        "show_main" => Ok(Command::ShowMain),
+        "hide_main" => Ok(Command::HideMain),
+        "open_organ" => organ(&incoming.args).map(Command::OpenOrgan),
+        "close_organ" => organ(&incoming.args).map(Command::CloseOrgan),
+        "resume_organ" => organ(&incoming.args).map(Command::ResumeOrgan),
+        "send_message" => match (text_arg("chat"), text_arg("text")) {
+            (Some(chat), Some(text)) => Ok(Command::SendMessage { chat, text }),
+            _ => Err("send_message takes a chat and a text".to_string()),
+        },
+        "organ_action" => match (organ(&incoming.args), text_arg("action")) {
+            (Ok(id), Some(action)) => {
+                let args = incoming.args.get("args").cloned().unwrap_or_default();
+                Ok(Command::OrganAction { id, action, args })
+            }
+            (Err(e), _) => Err(e),
+            (_, None) => Err("organ_action takes an action".to_string()),
+        },
+        "notify" => match (text_arg("title"), text_arg("body"), text_arg("chat")) {
+            (Some(title), Some(body), None) => Ok(Command::Notify { title, body, chat: None }),
+            (Some(title), Some(body), Some(chat)) => {
+                organ(&incoming.args).map(|id| Command::Notify { title, body, chat: Some((id, chat)) })
+            }
+            _ => Err("notify takes a title and a body".to_string()),
+        },
+        other => Err(format!("unknown command '{other}'")),
+    };
+    match command {
+        Ok(command) => Ok((incoming.id, command)),
+        Err(e) => Err(Reply::new(incoming.id, Err(e))),
+    }
+}
+
+fn run(app: &tauri::AppHandle, command: Command) -> Result<String, String> {
+    use crate::cli::{Command as Cli, OrganAction};
+
+    match command {
+        Command::Ping => Ok("pong".into()),
+        Command::ShowMain => crate::cli::execute(app, Cli::Show),
+        Command::HideMain => crate::cli::execute(app, Cli::Hide),
+        Command::OpenOrgan(id) => crate::cli::execute(app, Cli::Organ { id, action: OrganAction::Open }),
+        Command::CloseOrgan(id) => crate::cli::execute(app, Cli::Organ { id, action: OrganAction::Close }),
+        Command::ResumeOrgan(id) => match crate::idle::resume(app, &id)? {
+            true => Ok("resuming".into()),
+            false => Ok("not suspended".into()),
+        },
+        Command::SendMessage { chat, text } => crate::whatsapp::send(app, &chat, &text),
+        Command::OrganAction { id, action, args } => {
+            crate::actions::run(app, &id, &action, &args).and_then(|value| serde_json::to_string(&value).map_err(|e| e.to_string()))
+        }
+        Command::Notify { title, body, chat } => {
+            let shown = match chat {
+                Some((id, chat)) => crate::notify::notify(app, &id, &chat, &title, &body)?,
+                None => crate::notify::notify_main(app, &title, &body)?,
+            };
+            Ok(if shown.is_some() { "shown" } else { "held back" }.into())
+        }
+    }
+}
+
+fn set_read_timeout(socket: &mut WebSocket<MaybeTlsStream<TcpStream>>, timeout: Duration) {
+    let stream = match socket.get_mut() {
+        MaybeTlsStream::Plain(stream) => stream,
+        MaybeTlsStream::Rustls(stream) => &mut stream.sock,
+        _ => return,
+    };
+    let _ = stream.set_read_timeout(Some(timeout));
+}
+
+/// Whether the channel should still be open to `url`.
+fn wanted(app: &tauri::AppHandle, url: &str) -> bool {
+    let config = crate::config::current(app).brain;
+    config.control && control_url(config.effective_url()).is_ok_and(|current| current == url)
+}
+
+/// One connection, until it drops or is no longer wanted. Ok if it
+/// ended on our side.
+fn session(app: &tauri::AppHandle, url: &str) -> Result<(), String> {
+    let (mut socket, _) = tungstenite::connect(url).map_err(|e| e.to_string())?;
+    set_read_timeout(&mut socket, POLL);
+    crate::brain::set_control(app, ControlState::Connected);
+    tracing::info!("control channel open to {url}");
+    let (replies, replied) = mpsc::channel::<Reply>();
+    loop {
+        if !wanted(app, url) || RECONNECT.swap(false, Ordering::Relaxed) {
+            let _ = socket.close(None);
+            let _ = socket.flush();
+            return Ok(());
+        }
+        while let Ok(reply) = replied.try_recv() {
+            let text = serde_json::to_string(&reply).unwrap_or_default();
+            socket.send(Message::text(text)).map_err(|e| e.to_string())?;
+        }
+        match socket.read() {
+            Ok(Message::Text(text)) => match parse(&text) {
+                Ok((id, command)) => {
+                    tracing::debug!("control: {command:?}");
+                    let (app, replies) = (app.clone(), replies.clone());
+                    std::thread::spawn(move || {
+                        let _ = replies.send(Reply::new(id, run(&app, command)));
+                    });
+                }
+                Err(reply) => {
+                    tracing::warn!("control: {}", reply.error.as_deref().unwrap_or_default());
+                    let _ = replies.send(reply);
+                }
+            },
+            Ok(Message::Close(_)) => return Err("closed by the Brain".into()),
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e))
+                if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) =>
+            {
+                // Pongs queued by the read go out here.
+                let _ = socket.flush();
+            }
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+}
+
+/// The wait before reconnecting after `failures` failed tries in a row.
+fn retry_after(failures: u32) -> Duration {
+    FIRST_RETRY.saturating_mul(1 << failures.min(6)).min(MAX_RETRY)
+}
+
+/// Sleep for `wait`, or until [`reconnect`]. True if that cut it short.
+fn wait_retry(wait: Duration) -> bool {
+    let deadline = std::time::Instant::now() + wait;
+    while std::time::Instant::now() < deadline {
+        if RECONNECT.swap(false, Ordering::Relaxed) {
+            return true;
+        }
+        std::thread::sleep(POLL.min(deadline.saturating_duration_since(std::time::Instant::now())));
+    }
+    false
+}
+
+/// Keep the channel open for the life of the app, while it is wanted.
+pub fn start(app: &tauri::AppHandle) {
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let mut failures = 0;
+        loop {
+            let config = crate::config::current(&app).brain;
+            let url = match control_url(config.effective_url()) {
+                Ok(url) if config.control && !crate::power::is_suspended() => url,
+                _ => {
+                    crate::brain::set_control(&app, ControlState::Disabled);
+                    failures = 0;
+                    std::thread::sleep(POLL);
+                    continue;
+                }
+            };
+            crate::brain::set_control(&app, ControlState::Connecting);
+            match session(&app, &url) {
+                Ok(()) => failures = 0,
+                Err(e) => {
+                    crate::brain::set_control(&app, ControlState::Disconnected);
+                    if failures == 0 {
+                        tracing::info!("control channel to {url} down: {e}");
+                    }
+                    failures = if wait_retry(retry_after(failures)) { 0 } else { failures + 1 };
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn the_control_url_follows_the_brain() {
+        assert_eq!(control_url("http://127.0.0.1:8000").unwrap(), "ws://127.0.0.1:8000/control");
+        assert_eq!(control_url("https://brain.lan/api").unwrap(), "wss://brain.lan/api/control");
+        assert!(control_url("ftp://brain.lan").is_err());
+    }
+
+    #[test]
+    fn commands_parse_and_the_rest_get_an_error_reply() {
+        let frame = |value: serde_json::Value| parse(&value.to_string());
+        assert_eq!(frame(json!({ "id": 1, "command": "ping" })).unwrap(), (json!(1), Command::Ping));
+        assert_eq!(
+            frame(json!({ "id": "a", "command": "open_organ", "args": { "label": "whatsapp-organ" } })).unwrap().1,
+            Command::OpenOrgan("whatsapp".into())
+        );
+        assert_eq!(
+            frame(json!({ "id": 3, "command": "resume_organ", "args": { "id": "whatsapp-work" } })).unwrap().1,
+            Command::ResumeOrgan("whatsapp-work".into())
+        );
+        assert_eq!(
+            frame(json!({ "command": "organ_action", "args": { "label": "whatsapp", "action": "markRead", "args": { "chat": "Ana" } } }))
+                .unwrap()
+                .1,
+            Command::OrganAction { id: "whatsapp".into(), action: "markRead".into(), args: json!({ "chat": "Ana" }) }
+        );
+        assert_eq!(
+            frame(json!({ "command": "send_message", "args": { "chat": "Ana", "text": "hi" } })).unwrap(),
+            (serde_json::Value::Null, Command::SendMessage { chat: "Ana".into(), text: "hi".into() })
+        );
+
+        assert_eq!(
+            frame(json!({ "command": "notify", "args": { "title": "Brain", "body": "Done", "label": "whatsapp", "chat": "Ana" } }))
+                .unwrap()
+                .1,
+            Command::Notify { title: "Brain".into(), body: "Done".into(), chat: Some(("whatsapp".into(), "Ana".into())) }
+        );
+        assert!(frame(json!({ "command": "notify", "args": { "title": "Brain", "body": "Done", "chat": "Ana" } })).is_err());
+        assert!(frame(json!({ "command": "notify", "args": { "title": "Brain" } })).is_err());
+
+        let unknown = frame(json!({ "id": 9, "command": "format_disk" })).unwrap_err();
+        assert_eq!(unknown, Reply::new(json!(9), Err("unknown command 'format_disk'".into())));
+        assert!(frame(json!({ "id": 2, "command": "close_organ" })).unwrap_err().error.unwrap().contains("label"));
+        assert!(parse("{ nope").unwrap_err().error.unwrap().starts_with("bad control frame"));
+        assert_eq!(
+            serde_json::to_value(Reply::new(json!(1), Ok("pong".into()))).unwrap(),
+            json!({ "id": 1, "ok": true, "result": "pong" })
+        );
+    }
+
+    #[test]
+    fn reconnects_back_off_to_a_minute() {
+        assert_eq!(retry_after(0), Duration::from_secs(1));
+        assert_eq!(retry_after(3), Duration::from_secs(8));
+        assert_eq!(retry_after(40), MAX_RETRY);
+    }
+}
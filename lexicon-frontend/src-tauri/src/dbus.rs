@@ -0,0 +1,288 @@
+//! `org.lexicon.Frontend` on the session bus (Linux).
+//!
+//! Methods map onto the same code paths as the IPC commands:
+//!
+//! ```text
+//! Toggle()
+//! ShowOrgan(s id)
+//! Dnd(b enabled)
+//! Health() -> s        get_health as JSON
+//! ```
+//!
+//! Signals carry metadata only, never message content:
+//! `MessageRelayed(s organ, s chat)` is sent for each new message the
+//! relay announces (see `relay::Messages`) and held back while the
+//! presentation guard is on, and `HealthChanged(s)` is mirrored from
+//! `health://changed`.
+//!
+//! The service connects from a background thread after startup, so a slow
+//! or missing bus never delays the window. Only the primary instance gets
+//! that far, so there is never more than one owner of the name. Set
+//! `dbus.enabled = false` in the config to skip it.
+
+use std::sync::OnceLock;
+
+use tauri::Listener;
+use zbus::blocking::Connection;
+use zbus::fdo;
+use zbus::object_server::SignalEmitter;
+
+pub const NAME: &str = "org.lexicon.Frontend";
+pub const PATH: &str = "/org/lexicon/Frontend";
+
+/// What the bus can ask the app to do. Implemented for `AppHandle`.
+pub trait Control: Send + Sync + 'static {
+    fn toggle(&self) -> Result<(), String>;
+    fn show_organ(&self, id: &str) -> Result<(), String>;
+    fn set_dnd(&self, enabled: bool);
+    fn health(&self) -> String;
+}
+
+struct Frontend {
+    control: Box<dyn Control>,
+}
+
+#[zbus::interface(name = "org.lexicon.Frontend")]
+impl Frontend {
+    fn toggle(&self) -> fdo::Result<()> {
+        self.control.toggle().map_err(fdo::Error::Failed)
+    }
+
+    fn show_organ(&self, id: &str) -> fdo::Result<()> {
+        self.control.show_organ(id).map_err(fdo::Error::Failed)
+    }
+
+    fn dnd(&self, enabled: bool) {
+        self.control.set_dnd(enabled);
+    }
+
+    fn health(&self) -> String {
+        self.control.health()
+    }
+
+    #[zbus(signal)]
+    async fn message_relayed(emitter: &SignalEmitter<'_>, organ: &str, chat: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn health_changed(emitter: &SignalEmitter<'_>, status: &str) -> zbus::Result<()>;
+}
+
+/// Connect to `address` (the session bus when `None`), claim the name and
+/// serve the interface.
+pub fn serve(address: Option<&str>, control: Box<dyn Control>) -> zbus::Result<Connection> {
+    let builder = match address {
+        Some(address) => zbus::blocking::connection::Builder::address(address)?,
+        None => zbus::blocking::connection::Builder::session()?,
+    };
+    builder
+        .name(NAME)?
+        .allow_name_replacements(false)
+        .replace_existing_names(false)
+        .serve_at(PATH, Frontend { control })?
+        .build()
+}
+
+fn emitter(connection: &Connection) -> zbus::Result<SignalEmitter<'static>> {
+    Ok(connection.object_server().interface::<_, Frontend>(PATH)?.signal_emitter().clone())
+}
+
+pub fn emit_message_relayed(connection: &Connection, organ: &str, chat: &str) -> zbus::Result<()> {
+    zbus::block_on(Frontend::message_relayed(&emitter(connection)?, organ, chat))
+}
+
+/// `MessageRelayed` for a relayed message body, with its chat (empty if
+/// it has none).
+fn emit_relayed(connection: &Connection, organ: &str, message: &serde_json::Value) -> zbus::Result<()> {
+    let chat = message.get("chat").and_then(|chat| chat.as_str()).unwrap_or_default();
+    emit_message_relayed(connection, organ, chat)
+}
+
+pub fn emit_health_changed(connection: &Connection, status: &str) -> zbus::Result<()> {
+    zbus::block_on(Frontend::health_changed(&emitter(connection)?, status))
+}
+
+// ── App wiring ─────────────────────────────────────────────────
+
+impl Control for tauri::AppHandle {
+    fn toggle(&self) -> Result<(), String> {
+        crate::toggle_main(self);
+        Ok(())
+    }
+
+    fn show_organ(&self, id: &str) -> Result<(), String> {
+        if crate::presentation::is_active() {
+            return Err("presentation guard is on".into());
+        }
+        crate::organs::open(self, id).map(|_| ())
+    }
+
+    fn set_dnd(&self, enabled: bool) {
+        crate::dnd::set_dnd(self.clone(), enabled);
+    }
+
+    fn health(&self) -> String {
+        serde_json::to_string(&crate::health::snapshot()).unwrap_or_default()
+    }
+}
+
+static CONNECTION: OnceLock<Connection> = OnceLock::new();
+
+/// The relay announced `message`, new from `organ`: signal it, unless the
+/// guard is on or the service isn't up.
+pub fn relayed(organ: &str, message: &serde_json::Value) {
+    if crate::presentation::is_active() {
+        return;
+    }
+    if let Some(connection) = CONNECTION.get() {
+        let _ = emit_relayed(connection, organ, message);
+    }
+}
+
+/// Start the service in the background, unless disabled in the config.
+pub fn init(app: &tauri::AppHandle) {
+    if !crate::config::current(app).dbus.enabled {
+        return;
+    }
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let connection = match serve(None, Box::new(app.clone())) {
+            Ok(connection) => CONNECTION.get_or_init(|| connection),
+            Err(e) => {
+                tracing::warn!("D-Bus service unavailable: {e}");
+                return;
+            }
+        };
+        tracing::info!("D-Bus service {NAME} ready");
+
+        app.listen_any("health://changed", move |event| {
+            let _ = emit_health_changed(connection, event.payload());
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use std::sync::{Arc, Mutex};
+
+    /// A private bus, torn down on drop.
+    struct Bus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl Bus {
+        fn start() -> Option<Self> {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address=1"])
+                .stdout(Stdio::piped())
+                .spawn()
+                .ok()?;
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.take()?).read_line(&mut address).ok()?;
+            Some(Self { daemon, address: address.trim().to_string() })
+        }
+    }
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        calls: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Control for Recorder {
+        fn toggle(&self) -> Result<(), String> {
+            self.calls.lock().unwrap().push("toggle".into());
+            Ok(())
+        }
+        fn show_organ(&self, id: &str) -> Result<(), String> {
+            match id {
+                "whatsapp" => {
+                    self.calls.lock().unwrap().push(format!("organ {id}"));
+                    Ok(())
+                }
+                _ => Err(format!("unknown organ: {id}")),
+            }
+        }
+        fn set_dnd(&self, enabled: bool) {
+            self.calls.lock().unwrap().push(format!("dnd {enabled}"));
+        }
+        fn health(&self) -> String {
+            r#"{"dnd":false}"#.into()
+        }
+    }
+
+    #[test]
+    fn methods_and_signals_round_trip() {
+        let Some(bus) = Bus::start() else {
+            eprintln!("dbus-daemon not available — skipping");
+            return;
+        };
+        let recorder = Recorder::default();
+        let calls = recorder.calls.clone();
+        let service = serve(Some(&bus.address), Box::new(recorder)).unwrap();
+
+        let client = zbus::blocking::connection::Builder::address(bus.address.as_str()).unwrap().build().unwrap();
+        let proxy = zbus::blocking::Proxy::new(&client, NAME, PATH, NAME).unwrap();
+
+        proxy.call_method("Toggle", &()).unwrap();
+        proxy.call_method("ShowOrgan", &("whatsapp",)).unwrap();
+        proxy.call_method("Dnd", &(true,)).unwrap();
+        let health: String = proxy.call("Health", &()).unwrap();
+        assert_eq!(health, r#"{"dnd":false}"#);
+        assert!(proxy.call_method("ShowOrgan", &("nope",)).is_err());
+        assert_eq!(*calls.lock().unwrap(), ["toggle", "organ whatsapp", "dnd true"]);
+
+        let mut relayed = proxy.receive_signal("MessageRelayed").unwrap();
+        let mut health_changed = proxy.receive_signal("HealthChanged").unwrap();
+        emit_message_relayed(&service, "whatsapp", "Alice").unwrap();
+        emit_health_changed(&service, r#"{"dnd":true}"#).unwrap();
+
+        let args: (String, String) = relayed.next().unwrap().body().deserialize().unwrap();
+        assert_eq!(args, ("whatsapp".to_string(), "Alice".to_string()));
+        let (status,): (String,) = health_changed.next().unwrap().body().deserialize().unwrap();
+        assert_eq!(status, r#"{"dnd":true}"#);
+    }
+
+    #[test]
+    fn relayed_messages_are_signalled() {
+        use crate::testing::{MockBrain, Reply, TestRelay};
+        let Some(bus) = Bus::start() else {
+            eprintln!("dbus-daemon not available — skipping");
+            return;
+        };
+        let service = serve(Some(&bus.address), Box::new(Recorder::default())).unwrap();
+        let client = zbus::blocking::connection::Builder::address(bus.address.as_str()).unwrap().build().unwrap();
+        let proxy = zbus::blocking::Proxy::new(&client, NAME, PATH, NAME).unwrap();
+        let mut signals = proxy.receive_signal("MessageRelayed").unwrap();
+
+        let brain = MockBrain::start();
+        brain.always("/whatsapp/messages", Reply::status(200));
+        let test = TestRelay::start(&brain);
+        let relay = test.messages(Box::new(|organ, body| emit_relayed(&service, organ, body).unwrap()));
+        let message = serde_json::json!({ "id": "3EB0", "chat": "Alice", "text": "hi" });
+        assert!(relay.relay(&test.host, "whatsapp", "/whatsapp/message", &message));
+        test.delivery.shutdown(std::time::Duration::from_secs(5));
+
+        let args: (String, String) = signals.next().unwrap().body().deserialize().unwrap();
+        assert_eq!(args, ("whatsapp".to_string(), "Alice".to_string()));
+    }
+
+    #[test]
+    fn second_owner_is_refused() {
+        let Some(bus) = Bus::start() else {
+            eprintln!("dbus-daemon not available — skipping");
+            return;
+        };
+        let _first = serve(Some(&bus.address), Box::new(Recorder::default())).unwrap();
+        assert!(serve(Some(&bus.address), Box::new(Recorder::default())).is_err());
+    }
+}
@@ -0,0 +1,168 @@
+//! Message dedup — the same message relayed once, not once per render.
+//!
+//! An organ's page can report one message several times: delivery ticks,
+//! edits, scrolling it back into view. A message that carries an `id`
+//! is remembered (its id and a hash of its body, less the `timestamp` of
+//! when it was relayed) for `relay.dedup_ttl_secs`,
+//! up to `relay.dedup_capacity` ids, oldest first out. The same id with
+//! the same body again is dropped; with a different body it goes out
+//! once more, flagged `"edited": true`. Messages without an id always go
+//! out. The counts are in `lexicon relay-metrics`.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Seen {
+    New,
+    Repeat,
+    Edited,
+    /// No id to go by.
+    Unkeyed,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct DedupStats {
+    pub passed: u64,
+    pub dropped: u64,
+    pub edited: u64,
+    pub unkeyed: u64,
+    /// Ids remembered now.
+    pub tracked: usize,
+}
+
+#[derive(Default)]
+struct Inner {
+    /// Body hash and when each id was last relayed.
+    seen: HashMap<String, (u64, Instant)>,
+    /// Ids in the order they were relayed; an id relayed again (edited)
+    /// has a stale entry here too, skipped when it comes up.
+    order: VecDeque<(String, Instant)>,
+    stats: DedupStats,
+}
+
+#[derive(Default)]
+pub struct Dedup(Mutex<Inner>);
+
+fn hash(body: &serde_json::Value) -> u64 {
+    let mut body = body.clone();
+    if let Some(fields) = body.as_object_mut() {
+        fields.remove("timestamp");
+    }
+    let mut hasher = DefaultHasher::new();
+    body.to_string().hash(&mut hasher);
+    hasher.finish()
+}
+
+impl Dedup {
+    /// Whether `body`, from `organ`, should go out; see the module docs.
+    pub fn check(&self, organ: &str, body: &serde_json::Value, capacity: usize, ttl: Duration, now: Instant) -> Seen {
+        let mut inner = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        inner.expire(capacity.max(1), ttl, now);
+        let Some(id) = body.get("id").and_then(id_text) else {
+            inner.stats.unkeyed += 1;
+            return Seen::Unkeyed;
+        };
+        let key = format!("{organ}/{id}");
+        let digest = hash(body);
+        let seen = match inner.seen.get(&key) {
+            Some((previous, _)) if *previous == digest => {
+                inner.stats.dropped += 1;
+                return Seen::Repeat;
+            }
+            Some(_) => {
+                inner.stats.edited += 1;
+                Seen::Edited
+            }
+            None => {
+                inner.stats.passed += 1;
+                Seen::New
+            }
+        };
+        inner.seen.insert(key.clone(), (digest, now));
+        inner.order.push_back((key, now));
+        inner.expire(capacity.max(1), ttl, now);
+        seen
+    }
+
+    pub fn stats(&self) -> DedupStats {
+        let inner = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        DedupStats { tracked: inner.seen.len(), ..inner.stats.clone() }
+    }
+}
+
+/// Ids come as strings or numbers.
+fn id_text(id: &serde_json::Value) -> Option<String> {
+    match id {
+        serde_json::Value::String(id) if !id.is_empty() => Some(id.clone()),
+        serde_json::Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+impl Inner {
+    fn expire(&mut self, capacity: usize, ttl: Duration, now: Instant) {
+        while let Some((key, at)) = self.order.front() {
+            let current = self.seen.get(key).is_some_and(|(_, last)| last == at);
+            if current && self.seen.len() <= capacity && now.saturating_duration_since(*at) < ttl {
+                break;
+            }
+            if current {
+                self.seen.remove(key);
+            }
+            self.order.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    #[test]
+    fn repeats_drop_and_edits_pass_flagged() {
+        let dedup = Dedup::default();
+        let now = Instant::now();
+        let message = json!({ "id": "ABC", "text": "hi" });
+        assert_eq!(dedup.check("whatsapp", &message, 10, HOUR, now), Seen::New);
+        assert_eq!(dedup.check("whatsapp", &message, 10, HOUR, now), Seen::Repeat);
+        let later = json!({ "id": "ABC", "text": "hi", "timestamp": 5 });
+        assert_eq!(dedup.check("whatsapp", &later, 10, HOUR, now), Seen::Repeat);
+        assert_eq!(dedup.check("telegram", &message, 10, HOUR, now), Seen::New);
+        let edited = json!({ "id": "ABC", "text": "hi!" });
+        assert_eq!(dedup.check("whatsapp", &edited, 10, HOUR, now), Seen::Edited);
+        assert_eq!(dedup.check("whatsapp", &edited, 10, HOUR, now), Seen::Repeat);
+        assert_eq!(dedup.check("whatsapp", &json!({ "text": "hi" }), 10, HOUR, now), Seen::Unkeyed);
+        assert_eq!(dedup.check("whatsapp", &json!({ "id": "", "text": "hi" }), 10, HOUR, now), Seen::Unkeyed);
+        assert_eq!(
+            dedup.stats(),
+            DedupStats { passed: 2, dropped: 3, edited: 1, unkeyed: 2, tracked: 2 }
+        );
+    }
+
+    #[test]
+    fn ids_are_forgotten_past_the_ttl_or_capacity() {
+        let dedup = Dedup::default();
+        let now = Instant::now();
+        for n in 0..5 {
+            dedup.check("whatsapp", &json!({ "id": n }), 3, HOUR, now);
+        }
+        assert_eq!(dedup.stats().tracked, 3);
+        // The oldest went first.
+        assert_eq!(dedup.check("whatsapp", &json!({ "id": 0 }), 3, HOUR, now), Seen::New);
+        assert_eq!(dedup.check("whatsapp", &json!({ "id": 4 }), 3, HOUR, now), Seen::Repeat);
+
+        let later = now + HOUR;
+        assert_eq!(dedup.check("whatsapp", &json!({ "id": 4 }), 3, HOUR, later), Seen::New);
+        assert_eq!(dedup.stats().tracked, 1);
+    }
+}
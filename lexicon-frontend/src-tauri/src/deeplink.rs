@@ -0,0 +1,228 @@
+//! `lexicon://` deep links.
+//!
+//! Links are parsed into a typed `DeepLink`, validated, and dispatched to
+//! the same code paths the IPC commands use. They reach us three ways:
+//! the launch URL of the first process, `deep-link://new-url` from the
+//! plugin, and arguments forwarded by a second launch. WhatsApp links
+//! (see `whatsapp`) take the same route.
+//!
+//! | link                                   | action                 |
+//! |----------------------------------------|------------------------|
+//! | `lexicon://toggle`                     | toggle the main window |
+//! | `lexicon://organ/whatsapp`             | open an organ          |
+//! | `lexicon://whatsapp/chat?name=Alice`   | open a chat in an organ|
+//! | `lexicon://search?q=weather`           | search on the canvas   |
+//! | `whatsapp://send?phone=…`, `wa.me/…`   | open a WhatsApp chat   |
+
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager, Url};
+
+use crate::whatsapp::ChatTarget;
+
+pub const SCHEME: &str = "lexicon";
+/// How long `open-chat` waits for a new organ window.
+const ORGAN_WAIT: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// Longest query string we accept from a link.
+const MAX_QUERY_LEN: usize = 256;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeepLink {
+    Toggle,
+    OpenOrgan { id: String },
+    OpenChat { organ: String, query: String },
+    Search { query: String },
+    WhatsAppChat(ChatTarget),
+}
+
+/// What to do with links that arrive while the presentation guard is on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[serde(rename_all = "lowercase")]
+pub enum GuardedLinkPolicy {
+    /// Hold them and run them when the guard is released.
+    #[default]
+    Queue,
+    /// Drop them.
+    Reject,
+}
+
+/// Links held back by the presentation guard.
+static QUEUED: Mutex<Vec<DeepLink>> = Mutex::new(Vec::new());
+
+#[derive(Clone, Serialize)]
+struct OpenChat<'a> {
+    query: &'a str,
+}
+
+fn valid_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= 64 && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn clean_query(raw: &str) -> Option<String> {
+    let query: String = raw.chars().filter(|c| !c.is_control()).collect();
+    let query = query.trim();
+    (!query.is_empty() && query.len() <= MAX_QUERY_LEN).then(|| query.to_string())
+}
+
+impl DeepLink {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let url = Url::parse(raw).map_err(|e| format!("not a URL: {e}"))?;
+        if let Some(target) = ChatTarget::from_url(&url) {
+            return target.map(DeepLink::WhatsAppChat);
+        }
+        if url.scheme() != SCHEME {
+            return Err(format!("unexpected scheme '{}'", url.scheme()));
+        }
+        let host = url.host_str().unwrap_or_default();
+        let segments: Vec<&str> = url.path_segments().map(|s| s.filter(|p| !p.is_empty()).collect()).unwrap_or_default();
+        let param = |key: &str| url.query_pairs().find(|(k, _)| k == key).map(|(_, v)| v.into_owned());
+
+        let link = match (host, segments.as_slice()) {
+            ("toggle", []) => DeepLink::Toggle,
+            ("organ", [id]) if valid_id(id) => DeepLink::OpenOrgan { id: id.to_string() },
+            ("search", []) => DeepLink::Search {
+                query: param("q").as_deref().and_then(clean_query).ok_or("search needs a q parameter")?,
+            },
+            (organ, ["chat"]) if valid_id(organ) => DeepLink::OpenChat {
+                organ: organ.to_string(),
+                query: param("name").as_deref().and_then(clean_query).ok_or("chat needs a name parameter")?,
+            },
+            _ => return Err(format!("unrecognised link '{raw}'")),
+        };
+        Ok(link)
+    }
+}
+
+/// Whether a launch argument is a link for us rather than a CLI word.
+pub fn is_link(arg: &str) -> bool {
+    arg.starts_with("lexicon:") || crate::whatsapp::is_link(arg)
+}
+
+/// Parse and dispatch a raw link. Malformed links are logged and ignored.
+pub fn handle(app: &tauri::AppHandle, raw: &str) {
+    match DeepLink::parse(raw) {
+        Ok(link) => dispatch(app, link),
+        Err(e) => tracing::warn!("deep link ignored: {e}"),
+    }
+}
+
+pub fn dispatch(app: &tauri::AppHandle, link: DeepLink) {
+    if crate::presentation::is_active() {
+        match crate::config::current(app).deep_links.while_guarded {
+            GuardedLinkPolicy::Queue => {
+                tracing::info!("deep link queued until presentation guard is released: {link:?}");
+                QUEUED.lock().unwrap_or_else(|e| e.into_inner()).push(link);
+            }
+            GuardedLinkPolicy::Reject => tracing::warn!("deep link rejected — presentation guard is on"),
+        }
+        return;
+    }
+
+    let result = match &link {
+        DeepLink::Toggle => {
+            crate::toggle_main(app);
+            Ok(())
+        }
+        DeepLink::OpenOrgan { id } => crate::organs::open(app, id).map(|_| ()),
+        DeepLink::OpenChat { organ, query } => crate::organs::open(app, organ).map(|_| {
+            // A new window may still be building; the event waits for it.
+            let (app, organ, query) = (app.clone(), organ.clone(), query.clone());
+            std::thread::spawn(move || match crate::organs::wait_ready(&app, &organ, ORGAN_WAIT) {
+                Ok(()) => {
+                    let label = crate::organs::window_label(&organ);
+                    let _ = app.emit_to(label, "organ://open-chat", OpenChat { query: &query });
+                }
+                Err(e) => tracing::warn!("deep link chat not opened: {e}"),
+            });
+        }),
+        DeepLink::Search { query } => {
+            if let Some(main) = app.get_webview_window("main") {
+                crate::show_window(&main);
+            }
+            app.emit_to("main", "lexicon://search", query).map_err(|e| e.to_string())
+        }
+        DeepLink::WhatsAppChat(target) => {
+            // May wait for the organ to load.
+            let app = app.clone();
+            let target = target.clone();
+            std::thread::spawn(move || {
+                if let Err(e) = crate::whatsapp::open_chat(&app, &target) {
+                    tracing::warn!("whatsapp chat {} failed: {e}", target.phone);
+                }
+            });
+            Ok(())
+        }
+    };
+    match result {
+        Ok(()) => tracing::info!("deep link → {link:?}"),
+        Err(e) => tracing::warn!("deep link {link:?} failed: {e}"),
+    }
+}
+
+/// Run the links the presentation guard held back.
+pub fn drain_queued(app: &tauri::AppHandle) {
+    let queued = std::mem::take(&mut *QUEUED.lock().unwrap_or_else(|e| e.into_inner()));
+    for link in queued {
+        dispatch(app, link);
+    }
+}
+
+/// Register the bundled schemes with the OS. Linux and Windows only, and
+/// it shells out, so it runs after first paint.
+pub fn register(app: &tauri::AppHandle) {
+    #[cfg(any(target_os = "linux", windows))]
+    {
+        use tauri_plugin_deep_link::DeepLinkExt;
+
+        if let Err(e) = app.deep_link().register_all() {
+            tracing::warn!("could not register {SCHEME}:// handler: {e}");
+        }
+    }
+    #[cfg(not(any(target_os = "linux", windows)))]
+    let _ = app;
+}
+
+/// Hook up incoming links, including the one we were launched with.
+pub fn init(app: &tauri::AppHandle) {
+    use tauri_plugin_deep_link::DeepLinkExt;
+
+    let app_handle = app.clone();
+    app.deep_link().on_open_url(move |event| {
+        for url in event.urls() {
+            handle(&app_handle, url.as_str());
+        }
+    });
+
+    // Launched by a link: the plugin has already read it from argv.
+    if let Ok(Some(urls)) = app.deep_link().get_current() {
+        for url in urls {
+            handle(app, url.as_str());
+        }
+    }
+    // The plugin only knows the bundled schemes; `whatsapp:` is ours.
+    #[cfg(desktop)]
+    for arg in std::env::args().skip(1).filter(|a| crate::whatsapp::is_link(a)) {
+        handle(app, &arg);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn any_input_parses_or_is_rejected(raw in any::<String>()) {
+            let _ = DeepLink::parse(&raw);
+        }
+
+        #[test]
+        fn any_lexicon_url_parses_or_is_rejected(rest in "[a-z0-9/?=&%#.+-]{0,40}") {
+            let _ = DeepLink::parse(&format!("{SCHEME}://{rest}"));
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! Discord — the web app in the `discord` organ, its DMs and mentions
+//! relayed to the Brain.
+//!
+//! Discord only renders the text of the channel that is open, but it
+//! raises a desktop notification for every DM and every mention, which is
+//! exactly what the Brain should hear about. So the monitor run on every
+//! page load stands in for the page's `Notification`: permission is
+//! always granted, nothing is shown (the relay notifies instead, see
+//! `notify`), and each notification is reported back through
+//! `organs::REPORT_HOST`. Its title is the author, plus `(#channel,
+//! server)` for a mention in a server; that is split into fields here and
+//! the message goes through `relay::message` to `/discord/message` as a
+//! [`DiscordMessage`], with the notification's tag (Discord's message id)
+//! as its `id`.
+//!
+//! What Discord's own settings mute never raises a notification, so it
+//! isn't relayed either.
+
+use serde::{Deserialize, Serialize};
+
+use crate::organs::{clean, now_ms};
+
+pub const ORGAN: &str = "discord";
+const MESSAGE_PATH: &str = "/discord/message";
+
+/// Longest message text relayed; the rest is cut.
+const MAX_TEXT_LEN: usize = 2000;
+/// Longest author, channel, server or id accepted.
+const MAX_NAME_LEN: usize = 256;
+
+/// A notification, as the monitor reports it.
+#[derive(Debug, Deserialize)]
+struct Notification {
+    #[serde(default)]
+    id: String,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    text: String,
+}
+
+/// A DM or mention, as relayed to `/discord/message`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiscordMessage {
+    /// Discord's message id.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub from: String,
+    pub text: String,
+    /// A direct message, not a mention in a server.
+    pub dm: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+    /// The rest is the relay's, not the page's.
+    #[serde(default)]
+    pub organ: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    /// Unix milliseconds it was relayed.
+    #[serde(default)]
+    pub timestamp: u64,
+}
+
+/// The stand-in `Notification`, run by `organs::monitor`.
+const MONITOR: &str = r#"
+  function Notification(title, options) {
+    options = options || {};
+    report('message', { id: options.tag || '', title: String(title), text: options.body || '' });
+  }
+  Notification.permission = 'granted';
+  Notification.requestPermission = function (done) {
+    if (done) done('granted');
+    return Promise.resolve('granted');
+  };
+  Notification.prototype.close = function () {};
+  Notification.prototype.addEventListener = function () {};
+  Notification.prototype.removeEventListener = function () {};
+  window.Notification = Notification;"#;
+
+/// A notification title: the author, and for a mention in a server the
+/// channel and server, as in "Ana (#general, Rust)".
+fn split_title(title: &str) -> (&str, Option<(&str, &str)>) {
+    let place = title.strip_suffix(')').and_then(|rest| rest.rsplit_once(" (#"));
+    match place.and_then(|(author, place)| Some((author, place.split_once(", ")?))) {
+        Some((author, (channel, server))) => (author, Some((channel, server))),
+        None => (title, None),
+    }
+}
+
+/// The message relayed for a reported notification, of `account`, or why
+/// it isn't one.
+fn parse_notification(
+    notification: Notification,
+    account: Option<&str>,
+    now_ms: u64,
+) -> Result<DiscordMessage, String> {
+    let title = clean(&notification.title, MAX_NAME_LEN * 3).ok_or("a notification needs a title")?;
+    let text = clean(&notification.text, MAX_TEXT_LEN).ok_or("a notification needs a text")?;
+    let (author, place) = split_title(&title);
+    let short = |s: &str| s.chars().take(MAX_NAME_LEN).collect::<String>();
+    Ok(DiscordMessage {
+        id: clean(&notification.id, MAX_NAME_LEN),
+        from: short(author),
+        text,
+        dm: place.is_none(),
+        channel: place.map(|(channel, _)| short(channel)),
+        server: place.map(|(_, server)| short(server)),
+        organ: ORGAN.into(),
+        account: account.map(String::from),
+        timestamp: now_ms,
+    })
+}
+
+/// Install the notification monitor in `id`'s page, if it is Discord.
+pub fn watch(app: &tauri::AppHandle, id: &str) {
+    crate::organs::monitor(app, id, ORGAN, "__lexiconNotifications", MONITOR);
+}
+
+/// Organ `id`'s monitor reported `raw`, a notification as JSON.
+pub fn reported(app: &tauri::AppHandle, id: &str, raw: &str) {
+    let account = crate::organs::account(app, id);
+    let notification = serde_json::from_str(raw).map_err(|e| e.to_string());
+    match notification.and_then(|n| parse_notification(n, account.as_deref(), now_ms())) {
+        Ok(message) => {
+            crate::relay::message(app, id, MESSAGE_PATH, &message);
+        }
+        Err(e) => tracing::warn!("organ {id} reported a notification that isn't one ({e}) — dropped"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(report: serde_json::Value) -> Result<serde_json::Value, String> {
+        let notification = serde_json::from_value(report).map_err(|e| e.to_string())?;
+        Ok(serde_json::to_value(parse_notification(notification, None, 3)?).unwrap())
+    }
+
+    #[test]
+    fn dms_and_mentions_are_told_apart() {
+        assert_eq!(split_title("Ana"), ("Ana", None));
+        assert_eq!(split_title("Ana (#general, Rust)"), ("Ana", Some(("general", "Rust"))));
+        assert_eq!(split_title("Ana (away)"), ("Ana (away)", None));
+
+        let mention = json!({ "id": "1234", "title": "Ana (#general, Rust, Inc)", "text": "@you look" });
+        assert_eq!(
+            parse(mention).unwrap(),
+            json!({
+                "id": "1234",
+                "from": "Ana",
+                "text": "@you look",
+                "dm": false,
+                "channel": "general",
+                "server": "Rust, Inc",
+                "organ": "discord",
+                "timestamp": 3,
+            })
+        );
+        let dm = parse(json!({ "title": "Ana", "text": "hi", "id": "" })).unwrap();
+        assert_eq!(dm, json!({ "from": "Ana", "text": "hi", "dm": true, "organ": "discord", "timestamp": 3 }));
+        assert!(parse(json!({ "title": "Ana" })).is_err());
+    }
+}
@@ -0,0 +1,27 @@
+//! Do-not-disturb — suppresses anything that would ask for attention.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tauri::Emitter;
+
+static DND: AtomicBool = AtomicBool::new(false);
+
+/// The user-facing DND switch, as shown in the tray checkmark.
+pub fn is_enabled() -> bool {
+    DND.load(Ordering::Relaxed)
+}
+
+#[tauri::command]
+pub fn set_dnd(app: tauri::AppHandle, enabled: bool) -> bool {
+    if DND.swap(enabled, Ordering::Relaxed) != enabled {
+        tracing::info!("do-not-disturb {}", if enabled { "on" } else { "off" });
+        let _ = app.emit("dnd://changed", enabled);
+        crate::health::announce(&app);
+    }
+    enabled
+}
+
+#[tauri::command]
+pub fn get_dnd() -> bool {
+    is_enabled()
+}
@@ -0,0 +1,158 @@
+//! Email — Gmail in the `email` organ, its new mail relayed to the Brain.
+//!
+//! A monitor run on every page load watches the inbox list. Once the page
+//! is up (the compose button is there), the unread rows it shows are taken
+//! as already known; each unread thread that turns up after that is
+//! reported back through `organs::REPORT_HOST` with its sender, subject
+//! and snippet, and relayed through `relay::message` to `/email/message`
+//! as an [`Email`], deduplicated by thread id and filtered like any message. Only what the
+//! list shows is read: no mail is opened, so nothing is marked read.
+//!
+//! Its status goes to `/email/status` like any organ's; a page on Google's
+//! sign-in counts as waiting for a login.
+
+use serde::{Deserialize, Serialize};
+
+use crate::organs::{clean, now_ms};
+
+pub const ORGAN: &str = "email";
+const MESSAGE_PATH: &str = "/email/message";
+
+/// Longest subject or sender relayed.
+const MAX_FIELD_LEN: usize = 256;
+/// Longest snippet relayed; Gmail's own are shorter.
+const MAX_SNIPPET_LEN: usize = 500;
+
+/// An email the monitor reported, as relayed to `/email/message`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Email {
+    /// Gmail's thread id.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub from: String,
+    /// The sender's address, if the list showed one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    #[serde(default)]
+    pub subject: String,
+    #[serde(default)]
+    pub snippet: String,
+    /// The rest is the relay's, not the page's.
+    #[serde(default)]
+    pub organ: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    /// Unix milliseconds it was relayed.
+    #[serde(default)]
+    pub timestamp: u64,
+}
+
+/// The inbox scan, run by `organs::monitor`.
+const MONITOR: &str = r#"
+  var seen = null, timer = null;
+  function text(el, selector) {
+    var node = el.querySelector(selector);
+    return node ? node.textContent.trim() : '';
+  }
+  function scan() {
+    if (!document.querySelector('[gh=cm]')) return;
+    var rows = document.querySelectorAll('tr.zA.zE');
+    var baseline = seen === null;
+    if (baseline) seen = {};
+    rows.forEach(function (row) {
+      var thread = row.querySelector('[data-legacy-thread-id]');
+      var id = thread ? thread.getAttribute('data-legacy-thread-id') : '';
+      if (!id || seen[id]) return;
+      seen[id] = true;
+      if (baseline) return;
+      var sender = row.querySelector('.yW [email]');
+      report('message', {
+        id: id,
+        from: sender ? (sender.getAttribute('name') || sender.textContent.trim()) : text(row, '.yW'),
+        address: sender ? sender.getAttribute('email') : '',
+        subject: text(row, '.bog'),
+        snippet: text(row, '.y2').replace(/^[\s–-]+/, '')
+      });
+    });
+  }
+  new MutationObserver(function () {
+    if (!timer) timer = setTimeout(function () { timer = null; scan(); }, 500);
+  }).observe(document.documentElement, { childList: true, subtree: true });
+  scan();"#;
+
+/// The email relayed for a reported one, of `account`, or why it isn't
+/// one.
+fn parse_email(email: Email, account: Option<&str>, now_ms: u64) -> Result<Email, String> {
+    let subject = clean(&email.subject, MAX_FIELD_LEN);
+    let snippet = clean(&email.snippet, MAX_SNIPPET_LEN);
+    if subject.is_none() && snippet.is_none() {
+        return Err("an email needs a subject or a snippet".into());
+    }
+    Ok(Email {
+        id: email.id.and_then(|id| clean(&id, MAX_FIELD_LEN)),
+        from: clean(&email.from, MAX_FIELD_LEN).ok_or("an email needs a sender")?,
+        address: email.address.and_then(|a| clean(&a, MAX_FIELD_LEN)).filter(|a| a.contains('@')),
+        subject: subject.unwrap_or_default(),
+        snippet: snippet.unwrap_or_default(),
+        organ: ORGAN.into(),
+        account: account.map(String::from),
+        timestamp: now_ms,
+    })
+}
+
+/// Install the inbox monitor in `id`'s page, if it is the email organ.
+pub fn watch(app: &tauri::AppHandle, id: &str) {
+    crate::organs::monitor(app, id, ORGAN, "__lexiconMail", MONITOR);
+}
+
+/// Organ `id`'s monitor reported `raw`, an [`Email`] as JSON.
+pub fn reported(app: &tauri::AppHandle, id: &str, raw: &str) {
+    let account = crate::organs::account(app, id);
+    let email = serde_json::from_str(raw).map_err(|e| e.to_string());
+    match email.and_then(|e| parse_email(e, account.as_deref(), now_ms())) {
+        Ok(email) => {
+            crate::relay::message(app, id, MESSAGE_PATH, &email);
+        }
+        Err(e) => tracing::warn!("organ {id} reported an email that isn't one ({e}) — dropped"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parse(report: serde_json::Value) -> Result<serde_json::Value, String> {
+        let email = serde_json::from_value(report).map_err(|e| e.to_string())?;
+        Ok(serde_json::to_value(parse_email(email, Some("ana@example.com"), 9)?).unwrap())
+    }
+
+    #[test]
+    fn reported_emails_are_checked_and_cleaned() {
+        let email = json!({
+            "id": "18f2a",
+            "from": "Ana Silva",
+            "address": "ana@example.com",
+            "subject": " Lunch?\u{7} ",
+            "snippet": "Are you free on Friday",
+        });
+        assert_eq!(
+            parse(email).unwrap(),
+            json!({
+                "id": "18f2a",
+                "from": "Ana Silva",
+                "address": "ana@example.com",
+                "subject": "Lunch?",
+                "snippet": "Are you free on Friday",
+                "organ": "email",
+                "account": "ana@example.com",
+                "timestamp": 9,
+            })
+        );
+        let bare = parse(json!({ "from": "Ana", "address": "nope", "snippet": "hi" })).unwrap();
+        assert_eq!((bare["subject"].as_str(), bare.get("address")), (Some(""), None));
+        assert!(parse(json!({ "from": "Ana" })).is_err());
+        assert!(parse(json!({ "subject": "Lunch?" })).is_err());
+    }
+}
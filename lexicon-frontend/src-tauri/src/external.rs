@@ -0,0 +1,234 @@
+//! Links that leave Lexicon for the system browser.
+//!
+//! Every external open goes through [`request`]: links clicked on the
+//! canvas (`open_external`) and navigations an organ isn't allowed to make
+//! itself (see `organs`). Only http(s) and the schemes listed in
+//! `external.allowed_schemes` are opened. Links on known shorteners are
+//! expanded first so the user sees where they really lead.
+//!
+//! Unless `confirm = false` or the destination is on `trusted_domains`,
+//! the main window gets `external://confirm` with the full URL and the
+//! destination, and nothing opens until it answers with
+//! `confirm_external`. Every open, refusal and decline is appended to
+//! `external-opens.log` in the app data dir.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager, Url};
+use tauri_plugin_opener::OpenerExt;
+
+/// Hosts whose links only redirect somewhere else.
+const SHORTENERS: &[&str] = &[
+    "bit.ly", "buff.ly", "cutt.ly", "goo.gl", "is.gd", "lnkd.in", "ow.ly", "rb.gy", "rebrand.ly", "shorturl.at",
+    "t.co", "t.ly", "tiny.cc", "tinyurl.com",
+];
+/// Redirects followed while expanding one link.
+const MAX_HOPS: usize = 5;
+/// How long a confirmation stays answerable.
+const CONFIRM_TTL: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+#[serde(default)]
+pub struct ExternalConfig {
+    /// Ask the main window before opening anything not on `trusted_domains`.
+    pub confirm: bool,
+    /// Domains (and their subdomains) that open without asking.
+    pub trusted_domains: Vec<String>,
+    /// Schemes besides http(s) that may be opened, e.g. "mailto".
+    pub allowed_schemes: Vec<String>,
+    /// Resolve known shorteners before confirming.
+    pub expand_shorteners: bool,
+}
+
+impl Default for ExternalConfig {
+    fn default() -> Self {
+        Self {
+            confirm: true,
+            trusted_domains: Vec::new(),
+            allowed_schemes: Vec::new(),
+            expand_shorteners: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum OpenResult {
+    Opened,
+    /// Waiting for `confirm_external` with this id.
+    Pending { id: u64 },
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Confirm {
+    id: u64,
+    url: String,
+    /// Where the link ends up after expanding shorteners; same as `url`
+    /// when there was nothing to expand.
+    destination: String,
+    /// "main" or the organ id the link came from.
+    origin: String,
+}
+
+struct Pending {
+    destination: Url,
+    url: String,
+    origin: String,
+    asked: Instant,
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+static PENDING: Mutex<BTreeMap<u64, Pending>> = Mutex::new(BTreeMap::new());
+
+fn is_shortener(url: &Url) -> bool {
+    url.host_str().is_some_and(|host| SHORTENERS.contains(&host.trim_start_matches("www.")))
+}
+
+fn on_domain(url: &Url, domains: &[String]) -> bool {
+    let Some(host) = url.host_str() else { return false };
+    domains.iter().map(|d| d.trim_start_matches('.')).any(|d| {
+        host.eq_ignore_ascii_case(d) || host.to_ascii_lowercase().ends_with(&format!(".{}", d.to_ascii_lowercase()))
+    })
+}
+
+/// Follow a shortener's redirects by hand, without fetching the target.
+/// Falls back to what we had on any error.
+fn expand(url: &Url) -> Url {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(Duration::from_secs(5)))
+        .max_redirects(0)
+        .http_status_as_error(false)
+        .build()
+        .into();
+    let mut current = url.clone();
+    for _ in 0..MAX_HOPS {
+        if !is_shortener(&current) {
+            break;
+        }
+        let Ok(response) = agent.head(current.as_str()).call() else { break };
+        if !response.status().is_redirection() {
+            break;
+        }
+        let next = response.headers().get("location").and_then(|l| l.to_str().ok()).and_then(|l| current.join(l).ok());
+        match next {
+            Some(next) if matches!(next.scheme(), "http" | "https") => current = next,
+            _ => break,
+        }
+    }
+    current
+}
+
+fn audit(app: &tauri::AppHandle, action: &str, url: &str, destination: Option<&Url>, origin: &str) {
+    tracing::info!("external {action}: {url} (from {origin})");
+    let Ok(dir) = app.path().app_data_dir() else { return };
+    let _ = std::fs::create_dir_all(&dir);
+    let line = serde_json::json!({
+        "at": chrono::Local::now().to_rfc3339(),
+        "action": action,
+        "url": url,
+        "destination": destination.map(Url::as_str),
+        "origin": origin,
+    });
+    let file = std::fs::OpenOptions::new().create(true).append(true).open(dir.join("external-opens.log"));
+    if let Ok(mut file) = file {
+        let _ = writeln!(file, "{line}");
+    }
+}
+
+fn open(app: &tauri::AppHandle, url: &str, destination: &Url, origin: &str) -> Result<(), String> {
+    app.opener().open_url(destination.as_str(), None::<&str>).map_err(|e| {
+        audit(app, "failed", url, Some(destination), origin);
+        e.to_string()
+    })?;
+    audit(app, "opened", url, Some(destination), origin);
+    Ok(())
+}
+
+/// Vet a link and open it, or ask the main window first. May block on
+/// shortener expansion; call it off the main thread.
+pub fn request(app: &tauri::AppHandle, raw: &str, origin: &str) -> Result<OpenResult, String> {
+    let config = crate::config::current(app).external;
+    let url = match Url::parse(raw) {
+        Ok(url) => url,
+        Err(e) => {
+            audit(app, "refused", raw, None, origin);
+            return Err(format!("not a URL: {e}"));
+        }
+    };
+    let web = matches!(url.scheme(), "http" | "https");
+    if !web && !config.allowed_schemes.iter().any(|s| s.eq_ignore_ascii_case(url.scheme())) {
+        audit(app, "refused", raw, None, origin);
+        return Err(format!("'{}:' links are not allowed", url.scheme()));
+    }
+    if crate::presentation::is_active() {
+        audit(app, "refused", raw, None, origin);
+        return Err("presentation guard is on".into());
+    }
+
+    let destination = if web && config.expand_shorteners && is_shortener(&url) { expand(&url) } else { url.clone() };
+    if !config.confirm || on_domain(&destination, &config.trusted_domains) {
+        open(app, raw, &destination, origin)?;
+        return Ok(OpenResult::Opened);
+    }
+
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let confirm = Confirm { id, url: raw.to_string(), destination: destination.to_string(), origin: origin.to_string() };
+    {
+        let mut pending = PENDING.lock().unwrap_or_else(|e| e.into_inner());
+        pending.retain(|_, p| p.asked.elapsed() < CONFIRM_TTL);
+        pending.insert(id, Pending { destination, url: confirm.url.clone(), origin: confirm.origin.clone(), asked: Instant::now() });
+    }
+    if let Some(main) = app.get_webview_window("main") {
+        crate::show_window(&main);
+    }
+    app.emit_to("main", "external://confirm", &confirm).map_err(|e| e.to_string())?;
+    Ok(OpenResult::Pending { id })
+}
+
+/// `request` on a thread of its own, for callers that can't wait.
+pub fn request_detached(app: &tauri::AppHandle, url: &Url, origin: &str) {
+    let app = app.clone();
+    let url = url.to_string();
+    let origin = origin.to_string();
+    std::thread::spawn(move || {
+        if let Err(e) = request(&app, &url, &origin) {
+            tracing::warn!("external link not opened: {e}");
+        }
+    });
+}
+
+// ── Commands ───────────────────────────────────────────────────
+
+#[tauri::command]
+pub async fn open_external(window: tauri::WebviewWindow, app: tauri::AppHandle, url: String) -> Result<OpenResult, String> {
+    if window.label() != "main" {
+        return Err("only the main window can open external links".into());
+    }
+    tauri::async_runtime::spawn_blocking(move || request(&app, &url, "main"))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Answer an `external://confirm`.
+#[tauri::command]
+pub fn confirm_external(window: tauri::WebviewWindow, app: tauri::AppHandle, id: u64, open: bool) -> Result<(), String> {
+    if window.label() != "main" {
+        return Err("only the main window can confirm external links".into());
+    }
+    let pending = PENDING.lock().unwrap_or_else(|e| e.into_inner()).remove(&id);
+    let Some(pending) = pending.filter(|p| p.asked.elapsed() < CONFIRM_TTL) else {
+        return Err("that link has expired".into());
+    };
+    if open {
+        self::open(&app, &pending.url, &pending.destination, &pending.origin)
+    } else {
+        audit(&app, "declined", &pending.url, Some(&pending.destination), &pending.origin);
+        Ok(())
+    }
+}
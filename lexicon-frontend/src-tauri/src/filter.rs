@@ -0,0 +1,215 @@
+//! Relay filters — chats and messages the Brain never hears about.
+//!
+//! `relay-filters.json` in the app data dir holds the rules, set with
+//! `set_relay_filters` and applied to every message `relay::message`
+//! sends, before dedup:
+//!
+//! - `mute`: chats to drop, by id (`chat_id`) or name (`chat`),
+//!   exactly or with `*` and `?` wildcards;
+//! - `mute_groups`: drop every group chat (`"group": true`, or a
+//!   `…@g.us` id);
+//! - `allow`: if not empty, only these chats go through;
+//! - `drop_matching`: regexes; a message whose `text` or `caption`
+//!   matches one is dropped.
+//!
+//! Messages filtered out are counted (`filtered` in `lexicon
+//! relay-metrics`) and neither sent nor spooled. `test_relay_filter`
+//! answers whether a message would go through.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tauri::Manager;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+#[serde(default)]
+pub struct RelayFilters {
+    pub mute: Vec<String>,
+    pub mute_groups: bool,
+    pub allow: Vec<String>,
+    pub drop_matching: Vec<String>,
+}
+
+/// The rules with their regexes built.
+#[derive(Debug, Default)]
+struct Rules {
+    filters: RelayFilters,
+    drop_matching: Vec<Regex>,
+}
+
+impl Rules {
+    fn new(filters: RelayFilters) -> Result<Self, String> {
+        let drop_matching = filters
+            .drop_matching
+            .iter()
+            .map(|pattern| Regex::new(pattern).map_err(|e| format!("bad pattern '{pattern}': {e}")))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { filters, drop_matching })
+    }
+
+    /// Whether `message` goes through.
+    fn pass(&self, message: &serde_json::Value) -> bool {
+        let field = |key: &str| message.get(key).and_then(|v| v.as_str());
+        let chats: Vec<&str> = [field("chat_id"), field("chat")].into_iter().flatten().collect();
+        let matches = |patterns: &[String]| patterns.iter().any(|p| chats.iter().any(|chat| glob(p, chat)));
+        let group = message.get("group").and_then(|v| v.as_bool()).unwrap_or(false)
+            || field("chat_id").is_some_and(|id| id.ends_with("@g.us"));
+        if matches(&self.filters.mute) || (self.filters.mute_groups && group) {
+            return false;
+        }
+        if !self.filters.allow.is_empty() && !matches(&self.filters.allow) {
+            return false;
+        }
+        let texts: Vec<&str> = [field("text"), field("caption")].into_iter().flatten().collect();
+        !self.drop_matching.iter().any(|re| texts.iter().any(|text| re.is_match(text)))
+    }
+}
+
+/// `*` is any run of characters, `?` any one; the rest matches itself,
+/// ignoring case.
+fn glob(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Where the last `*` was, and the text position it now covers up to.
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((sp, st)) => {
+                    p = sp + 1;
+                    t = st + 1;
+                    star = Some((sp, st + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[derive(Default)]
+pub struct Filters {
+    rules: RwLock<Rules>,
+    filtered: AtomicU64,
+}
+
+impl Filters {
+    /// Whether `message` goes through; counted if not.
+    pub fn pass(&self, message: &serde_json::Value) -> bool {
+        let pass = self.rules.read().unwrap_or_else(|e| e.into_inner()).pass(message);
+        if !pass {
+            self.filtered.fetch_add(1, Ordering::Relaxed);
+        }
+        pass
+    }
+
+    /// Messages filtered out since startup.
+    pub fn filtered(&self) -> u64 {
+        self.filtered.load(Ordering::Relaxed)
+    }
+}
+
+fn path(app: &tauri::AppHandle) -> Option<PathBuf> {
+    app.path().app_data_dir().ok().map(|dir| dir.join("relay-filters.json"))
+}
+
+/// Load the saved rules. A file that doesn't parse, or holds a bad
+/// pattern, is logged and ignored.
+pub fn init(app: &tauri::AppHandle) {
+    let saved = path(app).and_then(|p| std::fs::read_to_string(p).ok()).map(|text| {
+        serde_json::from_str::<RelayFilters>(&text).map_err(|e| e.to_string()).and_then(Rules::new)
+    });
+    let rules = match saved {
+        Some(Ok(rules)) => rules,
+        Some(Err(e)) => {
+            tracing::warn!("relay filters unreadable ({e}) — relaying everything");
+            Rules::default()
+        }
+        None => Rules::default(),
+    };
+    app.manage(Filters { rules: RwLock::new(rules), filtered: AtomicU64::new(0) });
+}
+
+#[tauri::command]
+pub fn get_relay_filters(app: tauri::AppHandle) -> RelayFilters {
+    app.state::<Filters>().rules.read().unwrap_or_else(|e| e.into_inner()).filters.clone()
+}
+
+/// Replace the rules with `filters` (a JSON [`RelayFilters`]) and save them.
+#[tauri::command]
+pub fn set_relay_filters(window: tauri::WebviewWindow, app: tauri::AppHandle, filters: String) -> Result<RelayFilters, String> {
+    if window.label() != "main" {
+        return Err("only the main window can change the relay filters".into());
+    }
+    let filters: RelayFilters = serde_json::from_str(&filters).map_err(|e| format!("bad relay filters: {e}"))?;
+    let rules = Rules::new(filters.clone())?;
+    let path = path(&app).ok_or("no app data dir")?;
+    path.parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|()| std::fs::write(&path, serde_json::to_vec_pretty(&filters).unwrap_or_default()))
+        .map_err(|e| format!("could not save {}: {e}", path.display()))?;
+    *app.state::<Filters>().rules.write().unwrap_or_else(|e| e.into_inner()) = rules;
+    tracing::info!("relay filters updated");
+    Ok(filters)
+}
+
+/// Whether the current rules would relay `payload`. Not counted.
+#[tauri::command]
+pub fn test_relay_filter(app: tauri::AppHandle, payload: serde_json::Value) -> bool {
+    app.state::<Filters>().rules.read().unwrap_or_else(|e| e.into_inner()).pass(&payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rules(filters: serde_json::Value) -> Rules {
+        Rules::new(serde_json::from_value(filters).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn globs_match_whole_names_ignoring_case() {
+        assert!(glob("Family*", "family group"));
+        assert!(glob("*@g.us", "12345-678@g.us"));
+        assert!(glob("a?c", "ABC"));
+        assert!(glob("*a*b*", "xaxxbx"));
+        assert!(!glob("Family", "Family group"));
+        assert!(!glob("a?c", "ac"));
+    }
+
+    #[test]
+    fn chats_are_muted_allowed_and_texts_dropped() {
+        let family = json!({ "chat": "Family ❤", "chat_id": "1203@g.us", "text": "dinner?" });
+        let ana = json!({ "chat": "Ana", "chat_id": "4915550001@c.us", "text": "your code is 123456" });
+
+        let muted = rules(json!({ "mute": ["family*"] }));
+        assert!(!muted.pass(&family) && muted.pass(&ana));
+        assert!(!rules(json!({ "mute_groups": true })).pass(&family));
+        assert!(!rules(json!({ "mute": ["*@g.us"] })).pass(&family));
+
+        let allow = rules(json!({ "allow": ["Ana"] }));
+        assert!(allow.pass(&ana) && !allow.pass(&family));
+        assert!(!allow.pass(&json!({ "text": "no chat" })));
+
+        let codes = rules(json!({ "drop_matching": [r"\bcode is \d{6}\b"] }));
+        assert!(!codes.pass(&ana) && codes.pass(&family));
+        assert!(!codes.pass(&json!({ "chat": "Bank", "caption": "Your code is 654321" })));
+
+        assert!(Rules::new(RelayFilters { drop_matching: vec!["(".into()], ..RelayFilters::default() }).is_err());
+        assert!(Rules::default().pass(&family));
+    }
+}
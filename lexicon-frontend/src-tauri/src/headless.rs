@@ -0,0 +1,67 @@
+//! Headless relay mode — `lexicon --headless`, for an always-on box that
+//! only relays WhatsApp to the Brain and never shows anything.
+//!
+//! The main canvas isn't built, nor the tray or the global shortcuts.
+//! Every WhatsApp account's organ starts at launch, whatever
+//! `organs.autostart_whatsapp` says, in a 1×1 window far off screen:
+//! some compositors (GNOME on Wayland) don't run a hidden window's
+//! scripts, so it stays up where nobody sees it, and what would show,
+//! hide, raise or fullscreen a window leaves it alone. Organs count as in
+//! the background and aren't suspended when idle (see `idle`).
+//!
+//! `toggle_window` and anything else that would bring up the overlay
+//! (`lexicon toggle | show | hide`, the Brain's `show_main`) is refused.
+//! The rest stays reachable for supervising it remotely: `lexicon status`,
+//! `lexicon organ whatsapp status`, `lexicon relay-metrics`, the control
+//! channel and the metrics endpoint. With no UI to look at, the log file
+//! (see `logging`) is where everything ends up.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub const FLAG: &str = "--headless";
+
+/// Where organ windows go, and how big they are.
+pub const POSITION: (f64, f64) = (-32000.0, -32000.0);
+pub const SIZE: (f64, f64) = (1.0, 1.0);
+
+static HEADLESS: AtomicBool = AtomicBool::new(false);
+
+/// Take the flag from the launch arguments, before the app is built.
+pub fn init(args: &[String]) {
+    if args.iter().any(|a| a == FLAG) {
+        HEADLESS.store(true, Ordering::Relaxed);
+    }
+}
+
+pub fn enabled() -> bool {
+    HEADLESS.load(Ordering::Relaxed)
+}
+
+/// Turn away `what`, which needs a screen, when headless.
+pub fn refuse(what: &str) -> Result<(), String> {
+    if enabled() {
+        return Err(format!("running headless: {what} needs a screen"));
+    }
+    Ok(())
+}
+
+/// Leave the main window out of the app config, so it is never built.
+pub fn strip_main<R: tauri::Runtime>(context: &mut tauri::Context<R>) {
+    if enabled() {
+        context.config_mut().app.windows.retain(|w| w.label != "main");
+        tracing::info!("headless — relaying only, nothing on screen");
+    }
+}
+
+/// Start every WhatsApp account's organ.
+pub fn start_organs(app: &tauri::AppHandle) {
+    use tauri::Manager;
+
+    let manager = app.state::<crate::organs::OrganManager>();
+    for def in manager.defs().iter().filter(|d| d.service() == crate::whatsapp::ORGAN) {
+        match crate::organs::preload(app, &def.id) {
+            Ok(()) => tracing::info!("starting organ {} headless", def.id),
+            Err(e) => tracing::warn!("could not start organ {}: {e}", def.id),
+        }
+    }
+}
@@ -0,0 +1,71 @@
+//! `get_health` — a single snapshot of the body's runtime state.
+
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::Emitter;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Health {
+    pub overlay_visible: bool,
+    pub presentation_guard: bool,
+    pub dnd: bool,
+    /// Why idle/suspend is being held off, if it is.
+    pub inhibited: Vec<crate::inhibit::Reason>,
+    /// The microphone is being recorded for the Brain.
+    pub listening: bool,
+    /// Effective light/dark theme, override included.
+    pub theme: crate::theme::Theme,
+    /// Last network change and the Brain reachability verdict.
+    pub network: crate::network::NetworkStatus,
+}
+
+/// Collect the current health snapshot.
+pub fn snapshot() -> Health {
+    Health {
+        overlay_visible: crate::OVERLAY_VISIBLE.load(Ordering::Relaxed),
+        presentation_guard: crate::presentation::is_active(),
+        dnd: crate::dnd::is_enabled(),
+        inhibited: crate::inhibit::reasons(),
+        listening: crate::voice::is_active(),
+        theme: crate::theme::current(),
+        network: crate::network::status(),
+    }
+}
+
+/// Last snapshot sent on `health://changed`.
+static ANNOUNCED: Mutex<Option<Health>> = Mutex::new(None);
+
+/// Emit `health://changed` with the current snapshot if it differs from
+/// the last one announced. Cheap enough to call on every state change.
+pub fn announce(app: &tauri::AppHandle) {
+    let health = snapshot();
+    let mut announced = ANNOUNCED.lock().unwrap_or_else(|e| e.into_inner());
+    if announced.as_ref() != Some(&health) {
+        let _ = app.emit("health://changed", &health);
+        *announced = Some(health);
+    }
+}
+
+/// Make the next `announce` emit even if nothing changed.
+pub fn forget_announced() {
+    *ANNOUNCED.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// One-line summary for the tray tooltip, e.g. "Lexicon · hidden · DND".
+pub fn summary(health: &Health) -> String {
+    let mut parts = vec!["Lexicon", if health.overlay_visible { "shown" } else { "hidden" }];
+    if health.dnd {
+        parts.push("DND");
+    }
+    if health.listening {
+        parts.push("listening");
+    }
+    parts.join(" · ")
+}
+
+#[tauri::command]
+pub fn get_health() -> Health {
+    snapshot()
+}
@@ -0,0 +1,61 @@
+//! The part of the app the background pipelines touch — config, data
+//! and cache dirs, the Brain transport, events — as a trait.
+//!
+//! `tauri::AppHandle` is the real implementation. Tests use
+//! `testing::TestHost`, which needs no window system, so the pipelines
+//! can run end to end against a mock Brain under plain `cargo test`.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use serde::Serialize;
+use tauri::{Emitter, Manager};
+
+use crate::brain::{BrainClient, BrainTransport};
+use crate::config::Config;
+
+pub trait Host: Clone + Send + Sync + 'static {
+    fn config(&self) -> Config;
+    /// Persistent per-app storage (`app_data_dir`).
+    fn data_dir(&self) -> Option<PathBuf>;
+    /// Disposable per-app storage (`app_cache_dir`).
+    fn cache_dir(&self) -> Option<PathBuf>;
+    /// How to reach the Brain.
+    fn transport(&self) -> Arc<dyn BrainTransport>;
+    /// Emit to every window.
+    fn publish<S: Serialize + Clone>(&self, event: &str, payload: S);
+    /// Emit to one window.
+    fn publish_to<S: Serialize + Clone>(&self, window: &str, event: &str, payload: S);
+    /// Whether the presentation guard is on (see `presentation`).
+    fn presenting(&self) -> bool;
+}
+
+impl Host for tauri::AppHandle {
+    fn config(&self) -> Config {
+        crate::config::current(self)
+    }
+
+    fn data_dir(&self) -> Option<PathBuf> {
+        self.path().app_data_dir().ok()
+    }
+
+    fn cache_dir(&self) -> Option<PathBuf> {
+        self.path().app_cache_dir().ok()
+    }
+
+    fn transport(&self) -> Arc<dyn BrainTransport> {
+        self.try_state::<BrainClient>().map(|client| client.transport()).unwrap_or_else(|| BrainClient::default().transport())
+    }
+
+    fn publish<S: Serialize + Clone>(&self, event: &str, payload: S) {
+        let _ = self.emit(event, payload);
+    }
+
+    fn publish_to<S: Serialize + Clone>(&self, window: &str, event: &str, payload: S) {
+        let _ = self.emit_to(window, event, payload);
+    }
+
+    fn presenting(&self) -> bool {
+        crate::presentation::is_active()
+    }
+}
@@ -0,0 +1,181 @@
+//! Idle suspension — giving back the memory of a WhatsApp organ nobody
+//! is looking at.
+//!
+//! A hidden WhatsApp Web sits at several hundred megabytes. Once a
+//! WhatsApp organ (any account's) has been in the background for
+//! `organs.suspend_after_mins` (30 by default; 0 never) without relaying
+//! a message, its window is destroyed and the organ remembered as
+//! suspended: `organ_status` and `whatsapp_organ_status` say
+//! "suspended", the Brain is relayed `suspended`, and window
+//! state keeps it among the open organs. An organ that isn't signed in —
+//! a QR code waiting to be scanned, say — is never suspended, and
+//! neither is one running `--headless` (see `headless`).
+//!
+//! Whatever builds the window again brings it back (`open_organ`,
+//! `show_organ(id, true)`, `open_whatsapp_organ`, the Brain's
+//! `open_organ`, or its `resume_organ` for the background). The page
+//! loads WhatsApp Web and gets its scripts like any other load, and
+//! `{id}://resuming` (`whatsapp://resuming`) goes to the main window
+//! first, since the session takes a few seconds to come back.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tauri::{Emitter, Manager};
+
+use crate::organs::Login;
+
+/// How often the organs are looked at.
+const TICK: Duration = Duration::from_secs(60);
+
+/// How an organ looked on one pass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Seen {
+    Closed,
+    /// On screen, being built or not signed in: not idle.
+    Busy,
+    Background,
+}
+
+/// When each open organ was last busy.
+#[derive(Debug, Default)]
+struct Idle {
+    since: BTreeMap<String, Instant>,
+}
+
+impl Idle {
+    /// Whether `id` has been idle for `after`.
+    fn check(&mut self, id: &str, seen: Seen, now: Instant, after: Duration) -> bool {
+        match seen {
+            Seen::Closed => {
+                self.since.remove(id);
+                false
+            }
+            Seen::Busy => {
+                self.since.insert(id.to_string(), now);
+                false
+            }
+            Seen::Background => {
+                let since = *self.since.entry(id.to_string()).or_insert(now);
+                now.saturating_duration_since(since) >= after
+            }
+        }
+    }
+
+    fn touch(&mut self, id: &str, now: Instant) {
+        if let Some(since) = self.since.get_mut(id) {
+            *since = now;
+        }
+    }
+}
+
+static IDLE: Mutex<Idle> = Mutex::new(Idle { since: BTreeMap::new() });
+static SUSPENDED: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// `organ` relayed a message; it isn't idle.
+pub fn touch(organ: &str) {
+    IDLE.lock().unwrap_or_else(|e| e.into_inner()).touch(organ, Instant::now());
+}
+
+pub fn suspended(id: &str) -> bool {
+    SUSPENDED.lock().unwrap_or_else(|e| e.into_inner()).contains(id)
+}
+
+/// The organ was closed; it isn't coming back on its own.
+pub fn forget(id: &str) {
+    SUSPENDED.lock().unwrap_or_else(|e| e.into_inner()).remove(id);
+}
+
+/// The organ's window is about to be built. If it was suspended, tell
+/// the canvas it is on its way back.
+pub fn resuming(app: &tauri::AppHandle, id: &str) {
+    if SUSPENDED.lock().unwrap_or_else(|e| e.into_inner()).remove(id) {
+        tracing::info!("organ {id} resuming");
+        let _ = app.emit_to("main", &format!("{id}://resuming"), ());
+    }
+}
+
+/// Bring a suspended organ back in the background. False if it wasn't
+/// suspended.
+pub fn resume(app: &tauri::AppHandle, id: &str) -> Result<bool, String> {
+    if !suspended(id) {
+        return Ok(false);
+    }
+    crate::organs::preload(app, id).map(|()| true)
+}
+
+fn suspend(app: &tauri::AppHandle, id: &str, after: Duration) {
+    if let Err(e) = crate::organs::close(app, id) {
+        tracing::warn!("could not suspend organ {id}: {e}");
+        return;
+    }
+    SUSPENDED.lock().unwrap_or_else(|e| e.into_inner()).insert(id.to_string());
+    tracing::info!("organ {id} suspended after {} minutes in the background", after.as_secs() / 60);
+    crate::relay::report_state(app, id, crate::lifecycle::Lifecycle::Suspended);
+    crate::organs::announce(app, id);
+}
+
+fn seen(app: &tauri::AppHandle, id: &str) -> Seen {
+    match crate::organs::status(app, id) {
+        "closed" => Seen::Closed,
+        "background" if !crate::organs::settling(id) && signed_in(app, id) => Seen::Background,
+        _ => Seen::Busy,
+    }
+}
+
+fn signed_in(app: &tauri::AppHandle, id: &str) -> bool {
+    crate::organs::state(app, id).is_ok_and(|state| state.login == Login::LoggedIn)
+}
+
+/// Look for idle organs for as long as the app runs.
+pub fn start(app: &tauri::AppHandle) {
+    if crate::headless::enabled() {
+        return;
+    }
+    let app = app.clone();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(TICK);
+        let mins = crate::config::current(&app).organs.suspend_after_mins;
+        let after = Duration::from_secs(mins.saturating_mul(60));
+        let manager = app.state::<crate::organs::OrganManager>();
+        let ids: Vec<String> = manager.defs().iter().filter(|d| d.service() == "whatsapp").map(|d| d.id.clone()).collect();
+        for id in ids {
+            let seen = seen(&app, &id);
+            let idle = IDLE.lock().unwrap_or_else(|e| e.into_inner()).check(&id, seen, Instant::now(), after);
+            if idle && mins > 0 {
+                suspend(&app, &id, after);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AFTER: Duration = Duration::from_secs(30 * 60);
+
+    #[test]
+    fn only_a_quiet_background_organ_goes_idle() {
+        let start = Instant::now();
+        let at = |mins: u64| start + Duration::from_secs(mins * 60);
+        let mut idle = Idle::default();
+        assert!(!idle.check("whatsapp", Seen::Busy, at(0), AFTER));
+        assert!(!idle.check("whatsapp", Seen::Background, at(20), AFTER));
+        assert!(idle.check("whatsapp", Seen::Background, at(30), AFTER));
+
+        // A message relayed or a look at it starts the count over.
+        idle.touch("whatsapp", at(31));
+        assert!(!idle.check("whatsapp", Seen::Background, at(60), AFTER));
+        assert!(idle.check("whatsapp", Seen::Background, at(61), AFTER));
+        assert!(!idle.check("whatsapp", Seen::Busy, at(62), AFTER));
+        assert!(!idle.check("whatsapp", Seen::Background, at(91), AFTER));
+
+        // Closed and opened again, it starts from when it was next seen.
+        assert!(!idle.check("whatsapp", Seen::Closed, at(100), AFTER));
+        idle.touch("whatsapp", at(100));
+        assert!(!idle.check("whatsapp", Seen::Background, at(200), AFTER));
+        assert!(idle.check("whatsapp", Seen::Background, at(230), AFTER));
+    }
+}
@@ -0,0 +1,508 @@
+//! Files dropped on the main window, forwarded to the Brain.
+//!
+//! Each drop is one batch. Every file is validated against the `[ingest]`
+//! config (must exist, fit under the size cap, have an allowed extension),
+//! copied into a staging directory and POSTed to
+//! `{BRAIN_URL}/ingest/file` as multipart with origin metadata. Progress
+//! goes out on `ingest://progress`; when the batch is done,
+//! `ingest://file-ingested` carries the per-file results, including the
+//! Brain's response.
+//!
+//! Files that can't be delivered because the Brain is offline are kept in
+//! a persisted queue and sent once it answers `/health` again. Their
+//! results arrive in a later `ingest://file-ingested` for the same batch.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tauri::Manager;
+
+use crate::brain::{Body, Request, TransportError};
+use crate::host::Host;
+
+/// How often the pending queue checks whether the Brain is back.
+const RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+#[serde(default)]
+pub struct IngestConfig {
+    /// Largest file accepted, in megabytes.
+    #[cfg_attr(test, proptest(strategy = "crate::testing::toml_u64()"))]
+    pub max_file_mb: u64,
+    /// Accepted extensions, lowercase, without the dot.
+    pub extensions: Vec<String>,
+}
+
+impl Default for IngestConfig {
+    fn default() -> Self {
+        Self {
+            max_file_mb: 50,
+            extensions: ["pdf", "txt", "md", "csv", "json", "docx", "png", "jpg", "jpeg"]
+                .map(String::from)
+                .to_vec(),
+        }
+    }
+}
+
+/// A staged file waiting for the Brain.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+struct Pending {
+    batch: u64,
+    name: String,
+    staged: PathBuf,
+    dropped_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileResult {
+    pub name: String,
+    pub ok: bool,
+    /// Waiting in the pending queue for the Brain to come back.
+    pub queued: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl FileResult {
+    fn failed(name: &str, error: String) -> Self {
+        Self { name: name.into(), ok: false, queued: false, response: None, error: Some(error) }
+    }
+}
+
+#[derive(Clone, Serialize)]
+struct Progress<'a> {
+    batch: u64,
+    name: &'a str,
+    /// "staged" | "uploading" | "ingested" | "failed" | "queued"
+    stage: &'a str,
+}
+
+#[derive(Clone, Serialize)]
+struct Ingested {
+    batch: u64,
+    results: Vec<FileResult>,
+}
+
+enum Outcome {
+    Ingested(serde_json::Value),
+    Failed(String),
+    Offline,
+}
+
+/// Serialises access to the queue file.
+static QUEUE: Mutex<()> = Mutex::new(());
+static DRAINING: AtomicBool = AtomicBool::new(false);
+
+fn now_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or_default()
+}
+
+fn progress(app: &impl Host, batch: u64, name: &str, stage: &str) {
+    app.publish_to("main", "ingest://progress", Progress { batch, name, stage });
+}
+
+// ── Validation and staging ─────────────────────────────────────
+
+fn validate(config: &IngestConfig, path: &Path) -> Result<String, String> {
+    let name = path.file_name().and_then(|n| n.to_str()).ok_or("not a file")?.to_string();
+    let meta = std::fs::metadata(path).map_err(|_| format!("{name} does not exist"))?;
+    if !meta.is_file() {
+        return Err(format!("{name} is not a file"));
+    }
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_ascii_lowercase();
+    if !config.extensions.iter().any(|allowed| allowed.eq_ignore_ascii_case(&ext)) {
+        return Err(format!("{name}: .{ext} files are not accepted"));
+    }
+    if meta.len() > config.max_file_mb * 1024 * 1024 {
+        return Err(format!("{name} is larger than {} MB", config.max_file_mb));
+    }
+    Ok(name)
+}
+
+fn stage(app: &impl Host, batch: u64, path: &Path, name: &str) -> Result<PathBuf, String> {
+    let dir = app.cache_dir().ok_or("no cache directory on this platform")?.join("ingest").join(batch.to_string());
+    std::fs::create_dir_all(&dir).map_err(|e| format!("create {}: {e}", dir.display()))?;
+    let staged = dir.join(name);
+    std::fs::copy(path, &staged).map_err(|e| format!("stage {name}: {e}"))?;
+    Ok(staged)
+}
+
+fn unstage(item: &Pending) {
+    let _ = std::fs::remove_file(&item.staged);
+    if let Some(dir) = item.staged.parent() {
+        let _ = std::fs::remove_dir(dir);
+    }
+}
+
+// ── Upload ─────────────────────────────────────────────────────
+
+fn multipart(boundary: &str, fields: &[(&str, String)], name: &str, data: &[u8]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(data.len() + 512);
+    for (key, value) in fields {
+        body.extend_from_slice(
+            format!("--{boundary}\r\nContent-Disposition: form-data; name=\"{key}\"\r\n\r\n{value}\r\n").as_bytes(),
+        );
+    }
+    let filename = name.replace(['"', '\r', '\n'], "_");
+    body.extend_from_slice(
+        format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{filename}\"\r\n\
+             Content-Type: application/octet-stream\r\n\r\n"
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(data);
+    body.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+    body
+}
+
+fn upload(app: &impl Host, item: &Pending) -> Outcome {
+    let data = match std::fs::read(&item.staged) {
+        Ok(data) => data,
+        Err(e) => return Outcome::Failed(format!("staged copy is gone: {e}")),
+    };
+    let boundary = format!("lexicon-{}", now_millis());
+    let fields = [
+        ("origin", "drop".to_string()),
+        ("window", "main".to_string()),
+        ("batch", item.batch.to_string()),
+        ("dropped_at", item.dropped_at.to_string()),
+    ];
+    let body = multipart(&boundary, &fields, &item.name, &data);
+    let content_type = format!("multipart/form-data; boundary={boundary}");
+    match crate::brain::call(app, Request::post("/ingest/file", &content_type, Body::Bytes(body))) {
+        Ok(text) => Outcome::Ingested(serde_json::from_str(&text).unwrap_or(serde_json::Value::String(text))),
+        Err(TransportError::Offline(_)) => Outcome::Offline,
+        Err(e) => Outcome::Failed(e.to_string()),
+    }
+}
+
+/// Upload one staged file and report it. `None` means the Brain is offline.
+fn deliver(app: &impl Host, item: &Pending) -> Option<FileResult> {
+    progress(app, item.batch, &item.name, "uploading");
+    let result = match upload(app, item) {
+        Outcome::Offline => return None,
+        Outcome::Ingested(response) => {
+            progress(app, item.batch, &item.name, "ingested");
+            FileResult { name: item.name.clone(), ok: true, queued: false, response: Some(response), error: None }
+        }
+        Outcome::Failed(error) => {
+            progress(app, item.batch, &item.name, "failed");
+            FileResult::failed(&item.name, error)
+        }
+    };
+    unstage(item);
+    Some(result)
+}
+
+// ── Pending queue ──────────────────────────────────────────────
+
+fn queue_path(app: &impl Host) -> Option<PathBuf> {
+    app.data_dir().map(|dir| dir.join("ingest-queue.json"))
+}
+
+fn load_queue(app: &impl Host) -> Vec<Pending> {
+    queue_path(app)
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+fn save_queue(app: &impl Host, queue: &[Pending]) {
+    let Some(path) = queue_path(app) else { return };
+    if queue.is_empty() {
+        let _ = std::fs::remove_file(&path);
+        return;
+    }
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    match serde_json::to_string_pretty(queue) {
+        Ok(text) => {
+            if let Err(e) = std::fs::write(&path, text) {
+                tracing::warn!("could not persist ingest queue: {e}");
+            }
+        }
+        Err(e) => tracing::warn!("could not persist ingest queue: {e}"),
+    }
+}
+
+fn enqueue(app: &impl Host, items: Vec<Pending>) {
+    {
+        let _lock = QUEUE.lock().unwrap_or_else(|e| e.into_inner());
+        let mut queue = load_queue(app);
+        queue.extend(items);
+        save_queue(app, &queue);
+    }
+    start_draining(app);
+}
+
+/// One pass over the queue: deliver what the Brain takes, keep the rest.
+/// Returns how many files are still waiting.
+fn drain_once(app: &impl Host) -> usize {
+    let _lock = QUEUE.lock().unwrap_or_else(|e| e.into_inner());
+    let mut remaining = Vec::new();
+    let mut results: BTreeMap<u64, Vec<FileResult>> = BTreeMap::new();
+    for item in load_queue(app) {
+        match deliver(app, &item) {
+            Some(result) => results.entry(item.batch).or_default().push(result),
+            None => remaining.push(item),
+        }
+    }
+    save_queue(app, &remaining);
+    for (batch, results) in results {
+        app.publish("ingest://file-ingested", Ingested { batch, results });
+    }
+    remaining.len()
+}
+
+/// Retry the pending queue until it is empty, once the Brain is reachable.
+fn start_draining(app: &impl Host) {
+    if DRAINING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let app = app.clone();
+    std::thread::spawn(move || {
+        loop {
+            std::thread::sleep(RETRY_INTERVAL);
+            if crate::power::is_suspended() || !crate::brain::is_reachable(&app) {
+                continue;
+            }
+            if drain_once(&app) == 0 {
+                tracing::info!("ingest queue drained");
+                break;
+            }
+        }
+        DRAINING.store(false, Ordering::SeqCst);
+    });
+}
+
+/// Pick the queue back up at startup or after a wake, if anything is
+/// waiting.
+pub fn resume(app: &impl Host) {
+    if !load_queue(app).is_empty() {
+        start_draining(app);
+    }
+}
+
+/// Wait out any drain pass that is mid-write, so the queue on disk is
+/// complete before the process goes away. Returns how many files are
+/// still waiting.
+pub fn flush(app: &impl Host) -> usize {
+    let _lock = QUEUE.lock().unwrap_or_else(|e| e.into_inner());
+    load_queue(app).len()
+}
+
+// ── Drops ──────────────────────────────────────────────────────
+
+/// Validate, stage and upload one dropped batch.
+fn ingest_batch(app: &impl Host, paths: Vec<PathBuf>) {
+    let config = app.config().ingest;
+    let batch = now_millis();
+    let mut results = Vec::new();
+    let mut offline = Vec::new();
+
+    for path in paths {
+        let label = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let staged = validate(&config, &path).and_then(|name| Ok((stage(app, batch, &path, &name)?, name)));
+        let (staged, name) = match staged {
+            Ok(staged) => staged,
+            Err(e) => {
+                progress(app, batch, &label, "failed");
+                results.push(FileResult::failed(&label, e));
+                continue;
+            }
+        };
+        progress(app, batch, &name, "staged");
+        let item = Pending { batch, name, staged, dropped_at: batch };
+        // Once the Brain is known to be offline, queue the rest directly.
+        let delivered = if offline.is_empty() { deliver(app, &item) } else { None };
+        match delivered {
+            Some(result) => results.push(result),
+            None => {
+                progress(app, batch, &item.name, "queued");
+                results.push(FileResult { name: item.name.clone(), ok: false, queued: true, response: None, error: None });
+                offline.push(item);
+            }
+        }
+    }
+
+    if !offline.is_empty() {
+        tracing::info!("Brain offline — {} dropped file(s) queued", offline.len());
+        enqueue(app, offline);
+    }
+    app.publish("ingest://file-ingested", Ingested { batch, results });
+}
+
+/// Watch the main window for file drops.
+pub fn init(app: &tauri::AppHandle) {
+    if let Some(main) = app.get_webview_window("main") {
+        let handle = app.clone();
+        main.on_window_event(move |event| {
+            if let tauri::WindowEvent::DragDrop(tauri::DragDropEvent::Drop { paths, .. }) = event {
+                if paths.is_empty() || crate::presentation::is_active() {
+                    return;
+                }
+                let app = handle.clone();
+                let paths = paths.clone();
+                std::thread::spawn(move || ingest_batch(&app, paths));
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{MockBrain, Reply, TestHost};
+    use serde_json::json;
+
+    /// The queue lock is process-wide.
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    fn dropped(host: &TestHost, name: &str, contents: &str) -> PathBuf {
+        let path = host.scratch().join(name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn results(host: &TestHost) -> Vec<serde_json::Value> {
+        host.events("ingest://file-ingested")
+            .into_iter()
+            .flat_map(|e| e.payload["results"].as_array().cloned().unwrap_or_default())
+            .collect()
+    }
+
+    #[test]
+    fn delivers_a_drop_to_the_brain() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        let brain = MockBrain::start();
+        brain.script("/ingest/file", [Reply::json(200, json!({ "doc_id": "d1" }))]);
+        let host = TestHost::new().with_brain(&brain);
+
+        ingest_batch(&host, vec![dropped(&host, "notes.txt", "hello brain")]);
+
+        let results = results(&host);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0]["ok"], true);
+        assert_eq!(results[0]["response"]["doc_id"], "d1");
+        let uploads = brain.requests("/ingest/file");
+        assert_eq!(uploads.len(), 1);
+        assert_eq!(uploads[0].method, "POST");
+        let body = String::from_utf8_lossy(&uploads[0].body);
+        assert!(body.contains("filename=\"notes.txt\""));
+        assert!(body.contains("hello brain"));
+        assert!(load_queue(&host).is_empty());
+        let stages: Vec<_> = host.events("ingest://progress").into_iter().map(|e| e.payload["stage"].clone()).collect();
+        assert_eq!(stages, [json!("staged"), json!("uploading"), json!("ingested")]);
+    }
+
+    #[test]
+    fn brain_errors_fail_the_file_without_queueing() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        let brain = MockBrain::start();
+        brain.script("/ingest/file", [Reply::status(500)]);
+        let host = TestHost::new().with_brain(&brain);
+
+        ingest_batch(&host, vec![dropped(&host, "notes.txt", "x")]);
+
+        let results = results(&host);
+        assert_eq!(results[0]["ok"], false);
+        assert_eq!(results[0]["queued"], false);
+        assert!(results[0]["error"].as_str().is_some_and(|e| e.contains("500")));
+        assert!(load_queue(&host).is_empty());
+    }
+
+    #[test]
+    fn rejected_files_never_reach_the_brain() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        let brain = MockBrain::start();
+        let host = TestHost::new().with_brain(&brain);
+
+        ingest_batch(&host, vec![dropped(&host, "setup.exe", "MZ"), host.scratch().join("missing.txt")]);
+
+        let results = results(&host);
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| r["ok"] == false && r["queued"] == false));
+        assert!(brain.requests("/ingest/file").is_empty());
+    }
+
+    #[test]
+    fn offline_drops_are_queued_and_survive_a_restart() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        let brain = MockBrain::start();
+        let host = TestHost::new().with_brain(&brain);
+        brain.stop();
+
+        ingest_batch(&host, vec![dropped(&host, "a.txt", "a"), dropped(&host, "b.txt", "b")]);
+        assert!(results(&host).iter().all(|r| r["queued"] == true));
+        assert_eq!(load_queue(&host).len(), 2);
+
+        let brain = MockBrain::start();
+        brain.always("/ingest/file", Reply::json(200, json!({})));
+        let host = host.restart().with_brain(&brain);
+        let queue = load_queue(&host);
+        assert_eq!(queue.len(), 2);
+
+        assert_eq!(drain_once(&host), 0);
+        let results = results(&host);
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| r["ok"] == true));
+        assert_eq!(brain.requests("/ingest/file").len(), 2);
+        assert!(load_queue(&host).is_empty());
+        assert!(queue.iter().all(|item| !item.staged.exists()));
+    }
+
+    #[test]
+    fn dropped_connections_are_retried_on_the_next_pass() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        let brain = MockBrain::start();
+        brain.script(
+            "/ingest/file",
+            [Reply::Drop, Reply::Drop, Reply::delay(Duration::from_millis(200), Reply::json(200, json!({ "ok": 1 })))],
+        );
+        let host = TestHost::new().with_brain(&brain);
+
+        ingest_batch(&host, vec![dropped(&host, "notes.txt", "x")]);
+        assert_eq!(results(&host)[0]["queued"], true);
+
+        assert_eq!(drain_once(&host), 1);
+        assert_eq!(drain_once(&host), 0);
+        assert_eq!(brain.requests("/ingest/file").len(), 3);
+        assert_eq!(results(&host).last().unwrap()["ok"], true);
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn queue_entries_round_trip(entry in proptest::prelude::any::<Pending>()) {
+            let text = serde_json::to_string(&entry).unwrap();
+            proptest::prop_assert_eq!(serde_json::from_str::<Pending>(&text).unwrap(), entry);
+        }
+
+        #[test]
+        fn any_queue_file_loads_without_panicking(doc in crate::testing::json()) {
+            let host = TestHost::new();
+            let path = queue_path(&host).unwrap();
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, doc.to_string()).unwrap();
+            let _ = load_queue(&host);
+        }
+    }
+
+    #[test]
+    fn previous_queue_format_still_loads() {
+        let queue: Vec<Pending> = serde_json::from_str(include_str!("../fixtures/ingest-queue-v1.json")).unwrap();
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue[1].name, "notizen über café.md");
+        assert_eq!(queue[1].dropped_at, 1760000000123);
+        assert!(queue[1].staged.ends_with("1760000000000/notizen über café.md"));
+    }
+}
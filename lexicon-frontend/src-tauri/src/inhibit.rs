@@ -0,0 +1,123 @@
+//! Idle/suspend inhibition while critical work is in flight.
+//!
+//! Work that must not be cut off by a suspend holds an `InhibitGuard` for
+//! as long as it runs. The first guard takes a logind `sleep:idle` block
+//! lock; dropping the last one closes it. The lock is a file descriptor,
+//! so a job that panics releases it while unwinding and a crashed process
+//! releases it with the fd table — nothing can leak it.
+//!
+//! Off unless `inhibit.enabled = true` in the config. Only logind is
+//! supported; elsewhere guards are tracked and shown in `get_health` but
+//! take no lock.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reason {
+    /// A media upload is running.
+    MediaUpload,
+    /// The outbound send queue is draining.
+    OutboundQueue,
+    /// A backup or export job is running.
+    Export,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+#[serde(default)]
+pub struct InhibitConfig {
+    /// Block idle and suspend while critical work runs.
+    pub enabled: bool,
+}
+
+#[cfg(target_os = "linux")]
+type Lock = std::os::fd::OwnedFd;
+#[cfg(not(target_os = "linux"))]
+type Lock = ();
+
+struct State {
+    holds: BTreeMap<Reason, usize>,
+    lock: Option<Lock>,
+}
+
+static STATE: Mutex<State> = Mutex::new(State { holds: BTreeMap::new(), lock: None });
+
+/// Keeps the inhibitor for `reason` alive until dropped.
+#[must_use = "the inhibitor is released when the guard is dropped"]
+pub struct InhibitGuard {
+    app: tauri::AppHandle,
+    reason: Option<Reason>,
+}
+
+impl Drop for InhibitGuard {
+    fn drop(&mut self) {
+        let Some(reason) = self.reason else { return };
+        {
+            let mut state = STATE.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(count) = state.holds.get_mut(&reason) {
+                *count -= 1;
+                if *count == 0 {
+                    state.holds.remove(&reason);
+                }
+            }
+            if state.holds.is_empty() && state.lock.take().is_some() {
+                tracing::info!("suspend inhibitor released");
+            }
+        }
+        crate::health::announce(&self.app);
+    }
+}
+
+/// Hold off idle and suspend for `reason` until the guard is dropped.
+/// A no-op guard when inhibition is disabled.
+pub fn hold(app: &tauri::AppHandle, reason: Reason) -> InhibitGuard {
+    if !crate::config::current(app).inhibit.enabled {
+        return InhibitGuard { app: app.clone(), reason: None };
+    }
+    {
+        let mut state = STATE.lock().unwrap_or_else(|e| e.into_inner());
+        *state.holds.entry(reason).or_default() += 1;
+        if state.lock.is_none() {
+            match take_lock(reason) {
+                Ok(lock) => {
+                    state.lock = Some(lock);
+                    tracing::info!("suspend inhibitor taken ({reason:?})");
+                }
+                Err(e) => tracing::warn!("could not inhibit suspend: {e}"),
+            }
+        }
+    }
+    crate::health::announce(app);
+    InhibitGuard { app: app.clone(), reason: Some(reason) }
+}
+
+/// Reasons currently holding the inhibitor, for `get_health`.
+pub fn reasons() -> Vec<Reason> {
+    STATE.lock().unwrap_or_else(|e| e.into_inner()).holds.keys().copied().collect()
+}
+
+#[cfg(target_os = "linux")]
+fn take_lock(reason: Reason) -> Result<Lock, String> {
+    let bus = zbus::blocking::Connection::system().map_err(|e| e.to_string())?;
+    let why = format!("Lexicon: {reason:?} in progress");
+    let reply = bus
+        .call_method(
+            Some("org.freedesktop.login1"),
+            "/org/freedesktop/login1",
+            Some("org.freedesktop.login1.Manager"),
+            "Inhibit",
+            &("sleep:idle", "Lexicon", why.as_str(), "block"),
+        )
+        .map_err(|e| e.to_string())?;
+    let fd: zbus::zvariant::OwnedFd = reply.body().deserialize().map_err(|e| e.to_string())?;
+    Ok(fd.into())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn take_lock(_reason: Reason) -> Result<Lock, String> {
+    Err("not supported on this platform".into())
+}
@@ -0,0 +1,546 @@
+//! An opt-in journal of IPC command invocations, and replay.
+//!
+//! With `journal.enabled = true` every app command that reaches the
+//! invoke handler is appended to `<app data>/journal/<session>.jsonl`:
+//! the command, the calling window, redacted arguments, when it was
+//! called and whether it resolved or rejected. A journal stops growing at
+//! `journal.max_bytes`; the last [`KEEP_SESSIONS`] are kept.
+//!
+//! Arguments keep numbers, booleans and short strings under the keys in
+//! [`KEPT_KEYS`] (organ ids, action names). Every other string and every
+//! raw body is replaced by its length, so a journal holds no message
+//! text, phone numbers or paths.
+//!
+//! `replay_journal` re-issues a journal against the running app with the
+//! original spacing, scaled by `speed`, through the same IPC path a
+//! webview uses. Only the commands in [`REPLAYABLE`] run. Anything that
+//! writes settings, reaches outside the app or records the user is
+//! skipped unless named in `allow`. Entries with redacted arguments can't
+//! be reproduced and are skipped as well. `dry_run` only reports what
+//! would run.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tauri::http::{HeaderMap, HeaderValue};
+use tauri::ipc::{CallbackFn, Invoke, InvokeBody, InvokeResponse, InvokeResponseBody};
+use tauri::webview::InvokeRequest;
+use tauri::Manager;
+
+/// Header on invokes the journal sends itself, so they aren't journaled.
+const MARKER: &str = "x-lexicon-journal";
+pub const KEEP_SESSIONS: usize = 5;
+/// Kept strings longer than this are redacted anyway.
+const MAX_KEPT_LEN: usize = 64;
+/// How long one replayed command may take to answer.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Argument keys whose string values are identifiers, not content.
+pub const KEPT_KEYS: &[&str] = &["id", "action", "organ", "theme", "scope", "account"];
+
+/// Commands that only read state, or change it in ways the user can
+/// undo from the app. Everything else is a no-op on replay unless allowed.
+pub const REPLAYABLE: &[&str] = &[
+    "toggle_window",
+    "set_presentation_guard",
+    "get_health",
+    "brain_status",
+    "brain_logs",
+    "spool_stats",
+    "relay_queue_status",
+    "get_metrics",
+    "wa_get_qr",
+    "get_notification_prefs",
+    "get_relay_filters",
+    "get_proxy_config",
+    "test_relay_filter",
+    "get_layout",
+    "get_display_mode",
+    "list_monitors",
+    "get_recent_logs",
+    "get_brain_url",
+    "set_dnd",
+    "get_dnd",
+    "open_organ",
+    "show_organ",
+    "close_organ",
+    "organ_status",
+    "whatsapp_organ_status",
+    "whatsapp_status_history",
+    "open_whatsapp_organ",
+    "close_whatsapp_organ",
+    "organ_state",
+    "wait_for_organ",
+    "list_organs",
+    "get_organ_resources",
+    "get_autostart",
+    "get_system_theme",
+    "get_platform",
+    "get_startup_report",
+    "get_shortcuts",
+    "get_shortcut_status",
+    "stop_audio",
+    "stop_voice_capture",
+    "stop_load_test",
+    "get_load_test_report",
+];
+
+/// Never journaled: replaying a replay would loop.
+const UNJOURNALED: &[&str] = &["replay_journal"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+#[serde(default)]
+pub struct JournalConfig {
+    pub enabled: bool,
+    /// A session's journal stops growing past this.
+    #[cfg_attr(test, proptest(strategy = "crate::testing::toml_u64()"))]
+    pub max_bytes: u64,
+}
+
+impl Default for JournalConfig {
+    fn default() -> Self {
+        Self { enabled: false, max_bytes: 4 * 1024 * 1024 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Resolved,
+    Rejected,
+}
+
+/// One line of a journal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    /// Milliseconds from the start of the session.
+    pub at_ms: u64,
+    pub unix_ms: u64,
+    pub command: String,
+    pub window: String,
+    pub args: serde_json::Value,
+    /// Some argument was replaced, so the call can't be reproduced.
+    pub redacted: bool,
+    pub outcome: Outcome,
+}
+
+// ── Redaction ──────────────────────────────────────────────────
+
+fn redact_value(key: Option<&str>, value: serde_json::Value, redacted: &mut bool) -> serde_json::Value {
+    use serde_json::Value;
+    match value {
+        Value::String(s) if key.is_some_and(|k| KEPT_KEYS.contains(&k)) && s.len() <= MAX_KEPT_LEN => Value::String(s),
+        Value::String(s) => {
+            *redacted = true;
+            Value::String(format!("<redacted {} bytes>", s.len()))
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(|v| redact_value(key, v, redacted)).collect()),
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(k, v)| {
+                    let v = redact_value(Some(&k), v, redacted);
+                    (k, v)
+                })
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+/// The invoke's arguments with content replaced, and whether anything was.
+pub fn redact(body: &InvokeBody) -> (serde_json::Value, bool) {
+    match body {
+        InvokeBody::Json(value) => {
+            let mut redacted = false;
+            let value = redact_value(None, value.clone(), &mut redacted);
+            (value, redacted)
+        }
+        InvokeBody::Raw(bytes) => (serde_json::Value::String(format!("<raw {} bytes>", bytes.len())), true),
+    }
+}
+
+// ── Recording ──────────────────────────────────────────────────
+
+struct Writer {
+    path: PathBuf,
+    written: u64,
+    max_bytes: u64,
+    full: bool,
+}
+
+impl Writer {
+    fn append(&mut self, entry: &Entry) {
+        if self.full {
+            return;
+        }
+        let Ok(mut line) = serde_json::to_string(entry) else { return };
+        line.push('\n');
+        if self.written + line.len() as u64 > self.max_bytes {
+            self.full = true;
+            tracing::warn!("journal {} reached {} bytes — no longer recording", self.path.display(), self.max_bytes);
+            return;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&self.path);
+        match file.and_then(|mut f| f.write_all(line.as_bytes())) {
+            Ok(()) => self.written += line.len() as u64,
+            Err(e) => {
+                self.full = true;
+                tracing::warn!("journal write to {} failed: {e}", self.path.display());
+            }
+        }
+    }
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static STARTED: OnceLock<Instant> = OnceLock::new();
+static WRITER: Mutex<Option<Writer>> = Mutex::new(None);
+
+fn unix_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64)
+}
+
+/// Drop all but the newest `keep` journals in `dir`.
+fn prune(dir: &Path, keep: usize) {
+    let mut journals: Vec<PathBuf> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|x| x == "jsonl"))
+        .collect();
+    // Named by start time, so names sort oldest first.
+    journals.sort();
+    let excess = journals.len().saturating_sub(keep);
+    for old in &journals[..excess] {
+        let _ = std::fs::remove_file(old);
+    }
+}
+
+fn open_session(dir: &Path, config: &JournalConfig) -> Result<Writer, String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("create {}: {e}", dir.display()))?;
+    prune(dir, KEEP_SESSIONS.saturating_sub(1));
+    let path = dir.join(format!("{}.jsonl", unix_ms()));
+    Ok(Writer { path, written: 0, max_bytes: config.max_bytes, full: false })
+}
+
+pub fn init(app: &tauri::AppHandle) {
+    let config = crate::config::current(app).journal;
+    if !config.enabled {
+        return;
+    }
+    let Ok(dir) = app.path().app_data_dir().map(|d| d.join("journal")) else { return };
+    match open_session(&dir, &config) {
+        Ok(writer) => {
+            tracing::info!("journaling commands to {}", writer.path.display());
+            STARTED.get_or_init(Instant::now);
+            *WRITER.lock().unwrap_or_else(|e| e.into_inner()) = Some(writer);
+            ENABLED.store(true, Ordering::SeqCst);
+        }
+        Err(e) => tracing::warn!("command journal off: {e}"),
+    }
+}
+
+fn journaled(invoke: &Invoke) -> bool {
+    ENABLED.load(Ordering::Relaxed)
+        && !invoke.message.headers().contains_key(MARKER)
+        && !UNJOURNALED.contains(&invoke.message.command())
+}
+
+/// The app's invoke handler, journaled.
+pub fn wrap(commands: impl Fn(Invoke) -> bool + Send + Sync + 'static) -> impl Fn(Invoke) -> bool + Send + Sync + 'static {
+    move |invoke| dispatch(invoke, &commands)
+}
+
+/// A journaled invoke's result only exists once the command answers, so
+/// it is sent round once more through `Webview::on_message` with a
+/// responder that records the outcome and then answers the original
+/// caller. The marker header keeps that second pass out of the journal.
+fn dispatch(invoke: Invoke, commands: &impl Fn(Invoke) -> bool) -> bool {
+    if !journaled(&invoke) {
+        return commands(invoke);
+    }
+    let Invoke { message, resolver, .. } = invoke;
+    let webview = message.webview();
+    let (args, redacted) = redact(message.payload());
+    let at_ms = STARTED.get_or_init(Instant::now).elapsed().as_millis() as u64;
+    let entry = Entry {
+        at_ms,
+        unix_ms: unix_ms(),
+        command: message.command().to_string(),
+        window: webview.label().to_string(),
+        args,
+        redacted,
+        outcome: Outcome::Rejected,
+    };
+    let Ok(url) = webview.url() else {
+        resolver.reject("webview has no url");
+        return true;
+    };
+    let mut headers = message.headers().clone();
+    headers.insert(MARKER, HeaderValue::from_static("recorded"));
+    let request = InvokeRequest {
+        cmd: entry.command.clone(),
+        callback: CallbackFn(0),
+        error: CallbackFn(1),
+        url,
+        body: message.payload().clone(),
+        headers,
+        invoke_key: webview.app_handle().invoke_key().to_string(),
+    };
+    webview.clone().on_message(
+        request,
+        Box::new(move |_, _, response, _, _| {
+            let outcome = match &response {
+                InvokeResponse::Ok(_) => Outcome::Resolved,
+                InvokeResponse::Err(_) => Outcome::Rejected,
+            };
+            if let Some(writer) = WRITER.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+                writer.append(&Entry { outcome, ..entry });
+            }
+            match response {
+                InvokeResponse::Ok(body) => resolver.respond(Ok::<_, tauri::ipc::InvokeError>(body)),
+                InvokeResponse::Err(e) => resolver.invoke_error(e),
+            }
+        }),
+    );
+    true
+}
+
+// ── Replay ─────────────────────────────────────────────────────
+
+/// Lines that aren't entries (a write cut short by a crash) are skipped.
+pub fn load(path: &Path) -> Result<Vec<Entry>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("read {}: {e}", path.display()))?;
+    Ok(parse(&text))
+}
+
+fn parse(text: &str) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = text.lines().filter_map(|line| serde_json::from_str(line).ok()).collect();
+    // Entries are written as commands finish; replay them as they started.
+    entries.sort_by_key(|e| e.at_ms);
+    entries
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Step {
+    Resolved { value: serde_json::Value },
+    Rejected { error: serde_json::Value },
+    /// Dry run: it would have been sent.
+    Accepted,
+    Skipped { reason: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Replayed {
+    pub at_ms: u64,
+    pub command: String,
+    pub window: String,
+    pub recorded: Outcome,
+    pub step: Step,
+}
+
+/// Why `entry` won't be sent, if it won't.
+fn refusal(entry: &Entry, allow: &[String]) -> Option<String> {
+    if !REPLAYABLE.contains(&entry.command.as_str()) && !allow.contains(&entry.command) {
+        return Some("not replayable; pass it in allow to run it".into());
+    }
+    if entry.redacted {
+        return Some("arguments were redacted".into());
+    }
+    None
+}
+
+fn send(webview: &tauri::Webview, entry: &Entry) -> Result<Step, String> {
+    let mut headers = HeaderMap::new();
+    headers.insert(MARKER, HeaderValue::from_static("replay"));
+    let request = InvokeRequest {
+        cmd: entry.command.clone(),
+        callback: CallbackFn(0),
+        error: CallbackFn(1),
+        url: webview.url().map_err(|e| e.to_string())?,
+        body: InvokeBody::Json(entry.args.clone()),
+        headers,
+        invoke_key: webview.app_handle().invoke_key().to_string(),
+    };
+    let (answer, answered) = mpsc::channel();
+    webview.clone().on_message(
+        request,
+        Box::new(move |_, _, response, _, _| {
+            let _ = answer.send(response);
+        }),
+    );
+    match answered.recv_timeout(REPLY_TIMEOUT).map_err(|_| "no answer".to_string())? {
+        InvokeResponse::Ok(InvokeResponseBody::Json(json)) => {
+            Ok(Step::Resolved { value: serde_json::from_str(&json).unwrap_or(serde_json::Value::String(json)) })
+        }
+        InvokeResponse::Ok(InvokeResponseBody::Raw(bytes)) => {
+            Ok(Step::Resolved { value: format!("<raw {} bytes>", bytes.len()).into() })
+        }
+        InvokeResponse::Err(e) => Ok(Step::Rejected { error: e.0 }),
+    }
+}
+
+pub fn replay(app: &tauri::AppHandle, entries: &[Entry], speed: f64, dry_run: bool, allow: &[String]) -> Vec<Replayed> {
+    let first = entries.first().map_or(0, |e| e.at_ms);
+    let started = Instant::now();
+    entries
+        .iter()
+        .map(|entry| {
+            let step = if let Some(reason) = refusal(entry, allow) {
+                Step::Skipped { reason }
+            } else if let Some(webview) = app.get_webview(&entry.window) {
+                if dry_run {
+                    Step::Accepted
+                } else {
+                    let due = Duration::from_millis(entry.at_ms - first).div_f64(speed);
+                    std::thread::sleep(due.saturating_sub(started.elapsed()));
+                    send(&webview, entry).unwrap_or_else(|reason| Step::Skipped { reason })
+                }
+            } else {
+                Step::Skipped { reason: format!("no window {}", entry.window) }
+            };
+            Replayed {
+                at_ms: entry.at_ms,
+                command: entry.command.clone(),
+                window: entry.window.clone(),
+                recorded: entry.outcome,
+                step,
+            }
+        })
+        .collect()
+}
+
+// ── Commands ───────────────────────────────────────────────────
+
+/// Re-issue a journal. `speed` 2.0 replays twice as fast; commands
+/// outside [`REPLAYABLE`] only run when named in `allow`.
+#[tauri::command]
+pub async fn replay_journal(
+    window: tauri::WebviewWindow,
+    app: tauri::AppHandle,
+    path: String,
+    speed: Option<f64>,
+    dry_run: Option<bool>,
+    allow: Option<Vec<String>>,
+) -> Result<Vec<Replayed>, String> {
+    if window.label() != "main" {
+        return Err("only the main window can replay journals".into());
+    }
+    if !cfg!(debug_assertions) && !crate::config::current(&app).journal.enabled {
+        return Err("journal replay is off in release builds (set journal.enabled)".into());
+    }
+    let speed = speed.unwrap_or(1.0);
+    if !(speed.is_finite() && speed > 0.0) {
+        return Err("speed must be a positive number".into());
+    }
+    let entries = load(Path::new(&path))?;
+    let (dry_run, allow) = (dry_run.unwrap_or(false), allow.unwrap_or_default());
+    tauri::async_runtime::spawn_blocking(move || replay(&app, &entries, speed, dry_run, &allow))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn entry(at_ms: u64, command: &str, redacted: bool) -> Entry {
+        Entry {
+            at_ms,
+            unix_ms: 0,
+            command: command.into(),
+            window: "main".into(),
+            args: json!({}),
+            redacted,
+            outcome: Outcome::Resolved,
+        }
+    }
+
+    #[test]
+    fn content_is_redacted_and_identifiers_kept() {
+        let body = InvokeBody::Json(json!({
+            "id": "whatsapp",
+            "timeoutMs": 500,
+            "phone": "4915112345678",
+            "text": "hello",
+            "policy": { "scope": "all", "note": "private" },
+        }));
+        let (args, redacted) = redact(&body);
+        assert!(redacted);
+        assert_eq!(args["id"], "whatsapp");
+        assert_eq!(args["timeoutMs"], 500);
+        assert_eq!(args["phone"], "<redacted 13 bytes>");
+        assert_eq!(args["text"], "<redacted 5 bytes>");
+        assert_eq!(args["policy"]["scope"], "all");
+        assert_eq!(args["policy"]["note"], "<redacted 7 bytes>");
+
+        let (args, redacted) = redact(&InvokeBody::Json(json!({ "id": "whatsapp", "enabled": true })));
+        assert!(!redacted);
+        assert_eq!(args, json!({ "id": "whatsapp", "enabled": true }));
+        assert!(redact(&InvokeBody::Raw(vec![0; 3])).1);
+    }
+
+    #[test]
+    fn outbound_and_redacted_commands_are_refused_unless_allowed() {
+        assert_eq!(refusal(&entry(0, "open_organ", false), &[]), None);
+        assert!(refusal(&entry(0, "open_organ", true), &[]).unwrap().contains("redacted"));
+        assert!(refusal(&entry(0, "open_external", false), &[]).is_some());
+        assert_eq!(refusal(&entry(0, "open_external", false), &["open_external".into()]), None);
+    }
+
+    #[test]
+    fn journals_are_capped_read_in_call_order_and_pruned() {
+        let host = crate::testing::TestHost::new();
+        let dir = host.scratch().join("journal");
+        let config = JournalConfig { enabled: true, max_bytes: 400 };
+        let mut writer = open_session(&dir, &config).unwrap();
+        for at in [30, 10, 20, 40, 50, 60] {
+            writer.append(&entry(at, "get_health", false));
+        }
+        assert!(writer.full);
+        assert!(writer.written <= 400);
+
+        let mut text = std::fs::read_to_string(&writer.path).unwrap();
+        text.push_str("{\"at_ms\": 5, \"comm");
+        let order: Vec<u64> = parse(&text).iter().map(|e| e.at_ms).collect();
+        assert!(order.len() >= 2 && order.len() < 6, "{order:?}");
+        assert!(order.windows(2).all(|w| w[0] <= w[1]));
+
+        for n in 0..KEEP_SESSIONS + 2 {
+            std::fs::write(dir.join(format!("{n:04}.jsonl")), "").unwrap();
+        }
+        prune(&dir, KEEP_SESSIONS);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), KEEP_SESSIONS);
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn redaction_never_keeps_long_or_unlisted_strings(doc in crate::testing::json()) {
+            fn check(key: Option<&str>, value: &serde_json::Value) -> bool {
+                match value {
+                    serde_json::Value::String(s) => {
+                        s.starts_with("<redacted ") || key.is_some_and(|k| KEPT_KEYS.contains(&k)) && s.len() <= MAX_KEPT_LEN
+                    }
+                    serde_json::Value::Array(items) => items.iter().all(|v| check(key, v)),
+                    serde_json::Value::Object(map) => map.iter().all(|(k, v)| check(Some(k), v)),
+                    _ => true,
+                }
+            }
+            let (args, _) = redact(&InvokeBody::Json(doc));
+            proptest::prop_assert!(check(None, &args));
+        }
+
+        #[test]
+        fn any_journal_text_parses_without_panicking(text in "\\PC{0,200}") {
+            let _ = parse(&text);
+        }
+    }
+}
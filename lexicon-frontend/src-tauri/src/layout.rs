@@ -0,0 +1,416 @@
+//! Layout — how the canvas and the organs share the screen.
+//!
+//! `exclusive`, the default, is the fullscreen swap described in
+//! `organs`: one window on screen at a time. `split` puts the canvas in
+//! the left 60% of its monitor's work area (the primary, unless
+//! `monitors` says otherwise) and the organ on screen in the right 40%,
+//! both windowed and always on top. Opening an
+//! organ then only takes other organs down and brings the canvas along;
+//! toggling the canvas hides or shows the pair. Going back to exclusive
+//! keeps the organ if one was beside the canvas, fullscreen again.
+//!
+//! The display mode is the other half. `overlay`, the default, is all of
+//! the above: fullscreen or paned, always on top, undecorated. `windowed`
+//! makes every window an ordinary one — decorated, in the taskbar, never
+//! on top, at the size and place it was left — and lets them be on screen
+//! together: showing one raises it and leaves the rest alone. The layout
+//! only applies to the overlay.
+//!
+//! An organ can also leave the overlay on its own, for a tiling window
+//! manager: `organs.display.<id>` (or `set_organ_display_mode`) is
+//! `fullscreen`, the default, `maximized` or `floating`. The last two make
+//! it an ordinary window like `windowed` does, maximized or at the place
+//! and size it was last left in, and it stays on screen whatever else is
+//! shown or hidden.
+//!
+//! Both are saved with the rest of the window state.
+
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager};
+
+use crate::window_state::Geometry;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+    #[default]
+    Exclusive,
+    Split,
+}
+
+impl std::str::FromStr for Layout {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, String> {
+        match mode {
+            "exclusive" => Ok(Layout::Exclusive),
+            "split" => Ok(Layout::Split),
+            _ => Err(format!("unknown layout '{mode}' — use exclusive or split")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[serde(rename_all = "lowercase")]
+pub enum DisplayMode {
+    #[default]
+    Overlay,
+    Windowed,
+}
+
+impl std::str::FromStr for DisplayMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, String> {
+        match mode {
+            "overlay" => Ok(DisplayMode::Overlay),
+            "windowed" => Ok(DisplayMode::Windowed),
+            _ => Err(format!("unknown display mode '{mode}' — use overlay or windowed")),
+        }
+    }
+}
+
+/// How one organ's window is shown in the overlay.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[serde(rename_all = "lowercase")]
+pub enum OrganDisplay {
+    #[default]
+    Fullscreen,
+    Maximized,
+    Floating,
+}
+
+impl std::str::FromStr for OrganDisplay {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, String> {
+        match mode {
+            "fullscreen" => Ok(OrganDisplay::Fullscreen),
+            "maximized" => Ok(OrganDisplay::Maximized),
+            "floating" => Ok(OrganDisplay::Floating),
+            _ => Err(format!("unknown organ display mode '{mode}' — use fullscreen, maximized or floating")),
+        }
+    }
+}
+
+/// Share of the work area the canvas gets in `split`, in percent.
+const CANVAS_SHARE: u32 = 60;
+
+static LAYOUT: Mutex<Layout> = Mutex::new(Layout::Exclusive);
+static DISPLAY: Mutex<DisplayMode> = Mutex::new(DisplayMode::Overlay);
+
+/// Organs hidden along with the canvas by a split-layout toggle.
+static ASIDE: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+pub fn current() -> Layout {
+    *LAYOUT.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub fn display() -> DisplayMode {
+    *DISPLAY.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Take the layout and display mode saved by an earlier run, before any
+/// window is shown.
+pub fn restore(layout: Layout, display: DisplayMode) {
+    *LAYOUT.lock().unwrap_or_else(|e| e.into_inner()) = layout;
+    *DISPLAY.lock().unwrap_or_else(|e| e.into_inner()) = display;
+}
+
+/// The canvas's pane and the organ's, side by side in `area`.
+fn panes(area: Geometry) -> (Geometry, Geometry) {
+    let left = (u64::from(area.width) * u64::from(CANVAS_SHARE) / 100) as u32;
+    let canvas = Geometry { width: left, ..area };
+    let organ = Geometry { x: area.x.saturating_add_unsigned(left), width: area.width - left, ..area };
+    (canvas, organ)
+}
+
+fn work_area(window: &tauri::WebviewWindow) -> Option<Geometry> {
+    let app = window.app_handle();
+    let monitor = crate::monitors::assigned(app, "main")
+        .or_else(|| app.primary_monitor().ok().flatten())
+        .or_else(|| window.current_monitor().ok().flatten())?;
+    let area = monitor.work_area();
+    Some(Geometry { x: area.position.x, y: area.position.y, width: area.size.width, height: area.size.height })
+}
+
+/// Put `window` in its pane if the layout is `split`. False otherwise,
+/// and for windows that have no pane; the caller makes those fullscreen.
+pub fn place(window: &tauri::WebviewWindow) -> bool {
+    if current() != Layout::Split {
+        return false;
+    }
+    let is_canvas = window.label() == "main";
+    if !is_canvas && crate::organs::id_from_label(window.label()).is_none() {
+        return false;
+    }
+    let _ = window.set_fullscreen(false);
+    if let Some(area) = work_area(window) {
+        let (canvas, organ) = panes(area);
+        let pane = if is_canvas { canvas } else { organ };
+        let _ = window.set_position(tauri::PhysicalPosition::new(pane.x, pane.y));
+        let _ = window.set_size(tauri::PhysicalSize::new(pane.width, pane.height));
+    }
+    true
+}
+
+/// How window `label` is shown; the canvas is always `Fullscreen`.
+pub fn organ_display(app: &tauri::AppHandle, label: &str) -> OrganDisplay {
+    let Some(id) = crate::organs::id_from_label(label) else { return OrganDisplay::Fullscreen };
+    crate::config::current(app).organs.display.get(id).copied().unwrap_or_default()
+}
+
+/// Whether window `label` is an ordinary one: everything when windowed,
+/// and organs that aren't fullscreen.
+pub fn ordinary(app: &tauri::AppHandle, label: &str) -> bool {
+    display() == DisplayMode::Windowed || organ_display(app, label) != OrganDisplay::Fullscreen
+}
+
+/// Give an ordinary window its place: maximized, or where it was last left.
+pub fn arrange(window: &tauri::WebviewWindow) {
+    match organ_display(window.app_handle(), window.label()) {
+        // Windowed, where it was left.
+        OrganDisplay::Fullscreen => crate::window_state::put_back(window),
+        OrganDisplay::Maximized => {
+            let _ = window.maximize();
+        }
+        OrganDisplay::Floating => {
+            let _ = window.unmaximize();
+            crate::window_state::put_back(window);
+        }
+    }
+}
+
+/// Whether windows `a` and `b` stay on screen together: in `split`, the
+/// canvas and an organ do; an organ that isn't fullscreen does with
+/// anything; in `windowed`, any two.
+pub fn beside(app: &tauri::AppHandle, a: &str, b: &str) -> bool {
+    ordinary(app, a)
+        || ordinary(app, b)
+        || (current() == Layout::Split && (a == "main") != (b == "main"))
+}
+
+/// Window chrome for the display mode: decorations and a taskbar entry
+/// only for ordinary windows.
+pub fn dress(window: &tauri::WebviewWindow) {
+    let ordinary = ordinary(window.app_handle(), window.label());
+    let _ = window.set_decorations(ordinary);
+    let _ = window.set_skip_taskbar(!ordinary);
+}
+
+/// Before an organ is shown in `split`, bring the canvas up next to it.
+pub fn bring_canvas(app: &tauri::AppHandle, label: &str) {
+    if current() != Layout::Split || label == "main" {
+        return;
+    }
+    if let Some(main) = app.get_webview_window("main") {
+        if !main.is_visible().unwrap_or(false) {
+            crate::show_window(&main);
+        }
+    }
+}
+
+fn visible_organs(app: &tauri::AppHandle) -> Vec<tauri::WebviewWindow> {
+    let manager = app.state::<crate::organs::OrganManager>();
+    manager
+        .defs()
+        .iter()
+        .filter(|d| crate::organs::status(app, &d.id) == "visible")
+        .filter_map(|d| app.get_webview_window(&crate::organs::window_label(&d.id)))
+        .collect()
+}
+
+/// The canvas was hidden: in `split`, its organ goes too.
+pub fn hide_beside(app: &tauri::AppHandle) {
+    if current() != Layout::Split || display() == DisplayMode::Windowed {
+        return;
+    }
+    let mut aside = ASIDE.lock().unwrap_or_else(|e| e.into_inner());
+    aside.clear();
+    for window in visible_organs(app) {
+        crate::hide_window(&window);
+        aside.push(window.label().to_string());
+        if let Some(id) = crate::organs::id_from_label(window.label()) {
+            crate::organs::announce(app, id);
+        }
+    }
+}
+
+/// The canvas was shown: in `split`, bring back what `hide_beside` hid.
+pub fn show_beside(app: &tauri::AppHandle) {
+    let aside = std::mem::take(&mut *ASIDE.lock().unwrap_or_else(|e| e.into_inner()));
+    if current() != Layout::Split || display() == DisplayMode::Windowed {
+        return;
+    }
+    for label in aside {
+        let Some(window) = app.get_webview_window(&label) else { continue };
+        crate::show_window(&window);
+        if let Some(id) = crate::organs::id_from_label(&label) {
+            crate::organs::announce(app, id);
+        }
+    }
+}
+
+/// Switch layouts and rearrange what is on screen to match.
+pub fn set(app: &tauri::AppHandle, layout: Layout) {
+    *LAYOUT.lock().unwrap_or_else(|e| e.into_inner()) = layout;
+    ASIDE.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    let main = app.get_webview_window("main");
+    let main_visible = main.as_ref().is_some_and(|m| m.is_visible().unwrap_or(false));
+    let organ = visible_organs(app).into_iter().next();
+    match (layout, organ) {
+        (Layout::Split, organ) => {
+            if let Some(main) = main.as_ref().filter(|_| main_visible || organ.is_some()) {
+                crate::show_window(main);
+            }
+            if let Some(organ) = organ {
+                crate::show_window(&organ);
+            }
+        }
+        (Layout::Exclusive, Some(organ)) => {
+            let id = crate::organs::id_from_label(organ.label()).unwrap_or_default().to_string();
+            if let Err(e) = crate::organs::open(app, &id) {
+                tracing::warn!("layout: {e}");
+            }
+        }
+        (Layout::Exclusive, None) => {
+            if let Some(main) = main.as_ref().filter(|_| main_visible) {
+                crate::show_window(main);
+            }
+        }
+    }
+    crate::window_state::save(app);
+    let _ = app.emit("layout://changed", layout);
+    tracing::info!("layout {layout:?}");
+}
+
+/// Switch display modes. Windows on screen get the new mode's flags at
+/// once; going back to overlay keeps one of them (the canvas if it is up,
+/// with its organ in split) and hides the rest.
+pub fn set_display(app: &tauri::AppHandle, mode: DisplayMode) {
+    *DISPLAY.lock().unwrap_or_else(|e| e.into_inner()) = mode;
+    ASIDE.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    let windows = app.webview_windows();
+    for window in windows.values() {
+        dress(window);
+    }
+    let visible: Vec<_> = windows.values().filter(|w| w.is_visible().unwrap_or(false)).collect();
+    match mode {
+        DisplayMode::Windowed => {
+            for window in &visible {
+                let _ = window.set_always_on_top(false);
+                let _ = window.set_fullscreen(false);
+            }
+            if let (Some(main), Some(g)) = (windows.get("main"), crate::window_state::geometry(app)) {
+                let _ = main.set_position(tauri::PhysicalPosition::new(g.x, g.y));
+                let _ = main.set_size(tauri::PhysicalSize::new(g.width, g.height));
+            }
+        }
+        DisplayMode::Overlay => {
+            let front = visible.iter().find(|w| w.label() == "main").or(visible.first());
+            if let Some(front) = front {
+                if let Err(e) = crate::organs::bring_forward(app, front.label()) {
+                    tracing::warn!("display: {e}");
+                }
+            }
+        }
+    }
+    crate::window_state::save(app);
+    let _ = app.emit("display://changed", mode);
+    tracing::info!("display mode {mode:?}");
+}
+
+#[tauri::command]
+pub fn get_layout() -> Layout {
+    current()
+}
+
+/// `mode` is "exclusive" or "split".
+#[tauri::command]
+pub fn set_layout(app: tauri::AppHandle, mode: String) -> Result<Layout, String> {
+    let layout = mode.parse()?;
+    set(&app, layout);
+    Ok(layout)
+}
+
+#[tauri::command]
+pub fn get_display_mode() -> DisplayMode {
+    display()
+}
+
+/// `mode` is "overlay" or "windowed".
+#[tauri::command]
+pub fn set_display_mode(app: tauri::AppHandle, mode: String) -> Result<DisplayMode, String> {
+    let mode = mode.parse()?;
+    set_display(&app, mode);
+    Ok(mode)
+}
+
+/// Show organ `id` as `mode` ("fullscreen", "maximized" or "floating")
+/// from now on, and save it.
+#[tauri::command]
+pub fn set_organ_display_mode(
+    window: tauri::WebviewWindow,
+    app: tauri::AppHandle,
+    id: String,
+    mode: String,
+) -> Result<OrganDisplay, String> {
+    if window.label() != "main" {
+        return Err("only the main window can change how organs are shown".into());
+    }
+    let mode: OrganDisplay = mode.parse()?;
+    app.state::<crate::organs::OrganManager>().get(&id).ok_or_else(|| format!("unknown organ: {id}"))?;
+    let key = id.clone();
+    crate::config::update(&app, move |c| {
+        if mode == OrganDisplay::Fullscreen {
+            c.organs.display.remove(&key);
+        } else {
+            c.organs.display.insert(key, mode);
+        }
+    })?;
+    let label = crate::organs::window_label(&id);
+    if let Some(organ) = app.get_webview_window(&label) {
+        dress(&organ);
+        if organ.is_visible().unwrap_or(false) {
+            if mode == OrganDisplay::Fullscreen {
+                crate::organs::bring_forward(&app, &label)?;
+            } else {
+                let _ = organ.set_fullscreen(false);
+                crate::show_window(&organ);
+            }
+        }
+    }
+    tracing::info!("organ {id} display {mode:?}");
+    Ok(mode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_panes_share_the_work_area() {
+        let area = Geometry { x: -2560, y: 30, width: 5120, height: 1410 };
+        let (canvas, organ) = panes(area);
+        assert_eq!(canvas, Geometry { x: -2560, y: 30, width: 3072, height: 1410 });
+        assert_eq!(organ, Geometry { x: 512, y: 30, width: 2048, height: 1410 });
+    }
+
+    #[test]
+    fn modes_parse_by_name() {
+        assert_eq!("split".parse::<Layout>(), Ok(Layout::Split));
+        assert_eq!("exclusive".parse::<Layout>(), Ok(Layout::Exclusive));
+        assert!("tiled".parse::<Layout>().is_err());
+        assert_eq!("windowed".parse::<DisplayMode>(), Ok(DisplayMode::Windowed));
+        assert_eq!("overlay".parse::<DisplayMode>(), Ok(DisplayMode::Overlay));
+        assert!("kiosk".parse::<DisplayMode>().is_err());
+        assert_eq!("floating".parse::<OrganDisplay>(), Ok(OrganDisplay::Floating));
+        assert_eq!("maximized".parse::<OrganDisplay>(), Ok(OrganDisplay::Maximized));
+        assert!("tiled".parse::<OrganDisplay>().is_err());
+    }
+}
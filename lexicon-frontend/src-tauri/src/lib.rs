@@ -1,53 +1,573 @@
+mod actions;
+mod audio;
+mod autostart;
+mod badge;
+mod brain;
+#[cfg(desktop)]
+mod brain_supervisor;
+mod capture;
+#[cfg(desktop)]
+mod cli;
+mod clipboard;
+mod clipboard_watch;
+mod config;
+#[cfg(desktop)]
+mod control;
+#[cfg(target_os = "linux")]
+mod dbus;
+mod dedup;
+mod deeplink;
+mod discord;
+mod dnd;
+mod email;
+mod external;
+mod filter;
+mod headless;
+mod health;
+mod host;
+mod idle;
+mod ingest;
+mod inhibit;
+mod journal;
+#[cfg(desktop)]
+mod layout;
+mod lifecycle;
+mod loadtest;
+mod logging;
+mod media;
+mod metrics;
+#[cfg(desktop)]
+mod monitors;
+mod network;
+mod notify;
+mod organs;
+mod platform;
+mod power;
+mod presentation;
+mod proxy;
+mod qr;
+mod ratelimit;
+mod relay;
+#[cfg(desktop)]
+mod shortcuts;
+mod shutdown;
+mod spool;
+#[cfg(desktop)]
+mod single_instance;
+mod startup;
+mod telegram;
+#[cfg(test)]
+mod testing;
+mod theme;
+mod transition;
+#[cfg(desktop)]
+mod tray;
+#[cfg(mobile)]
+#[path = "tray_mobile.rs"]
+mod tray;
+#[cfg(desktop)]
+mod updater;
+mod voice;
+mod watchdog;
+mod whatsapp;
+#[cfg(desktop)]
+mod window_state;
+
 use tauri::Manager;
 
 use std::sync::atomic::{AtomicBool, Ordering};
 
 /// Track whether the main overlay is visible.
-static OVERLAY_VISIBLE: AtomicBool = AtomicBool::new(false);
+pub(crate) static OVERLAY_VISIBLE: AtomicBool = AtomicBool::new(false);
 
-// ── Toggle main overlay ────────────────────────────────────────
+/// Set once the `--hidden` boot has hidden the main window.
+#[cfg(desktop)]
+static BOOT_HIDDEN: AtomicBool = AtomicBool::new(false);
 
-#[tauri::command]
-fn toggle_window(app: tauri::AppHandle) {
-    if let Some(window) = app.get_webview_window("main") {
-        if window.is_visible().unwrap_or(false) {
+// ── Window helpers ─────────────────────────────────────────────
+
+/// Hide a window, dropping the overlay flags first so nothing lingers
+/// always-on-top while the compositor catches up.
+/// On mobile the app is its only window and the OS owns its visibility.
+pub(crate) fn hide_window(window: &tauri::WebviewWindow) {
+    #[cfg(desktop)]
+    if !headless::enabled() {
+        let _ = window.set_always_on_top(false);
+        let _ = window.set_fullscreen(false);
+        let _ = window.hide();
+    }
+    if window.label() == "main" {
+        OVERLAY_VISIBLE.store(false, Ordering::Relaxed);
+        health::announce(window.app_handle());
+    }
+}
+
+/// Show a window as the fullscreen, focused overlay — or in its pane,
+/// in the split layout. Windowed, it is only raised and focused.
+/// Headless, windows stay where they are; see `headless`.
+pub(crate) fn show_window(window: &tauri::WebviewWindow) {
+    #[cfg(desktop)]
+    if !headless::enabled() {
+        let _ = window.show();
+        if layout::ordinary(window.app_handle(), window.label()) {
             let _ = window.set_always_on_top(false);
-            let _ = window.set_fullscreen(false);
-            let _ = window.hide();
-            OVERLAY_VISIBLE.store(false, Ordering::Relaxed);
-            eprintln!("[lexicon] window hidden");
+            let _ = window.unminimize();
+            layout::arrange(window);
         } else {
-            let _ = window.show();
             let _ = window.set_always_on_top(true);
-            let _ = window.set_fullscreen(true);
-            let _ = window.set_focus();
-            OVERLAY_VISIBLE.store(true, Ordering::Relaxed);
-            eprintln!("[lexicon] window shown + fullscreen");
+            if !layout::place(window) {
+                monitors::place(window);
+                let _ = window.set_fullscreen(true);
+            }
+        }
+        let _ = window.set_focus();
+    }
+    if window.label() == "main" {
+        OVERLAY_VISIBLE.store(true, Ordering::Relaxed);
+        health::announce(window.app_handle());
+    }
+}
+
+/// Hide every visible window and return the labels that were hidden,
+/// so the caller can bring exactly those back later.
+pub(crate) fn hide_all_windows(app: &tauri::AppHandle) -> Vec<String> {
+    let mut hidden = Vec::new();
+    for (label, window) in app.webview_windows() {
+        if window.is_visible().unwrap_or(false) {
+            hide_window(&window);
+            hidden.push(label);
+        }
+    }
+    hidden
+}
+
+// ── Toggle main overlay ────────────────────────────────────────
+
+/// Show the main overlay if hidden, hide it if shown. With an organ on
+/// screen instead, leave it for the canvas. One transition (see
+/// `transition`); blocks until it is done, so keep it off the main thread.
+pub(crate) fn toggle(app: &tauri::AppHandle) -> Result<transition::Screen, String> {
+    headless::refuse("toggling the overlay")?;
+    app.state::<transition::WindowManager>().transition(app, || {
+        if presentation::is_active() {
+            tracing::info!("toggle ignored — presentation guard is on");
+            return Ok(());
+        }
+        #[cfg(desktop)]
+        if layout::current() == layout::Layout::Exclusive && layout::display() == layout::DisplayMode::Overlay {
+            if let Some(id) = organs::on_screen(app).filter(|id| organs::covers_canvas(app, id)) {
+                organs::hide(app, &id)?;
+                tray::refresh_tooltip(app);
+                return Ok(());
+            }
+        }
+        if let Some(window) = app.get_webview_window("main") {
+            if window.is_visible().unwrap_or(false) {
+                hide_main(app, &window);
+            } else {
+                show_window(&window);
+                #[cfg(desktop)]
+                layout::show_beside(app);
+                tracing::info!("window shown");
+            }
+        }
+        #[cfg(desktop)]
+        window_state::save(app);
+        tray::refresh_tooltip(app);
+        Ok(())
+    })
+}
+
+/// Take the main overlay down, and the organ beside it in the split layout.
+fn hide_main(app: &tauri::AppHandle, window: &tauri::WebviewWindow) {
+    hide_window(window);
+    #[cfg(desktop)]
+    layout::hide_beside(app);
+    #[cfg(mobile)]
+    let _ = app;
+    tracing::info!("window hidden");
+}
+
+/// The main window's close button (or Alt+F4) hides it as `toggle` does;
+/// the process, the organs and their relays keep running, and quitting is
+/// left to the tray and `quit_app`. With `close_exits_app` it quits.
+#[cfg(desktop)]
+fn guard_close(app: &tauri::AppHandle) {
+    let Some(main) = app.get_webview_window("main") else { return };
+    let handle = app.clone();
+    main.on_window_event(move |event| {
+        let tauri::WindowEvent::CloseRequested { api, .. } = event else { return };
+        api.prevent_close();
+        let app = handle.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            if config::current(&app).close_exits_app {
+                return shutdown::graceful_exit(&app);
+            }
+            if let Some(window) = app.get_webview_window("main") {
+                hide_main(&app, &window);
+                window_state::save(&app);
+                tray::refresh_tooltip(&app);
+                transition::changed(&app);
+            }
+        });
+    });
+}
+
+/// `toggle` in the background, for the tray, shortcuts and the rest.
+pub(crate) fn toggle_main(app: &tauri::AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        if let Err(e) = toggle(&app) {
+            tracing::info!("toggle ignored — {e}");
         }
+    });
+}
+
+#[tauri::command]
+async fn toggle_window(app: tauri::AppHandle) -> Result<transition::Screen, String> {
+    if platform::CURRENT == platform::Platform::Mobile {
+        return Err(platform::NotSupportedOnPlatform::new("toggling the overlay").into());
     }
+    tauri::async_runtime::spawn_blocking(move || toggle(&app)).await.map_err(|e| e.to_string())?
+}
+
+// ── Forwarded launches ─────────────────────────────────────────
+
+/// Handle the arguments of a second launch in this (primary) process.
+/// No arguments means the default action: toggle. A login launch
+/// (`--hidden`) finding us already running has nothing to do.
+#[cfg(desktop)]
+fn handle_forwarded(app: &tauri::AppHandle, args: Vec<String>) -> String {
+    if args.iter().any(|a| a == autostart::HIDDEN_FLAG) {
+        return "ok".into();
+    }
+    if let Some(link) = args.iter().find(|a| deeplink::is_link(a)) {
+        deeplink::handle(app, link);
+        return "ok".into();
+    }
+    let result = match cli::parse(&args) {
+        Ok(Some(command)) => cli::execute(app, command),
+        Ok(None) => cli::execute(app, cli::Command::Toggle),
+        Err(e) => Err(e),
+    };
+    result.unwrap_or_else(|e| format!("error: {e}"))
 }
 
 // ── App entry ──────────────────────────────────────────────────
 
+/// Everything that waits for first paint. See `startup`.
+fn deferred_phases() -> Vec<startup::Phase> {
+    use startup::Phase;
+    vec![
+        Phase {
+            name: "tray",
+            after: &[],
+            main_thread: true,
+            run: |app| {
+                if let Err(e) = tray::init(app) {
+                    tracing::warn!("tray unavailable: {e}");
+                }
+            },
+        },
+        Phase {
+            name: "schemes",
+            after: &[],
+            main_thread: false,
+            run: |app| {
+                deeplink::register(app);
+                whatsapp::init(app);
+            },
+        },
+        #[cfg(target_os = "linux")]
+        Phase { name: "dbus", after: &[], main_thread: false, run: dbus::init },
+        Phase { name: "power", after: &[], main_thread: false, run: power::init },
+        Phase { name: "clipboard watcher", after: &[], main_thread: false, run: clipboard_watch::init },
+        #[cfg(desktop)]
+        Phase { name: "brain process", after: &[], main_thread: false, run: brain_supervisor::init },
+        Phase { name: "metrics endpoint", after: &[], main_thread: false, run: metrics::serve },
+        Phase { name: "network", after: &["power"], main_thread: false, run: network::init },
+        Phase { name: "brain monitor", after: &["network"], main_thread: false, run: brain::monitor },
+        Phase { name: "ingest queue", after: &["network"], main_thread: false, run: |app| ingest::resume(app) },
+        // Organs show up in the tray menu.
+        Phase {
+            name: "organs",
+            after: &["tray"],
+            main_thread: false,
+            run: |app| {
+                shutdown::restore_session(app);
+                whatsapp::autostart(app);
+                watchdog::start(app);
+                idle::start(app);
+            },
+        },
+        #[cfg(desktop)]
+        Phase { name: "control channel", after: &["network"], main_thread: false, run: control::start },
+        #[cfg(desktop)]
+        Phase { name: "updater", after: &["network"], main_thread: false, run: updater::init },
+    ]
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    tauri::Builder::default()
+    logging::init();
+    startup::begin();
+    #[cfg(desktop)]
+    let instance = {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        if args.iter().any(|a| a == "--help" || a == "help") {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        headless::init(&args);
+        let command = match cli::parse(&args) {
+            Ok(command) => command,
+            Err(e) => {
+                eprintln!("lexicon: {e}\n{}", cli::USAGE);
+                std::process::exit(2);
+            }
+        };
+        match single_instance::acquire(&single_instance::default_lock_path(), &args) {
+            Ok(single_instance::Instance::Forwarded(reply)) => match reply.strip_prefix("error: ") {
+                Some(e) => {
+                    eprintln!("lexicon: {e}");
+                    std::process::exit(1);
+                }
+                None => {
+                    println!("{reply}");
+                    return;
+                }
+            },
+            // Control commands need someone to control.
+            Ok(single_instance::Instance::Primary(_)) | Err(_) if command.is_some() => {
+                eprintln!("lexicon: no running instance");
+                std::process::exit(1);
+            }
+            Ok(single_instance::Instance::Primary(lock)) => Some(lock),
+            Err(e) => {
+                tracing::warn!("single-instance check failed ({e}) — starting anyway");
+                None
+            }
+        }
+    };
+
+    let mut context = tauri::generate_context!();
+    headless::strip_main(&mut context);
+    let builder = tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_shell::init())
-        .invoke_handler(tauri::generate_handler![
+        .plugin(tauri_plugin_deep_link::init())
+        .plugin(tauri_plugin_clipboard_manager::init());
+    #[cfg(desktop)]
+    let builder = builder.plugin(
+        tauri_plugin_global_shortcut::Builder::new()
+            .with_handler(shortcuts::on_shortcut)
+            .build(),
+    );
+    #[cfg(desktop)]
+    let builder = builder.plugin(tauri_plugin_updater::Builder::new().build());
+
+    builder
+        // Every command goes through the journal, which passes it straight on unless recording.
+        .invoke_handler(journal::wrap(tauri::generate_handler![
             toggle_window,
-        ])
+            presentation::set_presentation_guard,
+            health::get_health,
+            brain::brain_status,
+            #[cfg(desktop)]
+            brain_supervisor::brain_start,
+            #[cfg(desktop)]
+            brain_supervisor::brain_stop,
+            #[cfg(desktop)]
+            brain_supervisor::brain_restart,
+            #[cfg(desktop)]
+            brain_supervisor::brain_logs,
+            relay::spool_stats,
+            relay::relay_queue_status,
+            filter::get_relay_filters,
+            metrics::get_metrics,
+            metrics::reset_metrics,
+            filter::set_relay_filters,
+            filter::test_relay_filter,
+            logging::get_recent_logs,
+            logging::set_log_level,
+            relay::set_relay_secret,
+            ratelimit::set_rate_limits,
+            brain::get_brain_url,
+            brain::set_brain_url,
+            dnd::set_dnd,
+            dnd::get_dnd,
+            organs::open_organ,
+            organs::show_organ,
+            organs::close_organ,
+            organs::organ_status,
+            organs::organ_state,
+            organs::wait_for_organ,
+            organs::list_organs,
+            organs::get_organ_resources,
+            #[cfg(desktop)]
+            window_state::reset_window_state,
+            shutdown::quit_app,
+            proxy::get_proxy_config,
+            proxy::set_proxy_config,
+            #[cfg(desktop)]
+            layout::get_layout,
+            #[cfg(desktop)]
+            layout::set_layout,
+            #[cfg(desktop)]
+            layout::get_display_mode,
+            #[cfg(desktop)]
+            layout::set_display_mode,
+            #[cfg(desktop)]
+            layout::set_organ_display_mode,
+            #[cfg(desktop)]
+            monitors::list_monitors,
+            #[cfg(desktop)]
+            monitors::move_window_to_monitor,
+            badge::set_tray_badge_policy,
+            autostart::set_autostart,
+            autostart::get_autostart,
+            notify::notify_message,
+            qr::wa_get_qr,
+            whatsapp::wa_reinject,
+            whatsapp::open_whatsapp_organ,
+            whatsapp::close_whatsapp_organ,
+            whatsapp::whatsapp_organ_status,
+            whatsapp::whatsapp_status_history,
+            actions::organ_action,
+            notify::get_notification_prefs,
+            notify::set_notification_prefs,
+            notify::mute_organ,
+            clipboard::copy_to_clipboard,
+            clipboard::copy_image_to_clipboard,
+            clipboard::wa_paste_attachment,
+            clipboard_watch::clipboard_watch_enable,
+            whatsapp::wa_open_chat,
+            whatsapp::wa_send_message,
+            whatsapp::clear_whatsapp_session,
+            telegram::tg_relay_message,
+            telegram::tg_relay_status,
+            media::wa_relay_media,
+            media::wa_relay_media_chunk,
+            external::open_external,
+            external::confirm_external,
+            capture::capture_screen,
+            capture::capture_window,
+            voice::start_voice_capture,
+            voice::stop_voice_capture,
+            audio::play_audio,
+            audio::stop_audio,
+            audio::set_output_volume,
+            theme::get_system_theme,
+            theme::set_theme_override,
+            platform::get_platform,
+            startup::frontend_ready,
+            startup::get_startup_report,
+            loadtest::start_load_test,
+            loadtest::stop_load_test,
+            loadtest::get_load_test_report,
+            journal::replay_journal,
+            #[cfg(desktop)]
+            shortcuts::get_shortcuts,
+            #[cfg(desktop)]
+            shortcuts::set_shortcut,
+            #[cfg(desktop)]
+            shortcuts::set_toggle_shortcut,
+            #[cfg(desktop)]
+            shortcuts::get_shortcut_status,
+            #[cfg(desktop)]
+            shortcuts::reset_shortcuts,
+            #[cfg(desktop)]
+            shortcuts::run_shortcut,
+            #[cfg(desktop)]
+            updater::check_for_updates,
+            #[cfg(desktop)]
+            updater::apply_update,
+        ]))
+        .on_page_load(|webview, payload| {
+            // Organs lose an evaluated theme on every navigation/reload,
+            // and may have signed in or out since the last one. A reload
+            // is a new session as far as status relaying goes.
+            if let Some(id) = organs::id_from_label(webview.label()) {
+                match payload.event() {
+                    tauri::webview::PageLoadEvent::Started => relay::reset(webview.app_handle(), id),
+                    tauri::webview::PageLoadEvent::Finished => organs::loaded(webview.app_handle(), id),
+                }
+                return;
+            }
+            // Started with --hidden (login): hide as soon as the canvas has
+            // loaded instead of leaving it up until it reaches the Brain.
+            #[cfg(desktop)]
+            if webview.label() == "main"
+                && payload.event() == tauri::webview::PageLoadEvent::Finished
+                && autostart::launched_hidden()
+                && !BOOT_HIDDEN.swap(true, Ordering::Relaxed)
+            {
+                if let Some(window) = webview.app_handle().get_webview_window("main") {
+                    let _ = window.hide();
+                    tracing::info!("--hidden → window hidden after page load");
+                }
+            }
+        })
         .setup(|app| {
-            if let Some(window) = app.get_webview_window("main") {
-                let w = window.clone();
-                std::thread::spawn(move || {
-                    std::thread::sleep(std::time::Duration::from_secs(2));
-                    let _ = w.hide();
-                    eprintln!("[lexicon] WebView booted → window hidden (waiting for toggle)");
+            let handle = app.handle().clone();
+            logging::attach(&handle);
+            let config = startup::span("config", || config::load(&handle));
+            startup::span("state", || {
+                let accounts = config.organs.whatsapp_accounts.clone();
+                let transport = proxy::transport(&config.proxy);
+                app.manage(config::ConfigState(std::sync::RwLock::new(config)));
+                config::watch(&handle);
+                app.manage(brain::BrainClient::new(transport));
+                proxy::init(&handle);
+                app.manage(organs::OrganManager::new().with_whatsapp_accounts(&accounts));
+                app.manage(transition::WindowManager::default());
+                app.manage(notify::NotificationDispatcher::default());
+                relay::init(&handle);
+                badge::init(&handle);
+                journal::init(&handle);
+                #[cfg(desktop)]
+                window_state::init(&handle);
+            });
+            #[cfg(desktop)]
+            startup::span("organs", || window_state::reopen(&handle));
+            startup::span("deeplink", || deeplink::init(&handle));
+            #[cfg(desktop)]
+            startup::span("shortcuts", || shortcuts::init(&handle));
+            startup::span("window hooks", || {
+                ingest::init(&handle);
+                audio::init(&handle);
+                theme::init(&handle);
+                #[cfg(desktop)]
+                guard_close(&handle);
+            });
+            startup::defer(&handle, deferred_phases());
+            // No canvas is coming to paint.
+            if headless::enabled() {
+                startup::frontend_ready(None);
+            }
+            #[cfg(desktop)]
+            if let Some(lock) = instance {
+                let forwarded = handle.clone();
+                lock.serve(move |args| handle_forwarded(&forwarded, args));
+            }
+
+            #[cfg(desktop)]
+            if let Some(window) = app.get_webview_window("main").filter(|_| !autostart::launched_hidden()) {
+                let hide = config::current(&handle).startup.hide_after_boot;
+                let restoring = handle.clone();
+                startup::when_connected(move || {
+                    if hide {
+                        let _ = window.hide();
+                        tracing::info!("canvas booted → window hidden (waiting for toggle)");
+                    }
+                    window_state::restore(&restoring, true);
                 });
+            } else {
+                window_state::restore(&handle, false);
             }
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(context)
+        .expect("error while building tauri application")
+        .run(|app, event| shutdown::on_run_event(app, &event));
 }
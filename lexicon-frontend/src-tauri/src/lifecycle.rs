@@ -0,0 +1,174 @@
+//! Organ lifecycle — where an organ's session is, as a state rather than
+//! whatever string its page last said.
+//!
+//! Organ pages report strings (see `organs::watch_status`), which
+//! [`Lifecycle::parse`] maps: "connected" is `logged_in`, "qr"
+//! `qr_pending`, "loading" `connecting`, "offline" `disconnected` and
+//! "error:<why>" an `error`. Anything else is logged and dropped. The app
+//! adds `booting` when an organ's page starts loading, `suspended` (see
+//! `idle`) and `shutdown` on exit.
+//!
+//! The relay forwards the state to the Brain by name (see `relay`). Every
+//! state it relays that differs from the one before, and every `booting`,
+//! is a transition: it is kept in a ring of the last 100 per organ, which
+//! `whatsapp_status_history` returns, and emitted to the main window as
+//! `{organ}://state-changed` (`whatsapp://state-changed`) with the
+//! [`Transition`]. Keepalives and repeats aren't transitions.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tauri::Emitter;
+
+/// Transitions kept per organ.
+const CAPACITY: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[serde(rename_all = "snake_case")]
+pub enum Lifecycle {
+    /// The page is loading; it hasn't said anything yet.
+    Booting,
+    /// Signed out; for WhatsApp, showing the QR code.
+    QrPending,
+    /// Online, but neither signed in nor asking to be yet.
+    Connecting,
+    LoggedIn,
+    /// The machine is offline.
+    Disconnected,
+    /// Closed while idle; see `idle`.
+    Suspended,
+    /// The app is exiting.
+    #[serde(rename = "shutdown")]
+    ShutDown,
+    /// What the page said went wrong.
+    Error(String),
+}
+
+impl Lifecycle {
+    /// The state an organ page's report stands for, if any.
+    pub fn parse(report: &str) -> Option<Self> {
+        Some(match report {
+            "connected" => Self::LoggedIn,
+            "qr" => Self::QrPending,
+            "loading" => Self::Connecting,
+            "offline" => Self::Disconnected,
+            other => Self::Error(other.strip_prefix("error:")?.trim().to_string()),
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Booting => "booting",
+            Self::QrPending => "qr_pending",
+            Self::Connecting => "connecting",
+            Self::LoggedIn => "logged_in",
+            Self::Disconnected => "disconnected",
+            Self::Suspended => "suspended",
+            Self::ShutDown => "shutdown",
+            Self::Error(_) => "error",
+        }
+    }
+}
+
+impl fmt::Display for Lifecycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error(why) => write!(f, "error ({why})"),
+            state => f.write_str(state.name()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Transition {
+    /// None for the first state this session.
+    pub from: Option<Lifecycle>,
+    pub to: Lifecycle,
+    /// Unix milliseconds.
+    pub at: u64,
+}
+
+/// The last transitions of each organ, oldest first.
+#[derive(Debug, Default)]
+struct History(BTreeMap<String, VecDeque<Transition>>);
+
+impl History {
+    /// Move `id` to `to`. The transition, unless it was there already.
+    fn enter(&mut self, id: &str, to: &Lifecycle, at: u64) -> Option<Transition> {
+        let ring = self.0.entry(id.to_string()).or_default();
+        let from = ring.back().map(|last| last.to.clone());
+        if from.as_ref() == Some(to) {
+            return None;
+        }
+        if ring.len() == CAPACITY {
+            ring.pop_front();
+        }
+        let transition = Transition { from, to: to.clone(), at };
+        ring.push_back(transition.clone());
+        Some(transition)
+    }
+}
+
+static HISTORY: Mutex<History> = Mutex::new(History(BTreeMap::new()));
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+/// Organ `id` is now in `state` (as of `at`, Unix milliseconds); record
+/// and announce it if that is a change.
+pub fn enter(app: &tauri::AppHandle, id: &str, state: &Lifecycle, at: u64) {
+    let Some(transition) = HISTORY.lock().unwrap_or_else(|e| e.into_inner()).enter(id, state, at) else { return };
+    match &transition.from {
+        Some(from) => tracing::info!("organ {id}: {from} → {state}"),
+        None => tracing::info!("organ {id}: {state}"),
+    }
+    let _ = app.emit_to("main", &format!("{id}://state-changed"), &transition);
+}
+
+/// Organ `id`'s page started loading.
+pub fn booting(app: &tauri::AppHandle, id: &str) {
+    enter(app, id, &Lifecycle::Booting, now_ms());
+}
+
+/// Organ `id`'s transitions this session, oldest first.
+pub fn history(id: &str) -> Vec<Transition> {
+    HISTORY.lock().unwrap_or_else(|e| e.into_inner()).0.get(id).map_or_else(Vec::new, |ring| ring.iter().cloned().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_map_to_states_and_only_changes_are_kept() {
+        assert_eq!(Lifecycle::parse("connected"), Some(Lifecycle::LoggedIn));
+        assert_eq!(Lifecycle::parse("qr"), Some(Lifecycle::QrPending));
+        assert_eq!(Lifecycle::parse("offline"), Some(Lifecycle::Disconnected));
+        assert_eq!(Lifecycle::parse("error: stream closed"), Some(Lifecycle::Error("stream closed".into())));
+        assert_eq!(Lifecycle::parse("online"), None);
+        assert_eq!(Lifecycle::parse("suspended"), None);
+        assert_eq!(serde_json::to_value(Lifecycle::ShutDown).unwrap(), serde_json::json!("shutdown"));
+        assert_eq!(serde_json::to_value(Lifecycle::QrPending).unwrap(), serde_json::json!("qr_pending"));
+
+        let mut history = History::default();
+        let first = history.enter("whatsapp", &Lifecycle::Booting, 1).unwrap();
+        assert_eq!(first.from, None);
+        let next = history.enter("whatsapp", &Lifecycle::LoggedIn, 2).unwrap();
+        assert_eq!((next.from, next.to), (Some(Lifecycle::Booting), Lifecycle::LoggedIn));
+        assert_eq!(history.enter("whatsapp", &Lifecycle::LoggedIn, 3), None);
+        assert!(history.enter("whatsapp-work", &Lifecycle::LoggedIn, 3).is_some());
+
+        for at in 0..2 * CAPACITY as u64 {
+            let state = if at % 2 == 0 { Lifecycle::Disconnected } else { Lifecycle::LoggedIn };
+            history.enter("whatsapp", &state, 10 + at);
+        }
+        let ring = &history.0["whatsapp"];
+        assert_eq!(ring.len(), CAPACITY);
+        assert_eq!(ring.back().map(|t| t.at), Some(9 + 2 * CAPACITY as u64));
+    }
+}
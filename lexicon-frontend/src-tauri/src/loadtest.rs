@@ -0,0 +1,433 @@
+//! Synthetic load through the relay pipeline, for soak tests.
+//!
+//! `start_load_test` feeds the relay's [`Delivery`] pool with fake
+//! WhatsApp messages at a fixed rate, without the webview, so a run
+//! against the configured (or a mock) Brain exercises the queue, the
+//! workers and the transport at volumes nobody can type.
+//! `get_load_test_report` gives throughput, latency percentiles, the
+//! queue's high-water mark, memory growth and errors; `stop_load_test`
+//! ends a run early. One run at a time.
+//!
+//! Every message goes to [`PATH`] with `"synthetic": true` and the run's
+//! id, so the Brain can ignore them or purge a run afterwards. Bursts are
+//! batched to [`BATCH_PATH`] the way relayed messages are. Debug
+//! builds always allow it; release builds only with
+//! `load_test.enabled = true`.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tauri::Manager;
+
+use crate::relay::{Acked, Delivery, Post};
+
+/// Where synthetic messages are sent.
+pub const PATH: &str = "/whatsapp/message";
+/// Where they go when several arrive together; see `Post::batched`.
+pub const BATCH_PATH: &str = "/whatsapp/messages";
+
+const MAX_RATE: u32 = 10_000;
+const MAX_DURATION_SECS: u64 = 24 * 60 * 60;
+const MAX_PAYLOAD: usize = 1024 * 1024;
+/// Latencies are counted per millisecond up to this; slower ones share
+/// the last bucket.
+const LATENCY_BUCKETS: usize = 10_000;
+/// Distinct error messages kept per run.
+const MAX_ERRORS: usize = 16;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+#[serde(default)]
+pub struct LoadTestConfig {
+    /// Allow load tests in release builds.
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Params {
+    pub messages_per_sec: u32,
+    pub duration_secs: u64,
+    /// Bytes of message text.
+    pub payload_size: usize,
+}
+
+impl Params {
+    fn check(&self) -> Result<(), String> {
+        if !(1..=MAX_RATE).contains(&self.messages_per_sec) {
+            return Err(format!("messages_per_sec must be 1–{MAX_RATE}"));
+        }
+        if !(1..=MAX_DURATION_SECS).contains(&self.duration_secs) {
+            return Err(format!("duration_secs must be 1–{MAX_DURATION_SECS}"));
+        }
+        if self.payload_size > MAX_PAYLOAD {
+            return Err(format!("payload_size must be at most {MAX_PAYLOAD}"));
+        }
+        Ok(())
+    }
+
+    fn total(&self) -> u64 {
+        u64::from(self.messages_per_sec) * self.duration_secs
+    }
+}
+
+struct Histogram(Vec<u64>);
+
+impl Histogram {
+    fn new() -> Self {
+        Self(vec![0; LATENCY_BUCKETS + 1])
+    }
+
+    fn record(&mut self, latency: Duration) {
+        let ms = (latency.as_millis() as usize).min(LATENCY_BUCKETS);
+        self.0[ms] += 1;
+    }
+
+    /// The smallest bucket holding at least `q` of the samples.
+    fn percentile(&self, q: f64) -> Option<u64> {
+        let count: u64 = self.0.iter().sum();
+        if count == 0 {
+            return None;
+        }
+        let rank = ((count as f64 * q).ceil() as u64).max(1);
+        let mut seen = 0;
+        self.0.iter().position(|n| {
+            seen += n;
+            seen >= rank
+        })
+        .map(|ms| ms as u64)
+    }
+}
+
+struct Run {
+    id: String,
+    params: Params,
+    started: Instant,
+    stop: AtomicBool,
+    generated: AtomicU64,
+    rejected: AtomicU64,
+    delivered: AtomicU64,
+    failed: AtomicU64,
+    high_water: AtomicUsize,
+    latency: Mutex<Histogram>,
+    errors: Mutex<BTreeMap<String, u64>>,
+    memory_start: Option<u64>,
+    /// Milliseconds from start to the last answer, offset by one so 0 can
+    /// mean "still running".
+    finished_ms: AtomicU64,
+}
+
+impl Run {
+    fn new(params: Params) -> Self {
+        let unix_ms = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis());
+        Self {
+            id: format!("load-{unix_ms}"),
+            params,
+            started: Instant::now(),
+            stop: AtomicBool::new(false),
+            generated: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+            delivered: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            high_water: AtomicUsize::new(0),
+            latency: Mutex::new(Histogram::new()),
+            errors: Mutex::new(BTreeMap::new()),
+            memory_start: memory(),
+            finished_ms: AtomicU64::new(0),
+        }
+    }
+
+    fn running(&self) -> bool {
+        self.finished_ms.load(Ordering::SeqCst) == 0
+    }
+
+    fn message(&self, seq: u64, text: &str) -> serde_json::Value {
+        let unix_ms = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64);
+        serde_json::json!({
+            "synthetic": true,
+            "load_test": self.id,
+            "seq": seq,
+            "chat": "Lexicon load test",
+            "from": "lexicon-load-test",
+            "timestamp": unix_ms,
+            "text": text,
+        })
+    }
+
+    fn record(&self, acked: Acked) {
+        self.latency.lock().unwrap_or_else(|e| e.into_inner()).record(acked.latency);
+        let Some(error) = acked.error else {
+            self.delivered.fetch_add(1, Ordering::Relaxed);
+            return;
+        };
+        self.failed.fetch_add(1, Ordering::Relaxed);
+        let mut errors = self.errors.lock().unwrap_or_else(|e| e.into_inner());
+        if errors.len() < MAX_ERRORS || errors.contains_key(&error) {
+            *errors.entry(error).or_default() += 1;
+        }
+    }
+}
+
+/// Submit the run's messages to `delivery` on schedule, until the run
+/// is over or stopped. Answers arrive on `acks`.
+fn generate(run: &Run, delivery: &Delivery, acks: mpsc::Sender<Acked>) {
+    let interval = (Duration::from_secs(1) / run.params.messages_per_sec).as_nanos() as u64;
+    let text = "x".repeat(run.params.payload_size);
+    let total = run.params.total();
+    let mut seq = 0;
+    while seq < total && !run.stop.load(Ordering::SeqCst) {
+        // Everything due by now, so a slow round doesn't lower the rate.
+        let due = (run.started.elapsed().as_nanos() as u64 / interval.max(1) + 1).min(total);
+        while seq < due {
+            let post = Post::new(PATH, run.message(seq, &text)).batched(BATCH_PATH).acked(acks.clone());
+            if !delivery.submit(post) {
+                run.rejected.fetch_add(1, Ordering::Relaxed);
+            }
+            run.generated.fetch_add(1, Ordering::Relaxed);
+            seq += 1;
+        }
+        run.high_water.fetch_max(delivery.metrics().queued, Ordering::Relaxed);
+        let next = run.started + Duration::from_nanos(interval.saturating_mul(seq));
+        std::thread::sleep(next.saturating_duration_since(Instant::now()));
+    }
+}
+
+/// Record answers until every submitted message has one.
+fn collect(run: &Run, acks: mpsc::Receiver<Acked>) {
+    for acked in acks {
+        run.record(acked);
+    }
+    run.finished_ms.store(run.started.elapsed().as_millis() as u64 + 1, Ordering::SeqCst);
+}
+
+#[cfg(target_os = "linux")]
+fn memory() -> Option<u64> {
+    Some(crate::organs::tree_memory())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn memory() -> Option<u64> {
+    None
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Latency {
+    pub p50_ms: Option<u64>,
+    pub p90_ms: Option<u64>,
+    pub p99_ms: Option<u64>,
+    pub max_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LoadTestReport {
+    pub id: String,
+    pub running: bool,
+    pub params: Params,
+    pub elapsed_ms: u64,
+    pub generated: u64,
+    /// Turned away because the relay queue was full.
+    pub rejected: u64,
+    pub delivered: u64,
+    pub failed: u64,
+    /// Delivered messages per second of the run.
+    pub throughput: f64,
+    pub latency: Latency,
+    /// Deepest the relay queue got during the run.
+    pub queue_high_water: usize,
+    /// This process and its children, where the platform tells us.
+    pub memory_start_bytes: Option<u64>,
+    pub memory_now_bytes: Option<u64>,
+    /// Error message → how often it was seen.
+    pub errors: BTreeMap<String, u64>,
+}
+
+fn report(run: &Run) -> LoadTestReport {
+    let elapsed_ms = run.finished_ms.load(Ordering::SeqCst).checked_sub(1).unwrap_or_else(|| run.started.elapsed().as_millis() as u64);
+    let delivered = run.delivered.load(Ordering::Relaxed);
+    let latency = {
+        let histogram = run.latency.lock().unwrap_or_else(|e| e.into_inner());
+        Latency {
+            p50_ms: histogram.percentile(0.5),
+            p90_ms: histogram.percentile(0.9),
+            p99_ms: histogram.percentile(0.99),
+            max_ms: histogram.percentile(1.0),
+        }
+    };
+    LoadTestReport {
+        id: run.id.clone(),
+        running: run.running(),
+        params: run.params,
+        elapsed_ms,
+        generated: run.generated.load(Ordering::Relaxed),
+        rejected: run.rejected.load(Ordering::Relaxed),
+        delivered,
+        failed: run.failed.load(Ordering::Relaxed),
+        throughput: if elapsed_ms == 0 { 0.0 } else { delivered as f64 * 1000.0 / elapsed_ms as f64 },
+        latency,
+        queue_high_water: run.high_water.load(Ordering::Relaxed),
+        memory_start_bytes: run.memory_start,
+        memory_now_bytes: memory(),
+        errors: run.errors.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+    }
+}
+
+/// The current or last run.
+static RUN: Mutex<Option<Arc<Run>>> = Mutex::new(None);
+
+fn allowed(app: &tauri::AppHandle) -> Result<(), String> {
+    if cfg!(debug_assertions) || crate::config::current(app).load_test.enabled {
+        Ok(())
+    } else {
+        Err("load tests are off in release builds (set load_test.enabled)".into())
+    }
+}
+
+pub fn start(app: &tauri::AppHandle, params: Params) -> Result<String, String> {
+    allowed(app)?;
+    params.check()?;
+    let mut current = RUN.lock().unwrap_or_else(|e| e.into_inner());
+    if current.as_ref().is_some_and(|run| run.running()) {
+        return Err("a load test is already running".into());
+    }
+    let run = Arc::new(Run::new(params));
+    *current = Some(run.clone());
+    drop(current);
+    tracing::info!(
+        "load test {}: {} msg/s for {}s, {} byte payloads",
+        run.id, params.messages_per_sec, params.duration_secs, params.payload_size
+    );
+
+    let (acks, answers) = mpsc::channel();
+    let collector = run.clone();
+    std::thread::spawn(move || collect(&collector, answers));
+    let (app, id) = (app.clone(), run.id.clone());
+    std::thread::spawn(move || generate(&run, &app.state::<Delivery>(), acks));
+    Ok(id)
+}
+
+/// Stop generating. Messages already queued are still delivered.
+pub fn stop() -> bool {
+    let current = RUN.lock().unwrap_or_else(|e| e.into_inner());
+    match current.as_ref() {
+        Some(run) if run.running() => !run.stop.swap(true, Ordering::SeqCst),
+        _ => false,
+    }
+}
+
+// ── Commands ───────────────────────────────────────────────────
+
+/// Start a run; returns its id, which tags every message it sends.
+#[tauri::command]
+pub fn start_load_test(
+    window: tauri::WebviewWindow,
+    app: tauri::AppHandle,
+    messages_per_sec: u32,
+    duration_secs: u64,
+    payload_size: usize,
+) -> Result<String, String> {
+    if window.label() != "main" {
+        return Err("only the main window can start load tests".into());
+    }
+    start(&app, Params { messages_per_sec, duration_secs, payload_size })
+}
+
+#[tauri::command]
+pub fn stop_load_test(window: tauri::WebviewWindow) -> Result<bool, String> {
+    if window.label() != "main" {
+        return Err("only the main window can stop load tests".into());
+    }
+    Ok(stop())
+}
+
+/// The current or last run; None before the first.
+#[tauri::command]
+pub fn get_load_test_report() -> Option<LoadTestReport> {
+    RUN.lock().unwrap_or_else(|e| e.into_inner()).as_deref().map(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::relay::RelayConfig;
+    use crate::testing::{MockBrain, Reply, TestHost};
+
+    fn run(brain: &MockBrain, params: Params, stop_after: Option<Duration>) -> LoadTestReport {
+        let host = TestHost::new().with_brain(brain);
+        let config = RelayConfig { workers: 4, queue: 64, attempts: 1, ..RelayConfig::default() };
+        let delivery = Delivery::start(host, &config, crate::metrics::Metrics::default());
+        let run = Arc::new(Run::new(params));
+        let (acks, answers) = mpsc::channel();
+        let collector = {
+            let run = run.clone();
+            std::thread::spawn(move || collect(&run, answers))
+        };
+        if let Some(after) = stop_after {
+            let run = run.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(after);
+                run.stop.store(true, Ordering::SeqCst);
+            });
+        }
+        generate(&run, &delivery, acks);
+        collector.join().unwrap();
+        delivery.shutdown(Duration::from_secs(5));
+        report(&run)
+    }
+
+    #[test]
+    fn percentiles_come_from_the_histogram() {
+        let mut histogram = Histogram::new();
+        assert_eq!(histogram.percentile(0.5), None);
+        for ms in 1..=100 {
+            histogram.record(Duration::from_millis(ms));
+        }
+        histogram.record(Duration::from_secs(60));
+        assert_eq!(histogram.percentile(0.5), Some(51));
+        assert_eq!(histogram.percentile(0.99), Some(100));
+        assert_eq!(histogram.percentile(1.0), Some(LATENCY_BUCKETS as u64));
+    }
+
+    #[test]
+    fn a_run_delivers_tagged_messages_at_the_requested_rate() {
+        let brain = MockBrain::start();
+        brain.always(PATH, Reply::status(200));
+        let params = Params { messages_per_sec: 200, duration_secs: 1, payload_size: 64 };
+        let report = run(&brain, params, None);
+
+        assert!(!report.running);
+        assert_eq!(report.generated, 200);
+        assert_eq!(report.delivered + report.failed + report.rejected, 200);
+        assert_eq!(report.failed, 0, "{:?}", report.errors);
+        assert!(report.elapsed_ms >= 990, "finished in {}ms", report.elapsed_ms);
+        assert!(report.latency.p50_ms.is_some());
+
+        let requests = brain.requests(PATH);
+        assert_eq!(requests.len() as u64, report.delivered);
+        let first: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(first["synthetic"], true);
+        assert_eq!(first["text"].as_str().unwrap().len(), 64);
+    }
+
+    #[test]
+    fn a_stopped_run_ends_early_and_counts_errors() {
+        let brain = MockBrain::start();
+        brain.always(PATH, Reply::status(500));
+        let params = Params { messages_per_sec: 100, duration_secs: 60, payload_size: 0 };
+        let report = run(&brain, params, Some(Duration::from_millis(300)));
+
+        assert!(report.generated < 100, "generated {}", report.generated);
+        assert_eq!(report.delivered, 0);
+        assert_eq!(report.failed + report.rejected, report.generated);
+        assert_eq!(report.errors.values().sum::<u64>(), report.failed);
+    }
+
+    #[test]
+    fn params_are_bounded() {
+        assert!(Params { messages_per_sec: 0, duration_secs: 1, payload_size: 0 }.check().is_err());
+        assert!(Params { messages_per_sec: 1, duration_secs: 0, payload_size: 0 }.check().is_err());
+        assert!(Params { messages_per_sec: 1, duration_secs: 1, payload_size: MAX_PAYLOAD + 1 }.check().is_err());
+        assert!(Params { messages_per_sec: MAX_RATE, duration_secs: 1, payload_size: MAX_PAYLOAD }.check().is_ok());
+    }
+}
@@ -0,0 +1,231 @@
+//! Logging — `tracing` events to a daily log file, stderr and a buffer.
+//!
+//! `init` runs first thing in `run`, before the app knows its log dir,
+//! so early events reach only stderr (when it is a terminal) and the
+//! buffer of recent lines `get_recent_logs` reads. `attach` adds the
+//! file once setup has the dir: `lexicon.YYYY-MM-DD.log` in the app log
+//! dir, a new one each day, the last seven kept.
+//!
+//! The level starts at info, or `LEXICON_LOG`, and `set_log_level`
+//! changes it while the app runs. Both take a level (`debug`, `warn`, …)
+//! and levels by module, as in `warn,relay=debug,tungstenite=off`: one of
+//! the app's modules by its name, anything else by its target path. Each
+//! `set_log_level` goes on top of what is set already.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{IsTerminal, Write};
+use std::sync::{Mutex, OnceLock};
+
+use tauri::Manager;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::fmt::{self, MakeWriter};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{reload, Registry};
+
+pub const LEVEL_ENV: &str = "LEXICON_LOG";
+/// Lines `get_recent_logs` can go back.
+const RECENT_LINES: usize = 2000;
+const KEEP_FILES: usize = 7;
+
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+static FILE: OnceLock<RollingFileAppender> = OnceLock::new();
+static LEVEL: OnceLock<reload::Handle<Targets, Registry>> = OnceLock::new();
+static LEVELS: Mutex<Levels> = Mutex::new(Levels { default: LevelFilter::INFO, modules: BTreeMap::new() });
+
+/// Appends each formatted event to `RECENT`, a line at a time.
+struct Recent;
+
+impl Write for Recent {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut recent = RECENT.lock().unwrap_or_else(|e| e.into_inner());
+        for line in String::from_utf8_lossy(buf).lines() {
+            if recent.len() == RECENT_LINES {
+                recent.pop_front();
+            }
+            recent.push_back(line.to_string());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The log file once `attach` has opened it; until then, nowhere.
+struct File;
+
+impl Write for File {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match FILE.get() {
+            Some(file) => file.make_writer().write(buf),
+            None => Ok(buf.len()),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match FILE.get() {
+            Some(file) => file.make_writer().flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+fn parse(level: &str) -> Result<LevelFilter, String> {
+    level
+        .trim()
+        .parse()
+        .map_err(|_| format!("unknown log level '{level}' — use error, warn, info, debug, trace or off"))
+}
+
+/// The level for everything, and for some modules.
+#[derive(Debug, Clone, PartialEq)]
+struct Levels {
+    default: LevelFilter,
+    modules: BTreeMap<String, LevelFilter>,
+}
+
+impl Levels {
+    /// Take `directives` (`warn`, `relay=debug`, or both, comma-separated)
+    /// on top of these. Nothing changes if one doesn't parse.
+    fn apply(&mut self, directives: &str) -> Result<(), String> {
+        let mut next = self.clone();
+        for directive in directives.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((module, level)) if !module.trim().is_empty() => {
+                    next.modules.insert(module.trim().to_string(), parse(level)?);
+                }
+                Some(_) => return Err(format!("no module in '{directive}'")),
+                None => next.default = parse(directive)?,
+            }
+        }
+        *self = next;
+        Ok(())
+    }
+
+    fn targets(&self) -> Targets {
+        let crate_name = env!("CARGO_CRATE_NAME");
+        let modules = self.modules.iter().flat_map(|(module, level)| {
+            // A bare name is tried as one of ours too.
+            let ours = (!module.contains("::")).then(|| (format!("{crate_name}::{module}"), *level));
+            std::iter::once((module.clone(), *level)).chain(ours)
+        });
+        Targets::new().with_default(self.default).with_targets(modules)
+    }
+}
+
+impl std::fmt::Display for Levels {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.default)?;
+        self.modules.iter().try_for_each(|(module, level)| write!(f, ",{module}={level}"))
+    }
+}
+
+/// Install the subscriber. A second call does nothing.
+pub fn init() {
+    let mut levels = LEVELS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Ok(directives) = std::env::var(LEVEL_ENV) {
+        let _ = levels.apply(&directives);
+    }
+    let (filter, handle) = reload::Layer::new(levels.targets());
+    drop(levels);
+    let stderr = std::io::stderr().is_terminal().then(|| fmt::layer().with_writer(std::io::stderr));
+    let subscriber = tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer().with_ansi(false).with_writer(|| Recent))
+        .with(fmt::layer().with_ansi(false).with_writer(|| File))
+        .with(stderr);
+    if tracing::subscriber::set_global_default(subscriber).is_ok() {
+        let _ = LEVEL.set(handle);
+    }
+}
+
+/// Start writing the log file.
+pub fn attach(app: &tauri::AppHandle) {
+    let Ok(dir) = app.path().app_log_dir() else { return };
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix("lexicon")
+        .filename_suffix("log")
+        .max_log_files(KEEP_FILES)
+        .build(&dir);
+    match appender {
+        Ok(appender) => {
+            let _ = FILE.set(appender);
+            tracing::info!("logging to {}", dir.display());
+        }
+        Err(e) => tracing::warn!("no log file in {}: {e}", dir.display()),
+    }
+}
+
+/// The last `lines` lines logged, oldest first.
+fn recent(lines: usize) -> Vec<String> {
+    let recent = RECENT.lock().unwrap_or_else(|e| e.into_inner());
+    recent.iter().skip(recent.len().saturating_sub(lines)).cloned().collect()
+}
+
+#[tauri::command]
+pub fn get_recent_logs(lines: u32) -> Vec<String> {
+    recent(lines as usize)
+}
+
+/// `level` is a level or levels by module (see the module docs); with
+/// `module`, it is that module's level.
+#[tauri::command]
+pub fn set_log_level(level: String, module: Option<String>) -> Result<(), String> {
+    let directives = match module {
+        Some(module) => format!("{module}={level}"),
+        None => level,
+    };
+    let handle = LEVEL.get().ok_or("logging is not set up")?;
+    let mut levels = LEVELS.lock().unwrap_or_else(|e| e.into_inner());
+    levels.apply(&directives)?;
+    handle.reload(levels.targets()).map_err(|e| e.to_string())?;
+    tracing::info!("log levels now {levels}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recent_lines_come_back_newest_last() {
+        let subscriber = tracing_subscriber::registry().with(fmt::layer().with_ansi(false).with_writer(|| Recent));
+        tracing::subscriber::with_default(subscriber, || {
+            for n in 0..RECENT_LINES + 5 {
+                tracing::warn!("line {n}");
+            }
+        });
+        let last = recent(2);
+        assert_eq!(last.len(), 2);
+        assert!(last[0].ends_with(&format!("line {}", RECENT_LINES + 3)) && last[0].contains("WARN"));
+        assert!(last[1].ends_with(&format!("line {}", RECENT_LINES + 4)));
+        assert_eq!(recent(usize::MAX).len(), RECENT_LINES);
+    }
+
+    #[test]
+    fn levels_parse_by_name() {
+        assert_eq!(parse("debug"), Ok(LevelFilter::DEBUG));
+        assert_eq!(parse(" WARN "), Ok(LevelFilter::WARN));
+        assert!(parse("loud").is_err());
+    }
+
+    #[test]
+    fn modules_get_levels_of_their_own() {
+        let mut levels = Levels { default: LevelFilter::INFO, modules: BTreeMap::new() };
+        levels.apply("warn, relay=debug,tungstenite=off").unwrap();
+        levels.apply("organs=trace").unwrap();
+        assert_eq!(levels.to_string(), "warn,organs=trace,relay=debug,tungstenite=off");
+        assert!(levels.apply("info,=debug").is_err() && levels.apply("relay=loud").is_err());
+        assert_eq!(levels.default, LevelFilter::WARN);
+
+        let targets = levels.targets();
+        let ours = |module: &str| format!("{}::{module}", env!("CARGO_CRATE_NAME"));
+        assert!(targets.would_enable(&ours("relay"), &tracing::Level::DEBUG));
+        assert!(!targets.would_enable(&ours("relay"), &tracing::Level::TRACE));
+        assert!(!targets.would_enable(&ours("brain"), &tracing::Level::INFO));
+        assert!(!targets.would_enable("tungstenite::protocol", &tracing::Level::ERROR));
+    }
+}
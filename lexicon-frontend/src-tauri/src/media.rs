@@ -0,0 +1,558 @@
+//! WhatsApp media — files the organ saw, kept on disk for the Brain.
+//!
+//! `wa_relay_media` takes a file whole, as base64 or a `data:` URL, with a
+//! JSON object of metadata (`mime` is required; a message `id`, `chat`,
+//! `from` and `caption` are passed through, cleaned, and anything else is
+//! dropped). It can take a `blob:` URL of the
+//! WhatsApp organ's page instead (the account's, with an `account`), where
+//! WhatsApp Web keeps what it has decrypted: the file is then read out of
+//! the page a slice at a time. The bytes are written once to `media/` in the app
+//! data dir, named by their SHA-256, and the Brain gets a [`WaMedia`], the
+//! metadata with `path`, `sha256`, `size` and the organ, account and relay
+//! time added, at `/whatsapp/media` through the relay, signed and spooled like any other send. A message `id` in the
+//! metadata relays it once; see `dedup`.
+//!
+//! Files too big for one IPC message come through `wa_relay_media_chunk`
+//! instead: the metadata with chunk 0, then each chunk in order, the last
+//! one flagged. An assembly that goes `media.chunk_timeout_secs` without
+//! a chunk is dropped, and so is one that skips a chunk or grows past
+//! `media.max_mb`. MIME types not in `media.mime_types` are refused
+//! before any bytes are kept.
+//!
+//! The WhatsApp monitor (see `whatsapp`) reports the media it sees come
+//! in the same way, metadata with the `blob:` URL as `url`; `reported`
+//! reads it out of the page and relays it like `wa_relay_media` would.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use crate::host::Host;
+use crate::organs::{clean, now_ms};
+
+/// Longest assembly id accepted.
+const MAX_ID_LEN: usize = 64;
+/// Bytes of a `blob:` URL read out of the page per answer.
+const BLOB_SLICE: usize = 512 * 1024;
+/// How long the page gets for each slice.
+const BLOB_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+#[serde(default)]
+pub struct MediaConfig {
+    /// Largest file accepted, in megabytes.
+    #[cfg_attr(test, proptest(strategy = "crate::testing::toml_u64()"))]
+    pub max_mb: u64,
+    /// Accepted MIME types, without parameters.
+    pub mime_types: Vec<String>,
+    /// How long a chunked upload may wait for its next chunk.
+    #[cfg_attr(test, proptest(strategy = "crate::testing::toml_u64()"))]
+    pub chunk_timeout_secs: u64,
+}
+
+impl Default for MediaConfig {
+    fn default() -> Self {
+        Self {
+            max_mb: 25,
+            mime_types: EXTENSIONS.iter().map(|(mime, _)| mime.to_string()).collect(),
+            chunk_timeout_secs: 120,
+        }
+    }
+}
+
+impl MediaConfig {
+    fn max_bytes(&self) -> u64 {
+        self.max_mb.saturating_mul(1024 * 1024)
+    }
+}
+
+/// What WhatsApp Web sends, and the extension each is saved with.
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("image/jpeg", "jpg"),
+    ("image/png", "png"),
+    ("image/webp", "webp"),
+    ("image/gif", "gif"),
+    ("video/mp4", "mp4"),
+    ("video/3gpp", "3gp"),
+    ("audio/ogg", "ogg"),
+    ("audio/mpeg", "mp3"),
+    ("audio/mp4", "m4a"),
+    ("audio/aac", "aac"),
+    ("application/pdf", "pdf"),
+];
+
+/// Longest message id, chat or sender passed on.
+const MAX_NAME_LEN: usize = 256;
+/// Longest caption passed on.
+const MAX_CAPTION_LEN: usize = 2000;
+
+/// The metadata a file comes with; anything else in it is ignored.
+#[derive(Debug, Clone, Deserialize)]
+struct Meta {
+    mime: String,
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    chat: Option<String>,
+    #[serde(default)]
+    from: Option<String>,
+    #[serde(default)]
+    caption: Option<String>,
+    /// The login whose page a `blob:` URL is in.
+    #[serde(default)]
+    account: Option<String>,
+    /// The `blob:` URL a monitor saw the file at.
+    #[serde(default)]
+    url: Option<String>,
+}
+
+/// `mime` lowercased and without parameters, if policy takes it.
+fn accepted(config: &MediaConfig, mime: &str) -> Result<String, String> {
+    let mime = mime.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    if mime.is_empty() {
+        return Err("no MIME type given".into());
+    }
+    if !config.mime_types.iter().any(|allowed| allowed.eq_ignore_ascii_case(&mime)) {
+        return Err(format!("{mime} is not accepted"));
+    }
+    Ok(mime)
+}
+
+fn parse_meta(config: &MediaConfig, meta: &str) -> Result<Meta, String> {
+    let mut meta: Meta = serde_json::from_str(meta).map_err(|e| format!("bad media metadata: {e}"))?;
+    meta.mime = accepted(config, &meta.mime)?;
+    Ok(meta)
+}
+
+fn decode(data: &str) -> Result<Vec<u8>, String> {
+    base64::engine::general_purpose::STANDARD.decode(data.trim()).map_err(|e| format!("bad media data: {e}"))
+}
+
+/// The base64 in a `data:` URL, or `data` as it is.
+fn strip_data_url(data: &str) -> &str {
+    match data.trim().strip_prefix("data:").and_then(|rest| rest.split_once(',')) {
+        Some((_, payload)) => payload,
+        None => data,
+    }
+}
+
+/// Read `start..start + BLOB_SLICE` of blob `url` and answer with the
+/// blob's size and the slice as base64, as "size,base64".
+fn blob_script(url: &str, start: usize, reply: &str) -> String {
+    let url = serde_json::Value::from(url);
+    let end = start + BLOB_SLICE;
+    format!(
+        r#"(function (done) {{
+  fetch({url}).then(function (r) {{ return r.blob(); }}).then(function (blob) {{
+    var reader = new FileReader();
+    reader.onload = function () {{ done(blob.size + ',' + String(reader.result).split(',')[1]); }};
+    reader.onerror = function () {{ done('error:unreadable'); }};
+    reader.readAsDataURL(blob.slice({start}, {end}));
+  }}).catch(function (e) {{ done('error:' + e); }});
+}})({reply});"#
+    )
+}
+
+/// A `blob_script` answer: the blob's size and the slice.
+fn read_slice(answer: &str) -> Result<(u64, Vec<u8>), String> {
+    if let Some(e) = answer.strip_prefix("error:") {
+        return Err(format!("could not read the blob: {e}"));
+    }
+    let (size, data) = answer.split_once(',').ok_or("the page answered something other than a blob slice")?;
+    let size = size.parse().map_err(|_| format!("bad blob size '{size}'"))?;
+    Ok((size, decode(data)?))
+}
+
+/// Read blob `url` out of organ `organ`'s page.
+fn fetch_blob(app: &tauri::AppHandle, config: &MediaConfig, organ: &str, url: &str) -> Result<Vec<u8>, String> {
+    let mut data = Vec::new();
+    loop {
+        let start = data.len();
+        let answer = crate::organs::ask(app, organ, |reply| blob_script(url, start, reply), BLOB_TIMEOUT)?;
+        let (size, slice) = read_slice(&answer)?;
+        if size > config.max_bytes() {
+            return Err(too_big(config));
+        }
+        data.extend_from_slice(&slice);
+        if data.len() as u64 >= size || slice.is_empty() {
+            return Ok(data);
+        }
+    }
+}
+
+fn too_big(config: &MediaConfig) -> String {
+    format!("media is over {} MB", config.max_mb)
+}
+
+/// Where a file was kept.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct Stored {
+    pub sha256: String,
+    pub path: PathBuf,
+    pub size: u64,
+    pub mime: String,
+}
+
+fn write_once(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    if path.exists() {
+        return Ok(());
+    }
+    let tmp = path.with_extension("part");
+    std::fs::write(&tmp, data)?;
+    std::fs::rename(&tmp, path)
+}
+
+/// Keep `data` under `media/` by its hash. The same file twice is
+/// written once.
+fn store(host: &impl Host, config: &MediaConfig, meta: &Meta, data: &[u8]) -> Result<Stored, String> {
+    if data.len() as u64 > config.max_bytes() {
+        return Err(too_big(config));
+    }
+    let dir = host.data_dir().ok_or("no app data dir")?.join("media");
+    std::fs::create_dir_all(&dir).map_err(|e| format!("could not create {}: {e}", dir.display()))?;
+    let digest = ring::digest::digest(&ring::digest::SHA256, data);
+    let sha256: String = digest.as_ref().iter().map(|b| format!("{b:02x}")).collect();
+    let ext = EXTENSIONS.iter().find(|(mime, _)| *mime == meta.mime).map_or("bin", |(_, ext)| ext);
+    let path = dir.join(format!("{sha256}.{ext}"));
+    write_once(&path, data).map_err(|e| format!("could not write {}: {e}", path.display()))?;
+    Ok(Stored { sha256, path, size: data.len() as u64, mime: meta.mime.clone() })
+}
+
+/// What the Brain gets at `/whatsapp/media` for a stored file.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct WaMedia {
+    /// The message it came in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chat: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub caption: Option<String>,
+    #[serde(flatten)]
+    pub stored: Stored,
+    pub organ: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    /// Unix milliseconds it was relayed.
+    pub timestamp: u64,
+}
+
+fn body(meta: &Meta, stored: &Stored, now_ms: u64) -> WaMedia {
+    let field = |value: &Option<String>, max| value.as_deref().and_then(|v| clean(v, max));
+    WaMedia {
+        id: field(&meta.id, MAX_NAME_LEN),
+        chat: field(&meta.chat, MAX_NAME_LEN),
+        from: field(&meta.from, MAX_NAME_LEN),
+        caption: field(&meta.caption, MAX_CAPTION_LEN),
+        stored: stored.clone(),
+        organ: crate::whatsapp::ORGAN.into(),
+        account: meta.account.clone(),
+        timestamp: now_ms,
+    }
+}
+
+fn relay(app: &tauri::AppHandle, meta: &Meta, data: &[u8]) -> Result<Stored, String> {
+    let stored = store(app, &Host::config(app).media, meta, data)?;
+    let organ = crate::whatsapp::organ_id(meta.account.as_deref());
+    if !crate::relay::message(app, &organ, "/whatsapp/media", &body(meta, &stored, now_ms())) {
+        tracing::debug!("media {} relayed already or filtered out", stored.sha256);
+    }
+    Ok(stored)
+}
+
+/// The metadata and `blob:` URL of a monitor's report.
+fn parse_report(config: &MediaConfig, raw: &str) -> Result<(Meta, String), String> {
+    let mut meta = parse_meta(config, raw)?;
+    let url = meta.url.take().filter(|url| url.starts_with("blob:")).ok_or("no blob: URL")?;
+    Ok((meta, url))
+}
+
+/// WhatsApp organ `organ`'s monitor reported `raw`, media in its page.
+/// Read and relayed off the caller's thread, which the page's answers
+/// come in on.
+pub fn reported(app: &tauri::AppHandle, organ: &str, raw: &str) {
+    let config = crate::config::current(app).media;
+    let (mut meta, url) = match parse_report(&config, raw) {
+        Ok(report) => report,
+        Err(e) => {
+            tracing::warn!("organ {organ} reported media that can't be relayed ({e}) — dropped");
+            return;
+        }
+    };
+    meta.account = crate::organs::account(app, organ);
+    let (app, organ) = (app.clone(), organ.to_string());
+    tauri::async_runtime::spawn_blocking(move || {
+        if let Err(e) = fetch_blob(&app, &config, &organ, &url).and_then(|data| relay(&app, &meta, &data)) {
+            tracing::warn!("{organ} media not relayed: {e}");
+        }
+    });
+}
+
+// ── Chunked uploads ────────────────────────────────────────────
+
+struct Assembly {
+    meta: Meta,
+    data: Vec<u8>,
+    next_seq: u32,
+    touched: Instant,
+}
+
+/// One call's worth of a chunked upload.
+struct Chunk {
+    seq: u32,
+    data: Vec<u8>,
+    /// With chunk 0 only.
+    meta: Option<Meta>,
+    last: bool,
+}
+
+#[derive(Default)]
+struct Assemblies(BTreeMap<String, Assembly>);
+
+impl Assemblies {
+    /// Add a chunk to upload `id`: the metadata and file with the last
+    /// chunk, nothing before it. Any error drops the upload.
+    fn add(&mut self, config: &MediaConfig, id: &str, chunk: Chunk, now: Instant) -> Result<Option<(Meta, Vec<u8>)>, String> {
+        let last = chunk.last;
+        let result = self.append(config, id, chunk, now);
+        if result.is_err() || last {
+            let assembly = self.0.remove(id);
+            result?;
+            return Ok(assembly.map(|a| (a.meta, a.data)));
+        }
+        result.map(|()| None)
+    }
+
+    fn append(&mut self, config: &MediaConfig, id: &str, chunk: Chunk, now: Instant) -> Result<(), String> {
+        if chunk.seq == 0 {
+            let meta = chunk.meta.ok_or("chunk 0 carries the metadata")?;
+            let assembly = Assembly { meta, data: Vec::new(), next_seq: 0, touched: now };
+            self.0.insert(id.to_string(), assembly);
+        }
+        let assembly = self.0.get_mut(id).ok_or_else(|| format!("no upload '{id}' — it expired or never started"))?;
+        if chunk.seq != assembly.next_seq {
+            return Err(format!("upload '{id}' expected chunk {}, got {}", assembly.next_seq, chunk.seq));
+        }
+        if (assembly.data.len() + chunk.data.len()) as u64 > config.max_bytes() {
+            return Err(too_big(config));
+        }
+        assembly.data.extend_from_slice(&chunk.data);
+        assembly.next_seq += 1;
+        assembly.touched = now;
+        Ok(())
+    }
+
+    /// Drop uploads that went `timeout` without a chunk.
+    fn expire(&mut self, timeout: Duration, now: Instant) {
+        self.0.retain(|id, assembly| {
+            let live = now.saturating_duration_since(assembly.touched) < timeout;
+            if !live {
+                tracing::info!("media upload '{id}' timed out after chunk {}", assembly.next_seq);
+            }
+            live
+        });
+    }
+}
+
+static ASSEMBLIES: Mutex<Assemblies> = Mutex::new(Assemblies(BTreeMap::new()));
+static SWEEPING: AtomicBool = AtomicBool::new(false);
+
+/// Expire stalled uploads in the background while there are any.
+fn sweep(timeout: Duration) {
+    if SWEEPING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    std::thread::spawn(move || loop {
+        std::thread::sleep(timeout);
+        let mut assemblies = ASSEMBLIES.lock().unwrap_or_else(|e| e.into_inner());
+        assemblies.expire(timeout, Instant::now());
+        if assemblies.0.is_empty() {
+            SWEEPING.store(false, Ordering::SeqCst);
+            break;
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn wa_relay_media(
+    window: tauri::WebviewWindow,
+    app: tauri::AppHandle,
+    meta: String,
+    data_base64: String,
+) -> Result<Stored, String> {
+    if window.label() != "main" {
+        return Err("only the main window can relay media".into());
+    }
+    tauri::async_runtime::spawn_blocking(move || {
+        let config = crate::config::current(&app).media;
+        let meta = parse_meta(&config, &meta)?;
+        let data = if data_base64.trim().starts_with("blob:") {
+            let organ = crate::whatsapp::organ_id(meta.account.as_deref());
+            fetch_blob(&app, &config, &organ, data_base64.trim())?
+        } else {
+            decode(strip_data_url(&data_base64))?
+        };
+        relay(&app, &meta, &data)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// One chunk of a file; `meta` goes with chunk 0. Returns what was kept
+/// once `last` is set, and nothing before.
+#[tauri::command]
+pub async fn wa_relay_media_chunk(
+    window: tauri::WebviewWindow,
+    app: tauri::AppHandle,
+    id: String,
+    seq: u32,
+    data: String,
+    last: bool,
+    meta: Option<String>,
+) -> Result<Option<Stored>, String> {
+    if window.label() != "main" {
+        return Err("only the main window can relay media".into());
+    }
+    if id.is_empty() || id.len() > MAX_ID_LEN {
+        return Err(format!("upload ids are 1 to {MAX_ID_LEN} bytes"));
+    }
+    tauri::async_runtime::spawn_blocking(move || {
+        let config = crate::config::current(&app).media;
+        let timeout = Duration::from_secs(config.chunk_timeout_secs.max(1));
+        let complete = {
+            let mut assemblies = ASSEMBLIES.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            assemblies.expire(timeout, now);
+            let parsed = meta.map(|m| parse_meta(&config, &m)).transpose();
+            let chunk = decode(&data);
+            match (parsed, chunk) {
+                (Ok(meta), Ok(data)) => assemblies.add(&config, &id, Chunk { seq, data, meta, last }, now)?,
+                (Err(e), _) | (_, Err(e)) => {
+                    assemblies.0.remove(&id);
+                    return Err(e);
+                }
+            }
+        };
+        sweep(timeout);
+        complete.map(|(meta, data)| relay(&app, &meta, &data)).transpose()
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHost;
+
+    fn meta(mime: &str) -> Meta {
+        parse_meta(&MediaConfig::default(), &format!(r#"{{"mime":"{mime}","chat":"Ana"}}"#)).unwrap()
+    }
+
+    #[test]
+    fn monitor_reports_need_a_blob_url() {
+        let config = MediaConfig::default();
+        let (meta, url) = parse_report(&config, r#"{"mime":"image/jpeg","id":"false_1","url":"blob:https://web.whatsapp.com/9f"}"#).unwrap();
+        assert_eq!((meta.mime.as_str(), url.as_str()), ("image/jpeg", "blob:https://web.whatsapp.com/9f"));
+        assert_eq!((meta.url, meta.id.as_deref()), (None, Some("false_1")));
+        assert!(parse_report(&config, r#"{"mime":"image/jpeg","url":"https://example.com/a.jpg"}"#).is_err());
+        assert!(parse_report(&config, r#"{"mime":"image/jpeg"}"#).is_err());
+        assert!(parse_report(&config, r#"{"mime":"text/html","url":"blob:https://web.whatsapp.com/9f"}"#).is_err());
+    }
+
+    #[test]
+    fn unknown_types_and_bad_input_are_refused() {
+        let config = MediaConfig::default();
+        assert_eq!(parse_meta(&config, r#"{"mime":"Audio/OGG; codecs=opus"}"#).unwrap().mime, "audio/ogg");
+        assert!(parse_meta(&config, r#"{"mime":"application/x-msdownload"}"#).unwrap_err().contains("not accepted"));
+        assert!(parse_meta(&config, r#"{"chat":"Ana"}"#).is_err());
+        assert!(parse_meta(&config, "not json").is_err());
+        assert!(decode("@@@").is_err());
+    }
+
+    #[test]
+    fn data_urls_and_blob_slices_decode() {
+        assert_eq!(decode(strip_data_url("data:image/png;base64,aGk=")).unwrap(), b"hi");
+        assert_eq!(decode(strip_data_url("aGk=")).unwrap(), b"hi");
+        assert_eq!(read_slice("700000,aGk=").unwrap(), (700_000, b"hi".to_vec()));
+        assert!(read_slice("error:TypeError: Failed to fetch").unwrap_err().contains("Failed to fetch"));
+        assert!(read_slice("aGk=").is_err() && read_slice("big,aGk=").is_err());
+    }
+
+    #[test]
+    fn files_are_kept_once_by_hash() {
+        let host = TestHost::new();
+        let meta = meta("image/png");
+        let config = MediaConfig::default();
+        let first = store(&host, &config, &meta, b"png bytes").unwrap();
+        let again = store(&host, &config, &meta, b"png bytes").unwrap();
+        assert_eq!(first, again);
+        assert!(first.path.ends_with(format!("media/{}.png", first.sha256)));
+        assert_eq!(std::fs::read(&first.path).unwrap(), b"png bytes");
+
+        let body = body(&meta, &first, 7);
+        assert_eq!((body.chat.as_deref(), body.stored.size, body.timestamp), (Some("Ana"), 9, 7));
+
+        let config = MediaConfig { max_mb: 0, ..config };
+        assert!(store(&host, &config, &meta, b"x").unwrap_err().contains("over 0 MB"));
+    }
+
+    #[test]
+    fn only_known_metadata_is_passed_on() {
+        let meta = r#"{"mime":"image/png","id":"false_1","chat":" Ana\u0007 ","caption":"  ","path":"/etc/passwd","organ":"telegram","x":1}"#;
+        let meta = parse_meta(&MediaConfig::default(), meta).unwrap();
+        let stored = Stored { sha256: "ab".into(), path: "/media/ab.png".into(), size: 2, mime: "image/png".into() };
+        assert_eq!(
+            serde_json::to_value(body(&meta, &stored, 7)).unwrap(),
+            serde_json::json!({
+                "id": "false_1",
+                "chat": "Ana",
+                "sha256": "ab",
+                "path": "/media/ab.png",
+                "size": 2,
+                "mime": "image/png",
+                "organ": "whatsapp",
+                "timestamp": 7,
+            })
+        );
+    }
+
+    fn chunk(seq: u32, data: &[u8], meta: Option<Meta>, last: bool) -> Chunk {
+        Chunk { seq, data: data.to_vec(), meta, last }
+    }
+
+    #[test]
+    fn chunks_assemble_in_order_and_stall_out() {
+        let config = MediaConfig::default();
+        let start = Instant::now();
+        let mut uploads = Assemblies::default();
+        assert!(uploads.add(&config, "a", chunk(0, b"ab", Some(meta("video/mp4")), false), start).unwrap().is_none());
+        assert!(uploads.add(&config, "a", chunk(1, b"cd", None, false), start).unwrap().is_none());
+        let (meta_out, data) = uploads.add(&config, "a", chunk(2, b"e", None, true), start).unwrap().unwrap();
+        assert_eq!((meta_out.mime.as_str(), data.as_slice()), ("video/mp4", &b"abcde"[..]));
+        assert!(uploads.0.is_empty());
+
+        // A skipped chunk drops the upload.
+        uploads.add(&config, "b", chunk(0, b"ab", Some(meta("video/mp4")), false), start).unwrap();
+        assert!(uploads.add(&config, "b", chunk(2, b"cd", None, false), start).is_err());
+        assert!(uploads.add(&config, "b", chunk(1, b"cd", None, false), start).unwrap_err().contains("never started"));
+        assert!(uploads.add(&config, "c", chunk(0, b"ab", None, false), start).is_err());
+
+        // So does going quiet.
+        uploads.add(&config, "d", chunk(0, b"ab", Some(meta("video/mp4")), false), start).unwrap();
+        uploads.expire(Duration::from_secs(120), start + Duration::from_secs(60));
+        assert_eq!(uploads.0.len(), 1);
+        uploads.expire(Duration::from_secs(120), start + Duration::from_secs(121));
+        assert!(uploads.0.is_empty());
+    }
+}
@@ -0,0 +1,350 @@
+//! Metrics — what the app has been up to, without reading the logs.
+//!
+//! `get_metrics` answers with the uptime, what the relay sent (messages
+//! and statuses, delivered or failed, and messages the filters or dedup
+//! dropped), the bytes POSTed to the Brain, the Brain's last answer and
+//! how long it took, how many window switches were made, each organ's
+//! state, the spool depth and the relay rate limiters' buckets (see
+//! `ratelimit`). `reset_metrics` zeroes the counters; the
+//! rest is read fresh every time.
+//!
+//! With `metrics.port` set (0, the default, is off), the same numbers are
+//! served in the Prometheus text format at
+//! `http://127.0.0.1:{port}/metrics`, for scraping. The port is read at
+//! startup.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write as _};
+use std::net::{Ipv4Addr, TcpListener};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::Manager;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+#[serde(default)]
+pub struct MetricsConfig {
+    /// Serve `/metrics` on this loopback port; 0 doesn't.
+    pub port: u16,
+}
+
+#[derive(Default)]
+struct Counters {
+    messages_sent: AtomicU64,
+    messages_failed: AtomicU64,
+    messages_filtered: AtomicU64,
+    messages_deduped: AtomicU64,
+    statuses_sent: AtomicU64,
+    statuses_failed: AtomicU64,
+    bytes_posted: AtomicU64,
+    /// The status of the Brain's last answer; 0 before the first.
+    last_status: AtomicU64,
+    last_latency_ms: AtomicU64,
+    switches: AtomicU64,
+}
+
+/// The counters, shared by the relay workers and the window code.
+#[derive(Clone, Default)]
+pub struct Metrics(Arc<Counters>);
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct MessageCounts {
+    pub ok: u64,
+    pub failed: u64,
+    pub filtered: u64,
+    pub deduped: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct StatusCounts {
+    pub ok: u64,
+    pub failed: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct BrainAnswer {
+    pub status: u16,
+    pub latency_ms: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct AppMetrics {
+    pub uptime_secs: u64,
+    pub messages: MessageCounts,
+    pub statuses: StatusCounts,
+    pub bytes_posted: u64,
+    /// `None` until the Brain has answered a relay send.
+    pub last_brain_answer: Option<BrainAnswer>,
+    pub window_switches: u64,
+    /// "closed", "visible" or "background", by organ id.
+    pub organs: BTreeMap<String, String>,
+    /// `None` before the relay is up.
+    pub spool_depth: Option<usize>,
+    /// Each organ's rate limit buckets, by endpoint.
+    pub rate_limits: Vec<crate::ratelimit::BucketState>,
+}
+
+fn bump(counter: &AtomicU64, by: u64) {
+    counter.fetch_add(by, Ordering::Relaxed);
+}
+
+impl Metrics {
+    /// One POST of `bytes` to the Brain, answered with `status` (`None`
+    /// if it never answered) after `latency`.
+    pub fn posted(&self, bytes: usize, status: Option<u16>, latency: Duration) {
+        let counters = &self.0;
+        bump(&counters.bytes_posted, bytes as u64);
+        if let Some(status) = status {
+            counters.last_status.store(status.into(), Ordering::Relaxed);
+            counters.last_latency_ms.store(latency.as_millis() as u64, Ordering::Relaxed);
+        }
+    }
+
+    /// `count` sends delivered (`ok`) or given up on; statuses if `status`,
+    /// messages otherwise.
+    pub fn relayed(&self, status: bool, ok: bool, count: u64) {
+        let counters = &self.0;
+        let counter = match (status, ok) {
+            (true, true) => &counters.statuses_sent,
+            (true, false) => &counters.statuses_failed,
+            (false, true) => &counters.messages_sent,
+            (false, false) => &counters.messages_failed,
+        };
+        bump(counter, count);
+    }
+
+    pub fn filtered(&self) {
+        bump(&self.0.messages_filtered, 1);
+    }
+
+    pub fn deduped(&self) {
+        bump(&self.0.messages_deduped, 1);
+    }
+
+    pub fn switched(&self) {
+        bump(&self.0.switches, 1);
+    }
+
+    pub fn reset(&self) {
+        let counters = &self.0;
+        for counter in [
+            &counters.messages_sent,
+            &counters.messages_failed,
+            &counters.messages_filtered,
+            &counters.messages_deduped,
+            &counters.statuses_sent,
+            &counters.statuses_failed,
+            &counters.bytes_posted,
+            &counters.last_status,
+            &counters.last_latency_ms,
+            &counters.switches,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// The counters, with the rest of `AppMetrics` left empty.
+    pub fn counts(&self) -> AppMetrics {
+        let counters = &self.0;
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        AppMetrics {
+            messages: MessageCounts {
+                ok: load(&counters.messages_sent),
+                failed: load(&counters.messages_failed),
+                filtered: load(&counters.messages_filtered),
+                deduped: load(&counters.messages_deduped),
+            },
+            statuses: StatusCounts { ok: load(&counters.statuses_sent), failed: load(&counters.statuses_failed) },
+            bytes_posted: load(&counters.bytes_posted),
+            last_brain_answer: match load(&counters.last_status) {
+                0 => None,
+                status => Some(BrainAnswer { status: status as u16, latency_ms: load(&counters.last_latency_ms) }),
+            },
+            window_switches: load(&counters.switches),
+            ..AppMetrics::default()
+        }
+    }
+}
+
+pub fn init(app: &tauri::AppHandle) -> Metrics {
+    let metrics = Metrics::default();
+    app.manage(metrics.clone());
+    metrics
+}
+
+pub fn snapshot(app: &tauri::AppHandle) -> AppMetrics {
+    let manager = app.state::<crate::organs::OrganManager>();
+    AppMetrics {
+        uptime_secs: crate::startup::uptime().as_secs(),
+        organs: manager.defs().iter().map(|d| (d.id.clone(), crate::organs::status(app, &d.id).to_string())).collect(),
+        spool_depth: app.try_state::<crate::relay::Delivery>().map(|d| d.metrics().spool.depth),
+        rate_limits: app.try_state::<crate::ratelimit::RateLimiter>().map(|l| l.state()).unwrap_or_default(),
+        ..app.state::<Metrics>().counts()
+    }
+}
+
+/// `metrics` in the Prometheus text format.
+fn render(metrics: &AppMetrics) -> String {
+    let mut out = String::new();
+    let mut family = |name: &str, kind: &str, samples: &[(String, u64)]| {
+        let _ = writeln!(out, "# TYPE lexicon_{name} {kind}");
+        for (labels, value) in samples {
+            let _ = writeln!(out, "lexicon_{name}{labels} {value}");
+        }
+    };
+    let outcome = |outcome: &str, value: u64| (format!("{{outcome=\"{outcome}\"}}"), value);
+    let one = |value: u64| [(String::new(), value)];
+    family("uptime_seconds", "gauge", &one(metrics.uptime_secs));
+    let messages = &metrics.messages;
+    family(
+        "messages_relayed_total",
+        "counter",
+        &[
+            outcome("ok", messages.ok),
+            outcome("failed", messages.failed),
+            outcome("filtered", messages.filtered),
+            outcome("deduped", messages.deduped),
+        ],
+    );
+    family(
+        "statuses_relayed_total",
+        "counter",
+        &[outcome("ok", metrics.statuses.ok), outcome("failed", metrics.statuses.failed)],
+    );
+    family("brain_posted_bytes_total", "counter", &one(metrics.bytes_posted));
+    if let Some(answer) = &metrics.last_brain_answer {
+        family("brain_last_status", "gauge", &one(answer.status.into()));
+        family("brain_last_latency_milliseconds", "gauge", &one(answer.latency_ms));
+    }
+    family("window_switches_total", "counter", &one(metrics.window_switches));
+    let organs: Vec<_> = metrics
+        .organs
+        .iter()
+        .map(|(id, state)| (format!("{{organ=\"{id}\",state=\"{state}\"}}"), 1))
+        .collect();
+    family("organ_state", "gauge", &organs);
+    if let Some(depth) = metrics.spool_depth {
+        family("spool_depth", "gauge", &one(depth as u64));
+    }
+    let (mut limited, mut limiting) = (Vec::new(), Vec::new());
+    for bucket in &metrics.rate_limits {
+        let endpoint = serde_json::to_value(bucket.endpoint).unwrap_or_default();
+        let labels = format!("{{organ=\"{}\",endpoint=\"{}\"}}", bucket.organ, endpoint.as_str().unwrap_or_default());
+        limited.push((labels.clone(), bucket.limited));
+        limiting.push((labels, bucket.limiting.into()));
+    }
+    family("rate_limited_total", "counter", &limited);
+    family("rate_limiting", "gauge", &limiting);
+    out
+}
+
+/// Serve `/metrics` on `metrics.port`, if set, for the life of the app.
+pub fn serve(app: &tauri::AppHandle) {
+    let port = crate::config::current(app).metrics.port;
+    if port == 0 {
+        return;
+    }
+    let listener = match TcpListener::bind((Ipv4Addr::LOCALHOST, port)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            tracing::warn!("metrics endpoint unavailable on port {port}: {e}");
+            return;
+        }
+    };
+    tracing::info!("metrics at http://127.0.0.1:{port}/metrics");
+    let app = app.clone();
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+            let mut line = String::new();
+            if BufReader::new(&stream).read_line(&mut line).is_err() {
+                continue;
+            }
+            let reply = match line.split_whitespace().take(2).collect::<Vec<_>>()[..] {
+                ["GET", "/metrics"] => {
+                    let body = render(&snapshot(&app));
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\n\
+                         Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                        body.len()
+                    )
+                }
+                _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+            };
+            let _ = stream.write_all(reply.as_bytes());
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_metrics(app: tauri::AppHandle) -> AppMetrics {
+    snapshot(&app)
+}
+
+#[tauri::command]
+pub fn reset_metrics(app: tauri::AppHandle) {
+    app.state::<Metrics>().reset();
+    tracing::info!("metrics reset");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters_add_up_and_reset() {
+        let metrics = Metrics::default();
+        metrics.posted(120, Some(200), Duration::from_millis(35));
+        metrics.posted(80, None, Duration::from_secs(10));
+        metrics.relayed(false, true, 3);
+        metrics.relayed(false, false, 1);
+        metrics.relayed(true, true, 2);
+        metrics.filtered();
+        metrics.deduped();
+        metrics.deduped();
+        metrics.switched();
+        let counts = metrics.counts();
+        assert_eq!(counts.messages, MessageCounts { ok: 3, failed: 1, filtered: 1, deduped: 2 });
+        assert_eq!(counts.statuses, StatusCounts { ok: 2, failed: 0 });
+        assert_eq!(counts.bytes_posted, 200);
+        // No answer leaves the last one standing.
+        assert_eq!(counts.last_brain_answer, Some(BrainAnswer { status: 200, latency_ms: 35 }));
+        assert_eq!(counts.window_switches, 1);
+
+        metrics.reset();
+        assert_eq!(metrics.counts(), AppMetrics::default());
+    }
+
+    #[test]
+    fn renders_prometheus_text() {
+        let metrics = AppMetrics {
+            uptime_secs: 90,
+            messages: MessageCounts { ok: 4, ..MessageCounts::default() },
+            organs: BTreeMap::from([("whatsapp".to_string(), "visible".to_string())]),
+            spool_depth: Some(2),
+            rate_limits: vec![crate::ratelimit::BucketState {
+                organ: "whatsapp".into(),
+                endpoint: crate::ratelimit::Endpoint::Statuses,
+                tokens: 0,
+                limited: 7,
+                limiting: true,
+            }],
+            ..AppMetrics::default()
+        };
+        let text = render(&metrics);
+        assert!(text.contains("# TYPE lexicon_uptime_seconds gauge\nlexicon_uptime_seconds 90\n"));
+        assert!(text.contains("lexicon_messages_relayed_total{outcome=\"ok\"} 4\n"));
+        assert!(text.contains("lexicon_organ_state{organ=\"whatsapp\",state=\"visible\"} 1\n"));
+        assert!(text.contains("lexicon_spool_depth 2\n"));
+        assert!(text.contains("lexicon_rate_limited_total{organ=\"whatsapp\",endpoint=\"statuses\"} 7\n"));
+        assert!(!text.contains("brain_last_status"));
+    }
+}
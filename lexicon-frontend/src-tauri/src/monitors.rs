@@ -0,0 +1,204 @@
+//! Monitors — which display each window goes fullscreen on.
+//!
+//! `list_monitors` names the displays, with their index (in the OS's
+//! order), position, size and scale, and which is the primary. The canvas
+//! and each organ can be given one under `[monitors]`, by name, by index
+//! or as `"primary"`; `move_window_to_monitor` moves a window now and saves
+//! its choice, and an empty monitor goes back to none. A display that
+//! isn't connected is passed over, as if nothing were set.
+//!
+//! ```toml
+//! [monitors]
+//! main = "primary"
+//!
+//! [monitors.organs]
+//! whatsapp = "HDMI-1"
+//! ```
+//!
+//! A window with a monitor goes fullscreen there, and one without wherever
+//! it was. Two windows on different displays, one of them by choice, stay
+//! on screen together: opening the organ on the secondary leaves the
+//! canvas up on the primary, and hiding it doesn't bring the canvas back.
+//! In the split layout both panes are on the canvas's monitor. Windowed,
+//! windows keep the place they were left in, so this doesn't apply.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use tauri::Manager;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+#[serde(default)]
+pub struct MonitorConfig {
+    /// The canvas's monitor.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub main: Option<String>,
+    /// Monitors by organ id.
+    pub organs: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MonitorInfo {
+    pub index: usize,
+    pub name: Option<String>,
+    pub primary: bool,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub scale_factor: f64,
+}
+
+/// Which of `monitors` (name, primary) `spec` means.
+fn pick(spec: &str, monitors: &[(Option<&str>, bool)]) -> Option<usize> {
+    let spec = spec.trim();
+    if spec.eq_ignore_ascii_case("primary") {
+        return monitors.iter().position(|(_, primary)| *primary);
+    }
+    if let Ok(index) = spec.parse::<usize>() {
+        return (index < monitors.len()).then_some(index);
+    }
+    monitors.iter().position(|(name, _)| name.is_some_and(|n| n.eq_ignore_ascii_case(spec)))
+}
+
+fn same(a: &tauri::Monitor, b: &tauri::Monitor) -> bool {
+    a.name() == b.name() && a.position() == b.position()
+}
+
+/// The connected monitors, and which is the primary.
+fn monitors(app: &tauri::AppHandle) -> Vec<(tauri::Monitor, bool)> {
+    let primary = app.primary_monitor().ok().flatten();
+    let all = app.available_monitors().unwrap_or_default();
+    all.into_iter()
+        .map(|m| {
+            let is_primary = primary.as_ref().is_some_and(|p| same(p, &m));
+            (m, is_primary)
+        })
+        .collect()
+}
+
+fn resolve(app: &tauri::AppHandle, spec: &str) -> Option<tauri::Monitor> {
+    let mut all = monitors(app);
+    let names: Vec<_> = all.iter().map(|(m, primary)| (m.name().map(String::as_str), *primary)).collect();
+    let index = pick(spec, &names)?;
+    Some(all.swap_remove(index).0)
+}
+
+/// The monitor setting for window `label`.
+fn setting(app: &tauri::AppHandle, label: &str) -> Option<String> {
+    let config = crate::config::current(app).monitors;
+    match crate::organs::id_from_label(label) {
+        Some(id) => config.organs.get(id).cloned(),
+        None if label == "main" => config.main,
+        None => None,
+    }
+}
+
+/// The connected monitor window `label` is given, if any.
+pub fn assigned(app: &tauri::AppHandle, label: &str) -> Option<tauri::Monitor> {
+    resolve(app, &setting(app, label)?)
+}
+
+/// Whether windows `a` and `b` are on different monitors, at least one of
+/// them by choice, so they can be on screen together.
+pub fn apart(app: &tauri::AppHandle, a: &str, b: &str) -> bool {
+    let (given_a, given_b) = (assigned(app, a), assigned(app, b));
+    if given_a.is_none() && given_b.is_none() {
+        return false;
+    }
+    let now = |label: &str| app.get_webview_window(label).and_then(|w| w.current_monitor().ok().flatten());
+    match (given_a.or_else(|| now(a)), given_b.or_else(|| now(b))) {
+        (Some(a), Some(b)) => !same(&a, &b),
+        _ => false,
+    }
+}
+
+/// Move `window` onto its monitor, filling it, before it goes fullscreen.
+pub fn place(window: &tauri::WebviewWindow) {
+    let Some(monitor) = assigned(window.app_handle(), window.label()) else { return };
+    if window.current_monitor().ok().flatten().is_some_and(|m| same(&m, &monitor)) {
+        return;
+    }
+    let area = monitor.work_area();
+    let _ = window.set_fullscreen(false);
+    let _ = window.set_position(area.position);
+    let _ = window.set_size(area.size);
+}
+
+#[tauri::command]
+pub fn list_monitors(app: tauri::AppHandle) -> Vec<MonitorInfo> {
+    monitors(&app)
+        .into_iter()
+        .enumerate()
+        .map(|(index, (m, primary))| MonitorInfo {
+            index,
+            name: m.name().cloned(),
+            primary,
+            x: m.position().x,
+            y: m.position().y,
+            width: m.size().width,
+            height: m.size().height,
+            scale_factor: m.scale_factor(),
+        })
+        .collect()
+}
+
+/// Give window `label` (`main`, an organ id or its window label) the
+/// monitor `monitor` and move it there if it is showing; saved in the
+/// config. An empty `monitor` takes the choice away.
+#[tauri::command]
+pub fn move_window_to_monitor(
+    window: tauri::WebviewWindow,
+    app: tauri::AppHandle,
+    label: String,
+    monitor: String,
+) -> Result<(), String> {
+    if window.label() != "main" {
+        return Err("only the main window can move windows between monitors".into());
+    }
+    let id = match label.as_str() {
+        "main" => None,
+        other => Some(crate::organs::id_from_label(other).unwrap_or(other).to_string()),
+    };
+    if let Some(id) = &id {
+        app.state::<crate::organs::OrganManager>().get(id).ok_or_else(|| format!("unknown window: {label}"))?;
+    }
+    let monitor = monitor.trim().to_string();
+    if !monitor.is_empty() && resolve(&app, &monitor).is_none() {
+        return Err(format!("no monitor '{monitor}'; see list_monitors"));
+    }
+    let setting = (!monitor.is_empty()).then_some(monitor);
+    let label = id.as_deref().map_or_else(|| "main".to_string(), crate::organs::window_label);
+    crate::config::update(&app, move |c| match (id, setting) {
+        (Some(id), Some(monitor)) => {
+            c.monitors.organs.insert(id, monitor);
+        }
+        (Some(id), None) => {
+            c.monitors.organs.remove(&id);
+        }
+        (None, monitor) => c.monitors.main = monitor,
+    })?;
+    if let Some(window) = app.get_webview_window(&label) {
+        if window.is_visible().unwrap_or(false) {
+            crate::show_window(&window);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn monitors_are_picked_by_name_index_or_primary() {
+        let monitors = [(Some("eDP-1"), false), (Some("HDMI-1"), true), (None, false)];
+        assert_eq!(pick("primary", &monitors), Some(1));
+        assert_eq!(pick("2", &monitors), Some(2));
+        assert_eq!(pick("3", &monitors), None);
+        assert_eq!(pick(" hdmi-1 ", &monitors), Some(1));
+        assert_eq!(pick("DP-2", &monitors), None);
+        assert_eq!(pick("primary", &[(Some("eDP-1"), false)]), None);
+    }
+}
@@ -0,0 +1,164 @@
+//! Network changes — re-check the Brain as soon as connectivity moves.
+//!
+//! On Linux an rtnetlink socket subscribed to link, address and route
+//! changes wakes us; it needs no privileges. Elsewhere the Brain is
+//! polled and a change in the verdict counts as a network change. Bursts
+//! of events (an interface flapping, DHCP settling) are folded into one.
+//!
+//! Each change probes the Brain once, kicks the ingest queue when it is
+//! reachable, emits `network://changed` and updates `get_health`.
+
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tauri::Emitter;
+
+/// Quiet period that ends a burst of changes.
+#[cfg(target_os = "linux")]
+const SETTLE: Duration = Duration::from_secs(2);
+
+/// Polling cadence where there are no change notifications.
+#[cfg(not(target_os = "linux"))]
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct NetworkStatus {
+    /// Unix time (ms) of the last network change seen.
+    pub last_change_ms: Option<u64>,
+    /// Whether the Brain answered the last probe; unknown until one ran.
+    pub brain_reachable: Option<bool>,
+}
+
+static STATUS: Mutex<NetworkStatus> = Mutex::new(NetworkStatus { last_change_ms: None, brain_reachable: None });
+
+pub fn status() -> NetworkStatus {
+    *STATUS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+/// Probe the Brain and record the verdict. Returns it.
+pub fn probe(app: &tauri::AppHandle) -> bool {
+    let latency = crate::brain::ping(app);
+    let reachable = latency.is_some();
+    STATUS.lock().unwrap_or_else(|e| e.into_inner()).brain_reachable = Some(reachable);
+    crate::brain::observe(app, latency);
+    reachable
+}
+
+fn on_change(app: &tauri::AppHandle, reachable: Option<bool>) {
+    if crate::power::is_suspended() {
+        return;
+    }
+    STATUS.lock().unwrap_or_else(|e| e.into_inner()).last_change_ms = Some(now_millis());
+    let reachable = reachable.unwrap_or_else(|| probe(app));
+    tracing::info!("network changed — Brain {}", if reachable { "reachable" } else { "unreachable" });
+    if reachable {
+        crate::ingest::resume(app);
+    }
+    let _ = app.emit("network://changed", status());
+    crate::health::announce(app);
+}
+
+// ── Change sources ─────────────────────────────────────────────
+
+#[cfg(target_os = "linux")]
+mod netlink {
+    use std::io;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::time::Duration;
+
+    pub struct Socket(OwnedFd);
+
+    impl Socket {
+        /// A route socket joined to the link, address and route groups.
+        pub fn open() -> io::Result<Self> {
+            // SAFETY: plain socket/bind calls on a fd we own; `addr` is a
+            // zeroed sockaddr_nl with the family and groups filled in.
+            unsafe {
+                let fd = libc::socket(libc::AF_NETLINK, libc::SOCK_RAW | libc::SOCK_CLOEXEC, libc::NETLINK_ROUTE);
+                if fd < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                let socket = Self(OwnedFd::from_raw_fd(fd));
+                let mut addr: libc::sockaddr_nl = std::mem::zeroed();
+                addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+                addr.nl_groups = (libc::RTMGRP_LINK
+                    | libc::RTMGRP_IPV4_IFADDR
+                    | libc::RTMGRP_IPV6_IFADDR
+                    | libc::RTMGRP_IPV4_ROUTE
+                    | libc::RTMGRP_IPV6_ROUTE) as u32;
+                let bound = libc::bind(
+                    fd,
+                    &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                    std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+                );
+                if bound < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(socket)
+            }
+        }
+
+        /// Wait for a notification and swallow it. False on timeout.
+        pub fn wait(&self, timeout: Option<Duration>) -> io::Result<bool> {
+            let mut poll = libc::pollfd { fd: self.0.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+            let ms = timeout.map_or(-1, |t| t.as_millis().min(i32::MAX as u128) as i32);
+            // SAFETY: one valid pollfd.
+            let ready = unsafe { libc::poll(&mut poll, 1, ms) };
+            if ready < 0 {
+                let e = io::Error::last_os_error();
+                return if e.kind() == io::ErrorKind::Interrupted { Ok(false) } else { Err(e) };
+            }
+            if ready == 0 {
+                return Ok(false);
+            }
+            // Only the fact of a change matters, not its contents.
+            let mut buf = [0u8; 8192];
+            // SAFETY: reading into a local buffer of the given length.
+            unsafe { libc::recv(self.0.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len(), libc::MSG_DONTWAIT) };
+            Ok(true)
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn watch(app: &tauri::AppHandle) -> Result<(), String> {
+    let socket = netlink::Socket::open().map_err(|e| format!("netlink: {e}"))?;
+    loop {
+        if !socket.wait(None).map_err(|e| e.to_string())? {
+            continue;
+        }
+        while socket.wait(Some(SETTLE)).map_err(|e| e.to_string())? {}
+        on_change(app, None);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn watch(app: &tauri::AppHandle) -> Result<(), String> {
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+        if crate::power::is_suspended() {
+            continue;
+        }
+        let before = status().brain_reachable;
+        let now = probe(app);
+        if before.is_some_and(|b| b != now) {
+            on_change(app, Some(now));
+        }
+    }
+}
+
+pub fn init(app: &tauri::AppHandle) {
+    let app = app.clone();
+    std::thread::spawn(move || {
+        probe(&app);
+        crate::health::announce(&app);
+        if let Err(e) = watch(&app) {
+            tracing::warn!("network change detection unavailable: {e}");
+        }
+    });
+}
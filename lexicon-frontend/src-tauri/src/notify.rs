@@ -0,0 +1,420 @@
+//! Desktop notifications for organ messages, with actions.
+//!
+//! Each notification gets a correlation id that maps back to the organ and
+//! chat it came from. Clicking the body or its "Open chat" action opens that
+//! chat through the same path as `lexicon://<organ>/chat?name=…`, so it works
+//! while every window is hidden. Servers without action support still
+//! deliver the body click, which degrades to the same click-to-open.
+//!
+//! Inline replies are not offered, but one that arrives anyway (macOS can
+//! attach one) is sent to its chat through `whatsapp::send` when it is a
+//! chat of the first WhatsApp login, the only organ Rust can send through.
+//! Any other reply, or one that fails to send, opens the chat instead of
+//! being dropped.
+//!
+//! Messages the relay sends while the canvas is hidden and their organ is
+//! in the background are announced too (`notifications.messages`), with the
+//! chat, sender and the text cut to `notifications.max_body` characters —
+//! or, in `notifications.private` mode, only "New WhatsApp message". Past
+//! three from one chat within 30 seconds they collapse into one "N new
+//! messages from X" per 30 seconds. Clicking one opens its organ, at the
+//! chat when the message names one. Messages the relay filters drop are
+//! never announced, and nor are those of organs in `notifications.muted`
+//! (see `mute_organ`); `set_dnd` holds every notification back.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[cfg(desktop)]
+use notify_rust::{Notification, NotificationResponse};
+use serde::{Deserialize, Serialize};
+use tauri::Manager;
+
+use crate::deeplink::DeepLink;
+
+#[cfg_attr(mobile, allow(dead_code))]
+const ACTION_OPEN: &str = "open";
+
+/// How long a chat's messages count towards one burst.
+const BURST_WINDOW: Duration = Duration::from_secs(30);
+/// Messages from one chat announced one by one before they collapse.
+const BURST_SINGLES: usize = 3;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+#[serde(default)]
+pub struct NotificationPrefs {
+    /// Announce messages relayed while the canvas is hidden.
+    pub messages: bool,
+    /// Characters of a message shown; the rest is cut with "…".
+    #[cfg_attr(test, proptest(strategy = "crate::testing::toml_usize()"))]
+    pub max_body: usize,
+    /// Leave out the chat, sender and text.
+    pub private: bool,
+    /// Organs whose messages are never announced, by id.
+    pub muted: Vec<String>,
+}
+
+impl Default for NotificationPrefs {
+    fn default() -> Self {
+        Self {
+            messages: true,
+            max_body: 100,
+            private: false,
+            muted: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(test, derive(PartialEq))]
+enum Target {
+    Chat { organ: String, chat: String },
+    Organ(String),
+    /// The canvas.
+    Main,
+}
+
+/// Live notifications, keyed by correlation id.
+#[derive(Default)]
+pub struct NotificationDispatcher {
+    next_id: AtomicU64,
+    pending: Mutex<HashMap<u64, Target>>,
+    bursts: Mutex<Bursts>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Alert {
+    One,
+    /// This many in the window; announce them together.
+    Many(usize),
+    /// Already announced together.
+    Quiet,
+}
+
+#[derive(Default)]
+struct Burst {
+    arrivals: VecDeque<Instant>,
+    collapsed: Option<Instant>,
+}
+
+/// Recent messages by chat.
+#[derive(Default)]
+struct Bursts(HashMap<String, Burst>);
+
+impl Bursts {
+    fn arrive(&mut self, chat: &str, now: Instant) -> Alert {
+        let recent = |at: &Instant| now.saturating_duration_since(*at) < BURST_WINDOW;
+        self.0.retain(|_, burst| burst.arrivals.back().is_some_and(recent));
+        let burst = self.0.entry(chat.to_string()).or_default();
+        while burst.arrivals.front().is_some_and(|at| !recent(at)) {
+            burst.arrivals.pop_front();
+        }
+        burst.arrivals.push_back(now);
+        let count = burst.arrivals.len();
+        if count <= BURST_SINGLES {
+            return Alert::One;
+        }
+        if burst.collapsed.as_ref().is_some_and(recent) {
+            return Alert::Quiet;
+        }
+        burst.collapsed = Some(now);
+        Alert::Many(count)
+    }
+}
+
+impl NotificationDispatcher {
+    fn track(&self, target: Target) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        self.pending.lock().unwrap_or_else(|e| e.into_inner()).insert(id, target);
+        id
+    }
+
+    fn take(&self, id: u64) -> Option<Target> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner()).remove(&id)
+    }
+}
+
+fn held_back() -> bool {
+    crate::dnd::is_enabled() || crate::presentation::is_active()
+}
+
+/// Show a notification for a message in `organ`/`chat`. Returns the
+/// correlation id, or `None` when DND or the presentation guard holds it back.
+pub fn notify(app: &tauri::AppHandle, organ: &str, chat: &str, title: &str, body: &str) -> Result<Option<u64>, String> {
+    if held_back() {
+        return Ok(None);
+    }
+    if app.state::<crate::organs::OrganManager>().get(organ).is_none() {
+        return Err(format!("unknown organ: {organ}"));
+    }
+    raise(app, Target::Chat { organ: organ.to_string(), chat: chat.to_string() }, title, body).map(Some)
+}
+
+/// Show a notification that brings up the canvas when clicked. `None`
+/// when DND or the presentation guard holds it back.
+pub fn notify_main(app: &tauri::AppHandle, title: &str, body: &str) -> Result<Option<u64>, String> {
+    if held_back() {
+        return Ok(None);
+    }
+    raise(app, Target::Main, title, body).map(Some)
+}
+
+fn raise(app: &tauri::AppHandle, target: Target, title: &str, body: &str) -> Result<u64, String> {
+    let action = match target {
+        Target::Chat { .. } => "Open chat",
+        Target::Organ(_) => "Open",
+        Target::Main => "Show Lexicon",
+    };
+    let dispatcher = app.state::<NotificationDispatcher>();
+    let id = dispatcher.track(target);
+    if let Err(e) = show(app, id, title, body, action) {
+        dispatcher.take(id);
+        return Err(e);
+    }
+    Ok(id)
+}
+
+/// `text` cut to `max` characters.
+fn truncate(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((end, _)) => format!("{}…", text[..end].trim_end()),
+        None => text.to_string(),
+    }
+}
+
+/// Summary and body announcing `count` messages like `message`, from an
+/// organ titled `title`.
+fn describe(title: &str, message: &serde_json::Value, count: usize, prefs: &NotificationPrefs) -> (String, String) {
+    let field = |key: &str| message.get(key).and_then(|v| v.as_str()).filter(|v| !v.is_empty());
+    if prefs.private {
+        let body = match count {
+            1 => format!("New {title} message"),
+            n => format!("{n} new {title} messages"),
+        };
+        return ("Lexicon".into(), body);
+    }
+    let sender = field("sender").or_else(|| field("from"));
+    let chat = field("chat").or(sender).unwrap_or(title);
+    if count > 1 {
+        return (chat.to_string(), format!("{count} new messages from {chat}"));
+    }
+    let text = field("text").or_else(|| field("caption")).map(|text| truncate(text, prefs.max_body));
+    let body = match (sender.filter(|s| *s != chat), text) {
+        (Some(sender), Some(text)) => format!("{sender}: {text}"),
+        (None, Some(text)) => text,
+        (Some(sender), None) => format!("New message from {sender}"),
+        (None, None) => format!("New {title} message"),
+    };
+    (chat.to_string(), body)
+}
+
+/// Where clicking the announcement of `organ`'s `message` goes: its chat,
+/// or the organ if it names none.
+fn target(organ: &str, message: &serde_json::Value) -> Target {
+    match message.get("chat").and_then(|v| v.as_str()).filter(|chat| !chat.trim().is_empty()) {
+        Some(chat) => Target::Chat { organ: organ.to_string(), chat: chat.to_string() },
+        None => Target::Organ(organ.to_string()),
+    }
+}
+
+/// Announce a message `organ` relayed, if the user can't see it come in.
+pub fn relayed(app: &tauri::AppHandle, organ: &str, message: &serde_json::Value) {
+    let prefs = crate::config::current(app).notifications;
+    let main_visible = app.get_webview_window("main").is_some_and(|w| w.is_visible().unwrap_or(false));
+    let muted = prefs.muted.iter().any(|id| id == organ);
+    if !prefs.messages || muted || held_back() || main_visible || crate::organs::status(app, organ) == "visible" {
+        return;
+    }
+    let Some(title) = app.state::<crate::organs::OrganManager>().get(organ).map(|d| d.title.clone()) else {
+        return;
+    };
+    let field = |key: &str| message.get(key).and_then(|v| v.as_str());
+    let chat = format!("{organ}/{}", field("chat_id").or_else(|| field("chat")).unwrap_or_default());
+    let dispatcher = app.state::<NotificationDispatcher>();
+    let alert = dispatcher.bursts.lock().unwrap_or_else(|e| e.into_inner()).arrive(&chat, Instant::now());
+    let count = match alert {
+        Alert::One => 1,
+        Alert::Many(count) => count,
+        Alert::Quiet => return,
+    };
+    let (summary, body) = describe(&title, message, count, &prefs);
+    if let Err(e) = raise(app, target(organ, message), &summary, &body) {
+        tracing::debug!("{organ} message not announced: {e}");
+    }
+}
+
+/// Show notification `id` and route its response back.
+#[cfg(desktop)]
+fn show(app: &tauri::AppHandle, id: u64, title: &str, body: &str, action: &str) -> Result<(), String> {
+    let shown = Notification::new()
+        .appname("Lexicon")
+        .summary(title)
+        .body(body)
+        .action("default", action)
+        .action(ACTION_OPEN, action)
+        .show();
+    let handle = shown.map_err(|e| format!("notification failed: {e}"))?;
+
+    // Blocks until the notification is acted on or closed.
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let _ = handle.wait_for_response(|response: &NotificationResponse| {
+            let Some(target) = app.state::<NotificationDispatcher>().take(id) else {
+                return;
+            };
+            match response {
+                NotificationResponse::Default => activate(&app, target),
+                NotificationResponse::Reply(text) => reply(&app, target, text),
+                NotificationResponse::Action(action) if action == ACTION_OPEN => activate(&app, target),
+                NotificationResponse::Action(action) => tracing::warn!("notification {id}: unknown action '{action}'"),
+                NotificationResponse::Closed(_) => {}
+            }
+        });
+        // Closed without a response (e.g. the server went away).
+        app.state::<NotificationDispatcher>().take(id);
+    });
+    Ok(())
+}
+
+#[cfg(mobile)]
+fn show(_app: &tauri::AppHandle, _id: u64, _title: &str, _body: &str, _action: &str) -> Result<(), String> {
+    Err(crate::platform::NotSupportedOnPlatform::new("desktop notifications").into())
+}
+
+/// Send `text` to `target`'s chat, if it is a WhatsApp one; otherwise, or
+/// if the send fails, open it. Blocks until the organ confirms, so only
+/// from a notification's own thread.
+#[cfg_attr(mobile, allow(dead_code))]
+fn reply(app: &tauri::AppHandle, target: Target, text: &str) {
+    if let Target::Chat { organ, chat } = &target {
+        if organ == crate::whatsapp::ORGAN {
+            match crate::whatsapp::send(app, chat, text) {
+                Ok(_) => return,
+                Err(e) => tracing::warn!("notification reply not sent: {e}"),
+            }
+        }
+    }
+    activate(app, target);
+}
+
+#[cfg_attr(mobile, allow(dead_code))]
+fn activate(app: &tauri::AppHandle, target: Target) {
+    match target {
+        Target::Chat { organ, chat } => crate::deeplink::dispatch(app, DeepLink::OpenChat { organ, query: chat }),
+        Target::Organ(organ) => {
+            if let Err(e) = crate::organs::open(app, &organ) {
+                tracing::warn!("notification: {e}");
+            }
+        }
+        Target::Main => {
+            let hidden = app.get_webview_window("main").is_some_and(|w| !w.is_visible().unwrap_or(false));
+            if hidden {
+                crate::toggle_main(app);
+            }
+        }
+    }
+}
+
+/// Only the main canvas may raise notifications; organ pages are remote content.
+#[tauri::command]
+pub fn notify_message(
+    window: tauri::WebviewWindow,
+    app: tauri::AppHandle,
+    organ: String,
+    chat: String,
+    title: String,
+    body: String,
+) -> Result<Option<u64>, String> {
+    if window.label() != "main" {
+        return Err("notifications can only be raised from the main window".into());
+    }
+    notify(&app, &organ, &chat, &title, &body)
+}
+
+#[tauri::command]
+pub fn get_notification_prefs(app: tauri::AppHandle) -> NotificationPrefs {
+    crate::config::current(&app).notifications
+}
+
+#[tauri::command]
+pub fn set_notification_prefs(
+    window: tauri::WebviewWindow,
+    app: tauri::AppHandle,
+    prefs: NotificationPrefs,
+) -> Result<NotificationPrefs, String> {
+    if window.label() != "main" {
+        return Err("only the main window can change notification settings".into());
+    }
+    crate::config::update(&app, |config| config.notifications = prefs.clone())?;
+    Ok(prefs)
+}
+
+/// Mute or unmute announcements of organ `id`'s messages. Returns the
+/// organs muted now.
+#[tauri::command]
+pub fn mute_organ(window: tauri::WebviewWindow, app: tauri::AppHandle, id: String, muted: bool) -> Result<Vec<String>, String> {
+    if window.label() != "main" {
+        return Err("only the main window can change notification settings".into());
+    }
+    if app.state::<crate::organs::OrganManager>().get(&id).is_none() {
+        return Err(format!("unknown organ: {id}"));
+    }
+    crate::config::update(&app, |config| {
+        let list = &mut config.notifications.muted;
+        list.retain(|other| *other != id);
+        if muted {
+            list.push(id.clone());
+        }
+    })?;
+    Ok(crate::config::current(&app).notifications.muted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn a_busy_chat_collapses_to_one_notification_per_window() {
+        let mut bursts = Bursts::default();
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let alerts: Vec<_> = (0..6).map(|n| bursts.arrive("whatsapp/ana", at(n))).collect();
+        assert_eq!(alerts, [Alert::One, Alert::One, Alert::One, Alert::Many(4), Alert::Quiet, Alert::Quiet]);
+        // Other chats count on their own.
+        assert_eq!(bursts.arrive("whatsapp/bob", at(6)), Alert::One);
+        assert_eq!(bursts.arrive("whatsapp/ana", at(20)), Alert::Quiet);
+        // Still busy once the window has passed: one more, counting what is recent.
+        assert_eq!(bursts.arrive("whatsapp/ana", at(33)), Alert::Many(4));
+        // A quiet chat starts over.
+        assert_eq!(bursts.arrive("whatsapp/ana", at(100)), Alert::One);
+    }
+
+    #[test]
+    fn messages_are_described_briefly_or_privately() {
+        let prefs = NotificationPrefs { max_body: 10, ..NotificationPrefs::default() };
+        let group = json!({ "chat": "Family", "sender": "Ana", "text": "dinner at eight tonight?" });
+        assert_eq!(describe("WhatsApp", &group, 1, &prefs), ("Family".into(), "Ana: dinner at…".into()));
+        let direct = json!({ "chat": "Ana", "sender": "Ana", "text": "hi" });
+        assert_eq!(describe("WhatsApp", &direct, 1, &prefs), ("Ana".into(), "hi".into()));
+        let photo = json!({ "chat": "Ana", "mime": "image/jpeg" });
+        assert_eq!(describe("WhatsApp", &photo, 1, &prefs).1, "New WhatsApp message");
+        assert_eq!(describe("WhatsApp", &group, 5, &prefs).1, "5 new messages from Family");
+
+        let private = NotificationPrefs { private: true, ..prefs };
+        assert_eq!(describe("WhatsApp", &group, 1, &private), ("Lexicon".into(), "New WhatsApp message".into()));
+        assert_eq!(describe("WhatsApp", &group, 4, &private).1, "4 new WhatsApp messages");
+    }
+
+    #[test]
+    fn clicks_open_the_chat_or_else_the_organ() {
+        assert_eq!(
+            target("whatsapp", &json!({ "chat": "Family", "text": "hi" })),
+            Target::Chat { organ: "whatsapp".into(), chat: "Family".into() }
+        );
+        assert_eq!(target("discord", &json!({ "from": "Ana", "chat": " " })), Target::Organ("discord".into()));
+    }
+}
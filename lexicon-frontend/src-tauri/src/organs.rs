@@ -0,0 +1,1398 @@
+//! Organs — real web apps (WhatsApp, …) hosted in their own webview
+//! windows next to the main canvas.
+//!
+//! The `OrganManager` holds the registry of organ definitions; each organ
+//! lives in a window labelled `{id}-organ`. Adding an organ is one more
+//! [`OrganDef`]; the commands (`open_organ`, `show_organ`, `close_organ`,
+//! `organ_status`, `organ_state`) all take the id. Lifecycle changes are
+//! announced with an `organ://changed` event carrying the organ id, and
+//! every page load on the organ's own hosts, once its scripts are in,
+//! with `organ://loaded`.
+//!
+//! Only one window is on screen at a time (outside the split layout,
+//! windowed mode and organs that aren't fullscreen, see `layout`):
+//! showing an organ hides the main canvas and any other organ, and hiding
+//! or closing the one on screen brings the canvas back.
+//! A switch waits for the outgoing windows to go before promoting the
+//! incoming one, and switches run one at a time. The commands resolve
+//! once theirs is done; the Rust-side callers (tray, shortcuts, CLI) let
+//! it finish in the background.
+//!
+//! Windows are built off the caller's thread: `open` and `preload`
+//! return at once and the result arrives as `organ-created` or
+//! `organ-create-failed` (`{ id, error }`). A second request while one is
+//! being built joins it instead of building again; `wait_for_organ`
+//! (or `wait_ready`) blocks until it is done.
+//!
+//! An organ may only navigate within its own `hosts`; anything else it
+//! tries to open, in place or in a new window, is handed to `external`
+//! for the system browser.
+//!
+//! Each organ has its own browser profile (cookies, storage, caches)
+//! under `organs/` in the app data dir. Organs that hold no login can
+//! name a `share_cache_group` instead; every organ in a group runs in one
+//! webview data context, so the engine's caches and network process are
+//! shared rather than duplicated. Organs with a persistent login must
+//! stay isolated. `get_organ_resources` reports disk use per profile
+//! and the memory of the whole process tree.
+//!
+//! Organ pages are remote and have no IPC. The little they report back
+//! (whether they hold a signed-in session, their connection status,
+//! WhatsApp's login QR code, the answer to a script run with [`ask`]) arrives as a navigation to
+//! [`REPORT_HOST`], which is cancelled before it leaves the webview.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{mpsc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{Emitter, Manager};
+
+use crate::badge::OrganBadge;
+use crate::lifecycle::Lifecycle;
+use crate::transition::Screen;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OrganDef {
+    pub id: String,
+    pub title: String,
+    pub url: String,
+    /// Domains (and subdomains) the organ may navigate to.
+    #[serde(skip)]
+    pub hosts: Vec<String>,
+    /// JS function expression taking "light" | "dark", evaluated in the
+    /// organ to switch its own theme; see `theme`.
+    #[serde(skip)]
+    pub theme_hook: Option<String>,
+    /// JS expression that is truthy when the page holds a signed-in
+    /// session; checked on every page load.
+    #[serde(skip)]
+    pub session_probe: Option<String>,
+    /// JS expression naming the page's connection status; reported when
+    /// the page changes and relayed to the Brain (see `relay`).
+    #[serde(skip)]
+    pub status_probe: Option<String>,
+    /// Profile shared with other organs in the same group. Only for
+    /// organs without a login: the group shares cookies and storage.
+    /// `None` keeps the organ in a profile of its own.
+    #[serde(skip)]
+    pub share_cache_group: Option<String>,
+    /// Which of several logins to the same service this is; `None` for
+    /// the first. See `with_whatsapp_accounts`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+}
+
+impl OrganDef {
+    /// Directory name of the organ's profile under `organs/`.
+    fn profile(&self) -> String {
+        match &self.share_cache_group {
+            Some(group) => format!("group-{group}"),
+            None => self.id.clone(),
+        }
+    }
+
+    /// The service the organ is a login to, as the Brain knows it:
+    /// `whatsapp` for every WhatsApp account.
+    pub fn service(&self) -> &str {
+        self.account
+            .as_deref()
+            .and_then(|account| self.id.strip_suffix(account)?.strip_suffix('-'))
+            .unwrap_or(&self.id)
+    }
+
+    /// Whether `url` is on one of the organ's own hosts.
+    pub fn owns(&self, url: &tauri::Url) -> bool {
+        url.host_str().is_some_and(|host| self.hosts.iter().any(|h| host == h || host.ends_with(&format!(".{h}"))))
+    }
+}
+
+/// WhatsApp Web keeps its theme in localStorage and a class on <body>.
+const WHATSAPP_THEME_HOOK: &str = r#"function (theme) {
+  try { localStorage.setItem('theme', JSON.stringify(theme)); } catch (_) {}
+  if (document.body) document.body.classList.toggle('dark', theme === 'dark');
+}"#;
+
+/// WhatsApp Web writes the paired device id once the QR code is scanned.
+const WHATSAPP_SESSION_PROBE: &str = "localStorage.getItem('last-wid-md')";
+
+/// The chat list only renders once connected; the QR code only before.
+const WHATSAPP_STATUS_PROBE: &str = "document.querySelector('#pane-side') ? 'connected' \
+  : document.querySelector('[data-ref]') ? 'qr' \
+  : navigator.onLine ? 'loading' : 'offline'";
+
+/// Telegram Web A keeps its session keys in localStorage.
+const TELEGRAM_SESSION_PROBE: &str = "localStorage.getItem('user_auth')";
+
+/// The chat list is there once signed in; the auth forms before.
+const TELEGRAM_STATUS_PROBE: &str = "document.querySelector('.chat-list') ? 'connected' \
+  : document.querySelector('#auth-qr-form, #auth-phone-number-form') ? 'qr' \
+  : navigator.onLine ? 'loading' : 'offline'";
+
+/// Gmail lives under /mail/; signed out, it sends the page to Google's sign-in.
+const GMAIL_SESSION_PROBE: &str = "location.host === 'mail.google.com' && location.pathname.indexOf('/mail/') === 0";
+
+/// The compose button is there once the mailbox has loaded.
+const GMAIL_STATUS_PROBE: &str = "document.querySelector('[gh=cm]') ? 'connected' \
+  : location.host === 'accounts.google.com' ? 'qr' \
+  : navigator.onLine ? 'loading' : 'offline'";
+
+/// The app is under /channels/ once signed in; /login before.
+const DISCORD_SESSION_PROBE: &str = "location.pathname.indexOf('/channels/') === 0";
+
+/// The server rail renders once the gateway is connected.
+const DISCORD_STATUS_PROBE: &str = "document.querySelector('[data-list-id=guildsnav]') ? 'connected' \
+  : /^\\/(login|register)/.test(location.pathname) ? 'qr' \
+  : navigator.onLine ? 'loading' : 'offline'";
+
+/// Host organ pages navigate to when reporting; never actually loaded.
+pub const REPORT_HOST: &str = "lexicon-organ.invalid";
+
+/// Organs whose window is being built, and whether to show it once it is.
+static CREATING: Mutex<BTreeMap<String, bool>> = Mutex::new(BTreeMap::new());
+/// Signalled whenever a build finishes.
+static CREATED: Condvar = Condvar::new();
+
+/// Organs being warmed up on screen behind everything; see `warm`.
+static WARMING: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// Longest a warm-up can wait for the page's first status.
+const WARM_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// When each open organ's page last reported; see `watchdog`.
+static HEARD: Mutex<BTreeMap<String, Instant>> = Mutex::new(BTreeMap::new());
+
+/// How often a watched page reports its status even if nothing changed.
+const HEARTBEAT: Duration = Duration::from_secs(30);
+
+/// Last session state each organ reported.
+static SIGNED_IN: Mutex<BTreeMap<String, bool>> = Mutex::new(BTreeMap::new());
+
+/// Scripts waiting for a page's answer: the organ asked, by token.
+#[derive(Debug, Default)]
+struct Questions(BTreeMap<u64, (String, mpsc::Sender<String>)>);
+
+impl Questions {
+    /// A new question to organ `id`: its token and where the answer arrives.
+    fn ask(&mut self, id: &str) -> (u64, mpsc::Receiver<String>) {
+        let (answer, answered) = mpsc::channel();
+        let token = loop {
+            let token = std::collections::hash_map::RandomState::new().build_hasher().finish();
+            if !self.0.contains_key(&token) {
+                break token;
+            }
+        };
+        self.0.insert(token, (id.to_string(), answer));
+        (token, answered)
+    }
+
+    /// Hand `value` to question `token`, if organ `id` is the one it was
+    /// put to. False if nobody is waiting for it.
+    fn answer(&self, id: &str, token: u64, value: String) -> bool {
+        match self.0.get(&token) {
+            Some((asked, answer)) if asked == id => answer.send(value).is_ok(),
+            _ => false,
+        }
+    }
+
+    fn forget(&mut self, token: u64) {
+        self.0.remove(&token);
+    }
+}
+
+static ASKED: Mutex<Questions> = Mutex::new(Questions(BTreeMap::new()));
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OrganInfo {
+    pub id: String,
+    pub title: String,
+    pub status: &'static str,
+}
+
+pub struct OrganManager {
+    defs: Vec<OrganDef>,
+}
+
+impl OrganManager {
+    pub fn new() -> Self {
+        Self {
+            defs: vec![
+                OrganDef {
+                    id: "whatsapp".into(),
+                    title: "WhatsApp".into(),
+                    url: "https://web.whatsapp.com".into(),
+                    hosts: vec!["whatsapp.com".into(), "whatsapp.net".into()],
+                    theme_hook: Some(WHATSAPP_THEME_HOOK.into()),
+                    session_probe: Some(WHATSAPP_SESSION_PROBE.into()),
+                    status_probe: Some(WHATSAPP_STATUS_PROBE.into()),
+                    // Signed in: never shares.
+                    share_cache_group: None,
+                    account: None,
+                },
+                OrganDef {
+                    id: "telegram".into(),
+                    title: "Telegram".into(),
+                    url: "https://web.telegram.org/a/".into(),
+                    hosts: vec!["telegram.org".into()],
+                    // Web A follows the system theme on its own.
+                    theme_hook: None,
+                    session_probe: Some(TELEGRAM_SESSION_PROBE.into()),
+                    status_probe: Some(TELEGRAM_STATUS_PROBE.into()),
+                    share_cache_group: None,
+                    account: None,
+                },
+                OrganDef {
+                    id: crate::email::ORGAN.into(),
+                    title: "Gmail".into(),
+                    url: "https://mail.google.com/mail/u/0/".into(),
+                    hosts: vec!["mail.google.com".into(), "accounts.google.com".into()],
+                    // Gmail's theme is a setting of the account, not the page.
+                    theme_hook: None,
+                    session_probe: Some(GMAIL_SESSION_PROBE.into()),
+                    status_probe: Some(GMAIL_STATUS_PROBE.into()),
+                    share_cache_group: None,
+                    account: None,
+                },
+                OrganDef {
+                    id: crate::discord::ORGAN.into(),
+                    title: "Discord".into(),
+                    url: "https://discord.com/app".into(),
+                    hosts: vec!["discord.com".into()],
+                    // Follows the account's appearance setting.
+                    theme_hook: None,
+                    session_probe: Some(DISCORD_SESSION_PROBE.into()),
+                    status_probe: Some(DISCORD_STATUS_PROBE.into()),
+                    share_cache_group: None,
+                    account: None,
+                },
+            ],
+        }
+    }
+
+    /// Add a WhatsApp organ for each of `accounts`, after the default one:
+    /// `whatsapp-work` for "work", in a profile of its own. Names other than
+    /// lowercase letters, digits and dashes, and repeats, are skipped.
+    pub fn with_whatsapp_accounts(mut self, accounts: &[String]) -> Self {
+        let Some(at) = self.defs.iter().position(|d| d.id == crate::whatsapp::ORGAN) else { return self };
+        let mut added: Vec<OrganDef> = Vec::new();
+        for account in accounts {
+            let id = crate::whatsapp::organ_id(Some(account));
+            let valid = (1..=32).contains(&account.len())
+                && account.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+                && !account.starts_with('-');
+            if !valid || self.get(&id).is_some() || added.iter().any(|d| d.id == id) {
+                tracing::warn!("WhatsApp account '{account}' skipped: bad or repeated name");
+                continue;
+            }
+            let title = format!("WhatsApp ({account})");
+            added.push(OrganDef { id, title, account: Some(account.clone()), ..self.defs[at].clone() });
+        }
+        let rest = self.defs.split_off(at + 1);
+        self.defs.extend(added);
+        self.defs.extend(rest);
+        self
+    }
+
+    pub fn defs(&self) -> &[OrganDef] {
+        &self.defs
+    }
+
+    pub fn get(&self, id: &str) -> Option<&OrganDef> {
+        self.defs.iter().find(|d| d.id == id)
+    }
+}
+
+/// Window label for an organ id.
+pub fn window_label(id: &str) -> String {
+    format!("{id}-organ")
+}
+
+/// Organ id for a window label, if it is an organ's.
+pub fn id_from_label(label: &str) -> Option<&str> {
+    label.strip_suffix("-organ")
+}
+
+/// "closed" | "visible" | "background"
+/// A window still warming up, or off screen when headless, is up but
+/// counts as background.
+pub fn status(app: &tauri::AppHandle, id: &str) -> &'static str {
+    match app.get_webview_window(&window_label(id)) {
+        None => "closed",
+        Some(w) if w.is_visible().unwrap_or(false) && !warming(id) && !crate::headless::enabled() => "visible",
+        Some(_) => "background",
+    }
+}
+
+fn warming(id: &str) -> bool {
+    WARMING.lock().unwrap_or_else(|e| e.into_inner()).contains(id)
+}
+
+/// Whether the organ's window is being built or warmed up.
+pub fn settling(id: &str) -> bool {
+    warming(id) || CREATING.lock().unwrap_or_else(|e| e.into_inner()).contains_key(id)
+}
+
+fn heard(id: &str) {
+    HEARD.lock().unwrap_or_else(|e| e.into_inner()).insert(id.to_string(), Instant::now());
+}
+
+/// When the organ's page last reported; `None` if it hasn't since its
+/// window was built.
+pub fn last_heard(id: &str) -> Option<Instant> {
+    HEARD.lock().unwrap_or_else(|e| e.into_inner()).get(id).copied()
+}
+
+/// Whether the organ reported a signed-in session; unknown until its
+/// page has loaded once.
+pub fn signed_in(id: &str) -> Option<bool> {
+    SIGNED_IN.lock().unwrap_or_else(|e| e.into_inner()).get(id).copied()
+}
+
+/// Ask the organ's page whether it is signed in; `signed_in` is unknown
+/// until it answers. See `REPORT_HOST`.
+pub fn probe_session(app: &tauri::AppHandle, id: &str) {
+    let manager = app.state::<OrganManager>();
+    let Some(probe) = manager.get(id).and_then(|def| def.session_probe.as_deref()) else { return };
+    let Some(window) = app.get_webview_window(&window_label(id)) else { return };
+    SIGNED_IN.lock().unwrap_or_else(|e| e.into_inner()).remove(id);
+    let _ = window.eval(format!(
+        "location.href = 'https://{REPORT_HOST}/session?signed_in=' + (({probe}) ? 1 : 0);"
+    ));
+}
+
+/// Report the organ's status whenever its page changes, at most once a
+/// second, and every `HEARTBEAT` regardless; the relay drops repeats.
+/// Installed on every page load.
+pub fn watch_status(app: &tauri::AppHandle, id: &str) {
+    let manager = app.state::<OrganManager>();
+    let Some(probe) = manager.get(id).and_then(|def| def.status_probe.as_deref()) else { return };
+    let Some(window) = app.get_webview_window(&window_label(id)) else { return };
+    let _ = window.eval(format!(
+        r#"(function () {{
+  if (window.__lexiconStatus) return;
+  window.__lexiconStatus = true;
+  var timer = null;
+  function report() {{
+    if (timer) return;
+    timer = setTimeout(function () {{
+      timer = null;
+      location.href = 'https://{REPORT_HOST}/status?value=' + encodeURIComponent({probe});
+    }}, 1000);
+  }}
+  new MutationObserver(report).observe(document.documentElement, {{ childList: true, subtree: true }});
+  setInterval(report, {heartbeat});
+  report();
+}})();"#,
+        heartbeat = HEARTBEAT.as_millis()
+    ));
+}
+
+/// Most reports a monitor holds back before it drops new ones.
+const REPORT_QUEUE: usize = 50;
+
+/// The page script for a message monitor: installed once per page
+/// (`window[flag]` marks it in), `scan` runs with `report(kind, value)`
+/// in scope, which queues `value` for `https://REPORT_HOST/{kind}`. The
+/// queue goes out one report every 250ms. `digest(text)` is there too, a
+/// short hash for ids made of text that may be longer than `clean` keeps.
+fn monitor_script(flag: &str, scan: &str) -> String {
+    format!(
+        r#"(function () {{
+  if (window.{flag}) return;
+  window.{flag} = true;
+  var queue = [];
+  function report(kind, value) {{
+    if (queue.length < {REPORT_QUEUE}) queue.push([kind, value]);
+  }}
+  function digest(text) {{
+    var hash = 0x811c9dc5;
+    for (var i = 0; i < text.length; i++) hash = Math.imul(hash ^ text.charCodeAt(i), 0x01000193) >>> 0;
+    return hash.toString(16);
+  }}
+  setInterval(function () {{
+    var next = queue.shift();
+    if (next) location.href = 'https://{REPORT_HOST}/' + next[0] + '?value=' + encodeURIComponent(JSON.stringify(next[1]));
+  }}, 250);{scan}
+}})();"#
+    )
+}
+
+/// Install message monitor `scan` (see `monitor_script`) in organ `id`'s
+/// page, if it is a login to `service`.
+pub fn monitor(app: &tauri::AppHandle, id: &str, service: &str, flag: &str, scan: &str) {
+    let manager = app.state::<OrganManager>();
+    if manager.get(id).is_none_or(|def| def.service() != service) {
+        return;
+    }
+    if let Some(window) = app.get_webview_window(&window_label(id)) {
+        let _ = window.eval(monitor_script(flag, scan));
+    }
+}
+
+/// The account organ `id` is a login of, for what its monitor reports.
+pub fn account(app: &tauri::AppHandle, id: &str) -> Option<String> {
+    app.state::<OrganManager>().get(id).and_then(|def| def.account.clone())
+}
+
+/// Text a monitor reported, as relayed: control characters other than
+/// line breaks dropped, trimmed and cut to `max` characters. None if
+/// nothing is left.
+pub fn clean(raw: &str, max: usize) -> Option<String> {
+    let text: String = raw.chars().filter(|c| *c == '\n' || !c.is_control()).collect();
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.chars().take(max).collect())
+}
+
+/// Unix milliseconds, the `timestamp` a relayed message gets.
+pub fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Run the page-load scripts (theme, session probe, status watch, actions,
+/// for WhatsApp the QR watch, and the message monitors for WhatsApp,
+/// Telegram, email and Discord) if the organ's window is on one of its own
+/// hosts. Each guards against installing twice, so running them again on
+/// a page that has them is harmless. False if the page is elsewhere.
+pub fn inject(app: &tauri::AppHandle, id: &str) -> bool {
+    let Some(window) = app.get_webview_window(&window_label(id)) else { return false };
+    let manager = app.state::<OrganManager>();
+    let Some(def) = manager.get(id) else { return false };
+    match window.url() {
+        Ok(url) if def.owns(&url) => {}
+        Ok(url) => {
+            tracing::info!("organ {id} loaded {} — not injecting", url.host_str().unwrap_or(url.scheme()));
+            return false;
+        }
+        Err(_) => return false,
+    }
+    crate::theme::apply_to_organ(app, id);
+    probe_session(app, id);
+    watch_status(app, id);
+    crate::actions::inject(app, id);
+    crate::whatsapp::watch(app, id);
+    crate::telegram::watch(app, id);
+    crate::email::watch(app, id);
+    crate::discord::watch(app, id);
+    if id == crate::whatsapp::ORGAN {
+        crate::qr::watch(app);
+    }
+    true
+}
+
+/// Run a script in the organ's page and wait for its answer. `script`
+/// gets a JS function expression to call with a string when it is done.
+/// Tokens are random and only the organ asked can answer, so a page can't
+/// answer a question it wasn't asked.
+pub fn ask(app: &tauri::AppHandle, id: &str, script: impl FnOnce(&str) -> String, timeout: Duration) -> Result<String, String> {
+    let window = app.get_webview_window(&window_label(id)).ok_or_else(|| format!("{id} is not open"))?;
+    let (token, answered) = ASKED.lock().unwrap_or_else(|e| e.into_inner()).ask(id);
+    let reply = format!(
+        "function (value) {{ location.href = 'https://{REPORT_HOST}/reply?token={token}&value=' + encodeURIComponent(value); }}"
+    );
+    let result = window
+        .eval(script(&reply))
+        .map_err(|e| e.to_string())
+        .and_then(|()| answered.recv_timeout(timeout).map_err(|_| format!("{id} did not answer")));
+    ASKED.lock().unwrap_or_else(|e| e.into_inner()).forget(token);
+    result
+}
+
+/// Decide on a navigation inside an organ: its own hosts load, reports
+/// are recorded and everything else goes to the system browser. False
+/// cancels it.
+#[cfg_attr(mobile, allow(dead_code))]
+fn on_navigation(app: &tauri::AppHandle, id: &str, url: &tauri::Url) -> bool {
+    if url.host_str() == Some(REPORT_HOST) {
+        heard(id);
+        if url.path() == "/session" {
+            let signed_in = url.query_pairs().any(|(k, v)| k == "signed_in" && v == "1");
+            SIGNED_IN.lock().unwrap_or_else(|e| e.into_inner()).insert(id.to_string(), signed_in);
+        }
+        if url.path() == "/status" {
+            if let Some((_, status)) = url.query_pairs().find(|(k, _)| k == "value") {
+                crate::relay::report(app, id, &status);
+            }
+            // Its scripts run; it can go to the background now.
+            end_warm(app, id, true);
+        }
+        if url.path() == "/qr" && id == crate::whatsapp::ORGAN {
+            if let Some((_, data_url)) = url.query_pairs().find(|(k, _)| k == "value") {
+                crate::qr::reported(app, &data_url);
+            }
+        }
+        if url.path() == "/message" {
+            let service = app.state::<OrganManager>().get(id).map(|def| def.service().to_string());
+            if let Some((_, message)) = url.query_pairs().find(|(k, _)| k == "value") {
+                match service.as_deref() {
+                    Some(crate::whatsapp::ORGAN) => crate::whatsapp::reported(app, id, &message),
+                    Some(crate::telegram::ORGAN) => crate::telegram::reported(app, id, &message),
+                    Some(crate::email::ORGAN) => crate::email::reported(app, id, &message),
+                    Some(crate::discord::ORGAN) => crate::discord::reported(app, id, &message),
+                    _ => {}
+                }
+            }
+        }
+        if url.path() == "/media" {
+            let service = app.state::<OrganManager>().get(id).map(|def| def.service().to_string());
+            if let Some((_, media)) = url.query_pairs().find(|(k, _)| k == "value") {
+                if service.as_deref() == Some(crate::whatsapp::ORGAN) {
+                    crate::media::reported(app, id, &media);
+                }
+            }
+        }
+        if url.path() == "/reply" {
+            let param = |key: &str| url.query_pairs().find(|(k, _)| k == key).map(|(_, v)| v.into_owned());
+            if let Some(token) = param("token").and_then(|t| t.parse::<u64>().ok()) {
+                let value = param("value").unwrap_or_default();
+                ASKED.lock().unwrap_or_else(|e| e.into_inner()).answer(id, token, value);
+            }
+        }
+        return false;
+    }
+    let manager = app.state::<OrganManager>();
+    let Some(def) = manager.get(id) else { return false };
+    if def.owns(url) || matches!(url.scheme(), "about" | "blob" | "data") {
+        return true;
+    }
+    crate::external::request_detached(app, url, id);
+    false
+}
+
+pub fn announce(app: &tauri::AppHandle, id: &str) {
+    let _ = app.emit("organ://changed", id);
+    crate::transition::changed(app);
+}
+
+/// Organ `id`'s page finished loading: inject it and, if it is on its own
+/// hosts, say so.
+pub fn loaded(app: &tauri::AppHandle, id: &str) {
+    if inject(app, id) {
+        let _ = app.emit("organ://loaded", id);
+    }
+}
+
+/// Web apps put their unread count in the page title — "(3) WhatsApp".
+fn unread_from_title(title: &str) -> u32 {
+    title
+        .trim_start()
+        .strip_prefix('(')
+        .and_then(|rest| rest.split_once(')'))
+        .and_then(|(n, _)| n.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+fn report_badge(app: &tauri::AppHandle, id: &str, count: u32) {
+    let _ = app.emit("organ://badge", OrganBadge { id: id.to_string(), count });
+}
+
+fn profile_dir(app: &tauri::AppHandle, profile: &str) -> Option<std::path::PathBuf> {
+    app.path().app_data_dir().ok().map(|dir| dir.join("organs").join(profile))
+}
+
+/// WKWebView has no data directory; profiles are keyed by a fixed id
+/// instead. FNV-1a, twice with different offsets, so it is stable across
+/// builds.
+#[cfg(target_os = "macos")]
+fn data_store_id(profile: &str) -> [u8; 16] {
+    let fnv = |offset: u64| {
+        profile.bytes().fold(offset, |hash, b| (hash ^ u64::from(b)).wrapping_mul(0x100_0000_01b3))
+    };
+    let mut id = [0; 16];
+    id[..8].copy_from_slice(&fnv(0xcbf2_9ce4_8422_2325).to_le_bytes());
+    id[8..].copy_from_slice(&fnv(0x8422_2325_cbf2_9ce4).to_le_bytes());
+    id
+}
+
+/// Delete an organ's profile, and with it its session. Refused while any
+/// organ using the profile has a window or one is being built: the engine
+/// holds the files open and would write them straight back.
+pub async fn clear_session(app: &tauri::AppHandle, id: &str) -> Result<(), String> {
+    let manager = app.state::<OrganManager>();
+    let def = manager.get(id).ok_or_else(|| format!("unknown organ: {id}"))?;
+    let profile = def.profile();
+    let busy = {
+        let creating = CREATING.lock().unwrap_or_else(|e| e.into_inner());
+        manager
+            .defs()
+            .iter()
+            .filter(|d| d.profile() == profile)
+            .find(|d| status(app, &d.id) != "closed" || creating.contains_key(&d.id))
+            .map(|d| d.title.clone())
+    };
+    if let Some(title) = busy {
+        return Err(format!("close {title} first — its window is using the profile"));
+    }
+    #[cfg(target_os = "macos")]
+    app.remove_data_store(data_store_id(&profile)).await.map_err(|e| format!("could not remove the {id} data store: {e}"))?;
+    if let Some(dir) = profile_dir(app, &profile) {
+        match std::fs::remove_dir_all(&dir) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                return Err(format!("could not remove {}: {e}", dir.display()));
+            }
+            _ => {}
+        }
+    }
+    SIGNED_IN.lock().unwrap_or_else(|e| e.into_inner()).remove(id);
+    tracing::info!("organ {id} session cleared");
+    Ok(())
+}
+
+/// Create an organ's window: hidden, or for a warm-up shown beneath
+/// everything else without taking focus.
+#[cfg(desktop)]
+fn create(app: &tauri::AppHandle, id: &str, def: &OrganDef) -> Result<tauri::WebviewWindow, String> {
+    let warming = warming(id);
+    let label = window_label(id);
+    let ordinary = crate::layout::ordinary(app, &label);
+    let url = def.url.parse().map_err(|e| format!("bad organ url {}: {e}", def.url))?;
+    let title_handle = app.clone();
+    let title_id = id.to_string();
+    let (navigation_handle, navigation_id) = (app.clone(), id.to_string());
+    let (popup_handle, popup_id) = (app.clone(), id.to_string());
+    let mut builder = tauri::WebviewWindowBuilder::new(app, &label, tauri::WebviewUrl::External(url));
+    // Same directory, same data context: see the module docs.
+    if let Some(dir) = profile_dir(app, &def.profile()) {
+        builder = builder.data_directory(dir);
+    }
+    #[cfg(target_os = "macos")]
+    {
+        builder = builder.data_store_identifier(data_store_id(&def.profile()));
+    }
+    if let Some(proxy) = crate::proxy::webview_proxy(app) {
+        builder = builder.proxy_url(proxy);
+    }
+    // Up, so its scripts run, but where nobody sees it.
+    let headless = crate::headless::enabled();
+    if headless {
+        let ((x, y), (width, height)) = (crate::headless::POSITION, crate::headless::SIZE);
+        builder = builder.position(x, y).inner_size(width, height);
+    }
+    let window = builder
+        .title(&def.title)
+        .on_navigation(move |url| on_navigation(&navigation_handle, &navigation_id, url))
+        .on_new_window(move |url, _| {
+            crate::external::request_detached(&popup_handle, &url, &popup_id);
+            tauri::webview::NewWindowResponse::Deny
+        })
+        .on_document_title_changed(move |_, title| {
+            report_badge(&title_handle, &title_id, unread_from_title(&title));
+        })
+        .decorations(ordinary && !headless)
+        .skip_taskbar(!ordinary || headless)
+        .visible(warming || headless)
+        .focused(!warming && !headless)
+        .always_on_bottom(warming || headless)
+        .build()
+        .map_err(|e| format!("failed to create {label}: {e}"))?;
+    if ordinary && !headless {
+        crate::window_state::put_back(&window);
+    }
+
+    let handle = app.clone();
+    let organ_id = id.to_string();
+    window.on_window_event(move |event| match event {
+        tauri::WindowEvent::Focused(focused) => crate::shortcuts::organ_focused(&handle, &organ_id, *focused),
+        tauri::WindowEvent::Moved(_) | tauri::WindowEvent::Resized(_) => crate::window_state::remember(&handle, &organ_id),
+        tauri::WindowEvent::CloseRequested { api, .. } => {
+            // To the background, as `show_organ(id, false)`; `close_organ`
+            // is what closes one.
+            api.prevent_close();
+            let (app, id) = (handle.clone(), organ_id.clone());
+            tauri::async_runtime::spawn_blocking(move || {
+                if let Err(e) = hide(&app, &id) {
+                    tracing::warn!("could not hide {id} on close: {e}");
+                }
+            });
+        }
+        tauri::WindowEvent::Destroyed => {
+            HEARD.lock().unwrap_or_else(|e| e.into_inner()).remove(&organ_id);
+            crate::shortcuts::organ_focused(&handle, &organ_id, false);
+            report_badge(&handle, &organ_id, 0);
+            announce(&handle, &organ_id);
+        }
+        _ => {}
+    });
+    tracing::info!("organ {id} created");
+    Ok(window)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Opening {
+    /// The window existed and is shown.
+    Open,
+    /// The window is being built; `organ-created` follows.
+    Creating,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct CreateFailed {
+    id: String,
+    error: String,
+}
+
+/// How often a switch checks whether the outgoing windows have gone.
+const SETTLE_POLL: Duration = Duration::from_millis(16);
+
+/// Held for the whole of a switch, so two rapid ones queue up instead of
+/// interleaving half-finished.
+static SWITCHING: Mutex<()> = Mutex::new(());
+
+/// Whether the caller waits for a switch or lets it finish on a thread of
+/// its own. The tray, shortcuts and CLI run on the main thread, which the
+/// compositor round trip needs, so they never wait.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Switch {
+    Wait,
+    Detach,
+}
+
+/// Make the window `label` the one on screen: take the canvas and other
+/// organs down, wait until they are actually hidden, then promote it.
+/// In the split layout the canvas stays, or comes up, beside an organ.
+/// Promoting early is what leaves two fullscreen surfaces fighting on
+/// some compositors. Past `organs.switch_settle_ms` it promotes anyway
+/// and reports the windows that never went; a compositor too slow for the
+/// default wants it raised, and 0 doesn't wait at all. Refused while the
+/// presentation guard is on.
+fn switch_blocking(app: &tauri::AppHandle, label: &str) -> Result<(), String> {
+    crate::presentation::refuse(app, &format!("showing {label}"))?;
+    let _turn = SWITCHING.lock().unwrap_or_else(|e| e.into_inner());
+    let window = app.get_webview_window(label).ok_or_else(|| format!("window {label} is gone"))?;
+    let manager = app.state::<OrganManager>();
+    let outgoing: Vec<_> = std::iter::once("main".to_string())
+        .chain(manager.defs().iter().map(|d| window_label(&d.id)))
+        .filter(|other| other != label && !beside(app, label, other))
+        .filter_map(|other| app.get_webview_window(&other))
+        .filter(|other| other.is_visible().unwrap_or(false))
+        .collect();
+    for other in &outgoing {
+        crate::hide_window(other);
+        if let Some(id) = id_from_label(other.label()) {
+            announce(app, id);
+        }
+    }
+
+    let settle = Duration::from_millis(crate::config::current(app).organs.switch_settle_ms);
+    let deadline = Instant::now() + settle;
+    let mut lingering: Vec<_> = outgoing.iter().filter(|w| w.is_visible().unwrap_or(false)).collect();
+    while !lingering.is_empty() && Instant::now() < deadline {
+        std::thread::sleep(SETTLE_POLL);
+        lingering.retain(|w| w.is_visible().unwrap_or(false));
+    }
+    #[cfg(desktop)]
+    crate::layout::bring_canvas(app, label);
+    if let Some(id) = id_from_label(label) {
+        end_warm(app, id, false);
+    }
+    crate::show_window(&window);
+    tracing::info!("switched to {label}");
+    app.state::<crate::metrics::Metrics>().switched();
+    if let Some(id) = id_from_label(label) {
+        announce(app, id);
+    }
+    #[cfg(desktop)]
+    crate::window_state::save(app);
+    if lingering.is_empty() {
+        return Ok(());
+    }
+    let labels: Vec<_> = lingering.iter().map(|w| w.label()).collect();
+    Err(format!(
+        "switched to {label}, but {} still showed after {}ms",
+        labels.join(", "),
+        settle.as_millis()
+    ))
+}
+
+/// Whether windows `a` and `b` can be on screen together: panes of the
+/// split layout, or on monitors of their own.
+fn beside(app: &tauri::AppHandle, a: &str, b: &str) -> bool {
+    #[cfg(desktop)]
+    return crate::layout::beside(app, a, b) || crate::monitors::apart(app, a, b);
+    #[cfg(mobile)]
+    {
+        let _ = (app, a, b);
+        false
+    }
+}
+
+/// Whether organ `id`, on screen, is where the canvas would be: fullscreen
+/// on the canvas's monitor, rather than an ordinary window or elsewhere.
+pub fn covers_canvas(app: &tauri::AppHandle, id: &str) -> bool {
+    let label = window_label(id);
+    #[cfg(desktop)]
+    return !crate::layout::ordinary(app, &label) && !crate::monitors::apart(app, "main", &label);
+    #[cfg(mobile)]
+    {
+        let _ = (app, label);
+        true
+    }
+}
+
+fn windowed() -> bool {
+    #[cfg(desktop)]
+    return crate::layout::display() == crate::layout::DisplayMode::Windowed;
+    #[cfg(mobile)]
+    false
+}
+
+fn switch(app: &tauri::AppHandle, label: &str, how: Switch) -> Result<(), String> {
+    if how == Switch::Wait {
+        return switch_blocking(app, label);
+    }
+    let app = app.clone();
+    let label = label.to_string();
+    tauri::async_runtime::spawn_blocking(move || {
+        if let Err(e) = switch_blocking(&app, &label) {
+            tracing::warn!("{e}");
+        }
+    });
+    Ok(())
+}
+
+/// Make `label` the window on screen, in the background; see
+/// `switch_blocking`.
+#[cfg(desktop)]
+pub fn bring_forward(app: &tauri::AppHandle, label: &str) -> Result<(), String> {
+    switch(app, label, Switch::Detach)
+}
+
+/// Back to the canvas, if this organ was the window on screen in its
+/// place (see `covers_canvas`). Windowed, the canvas is left where it is.
+fn step_back(app: &tauri::AppHandle, was_visible: bool, how: Switch) -> Result<(), String> {
+    if was_visible && !windowed() {
+        switch(app, "main", how)
+    } else {
+        Ok(())
+    }
+}
+
+fn known(app: &tauri::AppHandle, id: &str) -> Result<(), String> {
+    match app.state::<OrganManager>().get(id) {
+        Some(_) => Ok(()),
+        None => Err(format!("unknown organ: {id}")),
+    }
+}
+
+/// Build an organ's window on a thread of its own. While one build is in
+/// flight, further calls join it; if any of them asks to `show`, the
+/// window is shown when it is ready. Mobile has the one webview, so
+/// organs get no window there.
+fn spawn_create(app: &tauri::AppHandle, id: &str, show: bool) -> Result<(), String> {
+    #[cfg(mobile)]
+    {
+        let _ = (app, id, show);
+        Err(crate::platform::NotSupportedOnPlatform::new("organ windows").into())
+    }
+    #[cfg(desktop)]
+    {
+        {
+            let mut creating = CREATING.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(pending) = creating.get_mut(id) {
+                *pending |= show;
+                return Ok(());
+            }
+            creating.insert(id.to_string(), show);
+        }
+        crate::idle::resuming(app, id);
+        let app = app.clone();
+        let id = id.to_string();
+        std::thread::spawn(move || {
+            let manager = app.state::<OrganManager>();
+            let result = match manager.get(&id) {
+                Some(def) => create(&app, &id, def),
+                None => Err(format!("unknown organ: {id}")),
+            };
+            let show = CREATING.lock().unwrap_or_else(|e| e.into_inner()).remove(&id).unwrap_or(false);
+            CREATED.notify_all();
+            match result {
+                Ok(window) => {
+                    if show {
+                        // Already off the caller's thread.
+                        if let Err(e) = switch(&app, window.label(), Switch::Wait) {
+                            tracing::warn!("{e}");
+                        }
+                    } else {
+                        announce(&app, &id);
+                    }
+                    let _ = app.emit("organ-created", &id);
+                }
+                Err(error) => {
+                    tracing::warn!("organ {id}: {error}");
+                    let _ = app.emit("organ-create-failed", CreateFailed { id, error });
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+/// Bring an organ to the front, building its window first if needed.
+/// Never waits for the build, nor for the switch. Refused while the
+/// presentation guard is on.
+pub fn open(app: &tauri::AppHandle, id: &str) -> Result<Opening, String> {
+    open_as(app, id, Switch::Detach)
+}
+
+fn open_as(app: &tauri::AppHandle, id: &str, how: Switch) -> Result<Opening, String> {
+    known(app, id)?;
+    crate::presentation::refuse(app, &format!("opening {id}"))?;
+    match app.get_webview_window(&window_label(id)) {
+        Some(window) => switch(app, window.label(), how).map(|()| Opening::Open),
+        None => spawn_create(app, id, true).map(|()| Opening::Creating),
+    }
+}
+
+/// Like `preload`, but for when the page has to run from the start, such
+/// as relaying at login. Some compositors (GNOME on Wayland) don't run a
+/// hidden window's scripts, so the window goes up, unfocused and below
+/// every other window, until the page reports its first status (or
+/// `WARM_TIMEOUT` passes), and then hides. An `open` meanwhile simply
+/// promotes it.
+pub fn warm(app: &tauri::AppHandle, id: &str) -> Result<(), String> {
+    known(app, id)?;
+    if app.get_webview_window(&window_label(id)).is_some() {
+        return Ok(());
+    }
+    WARMING.lock().unwrap_or_else(|e| e.into_inner()).insert(id.to_string());
+    if let Err(e) = spawn_create(app, id, false) {
+        WARMING.lock().unwrap_or_else(|e| e.into_inner()).remove(id);
+        return Err(e);
+    }
+    let (app, id) = (app.clone(), id.to_string());
+    std::thread::spawn(move || {
+        std::thread::sleep(WARM_TIMEOUT);
+        let handle = app.clone();
+        let _ = app.run_on_main_thread(move || end_warm(&handle, &id, true));
+    });
+    Ok(())
+}
+
+/// The warm-up is over: the window leaves the bottom layer and, unless it
+/// was promoted meanwhile, hides.
+fn end_warm(app: &tauri::AppHandle, id: &str, hide: bool) {
+    if !WARMING.lock().unwrap_or_else(|e| e.into_inner()).remove(id) {
+        return;
+    }
+    let Some(window) = app.get_webview_window(&window_label(id)) else { return };
+    #[cfg(desktop)]
+    let _ = window.set_always_on_bottom(false);
+    if hide {
+        crate::hide_window(&window);
+        announce(app, id);
+        tracing::info!("organ {id} warmed up");
+    }
+}
+
+/// Build an organ's window in the background, if it isn't open yet.
+pub fn preload(app: &tauri::AppHandle, id: &str) -> Result<(), String> {
+    known(app, id)?;
+    if app.get_webview_window(&window_label(id)).is_none() {
+        spawn_create(app, id, false)?;
+    }
+    Ok(())
+}
+
+/// Block until the organ's window exists, for at most `timeout`. Fails
+/// straight away if it is neither open nor being built. Not for the main
+/// thread: building may need it.
+pub fn wait_ready(app: &tauri::AppHandle, id: &str, timeout: Duration) -> Result<(), String> {
+    let deadline = Instant::now() + timeout;
+    let mut creating = CREATING.lock().unwrap_or_else(|e| e.into_inner());
+    loop {
+        if !creating.contains_key(id) {
+            return match app.get_webview_window(&window_label(id)) {
+                Some(_) => Ok(()),
+                None => Err(format!("organ {id} is not open")),
+            };
+        }
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(format!("organ {id} is still being created"));
+        }
+        creating = CREATED.wait_timeout(creating, left).unwrap_or_else(|e| e.into_inner()).0;
+    }
+}
+
+/// Destroy an organ's window. Closing one that isn't open is fine.
+pub fn close(app: &tauri::AppHandle, id: &str) -> Result<(), String> {
+    close_as(app, id, Switch::Detach)
+}
+
+#[cfg_attr(mobile, allow(unused_variables))]
+fn close_as(app: &tauri::AppHandle, id: &str, how: Switch) -> Result<(), String> {
+    known(app, id)?;
+    crate::idle::forget(id);
+    #[cfg(desktop)]
+    if let Some(window) = app.get_webview_window(&window_label(id)) {
+        let in_place = window.is_visible().unwrap_or(false) && covers_canvas(app, id);
+        window.destroy().map_err(|e| format!("failed to close {id}: {e}"))?;
+        tracing::info!("organ {id} closed");
+        step_back(app, in_place, how)?;
+    }
+    Ok(())
+}
+
+/// Send an organ to the background; it keeps running. Hiding one that
+/// isn't open is fine. Waits for the switch back, so keep it off the main
+/// thread.
+pub fn hide(app: &tauri::AppHandle, id: &str) -> Result<(), String> {
+    hide_as(app, id, Switch::Wait)
+}
+
+fn hide_as(app: &tauri::AppHandle, id: &str, how: Switch) -> Result<(), String> {
+    known(app, id)?;
+    if let Some(window) = app.get_webview_window(&window_label(id)) {
+        let in_place = window.is_visible().unwrap_or(false) && covers_canvas(app, id);
+        crate::hide_window(&window);
+        announce(app, id);
+        step_back(app, in_place, how)?;
+    }
+    Ok(())
+}
+
+/// The organ whose window is showing, if any.
+#[cfg(desktop)]
+pub fn on_screen(app: &tauri::AppHandle) -> Option<String> {
+    let manager = app.state::<OrganManager>();
+    manager
+        .defs()
+        .iter()
+        .map(|d| d.id.clone())
+        .find(|id| status(app, id) == "visible")
+}
+
+/// Hide whichever organ is on screen, as `show_organ(id, false)` would.
+/// Presses that arrive while one is still going are ignored, so a double
+/// Escape doesn't run the switch twice.
+#[cfg(desktop)]
+pub fn dismiss(app: &tauri::AppHandle) {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    static DISMISSING: AtomicBool = AtomicBool::new(false);
+    let Some(id) = on_screen(app) else { return };
+    if DISMISSING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        if let Err(e) = hide_as(&app, &id, Switch::Wait) {
+            tracing::warn!("dismiss {id}: {e}");
+        }
+        // The blur can lag the hide; don't leave Escape held meanwhile.
+        let handle = app.clone();
+        let _ = app.run_on_main_thread(move || crate::shortcuts::organ_focused(&handle, &id, false));
+        DISMISSING.store(false, Ordering::SeqCst);
+    });
+}
+
+/// [`status`] of a registered organ, with a closed organ that was
+/// suspended (see `idle`) as "suspended".
+pub fn status_of(app: &tauri::AppHandle, id: &str) -> Result<&'static str, String> {
+    known(app, id).map(|()| match status(app, id) {
+        "closed" if crate::idle::suspended(id) => "suspended",
+        status => status,
+    })
+}
+
+/// Whether the organ holds a session, from the statuses it reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Login {
+    LoggedIn,
+    /// Signed out; for WhatsApp, showing the QR code.
+    QrPending,
+    Unknown,
+}
+
+/// The last relayed status says; while it says nothing either way
+/// (connecting, disconnected) the session probe's answer stands.
+fn login(last_status: Option<&Lifecycle>, signed_in: Option<bool>) -> Login {
+    match (last_status, signed_in) {
+        (Some(Lifecycle::LoggedIn), _) => Login::LoggedIn,
+        (Some(Lifecycle::QrPending), _) => Login::QrPending,
+        (_, Some(true)) => Login::LoggedIn,
+        (_, Some(false)) => Login::QrPending,
+        (_, None) => Login::Unknown,
+    }
+}
+
+/// Everything the sidebar shows for an organ.
+#[derive(Debug, Clone, Serialize)]
+pub struct OrganState {
+    /// "closed" | "visible" | "background" | "suspended", as `organ_status`.
+    pub window: &'static str,
+    pub login: Login,
+    /// The last status relayed to the Brain.
+    pub status: Option<Lifecycle>,
+    /// Unix milliseconds it was relayed.
+    pub last_relayed_at: Option<u64>,
+    /// Status updates relayed this session.
+    pub relayed: u64,
+    /// Messages relayed this session.
+    pub messages: u64,
+    /// Unix milliseconds the last one was relayed.
+    pub last_message_at: Option<u64>,
+    /// POSTs for this organ the Brain never took, after retries.
+    pub failed_posts: u64,
+}
+
+pub fn state(app: &tauri::AppHandle, id: &str) -> Result<OrganState, String> {
+    let window = status_of(app, id)?;
+    let (metrics, failed_posts) = crate::relay::organ_metrics(app, id);
+    Ok(OrganState {
+        window,
+        login: login(metrics.last_status.as_ref(), signed_in(id)),
+        status: metrics.last_status,
+        last_relayed_at: metrics.last_relayed_at,
+        relayed: metrics.relayed,
+        messages: metrics.messages,
+        last_message_at: metrics.last_message_at,
+        failed_posts,
+    })
+}
+
+/// Resolves once the organ is on screen, or is being built (see
+/// `Opening`). Fails if the switch timed out.
+///
+/// Each of these is a transition (see `transition`) that waits for its
+/// switch, and answers with the screen after it. An organ being built is
+/// announced as `organ-created` once it is up.
+#[tauri::command]
+pub async fn open_organ(app: tauri::AppHandle, id: String) -> Result<Screen, String> {
+    crate::transition::run(app, move |app| open_as(app, &id, Switch::Wait).map(|_| ())).await
+}
+
+/// Show (as with `open_organ`) or hide an organ.
+#[tauri::command]
+pub async fn show_organ(app: tauri::AppHandle, id: String, visible: bool) -> Result<Screen, String> {
+    crate::transition::run(app, move |app| {
+        if visible {
+            open_as(app, &id, Switch::Wait).map(|_| ())
+        } else {
+            hide_as(app, &id, Switch::Wait)
+        }
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn close_organ(app: tauri::AppHandle, id: String) -> Result<Screen, String> {
+    crate::transition::run(app, move |app| close_as(app, &id, Switch::Wait)).await
+}
+
+/// "closed" | "visible" | "background" | "suspended". `organ_state` has
+/// the rest.
+#[tauri::command]
+pub fn organ_status(app: tauri::AppHandle, id: String) -> Result<&'static str, String> {
+    status_of(&app, &id)
+}
+
+#[tauri::command]
+pub fn organ_state(app: tauri::AppHandle, id: String) -> Result<OrganState, String> {
+    state(&app, &id)
+}
+
+/// Wait for `open_organ`'s window to be built.
+#[tauri::command]
+pub async fn wait_for_organ(app: tauri::AppHandle, id: String, timeout_ms: u64) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || wait_ready(&app, &id, Duration::from_millis(timeout_ms)))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileResources {
+    /// The organ id, or the `share_cache_group` name.
+    pub profile: String,
+    pub shared: bool,
+    pub organs: Vec<String>,
+    pub open: usize,
+    /// Cookies, storage and caches on disk.
+    pub disk_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OrganResources {
+    /// Resident memory of Lexicon and every process it started.
+    pub total_memory_bytes: Option<u64>,
+    pub profiles: Vec<ProfileResources>,
+}
+
+fn disk_usage(path: &std::path::Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else { return 0 };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(t) if t.is_dir() => disk_usage(&entry.path()),
+            Ok(_) => entry.metadata().map(|m| m.len()).unwrap_or(0),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+/// Resident memory of this process and everything it started (the
+/// engine's web and network processes), from `/proc`.
+#[cfg(target_os = "linux")]
+pub fn tree_memory() -> u64 {
+    let read = |pid: &str, file: &str| std::fs::read_to_string(format!("/proc/{pid}/{file}")).ok();
+    let rss = |pid: &str| {
+        read(pid, "status")
+            .and_then(|s| s.lines().find_map(|l| l.strip_prefix("VmRSS:")).map(String::from))
+            .and_then(|kb| kb.trim().trim_end_matches("kB").trim().parse::<u64>().ok())
+            .map_or(0, |kb| kb * 1024)
+    };
+    let parents: Vec<(String, String)> = std::fs::read_dir("/proc")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let pid = entry.file_name().into_string().ok().filter(|p| p.chars().all(|c| c.is_ascii_digit()))?;
+            // The command name may hold spaces and parens; fields follow the last ')'.
+            let stat = read(&pid, "stat")?;
+            let ppid = stat.rsplit_once(')')?.1.split_whitespace().nth(1)?.to_string();
+            Some((pid, ppid))
+        })
+        .collect();
+    let own = std::process::id().to_string();
+    let mut total = rss(&own);
+    let mut tree = vec![own];
+    while let Some(parent) = tree.pop() {
+        for (pid, _) in parents.iter().filter(|(_, ppid)| *ppid == parent) {
+            total += rss(pid);
+            tree.push(pid.clone());
+        }
+    }
+    total
+}
+
+/// Memory and disk use of the organ profiles. Neither WebKitGTK nor
+/// WebView2 says which engine process serves which data context, so
+/// memory is the total for the whole process tree (Linux only); compare
+/// it with and without a shared group to see the saving.
+#[tauri::command]
+pub fn get_organ_resources(app: tauri::AppHandle) -> OrganResources {
+    let manager = app.state::<OrganManager>();
+    let mut profiles: Vec<ProfileResources> = Vec::new();
+    for def in manager.defs() {
+        let profile = def.profile();
+        let open = usize::from(app.get_webview_window(&window_label(&def.id)).is_some());
+        match profiles.iter_mut().find(|p| p.profile == profile) {
+            Some(entry) => {
+                entry.organs.push(def.id.clone());
+                entry.open += open;
+            }
+            None => profiles.push(ProfileResources {
+                disk_bytes: profile_dir(&app, &profile).map(|dir| disk_usage(&dir)).unwrap_or(0),
+                profile,
+                shared: def.share_cache_group.is_some(),
+                organs: vec![def.id.clone()],
+                open,
+            }),
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    let total_memory_bytes = Some(tree_memory());
+    #[cfg(not(target_os = "linux"))]
+    let total_memory_bytes = None;
+
+    OrganResources { total_memory_bytes, profiles }
+}
+
+#[tauri::command]
+pub fn list_organs(app: tauri::AppHandle) -> Vec<OrganInfo> {
+    let manager = app.state::<OrganManager>();
+    manager
+        .defs()
+        .iter()
+        .map(|d| OrganInfo {
+            id: d.id.clone(),
+            title: d.title.clone(),
+            status: status(&app, &d.id),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn logged_in_organs_keep_their_own_profile() {
+        let manager = OrganManager::new();
+        let whatsapp = manager.get("whatsapp").unwrap();
+        assert_eq!(whatsapp.share_cache_group, None);
+        assert_eq!(whatsapp.profile(), "whatsapp");
+
+        let watcher = OrganDef { id: "feeds".into(), share_cache_group: Some("watchers".into()), ..whatsapp.clone() };
+        assert_eq!(watcher.profile(), "group-watchers");
+    }
+
+    #[test]
+    fn each_whatsapp_account_gets_its_own_organ() {
+        let accounts = ["work", "Bad Name", "work", "side-gig"].map(String::from);
+        let manager = OrganManager::new().with_whatsapp_accounts(&accounts);
+        let ids: Vec<_> = manager.defs().iter().map(|d| d.id.as_str()).collect();
+        assert_eq!(ids, ["whatsapp", "whatsapp-work", "whatsapp-side-gig", "telegram", "email", "discord"]);
+        let work = manager.get("whatsapp-work").unwrap();
+        assert_eq!((work.service(), work.account.as_deref()), ("whatsapp", Some("work")));
+        assert_eq!((window_label(&work.id).as_str(), work.profile().as_str()), ("whatsapp-work-organ", "whatsapp-work"));
+        assert_eq!(work.title, "WhatsApp (work)");
+        assert_eq!(manager.get("whatsapp").unwrap().service(), "whatsapp");
+    }
+
+    #[test]
+    fn telegram_sits_next_to_whatsapp() {
+        let manager = OrganManager::new();
+        let ids: Vec<_> = manager.defs().iter().map(|d| d.id.as_str()).collect();
+        assert_eq!(ids, ["whatsapp", "telegram", "email", "discord"]);
+        let telegram = manager.get("telegram").unwrap();
+        assert_eq!(window_label(&telegram.id), "telegram-organ");
+        assert_eq!(telegram.profile(), "telegram");
+        assert!(telegram.session_probe.is_some() && telegram.status_probe.is_some());
+    }
+
+    #[test]
+    fn gmail_stays_on_its_own_hosts() {
+        let manager = OrganManager::new();
+        let email = manager.get("email").unwrap();
+        assert_eq!((email.service(), window_label(&email.id).as_str()), ("email", "email-organ"));
+        let owns = |url: &str| email.owns(&tauri::Url::parse(url).unwrap());
+        assert!(owns("https://mail.google.com/mail/u/0/#inbox"));
+        assert!(owns("https://accounts.google.com/signin"));
+        assert!(!owns("https://drive.google.com/file/d/1"));
+    }
+
+    #[test]
+    fn login_follows_the_relayed_status_then_the_probe() {
+        assert_eq!(login(Some(&Lifecycle::LoggedIn), Some(false)), Login::LoggedIn);
+        assert_eq!(login(Some(&Lifecycle::QrPending), Some(true)), Login::QrPending);
+        assert_eq!(login(Some(&Lifecycle::Connecting), Some(true)), Login::LoggedIn);
+        assert_eq!(login(Some(&Lifecycle::Disconnected), None), Login::Unknown);
+        assert_eq!(login(None, Some(false)), Login::QrPending);
+    }
+
+    #[test]
+    fn answers_reach_only_the_question_they_belong_to() {
+        let mut questions = Questions::default();
+        let (token, answered) = questions.ask("whatsapp");
+        let (other, other_answered) = questions.ask("whatsapp");
+        assert_ne!(token, other);
+
+        // Another organ, or a token nobody handed out, gets nowhere.
+        assert!(!questions.answer("telegram", token, "forged".into()));
+        let stray = (0..).find(|t| *t != token && *t != other).unwrap();
+        assert!(!questions.answer("whatsapp", stray, "stray".into()));
+        assert!(questions.answer("whatsapp", token, "yes".into()));
+        assert_eq!(answered.recv_timeout(Duration::from_millis(10)).unwrap(), "yes");
+
+        // Unanswered, the wait runs out; once forgotten, a late answer is dropped.
+        assert!(other_answered.recv_timeout(Duration::from_millis(10)).is_err());
+        questions.forget(other);
+        assert!(!questions.answer("whatsapp", other, "late".into()));
+    }
+}
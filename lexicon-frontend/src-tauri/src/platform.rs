@@ -0,0 +1,95 @@
+//! Desktop vs. mobile — what this build can do, and the error for what
+//! it can't.
+//!
+//! On Android/iOS Lexicon is a single full-screen webview: there is no
+//! overlay to toggle, no tray, no global shortcuts and no separate organ
+//! windows. Commands that depend on those fail with
+//! [`NotSupportedOnPlatform`] instead of silently doing nothing; the Brain,
+//! ingest, audio and the rest of the connectivity stack are unchanged.
+//! `get_platform` lets the canvas hide what won't work up front.
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    Desktop,
+    Mobile,
+}
+
+/// The platform this binary was built for.
+pub const CURRENT: Platform = if cfg!(mobile) { Platform::Mobile } else { Platform::Desktop };
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlatformInfo {
+    pub platform: Platform,
+    /// `std::env::consts::OS`, e.g. "linux" or "android".
+    pub os: &'static str,
+    pub overlay: bool,
+    pub organ_windows: bool,
+    pub tray: bool,
+    pub global_shortcuts: bool,
+    pub notifications: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NotSupportedOnPlatform {
+    pub feature: &'static str,
+    pub platform: Platform,
+}
+
+impl NotSupportedOnPlatform {
+    pub fn new(feature: &'static str) -> Self {
+        Self { feature, platform: CURRENT }
+    }
+}
+
+impl std::fmt::Display for NotSupportedOnPlatform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let platform = match self.platform {
+            Platform::Desktop => "desktop",
+            Platform::Mobile => "mobile",
+        };
+        write!(f, "not supported on {platform}: {}", self.feature)
+    }
+}
+
+impl From<NotSupportedOnPlatform> for String {
+    fn from(e: NotSupportedOnPlatform) -> Self {
+        e.to_string()
+    }
+}
+
+#[tauri::command]
+pub fn get_platform() -> PlatformInfo {
+    let desktop = CURRENT == Platform::Desktop;
+    PlatformInfo {
+        platform: CURRENT,
+        os: std::env::consts::OS,
+        overlay: desktop,
+        organ_windows: desktop,
+        tray: desktop,
+        global_shortcuts: desktop,
+        notifications: desktop,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capabilities_follow_the_build() {
+        let info = get_platform();
+        assert_eq!(info.platform, CURRENT);
+        assert_eq!(info.organ_windows, cfg!(desktop));
+        assert_eq!(info.global_shortcuts, cfg!(desktop));
+    }
+
+    #[test]
+    fn not_supported_names_the_feature() {
+        let error: String = NotSupportedOnPlatform::new("organ windows").into();
+        assert!(error.starts_with("not supported on "));
+        assert!(error.ends_with(": organ windows"));
+    }
+}
@@ -0,0 +1,374 @@
+//! Suspend/resume — pause background work before sleep and resync in one
+//! pass on wake.
+//!
+//! On Linux, logind's `PrepareForSleep` drives this, with a `delay`
+//! inhibitor held so the pause runs before the machine goes down.
+//! Elsewhere a wake is detected after the fact from the wall clock
+//! jumping ahead of the monotonic one.
+//!
+//! Before sleep, voice capture and playback stop and the ingest drainer
+//! stands still (`is_suspended`). On wake, in order: Instant-based
+//! debouncers are reset, the Brain is probed once, organ injections are
+//! re-applied, the drainer is let go — and a single `power://resumed`
+//! event reports the outcome instead of a run of offline/online flaps.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use serde::Serialize;
+use tauri::{Emitter, Manager};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Resumed {
+    /// Wall-clock time spent asleep.
+    pub slept_secs: u64,
+    /// How far monotonic timers fell behind the wall clock while asleep.
+    pub clock_skew_secs: u64,
+    pub brain_online: bool,
+}
+
+static SUSPENDED: AtomicBool = AtomicBool::new(false);
+
+/// When the current sleep began.
+static ASLEEP: Mutex<Option<Sleep>> = Mutex::new(None);
+
+/// Whether the system is going to or is asleep; background timers check
+/// this and sit out the tick.
+pub fn is_suspended() -> bool {
+    SUSPENDED.load(Ordering::SeqCst)
+}
+
+// ── Sequencing ─────────────────────────────────────────────────
+
+trait Clock {
+    fn wall(&self) -> SystemTime;
+    fn mono(&self) -> Instant;
+}
+
+struct SystemClock;
+
+impl Clock for SystemClock {
+    fn wall(&self) -> SystemTime {
+        SystemTime::now()
+    }
+    fn mono(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// What suspend and resume act on; `tauri::AppHandle` is the real one.
+trait Wake {
+    fn pause(&mut self);
+    fn reset_debouncers(&mut self);
+    fn probe_brain(&mut self) -> bool;
+    fn check_organs(&mut self);
+    fn resume_queues(&mut self, brain_online: bool);
+    fn announce(&mut self, resumed: &Resumed);
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sleep {
+    wall: SystemTime,
+    mono: Instant,
+}
+
+fn suspend(wake: &mut impl Wake, clock: &impl Clock) -> Sleep {
+    SUSPENDED.store(true, Ordering::SeqCst);
+    wake.pause();
+    Sleep { wall: clock.wall(), mono: clock.mono() }
+}
+
+fn resume(wake: &mut impl Wake, clock: &impl Clock, sleep: Sleep) -> Resumed {
+    let slept = clock.wall().duration_since(sleep.wall).unwrap_or_default();
+    // Monotonic time mostly stops in suspend; the gap is the skew every
+    // Instant-based timer now carries.
+    let skew = slept.saturating_sub(clock.mono().saturating_duration_since(sleep.mono));
+
+    // Debouncers first, so nothing acts on a pre-sleep timestamp.
+    wake.reset_debouncers();
+    let brain_online = wake.probe_brain();
+    wake.check_organs();
+    SUSPENDED.store(false, Ordering::SeqCst);
+    wake.resume_queues(brain_online);
+
+    let resumed = Resumed { slept_secs: slept.as_secs(), clock_skew_secs: skew.as_secs(), brain_online };
+    wake.announce(&resumed);
+    resumed
+}
+
+impl Wake for tauri::AppHandle {
+    fn pause(&mut self) {
+        crate::voice::stop();
+        crate::audio::stop(self);
+    }
+
+    fn reset_debouncers(&mut self) {
+        crate::clipboard::reset_rate_limit();
+        crate::health::forget_announced();
+    }
+
+    fn probe_brain(&mut self) -> bool {
+        crate::network::probe(self)
+    }
+
+    fn check_organs(&mut self) {
+        // Page-load hooks don't re-run on wake; re-apply them in case the
+        // organ reloaded itself while the network was down.
+        let ids: Vec<String> = self.state::<crate::organs::OrganManager>().defs().iter().map(|d| d.id.clone()).collect();
+        for id in ids.iter().filter(|id| crate::organs::status(self, id) != "closed") {
+            crate::theme::apply_to_organ(self, id);
+        }
+    }
+
+    fn resume_queues(&mut self, brain_online: bool) {
+        if brain_online {
+            crate::ingest::resume(self);
+        }
+    }
+
+    fn announce(&mut self, resumed: &Resumed) {
+        tracing::info!(
+            "resumed after {}s (skew {}s, Brain {})",
+            resumed.slept_secs,
+            resumed.clock_skew_secs,
+            if resumed.brain_online { "online" } else { "offline" }
+        );
+        let _ = self.emit("power://resumed", resumed);
+        crate::health::announce(self);
+    }
+}
+
+fn on_sleep(app: &tauri::AppHandle) {
+    let mut asleep = ASLEEP.lock().unwrap_or_else(|e| e.into_inner());
+    if asleep.is_none() {
+        tracing::info!("system is suspending");
+        *asleep = Some(suspend(&mut app.clone(), &SystemClock));
+    }
+}
+
+fn on_wake(app: &tauri::AppHandle, fallback: Option<Sleep>) {
+    let sleep = ASLEEP.lock().unwrap_or_else(|e| e.into_inner()).take().or(fallback);
+    if let Some(sleep) = sleep {
+        resume(&mut app.clone(), &SystemClock, sleep);
+    }
+}
+
+// ── Platform hooks ─────────────────────────────────────────────
+
+#[cfg(target_os = "linux")]
+fn delay_lock() -> Option<std::os::fd::OwnedFd> {
+    let bus = zbus::blocking::Connection::system().ok()?;
+    let reply = bus
+        .call_method(
+            Some("org.freedesktop.login1"),
+            "/org/freedesktop/login1",
+            Some("org.freedesktop.login1.Manager"),
+            "Inhibit",
+            &("sleep", "Lexicon", "Pausing background work", "delay"),
+        )
+        .ok()?;
+    let fd: zbus::zvariant::OwnedFd = reply.body().deserialize().ok()?;
+    Some(fd.into())
+}
+
+#[cfg(target_os = "linux")]
+fn watch(app: tauri::AppHandle) -> Result<(), String> {
+    let bus = zbus::blocking::Connection::system().map_err(|e| e.to_string())?;
+    let manager = zbus::blocking::Proxy::new(
+        &bus,
+        "org.freedesktop.login1",
+        "/org/freedesktop/login1",
+        "org.freedesktop.login1.Manager",
+    )
+    .map_err(|e| e.to_string())?;
+    let signals = manager.receive_signal("PrepareForSleep").map_err(|e| e.to_string())?;
+    let mut delay = delay_lock();
+    for signal in signals {
+        let Ok((starting,)) = signal.body().deserialize::<(bool,)>() else { continue };
+        if starting {
+            on_sleep(&app);
+            // Paused; let the suspend go ahead.
+            delay = None;
+        } else {
+            on_wake(&app, None);
+            delay = delay_lock();
+        }
+    }
+    drop(delay);
+    Err("logind went away".into())
+}
+
+/// How often the fallback compares clocks.
+#[cfg(not(target_os = "linux"))]
+const TICK: Duration = Duration::from_secs(5);
+
+/// A wall-clock jump beyond this counts as a sleep.
+#[cfg_attr(target_os = "linux", allow(dead_code))]
+const MIN_SLEEP: Duration = Duration::from_secs(30);
+
+#[cfg(not(target_os = "linux"))]
+fn watch(app: tauri::AppHandle) -> Result<(), String> {
+    let mut last = Sleep { wall: SystemTime::now(), mono: Instant::now() };
+    loop {
+        std::thread::sleep(TICK);
+        let now = Sleep { wall: SystemTime::now(), mono: Instant::now() };
+        if let Some(sleep) = detect_sleep(last, now) {
+            // Too late to pause; still resync.
+            SUSPENDED.store(true, Ordering::SeqCst);
+            on_wake(&app, Some(sleep));
+        }
+        last = now;
+    }
+}
+
+/// The sleep between two clock readings, if the wall clock ran ahead of
+/// the monotonic one by more than `MIN_SLEEP`.
+#[cfg_attr(target_os = "linux", allow(dead_code))]
+fn detect_sleep(before: Sleep, after: Sleep) -> Option<Sleep> {
+    let wall = after.wall.duration_since(before.wall).unwrap_or_default();
+    let mono = after.mono.saturating_duration_since(before.mono);
+    (wall.saturating_sub(mono) > MIN_SLEEP).then_some(before)
+}
+
+pub fn init(app: &tauri::AppHandle) {
+    let app = app.clone();
+    std::thread::spawn(move || {
+        if let Err(e) = watch(app) {
+            tracing::warn!("suspend/resume detection unavailable: {e}");
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    /// The tests share `SUSPENDED`.
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    struct MockClock {
+        wall: Cell<SystemTime>,
+        mono: Cell<Instant>,
+    }
+
+    impl MockClock {
+        fn new() -> Self {
+            Self { wall: Cell::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000)), mono: Cell::new(Instant::now()) }
+        }
+        /// Time passing while awake: both clocks move.
+        fn run(&self, d: Duration) {
+            self.wall.set(self.wall.get() + d);
+            self.mono.set(self.mono.get() + d);
+        }
+        /// Time passing in suspend: only the wall clock moves.
+        fn sleep(&self, d: Duration) {
+            self.wall.set(self.wall.get() + d);
+        }
+    }
+
+    impl Clock for MockClock {
+        fn wall(&self) -> SystemTime {
+            self.wall.get()
+        }
+        fn mono(&self) -> Instant {
+            self.mono.get()
+        }
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        calls: Vec<String>,
+        brain_online: bool,
+    }
+
+    impl Wake for Recorder {
+        fn pause(&mut self) {
+            self.calls.push(format!("pause suspended={}", is_suspended()));
+        }
+        fn reset_debouncers(&mut self) {
+            self.calls.push("reset".into());
+        }
+        fn probe_brain(&mut self) -> bool {
+            self.calls.push("probe".into());
+            self.brain_online
+        }
+        fn check_organs(&mut self) {
+            self.calls.push("organs".into());
+        }
+        fn resume_queues(&mut self, brain_online: bool) {
+            self.calls.push(format!("queues online={brain_online} suspended={}", is_suspended()));
+        }
+        fn announce(&mut self, resumed: &Resumed) {
+            self.calls.push(format!("resumed {}s", resumed.slept_secs));
+        }
+    }
+
+    #[test]
+    fn resume_resyncs_in_order_with_one_event() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        let clock = MockClock::new();
+        let mut wake = Recorder { brain_online: true, ..Default::default() };
+
+        let sleep = suspend(&mut wake, &clock);
+        assert!(is_suspended());
+        clock.run(Duration::from_secs(2));
+        clock.sleep(Duration::from_secs(8 * 3600));
+        clock.run(Duration::from_secs(1));
+        let resumed = resume(&mut wake, &clock, sleep);
+
+        assert!(!is_suspended());
+        assert_eq!(
+            wake.calls,
+            [
+                "pause suspended=true",
+                "reset",
+                "probe",
+                "organs",
+                "queues online=true suspended=false",
+                "resumed 28803s",
+            ]
+        );
+        assert_eq!(resumed, Resumed { slept_secs: 28803, clock_skew_secs: 28800, brain_online: true });
+    }
+
+    #[test]
+    fn offline_brain_is_reported_once() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        let clock = MockClock::new();
+        let mut wake = Recorder::default();
+        let sleep = suspend(&mut wake, &clock);
+        clock.sleep(Duration::from_secs(600));
+        let resumed = resume(&mut wake, &clock, sleep);
+
+        assert!(!resumed.brain_online);
+        assert_eq!(wake.calls.iter().filter(|c| c.starts_with("probe")).count(), 1);
+        assert_eq!(wake.calls.iter().filter(|c| c.starts_with("resumed")).count(), 1);
+    }
+
+    #[test]
+    fn wall_clock_set_backwards_is_not_a_sleep() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        let clock = MockClock::new();
+        let mut wake = Recorder::default();
+        let sleep = suspend(&mut wake, &clock);
+        clock.wall.set(clock.wall.get() - Duration::from_secs(3600));
+        clock.run(Duration::from_secs(5));
+        let resumed = resume(&mut wake, &clock, sleep);
+        assert_eq!((resumed.slept_secs, resumed.clock_skew_secs), (0, 0));
+    }
+
+    #[test]
+    fn fallback_detects_wall_clock_jumps() {
+        let clock = MockClock::new();
+        let before = Sleep { wall: clock.wall(), mono: clock.mono() };
+        clock.run(Duration::from_secs(5));
+        let awake = Sleep { wall: clock.wall(), mono: clock.mono() };
+        assert!(detect_sleep(before, awake).is_none());
+
+        clock.sleep(Duration::from_secs(3600));
+        let woke = Sleep { wall: clock.wall(), mono: clock.mono() };
+        assert!(detect_sleep(awake, woke).is_some());
+    }
+}
@@ -0,0 +1,81 @@
+//! Presentation guard — one switch that makes Lexicon invisible and inert
+//! before a screen share, and a second one that puts everything back.
+//!
+//! While the guard is on every window is hidden, `toggle_window` is a
+//! no-op, organs don't come to the front (see `refuse`), and subsystems
+//! that would surface content (notifications, tray badges, frontend
+//! message events) are expected to check `is_active()` and stay quiet. Relaying to the Brain is not affected.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use tauri::Manager;
+
+use crate::host::Host;
+
+static GUARD_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Windows the guard hid, restored on unlock. Holding this lock for the
+/// whole transition also keeps two guard calls from interleaving.
+static HIDDEN_BY_GUARD: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Whether the presentation guard is currently engaged.
+pub fn is_active() -> bool {
+    GUARD_ACTIVE.load(Ordering::SeqCst)
+}
+
+/// Fail with why `what` can't happen while `host`'s guard is on: it would
+/// put a window on screen.
+pub fn refuse(host: &impl Host, what: &str) -> Result<(), String> {
+    if host.presenting() {
+        return Err(format!("presentation guard is on: {what} would show a window"));
+    }
+    Ok(())
+}
+
+/// Engage or release the guard. Returns the resulting state.
+#[tauri::command]
+pub fn set_presentation_guard(app: tauri::AppHandle, enabled: bool) -> bool {
+    let mut hidden = HIDDEN_BY_GUARD.lock().unwrap_or_else(|e| e.into_inner());
+    if enabled == is_active() {
+        return enabled;
+    }
+
+    if enabled {
+        // Flag first so nothing can re-show a window while we hide them.
+        GUARD_ACTIVE.store(true, Ordering::SeqCst);
+        *hidden = crate::hide_all_windows(&app);
+        crate::voice::stop();
+        crate::audio::stop(&app);
+        tracing::info!("presentation guard on ({} window(s) hidden)", hidden.len());
+    } else {
+        for label in hidden.drain(..) {
+            if let Some(window) = app.get_webview_window(&label) {
+                crate::show_window(&window);
+            }
+        }
+        GUARD_ACTIVE.store(false, Ordering::SeqCst);
+        tracing::info!("presentation guard off");
+        crate::deeplink::drain_queued(&app);
+    }
+    crate::tray::refresh_tooltip(&app);
+    crate::badge::refresh(&app);
+    crate::health::announce(&app);
+    enabled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestHost;
+
+    #[test]
+    fn nothing_opens_while_presenting() {
+        let host = TestHost::new();
+        assert!(refuse(&host, "opening whatsapp").is_ok());
+        host.present(true);
+        assert!(refuse(&host, "opening whatsapp").unwrap_err().contains("presentation guard is on"));
+        host.present(false);
+        assert!(refuse(&host, "opening whatsapp").is_ok());
+    }
+}
@@ -0,0 +1,163 @@
+//! The WhatsApp login QR code, shown in the canvas instead of the organ.
+//!
+//! While WhatsApp Web shows its QR code, the organ's page looks at it
+//! every two seconds and reports each new one (WhatsApp replaces it about
+//! every 20 seconds) as a PNG data URL, through `organs::REPORT_HOST` like
+//! its other reports. Each goes to the main window as `whatsapp://qr`,
+//! `{ png, captured_at }` (base64 PNG, Unix milliseconds), until the
+//! organ is signed in: the page stops reporting once the code is gone,
+//! and a code reported after the organ said "connected" is dropped. So
+//! is one reported while the presentation guard is on; the next comes
+//! within 20 seconds of it going off.
+//!
+//! `wa_get_qr` reads the code on the page now. It fails with
+//! `organ_closed` when the organ isn't open, `logged_in` when there is
+//! nothing to scan, `not_shown` while the page has no code up (loading,
+//! offline) and `page` when the page doesn't answer sensibly.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::Engine;
+use serde::Serialize;
+use tauri::{Emitter, Manager};
+
+use crate::organs::Login;
+use crate::whatsapp::ORGAN;
+
+const ASK_TIMEOUT: Duration = Duration::from_secs(5);
+const DATA_URL_PREFIX: &str = "data:image/png;base64,";
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// The canvas WhatsApp Web draws the code on, if it is up.
+const QR_CANVAS: &str = "(function () { var holder = document.querySelector('[data-ref]'); \
+  return holder && holder.querySelector('canvas'); })()";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", content = "message", rename_all = "snake_case")]
+pub enum QrError {
+    OrganClosed,
+    LoggedIn,
+    NotShown,
+    Page(String),
+}
+
+impl std::fmt::Display for QrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QrError::OrganClosed => f.write_str("the WhatsApp organ is not open"),
+            QrError::LoggedIn => f.write_str("WhatsApp is already signed in"),
+            QrError::NotShown => f.write_str("WhatsApp is not showing a QR code"),
+            QrError::Page(e) => write!(f, "could not read the QR code: {e}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Qr {
+    /// Base64 PNG.
+    pub png: String,
+    pub captured_at: u64,
+}
+
+impl Qr {
+    fn from_data_url(data_url: &str) -> Result<Self, QrError> {
+        let png = data_url.strip_prefix(DATA_URL_PREFIX).ok_or_else(|| QrError::Page("not a PNG data URL".into()))?;
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(png)
+            .map_err(|e| QrError::Page(format!("bad base64: {e}")))?;
+        if !bytes.starts_with(PNG_SIGNATURE) {
+            return Err(QrError::Page("not a PNG".into()));
+        }
+        let captured_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+        Ok(Self { png: png.to_string(), captured_at })
+    }
+}
+
+fn signed_in(app: &tauri::AppHandle) -> bool {
+    crate::organs::state(app, ORGAN).is_ok_and(|state| state.login == Login::LoggedIn)
+}
+
+/// Report each new code while one is up. Installed on every page load.
+pub fn watch(app: &tauri::AppHandle) {
+    let Some(window) = app.get_webview_window(&crate::organs::window_label(ORGAN)) else { return };
+    let _ = window.eval(format!(
+        r#"(function () {{
+  if (window.__lexiconQr) return;
+  window.__lexiconQr = true;
+  var last = null;
+  setInterval(function () {{
+    var canvas = {QR_CANVAS};
+    if (!canvas) {{ last = null; return; }}
+    var ref = canvas.closest('[data-ref]').getAttribute('data-ref');
+    if (ref === last) return;
+    last = ref;
+    location.href = 'https://{host}/qr?value=' + encodeURIComponent(canvas.toDataURL('image/png'));
+  }}, 2000);
+}})();"#,
+        host = crate::organs::REPORT_HOST
+    ));
+}
+
+/// A code the page reported.
+pub fn reported(app: &tauri::AppHandle, data_url: &str) {
+    if signed_in(app) {
+        return;
+    }
+    if crate::presentation::is_active() {
+        tracing::debug!("WhatsApp QR code dropped — presentation guard is on");
+        return;
+    }
+    match Qr::from_data_url(data_url) {
+        Ok(qr) => {
+            tracing::debug!("new WhatsApp QR code");
+            let _ = app.emit_to("main", "whatsapp://qr", qr);
+        }
+        Err(e) => tracing::warn!("WhatsApp QR report dropped: {e}"),
+    }
+}
+
+/// The code on the page now. Blocks; call it off the main thread.
+pub fn current(app: &tauri::AppHandle) -> Result<Qr, QrError> {
+    if crate::organs::status(app, ORGAN) == "closed" {
+        return Err(QrError::OrganClosed);
+    }
+    if signed_in(app) {
+        return Err(QrError::LoggedIn);
+    }
+    let script = |reply: &str| {
+        format!("(function (canvas) {{ ({reply})(canvas ? canvas.toDataURL('image/png') : ''); }})({QR_CANVAS});")
+    };
+    let answer = crate::organs::ask(app, ORGAN, script, ASK_TIMEOUT).map_err(QrError::Page)?;
+    if answer.is_empty() {
+        return Err(QrError::NotShown);
+    }
+    Qr::from_data_url(&answer)
+}
+
+#[tauri::command]
+pub async fn wa_get_qr(app: tauri::AppHandle) -> Result<Qr, QrError> {
+    tauri::async_runtime::spawn_blocking(move || current(&app))
+        .await
+        .map_err(|e| QrError::Page(e.to_string()))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_png_data_urls_are_taken() {
+        let png = base64::engine::general_purpose::STANDARD.encode(b"\x89PNG\r\n\x1a\nrest of the image");
+        let qr = Qr::from_data_url(&format!("{DATA_URL_PREFIX}{png}")).unwrap();
+        assert_eq!(qr.png, png);
+
+        let jpeg = base64::engine::general_purpose::STANDARD.encode(b"\xff\xd8\xff\xe0");
+        assert_eq!(Qr::from_data_url(&format!("{DATA_URL_PREFIX}{jpeg}")).unwrap_err(), QrError::Page("not a PNG".into()));
+        assert!(Qr::from_data_url(&format!("data:image/jpeg;base64,{png}")).is_err());
+        assert!(Qr::from_data_url(&format!("{DATA_URL_PREFIX}%%%")).is_err());
+        assert_eq!(
+            serde_json::to_value(QrError::LoggedIn).unwrap(),
+            serde_json::json!({ "kind": "logged_in" })
+        );
+    }
+}
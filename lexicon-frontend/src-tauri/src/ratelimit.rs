@@ -0,0 +1,267 @@
+//! Relay rate limits — so a page stuck in a loop can't flood the Brain.
+//!
+//! Every organ gets a token bucket per endpoint: messages, statuses
+//! (`/…/status`) and media (`/…/media`). `relay.rate_limits` sets how
+//! many sends a second each refills with and how many it holds (30 a
+//! second with a burst of 100 for messages, 1 with 5 for statuses, 5 with
+//! 20 for media); `relay.rate_limits.organs.<id>` overrides them for one
+//! organ, and a rate of 0 is no limit.
+//!
+//! Past the limit, a status is dropped: the next report or keepalive says
+//! the same. A message or media send is spooled instead (see `relay`) and
+//! replayed behind the next send that gets through. Each run of sends
+//! held back is logged once, as it starts, and counted; `get_metrics`
+//! lists every bucket with its tokens and count. `set_rate_limits`
+//! changes the limits while the app runs, and saves them.
+
+use std::collections::BTreeMap;
+use std::sync::{Mutex, RwLock};
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+use tauri::Manager;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Endpoint {
+    Messages,
+    Statuses,
+    Media,
+}
+
+impl Endpoint {
+    pub fn of(path: &str) -> Self {
+        if path.ends_with("/status") {
+            Self::Statuses
+        } else if path.ends_with("/media") {
+            Self::Media
+        } else {
+            Self::Messages
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+pub struct Limit {
+    /// Sends a second the bucket refills with; 0 is no limit.
+    #[cfg_attr(test, proptest(strategy = "crate::testing::toml_u64()"))]
+    pub per_sec: u64,
+    /// Most sends at once, after a quiet spell.
+    #[cfg_attr(test, proptest(strategy = "crate::testing::toml_u64()"))]
+    pub burst: u64,
+}
+
+/// Overrides for one organ; what is unset comes from the defaults.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+#[serde(default)]
+pub struct OrganLimits {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub messages: Option<Limit>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub statuses: Option<Limit>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub media: Option<Limit>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+#[serde(default)]
+pub struct RateLimits {
+    pub messages: Limit,
+    pub statuses: Limit,
+    pub media: Limit,
+    /// By organ id.
+    pub organs: BTreeMap<String, OrganLimits>,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self {
+            messages: Limit { per_sec: 30, burst: 100 },
+            statuses: Limit { per_sec: 1, burst: 5 },
+            media: Limit { per_sec: 5, burst: 20 },
+            organs: BTreeMap::new(),
+        }
+    }
+}
+
+impl RateLimits {
+    fn limit(&self, organ: &str, endpoint: Endpoint) -> Limit {
+        let own = self.organs.get(organ);
+        match endpoint {
+            Endpoint::Messages => own.and_then(|o| o.messages).unwrap_or(self.messages),
+            Endpoint::Statuses => own.and_then(|o| o.statuses).unwrap_or(self.statuses),
+            Endpoint::Media => own.and_then(|o| o.media).unwrap_or(self.media),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    at: Instant,
+    /// Sends held back, ever.
+    limited: u64,
+    /// Held back since the last one let through.
+    running: u64,
+}
+
+impl Bucket {
+    fn full(limit: Limit, now: Instant) -> Self {
+        Self { tokens: limit.burst.max(1) as f64, at: now, limited: 0, running: 0 }
+    }
+
+    /// Take a token if there is one.
+    fn take(&mut self, limit: Limit, now: Instant) -> bool {
+        let burst = limit.burst.max(1) as f64;
+        let refill = now.saturating_duration_since(self.at).as_secs_f64() * limit.per_sec as f64;
+        self.tokens = (self.tokens + refill).min(burst);
+        self.at = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return true;
+        }
+        false
+    }
+}
+
+/// One bucket as `get_metrics` shows it.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct BucketState {
+    pub organ: String,
+    pub endpoint: Endpoint,
+    /// Sends it would let through right now, as of its last use.
+    pub tokens: u64,
+    pub limited: u64,
+    /// Holding sends back at the moment.
+    pub limiting: bool,
+}
+
+/// Managed state: the limits and every organ's buckets.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    limits: RwLock<RateLimits>,
+    buckets: Mutex<BTreeMap<(String, Endpoint), Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        Self { limits: RwLock::new(limits), buckets: Mutex::default() }
+    }
+
+    /// Whether `organ` may send to `endpoint` now. The first send of a
+    /// run held back is logged; the rest are only counted.
+    pub fn allow(&self, organ: &str, endpoint: Endpoint, now: Instant) -> bool {
+        let limit = self.limits.read().unwrap_or_else(|e| e.into_inner()).limit(organ, endpoint);
+        if limit.per_sec == 0 {
+            return true;
+        }
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        let bucket = buckets.entry((organ.to_string(), endpoint)).or_insert_with(|| Bucket::full(limit, now));
+        if bucket.take(limit, now) {
+            if bucket.running > 0 {
+                tracing::info!("relay: {organ} {endpoint:?} back under its limit after {} held back", bucket.running);
+                bucket.running = 0;
+            }
+            return true;
+        }
+        if bucket.running == 0 {
+            tracing::warn!(
+                "relay: {organ} is over its {endpoint:?} limit ({}/s, burst {}) — holding sends back",
+                limit.per_sec,
+                limit.burst
+            );
+        }
+        bucket.running += 1;
+        bucket.limited += 1;
+        false
+    }
+
+    /// Use `limits` from now on. Buckets keep what they hold, up to the new bursts.
+    pub fn set(&self, limits: RateLimits) {
+        *self.limits.write().unwrap_or_else(|e| e.into_inner()) = limits;
+    }
+
+    pub fn state(&self) -> Vec<BucketState> {
+        let buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        buckets
+            .iter()
+            .map(|((organ, endpoint), bucket)| BucketState {
+                organ: organ.clone(),
+                endpoint: *endpoint,
+                tokens: bucket.tokens as u64,
+                limited: bucket.limited,
+                limiting: bucket.running > 0,
+            })
+            .collect()
+    }
+}
+
+pub fn init(app: &tauri::AppHandle) {
+    app.manage(RateLimiter::new(crate::config::current(app).relay.rate_limits));
+}
+
+/// Save `limits` (a [`RateLimits`]) and apply them now.
+#[tauri::command]
+pub fn set_rate_limits(window: tauri::WebviewWindow, app: tauri::AppHandle, limits: RateLimits) -> Result<RateLimits, String> {
+    if window.label() != "main" {
+        return Err("only the main window can change the rate limits".into());
+    }
+    let saved = limits.clone();
+    crate::config::update(&app, move |c| c.relay.rate_limits = saved)?;
+    app.state::<RateLimiter>().set(limits.clone());
+    tracing::info!("relay rate limits updated");
+    Ok(limits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn buckets_refill_at_their_rate_up_to_the_burst() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let limiter = RateLimiter::new(RateLimits::default());
+        assert_eq!(Endpoint::of("/whatsapp/status"), Endpoint::Statuses);
+        assert_eq!(Endpoint::of("/whatsapp/media"), Endpoint::Media);
+        assert_eq!(Endpoint::of("/whatsapp/message"), Endpoint::Messages);
+
+        // A burst of 5 statuses, then one a second.
+        assert!((0..5).all(|_| limiter.allow("whatsapp", Endpoint::Statuses, at(0))));
+        assert!(!limiter.allow("whatsapp", Endpoint::Statuses, at(0)));
+        assert!(!limiter.allow("whatsapp", Endpoint::Statuses, at(500)));
+        assert!(limiter.allow("whatsapp", Endpoint::Statuses, at(1000)));
+        // Other organs and endpoints have buckets of their own.
+        assert!(limiter.allow("whatsapp-work", Endpoint::Statuses, at(1000)));
+        assert!((0..100).all(|_| limiter.allow("whatsapp", Endpoint::Messages, at(1000))));
+        assert!(!limiter.allow("whatsapp", Endpoint::Messages, at(1000)));
+
+        let state = limiter.state();
+        let statuses = state.iter().find(|s| s.organ == "whatsapp" && s.endpoint == Endpoint::Statuses).unwrap();
+        assert_eq!((statuses.limited, statuses.limiting), (2, false));
+        let messages = state.iter().find(|s| s.endpoint == Endpoint::Messages).unwrap();
+        assert_eq!((messages.limited, messages.limiting), (1, true));
+        // A long quiet spell fills up to the burst, no further.
+        assert_eq!((0..200).filter(|_| limiter.allow("whatsapp", Endpoint::Messages, at(60_000))).count(), 100);
+    }
+
+    #[test]
+    fn organs_override_the_defaults_and_zero_is_unlimited() {
+        let mut limits = RateLimits::default();
+        let unlimited = Limit { per_sec: 0, burst: 0 };
+        limits.organs.insert("telegram".into(), OrganLimits { statuses: Some(unlimited), ..OrganLimits::default() });
+        assert_eq!(limits.limit("telegram", Endpoint::Statuses), unlimited);
+        assert_eq!(limits.limit("telegram", Endpoint::Messages), RateLimits::default().messages);
+
+        let limiter = RateLimiter::new(limits);
+        let now = Instant::now();
+        assert!((0..1000).all(|_| limiter.allow("telegram", Endpoint::Statuses, now)));
+        limiter.set(RateLimits::default());
+        assert_eq!((0..10).filter(|_| limiter.allow("telegram", Endpoint::Statuses, now)).count(), 5);
+    }
+}
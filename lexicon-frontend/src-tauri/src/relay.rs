@@ -0,0 +1,1662 @@
+//! Organ connection status, relayed to the Brain at `/{organ}/status` as
+//! a [`StatusUpdate`].
+//!
+//! Organs report their status whenever their page re-renders (see
+//! `organs::watch_status`), which for WhatsApp Web means the same
+//! "connected" many times a minute. Reports are taken as a [`Lifecycle`]
+//! (unknown ones are dropped) and that goes to the Brain by name, with
+//! the time. Only transitions are forwarded, plus
+//! the unchanged status again once `relay.keepalive_secs` have passed so
+//! the Brain still sees the organ alive. A new status has to hold for
+//! `relay.settle_ms` before it goes out, which swallows flapping and
+//! reports arriving out of order.
+//!
+//! Each relayed update is also emitted to the main window as
+//! `{organ}://status` (`whatsapp://status`), so the canvas hears of it
+//! even when the Brain is slow or down.
+//!
+//! A reload starts a new session: `reset` forgets what was relayed, so
+//! the first report afterwards is always sent.
+//!
+//! Sends go through [`Delivery`]: `relay.workers` threads sharing the
+//! app's Brain transport and consuming a queue of at most `relay.queue` POSTs.
+//! A send never waits for room on a full queue: a status is dropped, since
+//! the next keepalive covers it, and anything else goes to the spool
+//! (below) instead. A send the Brain missed (offline, timed out,
+//! a 5xx or 429) is tried up to `relay.attempts` times, waiting
+//! `relay.backoff_ms` and then twice as long each time. While the Brain
+//! monitor (see `brain`) has it offline, sends wait in the queue and go
+//! out when it comes back. On exit no new sends are taken and the queue
+//! is drained (for a few seconds at most) before the workers stop; sends
+//! still held are dropped. Each organ that relayed a status this session
+//! then tells the Brain `"shutdown"`, with a second to do it, so the
+//! Brain doesn't wait for a keepalive that never comes. A Brain that
+//! hangs is left behind at the deadline rather than holding up the exit.
+//! `lexicon relay-metrics` prints the per-organ and per-worker counts.
+//!
+//! What the Brain missed is not lost, though: a send that ran out of
+//! attempts on an unreachable Brain, found the queue full or was dropped
+//! at exit goes to the [`Spool`] on disk, and is replayed in order once a send gets through
+//! again or the monitor sees the Brain come back, in this run or a later
+//! one. `spool_stats` reports its depth, and `relay_queue_status` how
+//! many sends are waiting in all, for a "3 messages pending" in the UI.
+//!
+//! Sends marked [`Post::batched`] that arrive within
+//! `relay.batch_window_ms` of each other (at most `relay.batch_max`) go
+//! out as one POST of a JSON array, in arrival order, and are retried and
+//! spooled as a unit.
+//!
+//! With a relay secret (`LEXICON_RELAY_SECRET`, else `relay.secret`, or
+//! `set_relay_secret` from the canvas) every POST — status, batch or
+//! replayed from the spool — carries `X-Lexicon-Timestamp`, Unix seconds
+//! when it went out, and `X-Lexicon-Signature`, the hex HMAC-SHA256 of
+//! `{timestamp}.{body}`. Each try is signed afresh, so a send that sat in
+//! the spool for a day still arrives with a current timestamp; the Brain
+//! should refuse timestamps more than five minutes off its own clock,
+//! which is what a replayed capture looks like. The secret stays in this
+//! module: no command returns it and the journal redacts it.
+//!
+//! Messages an organ saw go out through [`message`], which drops the ones
+//! already relayed (see `dedup`) and the ones the user filtered out (see
+//! `filter`). They go out batched (see [`Post::batched`]) to the plural
+//! route, `/whatsapp/messages` for `/whatsapp/message`; media goes one at
+//! a time. Each one that goes out is also emitted to the main window as
+//! `{organ}://message`, a [`RelayedMessage`], like statuses are, unless
+//! the presentation guard is on.
+//!
+//! Statuses and messages carry the app's `version` and a `seq`: every one
+//! relayed for an organ this run is numbered, from 1, so the Brain can
+//! tell one it never got (dropped on a full queue, lost at exit) from none
+//! sent. Statuses the relay suppresses or rate limits aren't numbered.
+//!
+//! Statuses and messages are rate limited per organ (see `ratelimit`): a
+//! status over the limit is dropped, a message is spooled for replay
+//! rather than queued.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager};
+
+use crate::brain::{Body, Request, TransportError};
+use crate::host::Host;
+use crate::lifecycle::Lifecycle;
+use crate::metrics::Metrics;
+use crate::dedup::{Dedup, DedupStats, Seen};
+use crate::spool::{Spool, SpoolStats};
+use crate::ratelimit::{Endpoint, RateLimiter};
+
+/// How long the exit path keeps delivering what is still queued.
+const DRAIN_DEADLINE: Duration = Duration::from_secs(3);
+/// How long past the drain deadline the exit waits for a send in flight.
+const DRAIN_GRACE: Duration = Duration::from_secs(1);
+/// How long the Brain gets to take the `"shutdown"` statuses.
+const FAREWELL_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Overrides `relay.secret`.
+pub const SECRET_ENV: &str = "LEXICON_RELAY_SECRET";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+#[serde(default)]
+pub struct RelayConfig {
+    /// Re-send an unchanged status after this long; 0 never re-sends.
+    #[cfg_attr(test, proptest(strategy = "crate::testing::toml_u64()"))]
+    pub keepalive_secs: u64,
+    /// How long a changed status must hold before it is relayed.
+    #[cfg_attr(test, proptest(strategy = "crate::testing::toml_u64()"))]
+    pub settle_ms: u64,
+    /// Sender threads.
+    #[cfg_attr(test, proptest(strategy = "crate::testing::toml_usize()"))]
+    pub workers: usize,
+    /// Sends waiting for a worker before new ones are dropped.
+    #[cfg_attr(test, proptest(strategy = "crate::testing::toml_usize()"))]
+    pub queue: usize,
+    /// Tries per send, the first included.
+    pub attempts: u32,
+    /// Wait before the first retry; doubles after each.
+    #[cfg_attr(test, proptest(strategy = "crate::testing::toml_u64()"))]
+    pub backoff_ms: u64,
+    /// Most sends kept on disk for a Brain that wasn't there.
+    #[cfg_attr(test, proptest(strategy = "crate::testing::toml_usize()"))]
+    pub spool_max_entries: usize,
+    #[cfg_attr(test, proptest(strategy = "crate::testing::toml_u64()"))]
+    pub spool_max_bytes: u64,
+    /// How long batched sends wait for company before going out.
+    #[cfg_attr(test, proptest(strategy = "crate::testing::toml_u64()"))]
+    pub batch_window_ms: u64,
+    /// Most sends in one batch.
+    #[cfg_attr(test, proptest(strategy = "crate::testing::toml_usize()"))]
+    pub batch_max: usize,
+    /// Key to sign sends with; unsigned when unset or empty.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    /// Most message ids remembered for dedup.
+    #[cfg_attr(test, proptest(strategy = "crate::testing::toml_usize()"))]
+    pub dedup_capacity: usize,
+    /// How long a message id is remembered.
+    #[cfg_attr(test, proptest(strategy = "crate::testing::toml_u64()"))]
+    pub dedup_ttl_secs: u64,
+    /// Sends a second each organ may make, by endpoint.
+    pub rate_limits: crate::ratelimit::RateLimits,
+}
+
+impl Default for RelayConfig {
+    fn default() -> Self {
+        Self {
+            keepalive_secs: 300,
+            settle_ms: 2000,
+            workers: 2,
+            queue: 256,
+            attempts: 3,
+            backoff_ms: 500,
+            spool_max_entries: 10_000,
+            spool_max_bytes: 50 << 20,
+            batch_window_ms: 200,
+            batch_max: 50,
+            secret: None,
+            dedup_capacity: 5000,
+            dedup_ttl_secs: 3600,
+            rate_limits: crate::ratelimit::RateLimits::default(),
+        }
+    }
+}
+
+impl RelayConfig {
+    fn keepalive(&self) -> Option<Duration> {
+        (self.keepalive_secs > 0).then(|| Duration::from_secs(self.keepalive_secs))
+    }
+
+    fn settle(&self) -> Duration {
+        Duration::from_millis(self.settle_ms)
+    }
+
+    fn batch_window(&self) -> Duration {
+        Duration::from_millis(self.batch_window_ms)
+    }
+
+    /// The wait after failed attempt `n` (1-based).
+    fn backoff(&self, n: u32) -> Duration {
+        Duration::from_millis(self.backoff_ms.saturating_mul(1 << (n - 1).min(16)))
+    }
+}
+
+/// Worth sending again: the Brain never saw it or asked us to wait.
+fn retryable(error: &TransportError) -> bool {
+    match error {
+        TransportError::Offline(_) => true,
+        TransportError::Status(code) => *code == 408 || *code == 429 || *code >= 500,
+        TransportError::Failed(_) => false,
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RelayMetrics {
+    pub relayed: u64,
+    /// Reports dropped as duplicates or flaps.
+    pub suppressed: u64,
+    pub last_status: Option<Lifecycle>,
+    /// Unix milliseconds of the last relayed update.
+    pub last_relayed_at: Option<u64>,
+    /// Messages relayed this session.
+    pub messages: u64,
+    /// Unix milliseconds of the last relayed message.
+    pub last_message_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WorkerMetrics {
+    pub sent: u64,
+    /// POSTs the Brain didn't accept or never saw, after every attempt.
+    pub failed: u64,
+    /// Attempts after the first.
+    pub retried: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DeliveryMetrics {
+    pub workers: Vec<WorkerMetrics>,
+    pub queued: usize,
+    /// Most sends ever waiting at once. Can exceed `relay.queue` by the
+    /// ones workers have just taken off it.
+    pub high_water: usize,
+    /// Statuses (and sends a caller is timing) dropped on a full queue,
+    /// and sends dropped past the exit deadline.
+    pub dropped: u64,
+    /// Sends spooled because the queue was full.
+    pub overflowed: u64,
+    /// `failed`, for the sends made on an organ's behalf.
+    pub failed_by_organ: BTreeMap<String, u64>,
+    pub spool: SpoolStats,
+}
+
+/// What hasn't reached the Brain yet.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct QueueStatus {
+    /// Sends in memory, waiting for a worker or for the Brain.
+    pub queued: usize,
+    /// Sends on disk, waiting to be replayed.
+    pub spooled: usize,
+    pub pending: usize,
+    /// Age of the oldest spooled send, in seconds.
+    pub oldest_age_secs: Option<u64>,
+}
+
+impl From<&DeliveryMetrics> for QueueStatus {
+    fn from(metrics: &DeliveryMetrics) -> Self {
+        Self {
+            queued: metrics.queued,
+            spooled: metrics.spool.depth,
+            pending: metrics.queued + metrics.spool.depth,
+            oldest_age_secs: metrics.spool.oldest_age_secs,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub organs: BTreeMap<String, RelayMetrics>,
+    pub delivery: DeliveryMetrics,
+    pub dedup: DedupStats,
+    /// Messages the relay filters dropped; see `filter`.
+    pub filtered: u64,
+}
+
+#[derive(Debug, PartialEq)]
+enum Offer {
+    Send,
+    Suppress,
+    /// Changed; relay it if it still holds after the settle window.
+    Settle,
+}
+
+/// What one organ has relayed and what is waiting to settle.
+#[derive(Debug, Default)]
+struct Gate {
+    relayed: Option<(Lifecycle, Instant)>,
+    pending: Option<(Lifecycle, Instant)>,
+    /// A settle check is already scheduled.
+    armed: bool,
+    metrics: RelayMetrics,
+}
+
+impl Gate {
+    fn offer(&mut self, status: &Lifecycle, now: Instant, config: &RelayConfig) -> Offer {
+        let Some((last, at)) = &self.relayed else {
+            self.pending = None;
+            return Offer::Send;
+        };
+        if last == status {
+            // Back where we were: whatever was settling was a flap.
+            if self.pending.take().is_some() {
+                self.metrics.suppressed += 1;
+            }
+            if config.keepalive().is_some_and(|k| now.duration_since(*at) >= k) {
+                return Offer::Send;
+            }
+            self.metrics.suppressed += 1;
+            return Offer::Suppress;
+        }
+        match &self.pending {
+            Some((waiting, _)) if waiting == status => self.metrics.suppressed += 1,
+            Some(_) => {
+                self.metrics.suppressed += 1;
+                self.pending = Some((status.clone(), now));
+            }
+            None => self.pending = Some((status.clone(), now)),
+        }
+        Offer::Settle
+    }
+
+    /// The pending status, once it has held for the settle window.
+    fn settled(&mut self, now: Instant, config: &RelayConfig) -> Option<Lifecycle> {
+        let (_, since) = self.pending.as_ref()?;
+        if now.duration_since(*since) < config.settle() {
+            return None;
+        }
+        self.pending.take().map(|(status, _)| status)
+    }
+
+    fn sent(&mut self, status: &Lifecycle, now: Instant) {
+        self.relayed = Some((status.clone(), now));
+        self.metrics.relayed += 1;
+        self.metrics.last_status = Some(status.clone());
+    }
+}
+
+// ── Delivery ───────────────────────────────────────────────────
+
+/// One POST to the Brain.
+#[derive(Debug, Clone)]
+pub struct Post {
+    pub path: String,
+    pub body: serde_json::Value,
+    /// Where to report how the send went, for callers that measure it.
+    pub ack: Option<Ack>,
+    /// The organ it was made for, to count its failures against.
+    pub organ: Option<String>,
+    /// Where a batch of sends to `path` goes; see `batched`.
+    pub batch: Option<String>,
+    /// The sends a batch carries, in order; empty for a plain send.
+    members: Vec<Post>,
+}
+
+impl Post {
+    pub fn new(path: impl Into<String>, body: serde_json::Value) -> Self {
+        Self { path: path.into(), body, ack: None, organ: None, batch: None, members: Vec::new() }
+    }
+
+    /// Coalesce with other sends to the same path that arrive within
+    /// `relay.batch_window_ms`, and POST them together to `route` as a
+    /// JSON array. A batch of one goes to `path` as usual, and so does
+    /// each send of a batch the Brain has no `route` for (404).
+    pub fn batched(mut self, route: &str) -> Self {
+        self.batch = Some(route.into());
+        self
+    }
+
+    /// One POST carrying `posts`, which share a path and batch route.
+    fn coalesce(posts: Vec<Post>) -> Self {
+        let first = &posts[0];
+        let body = serde_json::Value::Array(posts.iter().map(|p| p.body.clone()).collect());
+        let route = first.batch.clone().unwrap_or_else(|| first.path.clone());
+        Self { organ: first.organ.clone(), members: posts, ..Post::new(route, body) }
+    }
+
+    /// Whether a caller is timing this send or one it carries.
+    fn measured(&self) -> bool {
+        self.ack.is_some() || self.members.iter().any(|m| m.ack.is_some())
+    }
+
+    /// Whether this carries status updates rather than messages.
+    fn is_status(&self) -> bool {
+        self.members.first().unwrap_or(self).path.ends_with("/status")
+    }
+
+    /// Sends this stands for.
+    fn weight(&self) -> u64 {
+        self.members.len().max(1) as u64
+    }
+
+    pub fn for_organ(mut self, id: &str) -> Self {
+        self.organ = Some(id.into());
+        self
+    }
+
+    pub fn acked(mut self, to: mpsc::Sender<Acked>) -> Self {
+        self.ack = Some(Ack { since: Instant::now(), to });
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Ack {
+    since: Instant,
+    to: mpsc::Sender<Acked>,
+}
+
+/// The outcome of one acked send.
+#[derive(Debug, Clone)]
+pub struct Acked {
+    /// From `acked()` to the Brain's answer (or the give-up).
+    pub latency: Duration,
+    pub error: Option<String>,
+}
+
+fn acknowledge(send: &Post, error: Option<String>) {
+    if let Some(Ack { since, to }) = &send.ack {
+        let _ = to.send(Acked { latency: since.elapsed(), error: error.clone() });
+    }
+    for member in &send.members {
+        acknowledge(member, error.clone());
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    sent: AtomicU64,
+    failed: AtomicU64,
+    retried: AtomicU64,
+}
+
+struct Shared {
+    workers: Vec<Counters>,
+    queued: AtomicUsize,
+    high_water: AtomicUsize,
+    dropped: AtomicU64,
+    overflowed: AtomicU64,
+    failed_by_organ: Mutex<BTreeMap<String, u64>>,
+    /// Set on shutdown; sends still queued after it are dropped.
+    deadline: Mutex<Option<Instant>>,
+    /// The Brain is offline; workers wait instead of sending.
+    held: Mutex<bool>,
+    released: Condvar,
+    spool: Spool,
+    /// Wakes the spool replayer; `None` once shut down.
+    kick: Mutex<Option<SyncSender<()>>>,
+    /// `None` once shut down.
+    queue: Mutex<Option<SyncSender<Post>>>,
+    /// Batched sends whose window is still open, and when it opened.
+    batches: Mutex<Vec<(Instant, Vec<Post>)>>,
+    batch_added: Condvar,
+    secret: RwLock<Option<hmac::Key>>,
+    metrics: Metrics,
+    config: RelayConfig,
+}
+
+fn key(secret: Option<&str>) -> Option<hmac::Key> {
+    secret.filter(|s| !s.is_empty()).map(|s| hmac::Key::new(hmac::HMAC_SHA256, s.as_bytes()))
+}
+
+impl Shared {
+    /// A POST of `body` to `path`, signed if there is a secret.
+    fn request(&self, path: &str, body: Vec<u8>) -> Request {
+        let secret = self.secret.read().unwrap_or_else(|e| e.into_inner());
+        let signature = secret.as_ref().map(|key| {
+            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs().to_string();
+            let mut context = hmac::Context::with_key(key);
+            context.update(timestamp.as_bytes());
+            context.update(b".");
+            context.update(&body);
+            let hex: String = context.sign().as_ref().iter().map(|b| format!("{b:02x}")).collect();
+            (timestamp, hex)
+        });
+        let request = Request::post(path, "application/json", Body::Bytes(body)).timeout(Duration::from_secs(5));
+        match signature {
+            Some((timestamp, hex)) => request.header("X-Lexicon-Timestamp", timestamp).header("X-Lexicon-Signature", hex),
+            None => request,
+        }
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        *self.deadline.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn is_held(&self) -> bool {
+        *self.held.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Block while held. False if shutdown came first and the Brain is
+    /// still offline, so the send should be dropped.
+    fn wait_released(&self) -> bool {
+        let mut held = self.held.lock().unwrap_or_else(|e| e.into_inner());
+        while *held && self.deadline().is_none() {
+            held = self.released.wait(held).unwrap_or_else(|e| e.into_inner());
+        }
+        !*held
+    }
+
+    /// Keep a send the Brain never took for `replay`; a batch stays one.
+    /// Acked sends are measured by their caller, not retried later.
+    fn spool(&self, send: Post) {
+        if send.measured() {
+            return;
+        }
+        let fallback = send.members.first().map(|m| m.path.clone());
+        self.spool.push(&send.path, send.body, fallback);
+    }
+
+    /// Put a send on the queue without waiting, or hand it back if there
+    /// is no room (or no queue any more).
+    fn enqueue(&self, send: Post) -> Option<Post> {
+        let queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(queue) = queue.as_ref() else { return Some(send) };
+        // Count it first so a worker never sees more taken than queued.
+        let queued = self.queued.fetch_add(1, Ordering::SeqCst) + 1;
+        match queue.try_send(send) {
+            Ok(()) => {
+                self.high_water.fetch_max(queued, Ordering::Relaxed);
+                None
+            }
+            Err(TrySendError::Full(send) | TrySendError::Disconnected(send)) => {
+                self.queued.fetch_sub(1, Ordering::SeqCst);
+                Some(send)
+            }
+        }
+    }
+
+    /// A send `enqueue` had no room for: spooled to go out behind the next
+    /// send that gets through, unless it is a status or measured, which is
+    /// handed back dropped.
+    fn overflow(&self, send: Post) -> Option<Post> {
+        if send.is_status() || send.measured() {
+            self.dropped.fetch_add(send.weight(), Ordering::Relaxed);
+            return Some(send);
+        }
+        tracing::debug!("relay queue full — {} to {} spooled", send.weight(), send.path);
+        self.overflowed.fetch_add(send.weight(), Ordering::Relaxed);
+        self.spool(send);
+        None
+    }
+
+    /// Add a batched send to its batch, sending the batch once full.
+    fn collect(&self, send: Post) -> bool {
+        if self.queue.lock().unwrap_or_else(|e| e.into_inner()).is_none() {
+            return false;
+        }
+        let mut batches = self.batches.lock().unwrap_or_else(|e| e.into_inner());
+        let open = batches.iter().position(|(_, posts)| posts[0].path == send.path && posts[0].batch == send.batch);
+        let index = match open {
+            Some(index) => index,
+            None => {
+                batches.push((Instant::now(), Vec::new()));
+                batches.len() - 1
+            }
+        };
+        batches[index].1.push(send);
+        if batches[index].1.len() >= self.config.batch_max.max(1) {
+            let (_, posts) = batches.remove(index);
+            drop(batches);
+            self.flush(posts);
+        } else {
+            self.batch_added.notify_all();
+        }
+        true
+    }
+
+    /// Queue a closed batch. Nobody is waiting on `submit` any more, so a
+    /// batch dropped on a full queue is reported through the acks.
+    fn flush(&self, mut posts: Vec<Post>) {
+        let send = if posts.len() == 1 { posts.remove(0) } else { Post::coalesce(posts) };
+        if let Some(dropped) = self.enqueue(send).and_then(|send| self.overflow(send)) {
+            tracing::warn!("relay queue full — batch of {} to {} dropped", dropped.weight(), dropped.path);
+            acknowledge(&dropped, Some("dropped: queue full".into()));
+        }
+    }
+
+    fn kick(&self) {
+        if let Some(kick) = self.kick.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+            let _ = kick.try_send(());
+        }
+    }
+}
+
+/// A fixed pool of sender threads behind a bounded queue.
+pub struct Delivery {
+    handles: Mutex<Vec<JoinHandle<()>>>,
+    batcher: Mutex<Option<JoinHandle<()>>>,
+    shared: Arc<Shared>,
+}
+
+impl Delivery {
+    /// Start the workers, counting what goes out in `metrics`.
+    pub fn start(host: impl Host, config: &RelayConfig, metrics: Metrics) -> Self {
+        let (queue, jobs) = mpsc::sync_channel(config.queue.max(1));
+        let jobs = Arc::new(Mutex::new(jobs));
+        let (kick, kicks) = mpsc::sync_channel(1);
+        let spool = host.data_dir().map(|dir| dir.join("relay-spool.jsonl"));
+        let size = config.workers.max(1);
+        let shared = Arc::new(Shared {
+            workers: (0..size).map(|_| Counters::default()).collect(),
+            queued: AtomicUsize::new(0),
+            high_water: AtomicUsize::new(0),
+            dropped: AtomicU64::new(0),
+            overflowed: AtomicU64::new(0),
+            failed_by_organ: Mutex::new(BTreeMap::new()),
+            deadline: Mutex::new(None),
+            held: Mutex::new(false),
+            released: Condvar::new(),
+            spool: Spool::open(spool, config.spool_max_entries, config.spool_max_bytes),
+            kick: Mutex::new(Some(kick)),
+            queue: Mutex::new(Some(queue)),
+            batches: Mutex::new(Vec::new()),
+            batch_added: Condvar::new(),
+            secret: RwLock::new(key(config.secret.as_deref())),
+            metrics,
+            config: config.clone(),
+        });
+        let mut handles: Vec<_> = (0..size)
+            .map(|n| {
+                let (host, jobs, shared) = (host.clone(), jobs.clone(), shared.clone());
+                std::thread::Builder::new()
+                    .name(format!("relay-{n}"))
+                    .spawn(move || work(n, &host, &jobs, &shared))
+                    .expect("spawn relay worker")
+            })
+            .collect();
+        let replayer = shared.clone();
+        handles.push(
+            std::thread::Builder::new()
+                .name("relay-spool".into())
+                .spawn(move || replay(&host, &kicks, &replayer))
+                .expect("spawn relay spool"),
+        );
+        let batching = shared.clone();
+        let batcher = std::thread::Builder::new()
+            .name("relay-batch".into())
+            .spawn(move || close_batches(&batching))
+            .expect("spawn relay batcher");
+        // Whatever an earlier run left behind.
+        shared.kick();
+        Self { handles: Mutex::new(handles), batcher: Mutex::new(Some(batcher)), shared }
+    }
+
+    /// Queue a send without waiting, or spool it if the queue is full.
+    /// False if it was dropped: a status or measured send on a full queue,
+    /// or anything once shutdown has begun. A batched send is only
+    /// collected here; if its batch is dropped later, its ack says so.
+    pub fn submit(&self, send: Post) -> bool {
+        if self.shared.deadline().is_some() {
+            return false;
+        }
+        if send.batch.is_some() {
+            return self.shared.collect(send);
+        }
+        self.shared.enqueue(send).and_then(|send| self.shared.overflow(send)).is_none()
+    }
+
+    /// Keep a send for replay instead of queueing it, as if the Brain had
+    /// missed it; it goes out behind the next send that gets through.
+    pub fn defer(&self, send: Post) {
+        self.shared.spool(send);
+    }
+
+    /// Sign sends from now on with `secret`; `None` or empty stops signing.
+    pub fn set_secret(&self, secret: Option<&str>) {
+        *self.shared.secret.write().unwrap_or_else(|e| e.into_inner()) = key(secret);
+    }
+
+    /// Hold queued sends while the Brain is offline, or deliver them again.
+    pub fn hold(&self, held: bool) {
+        *self.shared.held.lock().unwrap_or_else(|e| e.into_inner()) = held;
+        self.shared.released.notify_all();
+        if !held {
+            self.shared.kick();
+        }
+    }
+
+    /// POST `body` to `path` once, now, signed like the rest, giving the
+    /// Brain `timeout` to answer. For what goes out after the workers stop.
+    pub fn post_now(
+        &self,
+        host: &impl Host,
+        path: &str,
+        body: &serde_json::Value,
+        timeout: Duration,
+    ) -> Result<(), TransportError> {
+        let request = self.shared.request(path, body.to_string().into_bytes()).timeout(timeout);
+        crate::brain::call(host, request).map(|_| ())
+    }
+
+    /// Stop taking sends, deliver what is queued until `deadline` passes,
+    /// then join the workers. Held sends are dropped.
+    pub fn shutdown(&self, deadline: Duration) {
+        *self.shared.deadline.lock().unwrap_or_else(|e| e.into_inner()) = Some(Instant::now() + deadline);
+        {
+            // Under the lock, so a worker about to wait can't miss it.
+            let _held = self.shared.held.lock().unwrap_or_else(|e| e.into_inner());
+            self.shared.released.notify_all();
+        }
+        {
+            let _batches = self.shared.batches.lock().unwrap_or_else(|e| e.into_inner());
+            self.shared.batch_added.notify_all();
+        }
+        // Open batches go out before the queue closes.
+        if let Some(batcher) = self.batcher.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            let _ = batcher.join();
+        }
+        drop(self.shared.kick.lock().unwrap_or_else(|e| e.into_inner()).take());
+        drop(self.shared.queue.lock().unwrap_or_else(|e| e.into_inner()).take());
+        let handles = std::mem::take(&mut *self.handles.lock().unwrap_or_else(|e| e.into_inner()));
+        for handle in handles {
+            let _ = handle.join();
+        }
+    }
+
+    pub fn metrics(&self) -> DeliveryMetrics {
+        let shared = &self.shared;
+        DeliveryMetrics {
+            workers: shared
+                .workers
+                .iter()
+                .map(|c| WorkerMetrics {
+                    sent: c.sent.load(Ordering::Relaxed),
+                    failed: c.failed.load(Ordering::Relaxed),
+                    retried: c.retried.load(Ordering::Relaxed),
+                })
+                .collect(),
+            queued: shared.queued.load(Ordering::SeqCst),
+            high_water: shared.high_water.load(Ordering::Relaxed),
+            dropped: shared.dropped.load(Ordering::Relaxed),
+            overflowed: shared.overflowed.load(Ordering::Relaxed),
+            failed_by_organ: shared.failed_by_organ.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+            spool: shared.spool.stats(),
+        }
+    }
+}
+
+/// POST `send`, retrying what the Brain missed. Gives up early once the
+/// exit deadline would pass before the next try.
+fn deliver(host: &impl Host, send: &Post, shared: &Shared, counters: &Counters) -> Result<(), TransportError> {
+    let body = send.body.to_string().into_bytes();
+    let mut attempt = 1;
+    loop {
+        let started = Instant::now();
+        let answer = crate::brain::call(host, shared.request(&send.path, body.clone()));
+        let status = match &answer {
+            Ok(_) => Some(200),
+            Err(TransportError::Status(code)) => Some(*code),
+            Err(_) => None,
+        };
+        shared.metrics.posted(body.len(), status, started.elapsed());
+        let error = match answer {
+            Ok(_) => return Ok(()),
+            Err(e) => e,
+        };
+        if retryable(&error) && shared.is_held() {
+            // Went offline mid-send: wait for the Brain, not the backoff.
+            if !shared.wait_released() {
+                return Err(error);
+            }
+            continue;
+        }
+        let wait = shared.config.backoff(attempt);
+        let past_deadline = shared.deadline().is_some_and(|d| Instant::now() + wait >= d);
+        if attempt >= shared.config.attempts || !retryable(&error) || past_deadline {
+            return Err(error);
+        }
+        std::thread::sleep(wait);
+        counters.retried.fetch_add(1, Ordering::Relaxed);
+        attempt += 1;
+    }
+}
+
+fn work(n: usize, host: &impl Host, jobs: &Mutex<Receiver<Post>>, shared: &Shared) {
+    loop {
+        let next = jobs.lock().unwrap_or_else(|e| e.into_inner()).recv();
+        let Ok(send) = next else { return };
+        shared.queued.fetch_sub(1, Ordering::SeqCst);
+        if shared.deadline().is_some_and(|d| Instant::now() >= d) {
+            shared.dropped.fetch_add(send.weight(), Ordering::Relaxed);
+            acknowledge(&send, Some("dropped at exit".into()));
+            shared.spool(send);
+            continue;
+        }
+        if !shared.wait_released() {
+            shared.dropped.fetch_add(send.weight(), Ordering::Relaxed);
+            acknowledge(&send, Some("dropped at exit, Brain offline".into()));
+            shared.spool(send);
+            continue;
+        }
+        finish(host, send, shared, &shared.workers[n]);
+    }
+}
+
+/// Deliver one send and account for how it went.
+fn finish(host: &impl Host, send: Post, shared: &Shared, counters: &Counters) {
+    match deliver(host, &send, shared, counters) {
+        Ok(_) => {
+            // Acked sends are measured by their caller; don't flood the log.
+            if send.measured() {
+                tracing::debug!(path = %send.path, "relayed");
+            } else {
+                tracing::info!(path = %send.path, "relayed");
+            }
+            counters.sent.fetch_add(1, Ordering::Relaxed);
+            shared.metrics.relayed(send.is_status(), true, send.weight());
+            acknowledge(&send, None);
+            // The Brain is taking sends again.
+            shared.kick();
+        }
+        Err(TransportError::Status(404)) if !send.members.is_empty() => {
+            // No batch route on this Brain: one at a time, in order.
+            for member in send.members {
+                finish(host, member, shared, counters);
+            }
+        }
+        Err(e) => {
+            let status = match &e {
+                TransportError::Status(code) => Some(*code),
+                _ => None,
+            };
+            if send.measured() {
+                tracing::debug!(path = %send.path, status, "relay failed: {e}");
+            } else {
+                tracing::warn!(path = %send.path, status, "relay failed: {e}");
+            }
+            counters.failed.fetch_add(1, Ordering::Relaxed);
+            shared.metrics.relayed(send.is_status(), false, send.weight());
+            if let Some(organ) = &send.organ {
+                *shared.failed_by_organ.lock().unwrap_or_else(|e| e.into_inner()).entry(organ.clone()).or_default() += 1;
+            }
+            acknowledge(&send, Some(e.to_string()));
+            if retryable(&e) {
+                shared.spool(send);
+            }
+        }
+    }
+}
+
+/// Send batches whose window has closed; on shutdown, all of them.
+fn close_batches(shared: &Shared) {
+    let window = shared.config.batch_window();
+    let mut batches = shared.batches.lock().unwrap_or_else(|e| e.into_inner());
+    loop {
+        let closing = shared.deadline().is_some();
+        let now = Instant::now();
+        let (due, open): (Vec<_>, Vec<_>) =
+            std::mem::take(&mut *batches).into_iter().partition(|(since, _)| closing || now.duration_since(*since) >= window);
+        *batches = open;
+        if !due.is_empty() {
+            drop(batches);
+            for (_, posts) in due {
+                shared.flush(posts);
+            }
+            batches = shared.batches.lock().unwrap_or_else(|e| e.into_inner());
+            continue;
+        }
+        if closing {
+            return;
+        }
+        let next = batches.iter().map(|(since, _)| (*since + window).saturating_duration_since(now)).min();
+        batches = match next {
+            Some(wait) => shared.batch_added.wait_timeout(batches, wait).unwrap_or_else(|e| e.into_inner()).0,
+            None => shared.batch_added.wait(batches).unwrap_or_else(|e| e.into_inner()),
+        };
+    }
+}
+
+fn post(host: &impl Host, shared: &Shared, path: &str, body: &serde_json::Value) -> Result<(), TransportError> {
+    crate::brain::call(host, shared.request(path, body.to_string().into_bytes())).map(|_| ())
+}
+
+/// Send spooled posts, oldest first, each time it is kicked. Stops at the
+/// first one the Brain misses; one it refuses outright would be refused
+/// forever, so it goes. A batch the Brain has no route for is sent item
+/// by item.
+fn replay(host: &impl Host, kicks: &Receiver<()>, shared: &Shared) {
+    while kicks.recv().is_ok() {
+        while let Some(entry) = shared.spool.front() {
+            if shared.deadline().is_some() || shared.is_held() {
+                break;
+            }
+            let mut result = post(host, shared, &entry.path, &entry.body);
+            if let (Err(TransportError::Status(404)), Some(single), Some(items)) =
+                (&result, &entry.fallback, entry.body.as_array())
+            {
+                result = items.iter().try_for_each(|item| match post(host, shared, single, item) {
+                    Err(e) if retryable(&e) => Err(e),
+                    _ => Ok(()),
+                });
+            }
+            match result {
+                Ok(()) => shared.spool.remove(entry.id),
+                Err(e) if retryable(&e) => break,
+                Err(e) => {
+                    tracing::warn!("relay spool: {} refused ({e}) — dropping it", entry.path);
+                    shared.spool.remove(entry.id);
+                }
+            }
+        }
+    }
+}
+
+// ── Status ─────────────────────────────────────────────────────
+
+/// The app version relayed with every send.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The last `seq` given out, by organ id.
+static SEQ: Mutex<BTreeMap<String, u64>> = Mutex::new(BTreeMap::new());
+
+/// The next `seq` for organ `id`.
+fn next_seq(id: &str) -> u64 {
+    let mut seqs = SEQ.lock().unwrap_or_else(|e| e.into_inner());
+    let seq = seqs.entry(id.to_string()).or_default();
+    *seq += 1;
+    *seq
+}
+
+/// The body of a status POST. An error's text is whatever the organ's
+/// page reported, so it goes through serde like any other untrusted string.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+pub struct StatusUpdate {
+    pub organ: String,
+    /// Which login to `organ`, when there are several.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    pub status: Lifecycle,
+    /// Unix milliseconds when it was relayed.
+    pub timestamp: u64,
+    /// See the module docs; 0 until it is relayed.
+    #[serde(default)]
+    pub seq: u64,
+    #[serde(default)]
+    pub version: String,
+}
+
+impl StatusUpdate {
+    pub fn new(organ: &str, status: Lifecycle) -> Self {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+        Self { organ: organ.into(), account: None, status, timestamp, seq: 0, version: VERSION.into() }
+    }
+}
+
+/// Managed state: one gate per organ.
+#[derive(Default)]
+pub struct StatusRelay(Mutex<BTreeMap<String, Gate>>);
+
+impl StatusRelay {
+    fn with<T>(&self, id: &str, f: impl FnOnce(&mut Gate) -> T) -> T {
+        f(self.0.lock().unwrap_or_else(|e| e.into_inner()).entry(id.to_string()).or_default())
+    }
+}
+
+/// The update for organ `id` having `status`, and where it goes.
+fn update(app: &tauri::AppHandle, id: &str, status: Lifecycle) -> (String, StatusUpdate) {
+    let manager = app.state::<crate::organs::OrganManager>();
+    let (service, account) = manager.get(id).map_or((id, None), |def| (def.service(), def.account.clone()));
+    (format!("/{service}/status"), StatusUpdate { account, ..StatusUpdate::new(service, status) })
+}
+
+fn send(app: &tauri::AppHandle, id: &str, status: Lifecycle) {
+    let (path, mut update) = update(app, id, status);
+    if !app.state::<RateLimiter>().allow(id, Endpoint::of(&path), Instant::now()) {
+        return;
+    }
+    update.seq = next_seq(id);
+    app.state::<StatusRelay>().with(id, |gate| {
+        gate.sent(&update.status, Instant::now());
+        gate.metrics.last_relayed_at = Some(update.timestamp);
+    });
+    crate::lifecycle::enter(app, id, &update.status, update.timestamp);
+    let _ = app.emit_to("main", &format!("{id}://status"), &update);
+    let send = Post::new(path, serde_json::to_value(&update).unwrap_or_default()).for_organ(id);
+    if !app.state::<Delivery>().submit(send) {
+        tracing::warn!("relay queue full — {id} status dropped");
+    }
+}
+
+/// Wait out the settle window, then relay whatever has settled. One of
+/// these per organ at a time; a newer status just moves the finish line.
+fn settle(app: tauri::AppHandle, id: String, config: RelayConfig) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(config.settle());
+        let next = app.state::<StatusRelay>().with(&id, |gate| {
+            if let Some(status) = gate.settled(Instant::now(), &config) {
+                gate.armed = false;
+                return Some(Some(status));
+            }
+            if gate.pending.is_none() {
+                gate.armed = false;
+                return Some(None);
+            }
+            None
+        });
+        match next {
+            Some(Some(status)) => return send(&app, &id, status),
+            Some(None) => return,
+            None => continue,
+        }
+    });
+}
+
+/// An organ's page reported `status`.
+pub fn report(app: &tauri::AppHandle, id: &str, status: &str) {
+    match Lifecycle::parse(status) {
+        Some(state) => report_state(app, id, state),
+        None => tracing::warn!("organ {id} reported an unknown status {status:?} — dropped"),
+    }
+}
+
+/// Organ `id` is in `status`.
+pub fn report_state(app: &tauri::AppHandle, id: &str, status: Lifecycle) {
+    let config = crate::config::current(app).relay;
+    let (offer, arm) = app.state::<StatusRelay>().with(id, |gate| {
+        let offer = gate.offer(&status, Instant::now(), &config);
+        let arm = offer == Offer::Settle && !std::mem::replace(&mut gate.armed, true);
+        (offer, arm)
+    });
+    match offer {
+        Offer::Send => send(app, id, status),
+        Offer::Suppress => {}
+        Offer::Settle if arm => settle(app.clone(), id.to_string(), config),
+        Offer::Settle => {}
+    }
+}
+
+/// The organ's page is reloading; relay its next report whatever it is.
+pub fn reset(app: &tauri::AppHandle, id: &str) {
+    app.state::<StatusRelay>().with(id, |gate| {
+        gate.relayed = None;
+        gate.pending = None;
+    });
+    crate::lifecycle::booting(app, id);
+}
+
+pub fn init(app: &tauri::AppHandle) {
+    app.manage(StatusRelay::default());
+    app.manage(Dedup::default());
+    crate::filter::init(app);
+    crate::ratelimit::init(app);
+    let mut config = crate::config::current(app).relay;
+    if let Ok(secret) = std::env::var(SECRET_ENV) {
+        config.secret = Some(secret);
+    }
+    let metrics = crate::metrics::init(app);
+    app.manage(Delivery::start(app.clone(), &config, metrics));
+}
+
+/// Sign relay sends with `secret` until the app exits; `None` stops. The
+/// config file is left alone. Only the canvas may set it.
+#[tauri::command]
+pub fn set_relay_secret(window: tauri::WebviewWindow, app: tauri::AppHandle, secret: Option<String>) -> Result<(), String> {
+    if window.label() != "main" {
+        return Err("only the main window can set the relay secret".into());
+    }
+    app.state::<Delivery>().set_secret(secret.as_deref());
+    Ok(())
+}
+
+#[tauri::command]
+pub fn spool_stats(app: tauri::AppHandle) -> SpoolStats {
+    app.state::<Delivery>().metrics().spool
+}
+
+#[tauri::command]
+pub fn relay_queue_status(app: tauri::AppHandle) -> QueueStatus {
+    QueueStatus::from(&app.state::<Delivery>().metrics())
+}
+
+/// Drain the queue, stop the workers and say goodbye, in a few seconds at
+/// most however the Brain is doing.
+pub fn shutdown(app: &tauri::AppHandle) {
+    if app.try_state::<Delivery>().is_none() {
+        return;
+    }
+    let (done, drained) = mpsc::channel();
+    let draining = app.clone();
+    std::thread::spawn(move || {
+        draining.state::<Delivery>().shutdown(DRAIN_DEADLINE);
+        let _ = done.send(());
+    });
+    if drained.recv_timeout(DRAIN_DEADLINE + DRAIN_GRACE).is_err() {
+        tracing::warn!("relay still sending at the exit deadline — leaving it behind");
+    }
+    farewell(app);
+}
+
+/// Relay `"shutdown"` for every organ that relayed a status this session,
+/// straight to the Brain, all at once.
+fn farewell(app: &tauri::AppHandle) {
+    let ids: Vec<String> = {
+        let gates = app.state::<StatusRelay>();
+        let gates = gates.0.lock().unwrap_or_else(|e| e.into_inner());
+        gates.iter().filter(|(_, gate)| gate.relayed.is_some()).map(|(id, _)| id.clone()).collect()
+    };
+    let (done, answered) = mpsc::channel();
+    for id in &ids {
+        let (path, mut update) = update(app, id, Lifecycle::ShutDown);
+        update.seq = next_seq(id);
+        let body = serde_json::to_value(&update).unwrap_or_default();
+        let (app, done, id) = (app.clone(), done.clone(), id.clone());
+        std::thread::spawn(move || {
+            let result = app.state::<Delivery>().post_now(&app, &path, &body, FAREWELL_TIMEOUT);
+            let _ = done.send((id, result));
+        });
+    }
+    let until = Instant::now() + FAREWELL_TIMEOUT + DRAIN_GRACE;
+    for _ in &ids {
+        match answered.recv_timeout(until.saturating_duration_since(Instant::now())) {
+            Ok((id, Ok(()))) => tracing::info!("told the Brain {id} is shutting down"),
+            Ok((id, Err(e))) => tracing::warn!("could not tell the Brain {id} is shutting down: {e}"),
+            Err(_) => {
+                tracing::warn!("the Brain did not take the shutdown statuses in time");
+                return;
+            }
+        }
+    }
+}
+
+/// Count a message relayed for `organ`.
+pub fn message_relayed(app: &tauri::AppHandle, organ: &str) {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+    app.state::<StatusRelay>().with(organ, |gate| {
+        gate.metrics.messages += 1;
+        gate.metrics.last_message_at = Some(now);
+    });
+}
+
+/// What has been relayed for one organ this session, and how many of its
+/// POSTs the Brain never took.
+pub fn organ_metrics(app: &tauri::AppHandle, id: &str) -> (RelayMetrics, u64) {
+    let metrics = app.state::<StatusRelay>().0.lock().unwrap_or_else(|e| e.into_inner()).get(id).map(|g| g.metrics.clone());
+    let failed = app.state::<Delivery>().metrics().failed_by_organ.get(id).copied().unwrap_or(0);
+    (metrics.unwrap_or_default(), failed)
+}
+
+#[cfg_attr(mobile, allow(dead_code))]
+pub fn metrics(app: &tauri::AppHandle) -> Report {
+    let gates = app.state::<StatusRelay>();
+    let gates = gates.0.lock().unwrap_or_else(|e| e.into_inner());
+    Report {
+        organs: gates.iter().map(|(id, gate)| (id.clone(), gate.metrics.clone())).collect(),
+        delivery: app.state::<Delivery>().metrics(),
+        dedup: app.state::<Dedup>().stats(),
+        filtered: app.state::<crate::filter::Filters>().filtered(),
+    }
+}
+
+/// A relayed message, as the main window hears of it: the organ's own
+/// body, such as a `whatsapp::WaMessage`, and what the relay added to it.
+#[derive(Debug, Clone, Serialize)]
+pub struct RelayedMessage<T> {
+    pub organ: String,
+    /// The Brain route it went to.
+    pub path: String,
+    pub seq: u64,
+    /// An edit of one relayed before.
+    pub edited: bool,
+    pub message: T,
+}
+
+/// Told of each relayed message that isn't a repeat or an edit.
+pub type Announce<'a> = Box<dyn Fn(&str, &serde_json::Value) + 'a>;
+
+/// What [`message`] goes through: the app's filters, dedup, rate limits
+/// and delivery, and who announces a new message (`notify` and, on
+/// Linux, `dbus`). Borrowed from the app by `of`; tests build one around
+/// a `testing::TestRelay`.
+pub struct Messages<'a> {
+    pub filters: &'a crate::filter::Filters,
+    pub dedup: &'a Dedup,
+    pub limiter: &'a RateLimiter,
+    pub delivery: &'a Delivery,
+    pub metrics: &'a Metrics,
+    pub announce: Announce<'a>,
+}
+
+impl<'a> Messages<'a> {
+    pub fn of(app: &'a tauri::AppHandle) -> Self {
+        Self {
+            filters: app.state::<crate::filter::Filters>().inner(),
+            dedup: app.state::<Dedup>().inner(),
+            limiter: app.state::<RateLimiter>().inner(),
+            delivery: app.state::<Delivery>().inner(),
+            metrics: app.state::<Metrics>().inner(),
+            announce: Box::new(move |organ, body| {
+                crate::notify::relayed(app, organ, body);
+                #[cfg(target_os = "linux")]
+                crate::dbus::relayed(organ, body);
+            }),
+        }
+    }
+
+    /// [`message`], with `host`'s config.
+    pub fn relay<T: Serialize>(&self, host: &impl Host, organ: &str, path: &str, message: &T) -> bool {
+        let mut body = match serde_json::to_value(message) {
+            Ok(body) => body,
+            Err(e) => {
+                tracing::warn!("{organ} message to {path} didn't serialize ({e}) — dropped");
+                return false;
+            }
+        };
+        if !self.filters.pass(&body) {
+            self.metrics.filtered();
+            return false;
+        }
+        let config = host.config().relay;
+        let ttl = Duration::from_secs(config.dedup_ttl_secs);
+        let edited = match self.dedup.check(organ, &body, config.dedup_capacity, ttl, Instant::now()) {
+            Seen::Repeat => {
+                self.metrics.deduped();
+                return false;
+            }
+            Seen::Edited => {
+                if let Some(fields) = body.as_object_mut() {
+                    fields.insert("edited".into(), true.into());
+                }
+                true
+            }
+            Seen::New | Seen::Unkeyed => {
+                (self.announce)(organ, &body);
+                false
+            }
+        };
+        crate::idle::touch(organ);
+        let seq = next_seq(organ);
+        if let Some(fields) = body.as_object_mut() {
+            fields.insert("seq".into(), seq.into());
+            fields.insert("version".into(), VERSION.into());
+        }
+        if !host.presenting() {
+            let event = RelayedMessage { organ: organ.into(), path: path.into(), seq, edited, message };
+            host.publish_to("main", &format!("{organ}://message"), &event);
+        }
+        let endpoint = Endpoint::of(path);
+        let mut send = Post::new(path, body).for_organ(organ);
+        if endpoint == Endpoint::Messages {
+            send = send.batched(&format!("{path}s"));
+        }
+        if !self.limiter.allow(organ, endpoint, Instant::now()) {
+            self.delivery.defer(send);
+        } else if !self.delivery.submit(send) {
+            tracing::warn!("{organ} message to {path} dropped: the relay is shutting down");
+        }
+        true
+    }
+}
+
+/// Relay a message `organ` saw to `path` (batched to `{path}s`), unless
+/// the relay filters drop it or the same one went out already. An edit of
+/// one that did goes out with `"edited": true`. False if it was dropped.
+pub fn message<T: Serialize>(app: &tauri::AppHandle, organ: &str, path: &str, message: &T) -> bool {
+    let relayed = Messages::of(app).relay(app, organ, path, message);
+    if relayed {
+        message_relayed(app, organ);
+    }
+    relayed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{MockBrain, Reply, TestHost};
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    fn config() -> RelayConfig {
+        RelayConfig { keepalive_secs: 300, settle_ms: 2000, ..RelayConfig::default() }
+    }
+
+    fn relayed(gate: &mut Gate, status: &Lifecycle, now: Instant) {
+        assert_eq!(gate.offer(status, now, &config()), Offer::Send);
+        gate.sent(status, now);
+    }
+
+    #[test]
+    fn repeats_are_suppressed_until_the_keepalive() {
+        let mut gate = Gate::default();
+        let start = Instant::now();
+        relayed(&mut gate, &Lifecycle::LoggedIn, start);
+        for i in 1..=10 {
+            assert_eq!(gate.offer(&Lifecycle::LoggedIn, start + i * SECOND, &config()), Offer::Suppress);
+        }
+        assert_eq!(gate.offer(&Lifecycle::LoggedIn, start + 300 * SECOND, &config()), Offer::Send);
+        assert_eq!(gate.metrics.suppressed, 10);
+    }
+
+    #[test]
+    fn changes_settle_and_flaps_are_dropped() {
+        let mut gate = Gate::default();
+        let start = Instant::now();
+        relayed(&mut gate, &Lifecycle::LoggedIn, start);
+
+        // Flap: gone and back inside the settle window.
+        assert_eq!(gate.offer(&Lifecycle::Disconnected, start + SECOND, &config()), Offer::Settle);
+        assert_eq!(gate.offer(&Lifecycle::LoggedIn, start + 2 * SECOND, &config()), Offer::Suppress);
+        assert_eq!(gate.settled(start + 4 * SECOND, &config()), None);
+
+        // A real change, reported repeatedly, goes out once it has held.
+        assert_eq!(gate.offer(&Lifecycle::Disconnected, start + 10 * SECOND, &config()), Offer::Settle);
+        assert_eq!(gate.offer(&Lifecycle::Disconnected, start + 11 * SECOND, &config()), Offer::Settle);
+        assert_eq!(gate.settled(start + 11 * SECOND, &config()), None);
+        assert_eq!(gate.settled(start + 12 * SECOND, &config()), Some(Lifecycle::Disconnected));
+    }
+
+    #[test]
+    fn a_newer_status_restarts_the_settle_window() {
+        let mut gate = Gate::default();
+        let start = Instant::now();
+        relayed(&mut gate, &Lifecycle::LoggedIn, start);
+        gate.offer(&Lifecycle::Disconnected, start + SECOND, &config());
+        gate.offer(&Lifecycle::QrPending, start + 2 * SECOND, &config());
+        assert_eq!(gate.settled(start + 3 * SECOND, &config()), None);
+        assert_eq!(gate.settled(start + 4 * SECOND, &config()), Some(Lifecycle::QrPending));
+    }
+
+    #[test]
+    fn first_report_after_a_reset_goes_through() {
+        let mut gate = Gate::default();
+        let start = Instant::now();
+        relayed(&mut gate, &Lifecycle::LoggedIn, start);
+        gate.relayed = None;
+        relayed(&mut gate, &Lifecycle::LoggedIn, start + SECOND);
+        assert_eq!(gate.metrics.relayed, 2);
+    }
+
+    #[test]
+    fn ten_thousand_sends_stay_within_the_pool() {
+        let brain = MockBrain::start();
+        brain.always("/whatsapp/status", Reply::status(200));
+        let host = TestHost::new().with_brain(&brain);
+        let config = RelayConfig { workers: 4, queue: 64, ..RelayConfig::default() };
+        let counted = Metrics::default();
+        let delivery = Delivery::start(host, &config, counted.clone());
+
+        let total = 10_000;
+        for i in 0..total {
+            let send = Post::new("/whatsapp/status", serde_json::json!({ "n": i }));
+            while !delivery.submit(send.clone()) {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+        }
+        delivery.shutdown(Duration::from_secs(60));
+
+        let metrics = delivery.metrics();
+        assert_eq!(metrics.workers.len(), 4);
+        assert!(metrics.high_water <= 64 + 4, "queue grew to {}", metrics.high_water);
+        assert_eq!(metrics.queued, 0);
+        let sent: u64 = metrics.workers.iter().map(|w| w.sent).sum();
+        let failed: u64 = metrics.workers.iter().map(|w| w.failed).sum();
+        assert_eq!(sent + failed, total);
+        assert_eq!(failed, 0);
+        assert_eq!(brain.requests("/whatsapp/status").len(), total as usize);
+        assert!(!delivery.submit(Post::new("/whatsapp/status", serde_json::Value::Null)));
+        let counts = counted.counts();
+        assert_eq!((counts.statuses.ok, counts.messages.ok), (total, 0));
+        assert_eq!(counts.last_brain_answer.map(|a| a.status), Some(200));
+        assert!(counts.bytes_posted >= total * 7);
+    }
+
+    fn retrying(attempts: u32) -> RelayConfig {
+        RelayConfig { workers: 1, queue: 8, attempts, backoff_ms: 10, ..RelayConfig::default() }
+    }
+
+    #[test]
+    fn status_bodies_survive_quotes_emoji_and_newlines() {
+        let brain = MockBrain::start();
+        brain.always("/whatsapp/status", Reply::status(200));
+        let delivery = Delivery::start(TestHost::new().with_brain(&brain), &retrying(1), Metrics::default());
+        let statuses = ["say \"connected\"", "back\\slash", "📱 online ✅", "line one\nline two\r\n\ttab"];
+        for status in statuses {
+            let update = serde_json::to_value(StatusUpdate::new("whatsapp", Lifecycle::Error(status.into()))).unwrap();
+            assert!(delivery.submit(Post::new("/whatsapp/status", update)));
+        }
+        delivery.shutdown(Duration::from_secs(10));
+
+        let received: Vec<StatusUpdate> =
+            brain.requests("/whatsapp/status").iter().map(|r| serde_json::from_slice(&r.body).unwrap()).collect();
+        let errors: Vec<_> = statuses.iter().map(|s| Lifecycle::Error(s.to_string())).collect();
+        assert_eq!(received.iter().map(|u| u.status.clone()).collect::<Vec<_>>(), errors);
+        assert!(received.iter().all(|u| u.organ == "whatsapp" && u.timestamp > 0 && u.version == VERSION));
+    }
+
+    #[test]
+    fn each_organ_numbers_its_sends() {
+        assert_eq!((next_seq("seq-test"), next_seq("seq-test")), (1, 2));
+        assert_eq!(next_seq("seq-test-work"), 1);
+        assert_eq!(next_seq("seq-test"), 3);
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn status_updates_round_trip(update in proptest::prelude::any::<StatusUpdate>()) {
+            let text = serde_json::to_string(&update).unwrap();
+            proptest::prop_assert_eq!(serde_json::from_str::<StatusUpdate>(&text).unwrap(), update);
+        }
+    }
+
+    #[test]
+    fn sends_are_signed_with_the_secret() {
+        let brain = MockBrain::start();
+        brain.always("/whatsapp/status", Reply::status(200));
+        let config = RelayConfig { secret: Some("hunter2".into()), ..retrying(1) };
+        let delivery = Delivery::start(TestHost::new().with_brain(&brain), &config, Metrics::default());
+        assert!(delivery.submit(Post::new("/whatsapp/status", serde_json::json!({ "status": "connected" }))));
+        delivery.shutdown(Duration::from_secs(10));
+
+        let request = &brain.requests("/whatsapp/status")[0];
+        let timestamp: u64 = request.header("x-lexicon-timestamp").unwrap().parse().unwrap();
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        assert!(now.abs_diff(timestamp) < 60);
+        let signature = request.header("x-lexicon-signature").unwrap();
+        let tag: Vec<u8> = (0..signature.len()).step_by(2).map(|i| u8::from_str_radix(&signature[i..i + 2], 16).unwrap()).collect();
+        let signed = [format!("{timestamp}.").into_bytes(), request.body.clone()].concat();
+        let key = hmac::Key::new(hmac::HMAC_SHA256, b"hunter2");
+        assert!(hmac::verify(&key, &signed, &tag).is_ok());
+        assert!(hmac::verify(&hmac::Key::new(hmac::HMAC_SHA256, b"hunter3"), &signed, &tag).is_err());
+    }
+
+    #[test]
+    fn without_a_secret_nothing_is_signed() {
+        let brain = MockBrain::start();
+        brain.always("/whatsapp/status", Reply::status(200));
+        let delivery = Delivery::start(TestHost::new().with_brain(&brain), &RelayConfig { secret: Some(String::new()), ..retrying(1) }, Metrics::default());
+        assert!(delivery.submit(Post::new("/whatsapp/status", serde_json::Value::Null)));
+        delivery.shutdown(Duration::from_secs(10));
+        let request = &brain.requests("/whatsapp/status")[0];
+        assert!(request.header("x-lexicon-signature").is_none() && request.header("x-lexicon-timestamp").is_none());
+    }
+
+    #[test]
+    fn backoff_doubles() {
+        let config = RelayConfig { backoff_ms: 500, ..RelayConfig::default() };
+        assert_eq!([1, 2, 3].map(|n| config.backoff(n).as_millis()), [500, 1000, 2000]);
+    }
+
+    #[test]
+    fn missed_sends_are_retried_until_the_brain_takes_them() {
+        let brain = MockBrain::start();
+        brain.script("/whatsapp/status", [Reply::status(503), Reply::Drop, Reply::status(200)]);
+        let delivery = Delivery::start(TestHost::new().with_brain(&brain), &retrying(3), Metrics::default());
+        assert!(delivery.submit(Post::new("/whatsapp/status", serde_json::Value::Null)));
+        delivery.shutdown(Duration::from_secs(10));
+
+        let worker = &delivery.metrics().workers[0];
+        assert_eq!((worker.sent, worker.failed, worker.retried), (1, 0, 2));
+        assert_eq!(brain.requests("/whatsapp/status").len(), 3);
+    }
+
+    #[test]
+    fn retries_stop_at_the_limit_and_on_refusals() {
+        let brain = MockBrain::start();
+        brain.always("/whatsapp/status", Reply::status(500));
+        brain.always("/whatsapp/bad", Reply::status(400));
+        let delivery = Delivery::start(TestHost::new().with_brain(&brain), &retrying(3), Metrics::default());
+        assert!(delivery.submit(Post::new("/whatsapp/status", serde_json::Value::Null).for_organ("whatsapp")));
+        assert!(delivery.submit(Post::new("/whatsapp/bad", serde_json::Value::Null)));
+        delivery.shutdown(Duration::from_secs(10));
+
+        let metrics = delivery.metrics();
+        let worker = &metrics.workers[0];
+        assert_eq!((worker.sent, worker.failed, worker.retried), (0, 2, 2));
+        assert_eq!(metrics.failed_by_organ, BTreeMap::from([("whatsapp".to_string(), 1)]));
+        assert_eq!(brain.requests("/whatsapp/status").len(), 3);
+        assert_eq!(brain.requests("/whatsapp/bad").len(), 1);
+    }
+
+    #[test]
+    fn sends_past_the_exit_deadline_are_dropped() {
+        let brain = MockBrain::start();
+        brain.always("/whatsapp/status", Reply::delay(Duration::from_millis(200), Reply::status(200)));
+        let host = TestHost::new().with_brain(&brain);
+        let delivery = Delivery::start(host, &RelayConfig { workers: 1, queue: 8, ..RelayConfig::default() }, Metrics::default());
+        for _ in 0..8 {
+            assert!(delivery.submit(Post::new("/whatsapp/status", serde_json::Value::Null)));
+        }
+        delivery.shutdown(Duration::from_millis(300));
+        let metrics = delivery.metrics();
+        assert!(metrics.dropped > 0);
+        assert_eq!(metrics.workers[0].sent + metrics.dropped, 8);
+    }
+
+    #[test]
+    fn a_hung_brain_cannot_hold_up_the_last_status() {
+        let brain = MockBrain::start();
+        brain.always("/whatsapp/status", Reply::delay(Duration::from_secs(5), Reply::status(200)));
+        let host = TestHost::new().with_brain(&brain);
+        let delivery = Delivery::start(host.clone(), &retrying(1), Metrics::default());
+        delivery.shutdown(Duration::from_secs(1));
+        assert!(!delivery.submit(Post::new("/whatsapp/status", serde_json::Value::Null)));
+
+        let started = Instant::now();
+        let body = serde_json::to_value(StatusUpdate::new("whatsapp", Lifecycle::ShutDown)).unwrap();
+        let result = delivery.post_now(&host, "/whatsapp/status", &body, Duration::from_millis(200));
+        assert!(result.is_err());
+        assert!(started.elapsed() < Duration::from_secs(2), "waited {:?}", started.elapsed());
+        let received: StatusUpdate = serde_json::from_slice(&brain.requests("/whatsapp/status")[0].body).unwrap();
+        assert_eq!(received.status, Lifecycle::ShutDown);
+    }
+
+    #[test]
+    fn held_sends_wait_for_the_brain() {
+        let brain = MockBrain::start();
+        let delivery = Delivery::start(TestHost::new().with_brain(&brain), &RelayConfig::default(), Metrics::default());
+        delivery.hold(true);
+        assert!(delivery.submit(Post::new("/whatsapp/status", serde_json::Value::Null)));
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(brain.requests("/whatsapp/status").is_empty());
+
+        delivery.hold(false);
+        delivery.shutdown(Duration::from_secs(5));
+        assert_eq!(brain.requests("/whatsapp/status").len(), 1);
+    }
+
+    #[test]
+    fn sends_still_held_at_exit_are_dropped() {
+        let brain = MockBrain::start();
+        let delivery = Delivery::start(TestHost::new().with_brain(&brain), &RelayConfig::default(), Metrics::default());
+        delivery.hold(true);
+        assert!(delivery.submit(Post::new("/whatsapp/status", serde_json::Value::Null)));
+        delivery.shutdown(Duration::from_secs(5));
+        assert_eq!(delivery.metrics().dropped, 1);
+        assert!(brain.requests("/whatsapp/status").is_empty());
+    }
+
+    fn drained(delivery: &Delivery) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while delivery.metrics().spool.depth > 0 {
+            if Instant::now() > deadline {
+                return false;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        true
+    }
+
+    #[test]
+    fn missed_sends_are_spooled_and_replayed_in_order() {
+        let brain = MockBrain::start();
+        brain.script("/whatsapp/status", [Reply::status(503), Reply::status(503)]);
+        brain.always("/whatsapp/status", Reply::status(200));
+        let delivery = Delivery::start(TestHost::new().with_brain(&brain), &retrying(1), Metrics::default());
+        for n in 0..2 {
+            assert!(delivery.submit(Post::new("/whatsapp/status", serde_json::json!({ "n": n }))));
+        }
+        let spooled = Instant::now() + Duration::from_secs(10);
+        while delivery.metrics().spool.depth < 2 && Instant::now() < spooled {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(delivery.metrics().spool.depth, 2);
+        let waiting = QueueStatus::from(&delivery.metrics());
+        assert_eq!((waiting.queued, waiting.spooled, waiting.pending), (0, 2, 2));
+
+        // A send getting through starts the replay.
+        assert!(delivery.submit(Post::new("/whatsapp/status", serde_json::json!({ "n": 2 }))));
+        assert!(drained(&delivery));
+        delivery.shutdown(Duration::from_secs(5));
+        let bodies: Vec<serde_json::Value> =
+            brain.requests("/whatsapp/status").iter().map(|r| serde_json::from_slice(&r.body).unwrap()).collect();
+        assert_eq!(bodies.len(), 5);
+        assert_eq!(bodies[3..], [serde_json::json!({ "n": 0 }), serde_json::json!({ "n": 1 })]);
+    }
+
+    #[test]
+    fn messages_that_find_the_queue_full_are_spooled() {
+        let brain = MockBrain::start();
+        brain.always("/whatsapp/message", Reply::status(200));
+        let config = RelayConfig { workers: 1, queue: 1, ..RelayConfig::default() };
+        let delivery = Delivery::start(TestHost::new().with_brain(&brain), &config, Metrics::default());
+        // The worker holds the first send and the queue the second.
+        delivery.hold(true);
+        assert!(delivery.submit(Post::new("/whatsapp/message", serde_json::json!(0))));
+        while delivery.metrics().queued > 0 {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        for n in 1..5 {
+            assert!(delivery.submit(Post::new("/whatsapp/message", serde_json::json!(n))));
+        }
+        // A status is dropped instead; the next keepalive covers it.
+        assert!(!delivery.submit(Post::new("/whatsapp/status", serde_json::Value::Null)));
+        let metrics = delivery.metrics();
+        assert_eq!((metrics.overflowed, metrics.dropped, metrics.spool.depth), (3, 1, 3));
+
+        delivery.hold(false);
+        assert!(drained(&delivery));
+        delivery.shutdown(Duration::from_secs(5));
+        let mut got = bodies(&brain, "/whatsapp/message");
+        got.sort_by_key(|n| n.as_u64());
+        assert_eq!(got, (0..5).map(|n| serde_json::json!(n)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn the_spool_outlives_a_restart() {
+        let brain = MockBrain::start();
+        let host = TestHost::new().with_brain(&brain);
+        let delivery = Delivery::start(host.clone(), &RelayConfig::default(), Metrics::default());
+        delivery.hold(true);
+        assert!(delivery.submit(Post::new("/whatsapp/status", serde_json::Value::Null)));
+        delivery.shutdown(Duration::from_secs(5));
+        assert!(brain.requests("/whatsapp/status").is_empty());
+
+        let delivery = Delivery::start(host.restart(), &RelayConfig::default(), Metrics::default());
+        assert!(drained(&delivery));
+        delivery.shutdown(Duration::from_secs(5));
+        assert_eq!(brain.requests("/whatsapp/status").len(), 1);
+    }
+
+    fn bodies(brain: &MockBrain, path: &str) -> Vec<serde_json::Value> {
+        brain.requests(path).iter().map(|r| serde_json::from_slice(&r.body).unwrap()).collect()
+    }
+
+    #[test]
+    fn a_burst_goes_out_as_one_ordered_batch() {
+        let brain = MockBrain::start();
+        brain.always("/whatsapp/messages", Reply::status(200));
+        let config = RelayConfig { batch_window_ms: 100, batch_max: 4, ..RelayConfig::default() };
+        let delivery = Delivery::start(TestHost::new().with_brain(&brain), &config, Metrics::default());
+        for n in 0..6 {
+            assert!(delivery.submit(Post::new("/whatsapp/message", serde_json::json!(n)).batched("/whatsapp/messages")));
+        }
+        std::thread::sleep(Duration::from_millis(300));
+        // A lone send skips the batch route.
+        assert!(delivery.submit(Post::new("/whatsapp/message", serde_json::json!(6)).batched("/whatsapp/messages")));
+        delivery.shutdown(Duration::from_secs(5));
+
+        assert_eq!(bodies(&brain, "/whatsapp/messages"), [serde_json::json!([0, 1, 2, 3]), serde_json::json!([4, 5])]);
+        assert_eq!(bodies(&brain, "/whatsapp/message"), [serde_json::json!(6)]);
+    }
+
+    #[test]
+    fn without_a_batch_route_the_sends_go_one_by_one() {
+        let brain = MockBrain::start();
+        brain.always("/whatsapp/message", Reply::status(200));
+        let delivery = Delivery::start(TestHost::new().with_brain(&brain), &RelayConfig::default(), Metrics::default());
+        let (acks, answers) = mpsc::channel();
+        for n in 0..3 {
+            let send = Post::new("/whatsapp/message", serde_json::json!(n)).batched("/whatsapp/messages");
+            assert!(delivery.submit(send.acked(acks.clone())));
+        }
+        drop(acks);
+        assert!(answers.iter().all(|a| a.error.is_none()));
+        delivery.shutdown(Duration::from_secs(5));
+        assert_eq!(brain.requests("/whatsapp/messages").len(), 1);
+        assert_eq!(bodies(&brain, "/whatsapp/message"), [serde_json::json!(0), serde_json::json!(1), serde_json::json!(2)]);
+    }
+
+    #[test]
+    fn a_missed_batch_is_spooled_and_replayed_whole() {
+        let brain = MockBrain::start();
+        brain.script("/whatsapp/messages", [Reply::status(503)]);
+        brain.always("/whatsapp/messages", Reply::status(200));
+        let delivery = Delivery::start(TestHost::new().with_brain(&brain), &RelayConfig { batch_window_ms: 50, ..retrying(1) }, Metrics::default());
+        for n in 0..2 {
+            assert!(delivery.submit(Post::new("/whatsapp/message", serde_json::json!(n)).batched("/whatsapp/messages")));
+        }
+        std::thread::sleep(Duration::from_millis(300));
+        assert_eq!(delivery.metrics().spool.depth, 1);
+        delivery.hold(false);
+        assert!(drained(&delivery));
+        delivery.shutdown(Duration::from_secs(5));
+        assert_eq!(bodies(&brain, "/whatsapp/messages"), [serde_json::json!([0, 1]), serde_json::json!([0, 1])]);
+    }
+}
@@ -0,0 +1,457 @@
+//! Keyboard shortcuts — one map from action names to accelerators.
+//!
+//! Global shortcuts are registered with the OS through the global-shortcut
+//! plugin and run here. Window shortcuts are matched by the canvas's own
+//! key handler, which reads the same map through `get_shortcuts` and
+//! re-reads it on `shortcuts://changed`; the dock hints come from there
+//! too. Overrides live in the `[shortcuts]` table of the config file, and
+//! every action not listed there keeps its default.
+//!
+//! Organ-scoped shortcuts are registered with the OS only while an organ
+//! window has focus and dropped when it loses it. Organ pages are remote
+//! and swallow keys as they please, so this is how `dismiss` (Escape, or
+//! `dismiss_alt`) always gets the organ on screen out of the way.
+//!
+//! ```toml
+//! [shortcuts.toggle]
+//! keys = "Super+`"
+//!
+//! [shortcuts.leave]
+//! keys = ""            # unbound
+//!
+//! [shortcuts.dismiss_alt]
+//! keys = "Ctrl+Shift+Q"
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutEvent, ShortcutState};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// Registered with the OS; fires whatever has focus.
+    Global,
+    /// Only while the main canvas has focus.
+    Window,
+    /// Registered with the OS while an organ window has focus.
+    Organ,
+}
+
+/// Whether two chords in these scopes can be live at once. The canvas and
+/// an organ never have focus together, so they may share keys.
+fn overlaps(a: Scope, b: Scope) -> bool {
+    a == b || a == Scope::Global || b == Scope::Global
+}
+
+/// A config override for one action.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+pub struct Binding {
+    /// Accelerator such as `Super+Shift+D`; empty to unbind.
+    pub keys: String,
+    /// Defaults to the action's own scope.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<Scope>,
+}
+
+struct ActionDef {
+    name: &'static str,
+    label: &'static str,
+    keys: &'static str,
+    scope: Scope,
+}
+
+const ACTIONS: &[ActionDef] = &[
+    ActionDef { name: "toggle", label: "Show or hide Lexicon", keys: "Super+`", scope: Scope::Global },
+    ActionDef { name: "peek", label: "Peek at Lexicon while held", keys: "Super+Alt+`", scope: Scope::Global },
+    ActionDef { name: "dnd", label: "Toggle do-not-disturb", keys: "Super+Shift+D", scope: Scope::Global },
+    ActionDef { name: "push_to_talk", label: "Talk to Lexicon while held", keys: "Super+Alt+Space", scope: Scope::Global },
+    ActionDef { name: "leave", label: "Clear the input, then hide", keys: "Escape", scope: Scope::Window },
+    ActionDef { name: "new_session", label: "New terminal", keys: "Ctrl+`", scope: Scope::Window },
+    ActionDef { name: "next_session", label: "Next terminal", keys: "Ctrl+Tab", scope: Scope::Window },
+    ActionDef { name: "interrupt", label: "Interrupt the terminal", keys: "Ctrl+C", scope: Scope::Window },
+    ActionDef { name: "dismiss", label: "Leave the organ on screen", keys: "Escape", scope: Scope::Organ },
+    ActionDef { name: "dismiss_alt", label: "Leave the organ on screen", keys: "Ctrl+Shift+L", scope: Scope::Organ },
+];
+
+/// Per-organ switching actions are `organ.<id>`.
+const ORGAN_PREFIX: &str = "organ.";
+
+/// Physical key and modifiers, in `KeyboardEvent` terms, so the canvas can
+/// match window shortcuts without parsing accelerators itself.
+#[derive(Debug, Clone, Serialize)]
+pub struct Chord {
+    pub code: String,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub meta: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ShortcutInfo {
+    pub action: String,
+    pub label: String,
+    pub keys: String,
+    pub scope: Scope,
+    /// `None` when the action is unbound.
+    pub chord: Option<Chord>,
+    #[serde(skip)]
+    shortcut: Option<Shortcut>,
+}
+
+/// A global chord the OS wouldn't give us, usually because the desktop
+/// environment or another application already owns it.
+#[derive(Debug, Clone, Serialize)]
+pub struct Refused {
+    pub action: String,
+    pub keys: String,
+    pub error: String,
+}
+
+/// Outcome of the last registration, for `get_shortcut_status`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ShortcutStatus {
+    /// Actions whose global chord is live.
+    pub registered: Vec<String>,
+    pub refused: Vec<Refused>,
+}
+
+/// Global shortcuts currently registered, by shortcut id.
+#[derive(Default)]
+pub struct ShortcutManager {
+    registered: Mutex<HashMap<u32, (Shortcut, String)>>,
+    refused: Mutex<Vec<Refused>>,
+    /// Organ-scoped shortcuts, registered while `focused` is set.
+    armed: Mutex<HashMap<u32, (Shortcut, String)>>,
+    /// The organ whose window has focus.
+    focused: Mutex<Option<String>>,
+}
+
+/// Set while a peek chord is held and the overlay is up because of it.
+static PEEKING: AtomicBool = AtomicBool::new(false);
+
+// ── Resolving the map ──────────────────────────────────────────
+
+/// Every known action with its default keys, in display order.
+fn defaults(app: &tauri::AppHandle) -> Vec<(String, String, Binding)> {
+    let mut actions: Vec<_> = ACTIONS
+        .iter()
+        .map(|a| (a.name.to_string(), a.label.to_string(), Binding { keys: a.keys.into(), scope: Some(a.scope) }))
+        .collect();
+    let manager = app.state::<crate::organs::OrganManager>();
+    for (i, def) in manager.defs().iter().enumerate() {
+        let keys = if i < 9 { format!("Super+Alt+{}", i + 1) } else { String::new() };
+        actions.push((
+            format!("{ORGAN_PREFIX}{}", def.id),
+            format!("Switch to {}", def.title),
+            Binding { keys, scope: Some(Scope::Global) },
+        ));
+    }
+    actions
+}
+
+fn chord(shortcut: &Shortcut) -> Chord {
+    use tauri_plugin_global_shortcut::Modifiers;
+    Chord {
+        code: shortcut.key.to_string(),
+        ctrl: shortcut.mods.contains(Modifiers::CONTROL),
+        alt: shortcut.mods.contains(Modifiers::ALT),
+        shift: shortcut.mods.contains(Modifiers::SHIFT),
+        meta: shortcut.mods.contains(Modifiers::SUPER),
+    }
+}
+
+/// Apply config overrides to the defaults, rejecting bad accelerators and
+/// two actions sharing one chord where both can fire.
+fn resolve(app: &tauri::AppHandle, overrides: &BTreeMap<String, Binding>) -> Result<Vec<ShortcutInfo>, String> {
+    let mut resolved = Vec::new();
+    let mut taken: HashMap<Shortcut, Vec<(Scope, String)>> = HashMap::new();
+    for (action, label, default) in defaults(app) {
+        let binding = overrides.get(&action).unwrap_or(&default);
+        let scope = binding.scope.or(default.scope).unwrap_or(Scope::Global);
+        let keys = binding.keys.trim().to_string();
+        let shortcut = if keys.is_empty() {
+            None
+        } else {
+            let shortcut: Shortcut =
+                keys.parse().map_err(|e| format!("{action}: '{keys}' is not a valid accelerator ({e})"))?;
+            let holders = taken.entry(shortcut).or_default();
+            if let Some((_, other)) = holders.iter().find(|(s, _)| overlaps(*s, scope)) {
+                return Err(format!("{keys} is bound to both {other} and {action} — rebind or clear one of them"));
+            }
+            holders.push((scope, action.clone()));
+            Some(shortcut)
+        };
+        resolved.push(ShortcutInfo { chord: shortcut.as_ref().map(chord), shortcut, action, label, keys, scope });
+    }
+    Ok(resolved)
+}
+
+fn known(app: &tauri::AppHandle, action: &str) -> bool {
+    match action.strip_prefix(ORGAN_PREFIX) {
+        Some(id) => app.state::<crate::organs::OrganManager>().get(id).is_some(),
+        None => ACTIONS.iter().any(|a| a.name == action),
+    }
+}
+
+// ── Registration ───────────────────────────────────────────────
+
+/// Swap the registered global shortcuts for those in `map`. Every chord is
+/// attempted; the error lists the ones the OS refused, which also stay
+/// on record for `get_shortcut_status`.
+fn register(app: &tauri::AppHandle, map: &[ShortcutInfo]) -> Result<(), String> {
+    let manager = app.state::<ShortcutManager>();
+    let mut registered = manager.registered.lock().unwrap_or_else(|e| e.into_inner());
+    let global = app.global_shortcut();
+    for (shortcut, _) in registered.values() {
+        let _ = global.unregister(*shortcut);
+    }
+    registered.clear();
+
+    let mut refused = Vec::new();
+    for info in map.iter().filter(|i| i.scope == Scope::Global) {
+        let Some(shortcut) = info.shortcut else { continue };
+        match global.register(shortcut) {
+            Ok(()) => {
+                registered.insert(shortcut.id(), (shortcut, info.action.clone()));
+            }
+            Err(e) => refused.push(Refused { action: info.action.clone(), keys: info.keys.clone(), error: e.to_string() }),
+        }
+    }
+    let message = refusal_message(&refused);
+    *manager.refused.lock().unwrap_or_else(|e| e.into_inner()) = refused;
+    message.map_or(Ok(()), Err)
+}
+
+fn refusal_message(refused: &[Refused]) -> Option<String> {
+    if refused.is_empty() {
+        return None;
+    }
+    let list: Vec<_> = refused.iter().map(|r| format!("{} for {} ({})", r.keys, r.action, r.error)).collect();
+    Some(format!(
+        "could not register {} — another application may own the chord; pick a different one with set_shortcut",
+        list.join(", ")
+    ))
+}
+
+/// Register the configured shortcuts at startup. Problems are logged, not
+/// fatal: a bad config falls back to the defaults.
+pub fn init(app: &tauri::AppHandle) {
+    app.manage(ShortcutManager::default());
+    if crate::headless::enabled() {
+        return;
+    }
+    let overrides = crate::config::current(app).shortcuts;
+    for action in overrides.keys().filter(|a| !known(app, a)) {
+        tracing::warn!("config: ignoring shortcut for unknown action '{action}'");
+    }
+    let map = resolve(app, &overrides).unwrap_or_else(|e| {
+        tracing::warn!("shortcuts: {e} — using defaults");
+        resolve(app, &BTreeMap::new()).unwrap_or_default()
+    });
+    if let Err(e) = register(app, &map) {
+        tracing::warn!("shortcuts: {e}");
+    }
+}
+
+/// Validate, register and persist a new override map. On failure the
+/// previous shortcuts stay registered.
+fn replace(app: &tauri::AppHandle, overrides: BTreeMap<String, Binding>) -> Result<Vec<ShortcutInfo>, String> {
+    let map = resolve(app, &overrides)?;
+    if let Err(e) = register(app, &map) {
+        let previous = resolve(app, &crate::config::current(app).shortcuts).unwrap_or_default();
+        let _ = register(app, &previous);
+        return Err(e);
+    }
+    crate::config::update(app, |config| config.shortcuts = overrides)?;
+    let manager = app.state::<ShortcutManager>();
+    if manager.focused.lock().unwrap_or_else(|e| e.into_inner()).is_some() {
+        arm(app, Some(&map));
+    }
+    let _ = app.emit("shortcuts://changed", &map);
+    Ok(map)
+}
+
+// ── Organ windows ──────────────────────────────────────────────
+
+/// Swap the armed organ shortcuts for those in `map`, or just drop them.
+fn arm(app: &tauri::AppHandle, map: Option<&[ShortcutInfo]>) {
+    let manager = app.state::<ShortcutManager>();
+    let mut armed = manager.armed.lock().unwrap_or_else(|e| e.into_inner());
+    let global = app.global_shortcut();
+    for (shortcut, _) in armed.values() {
+        let _ = global.unregister(*shortcut);
+    }
+    armed.clear();
+    for info in map.unwrap_or_default().iter().filter(|i| i.scope == Scope::Organ) {
+        let Some(shortcut) = info.shortcut else { continue };
+        match global.register(shortcut) {
+            Ok(()) => {
+                armed.insert(shortcut.id(), (shortcut, info.action.clone()));
+            }
+            Err(e) => tracing::warn!("shortcuts: could not arm {} for {}: {e}", info.keys, info.action),
+        }
+    }
+}
+
+/// An organ window gained or lost focus. Losing it only disarms if that
+/// organ was the one armed: the next organ's gain may arrive first.
+pub fn organ_focused(app: &tauri::AppHandle, id: &str, focused: bool) {
+    let Some(manager) = app.try_state::<ShortcutManager>() else { return };
+    let mut current = manager.focused.lock().unwrap_or_else(|e| e.into_inner());
+    if focused {
+        *current = Some(id.to_string());
+    } else if current.as_deref() == Some(id) {
+        *current = None;
+    } else {
+        return;
+    }
+    let map = focused.then(|| resolve(app, &crate::config::current(app).shortcuts).unwrap_or_default());
+    arm(app, map.as_deref());
+}
+
+// ── Running actions ────────────────────────────────────────────
+
+/// Handler for the global-shortcut plugin.
+pub fn on_shortcut(app: &tauri::AppHandle, shortcut: &Shortcut, event: ShortcutEvent) {
+    let action = {
+        let manager = app.state::<ShortcutManager>();
+        let registered = manager.registered.lock().unwrap_or_else(|e| e.into_inner());
+        let armed = manager.armed.lock().unwrap_or_else(|e| e.into_inner());
+        registered.get(&shortcut.id()).or_else(|| armed.get(&shortcut.id())).map(|(_, action)| action.clone())
+    };
+    if let Some(action) = action {
+        run(app, &action, event.state);
+    }
+}
+
+fn run(app: &tauri::AppHandle, action: &str, state: ShortcutState) {
+    if action == "peek" {
+        peek(app, state == ShortcutState::Pressed);
+        return;
+    }
+    if action == "push_to_talk" {
+        push_to_talk(app, state == ShortcutState::Pressed);
+        return;
+    }
+    if state != ShortcutState::Pressed {
+        return;
+    }
+    match action {
+        "toggle" => crate::toggle_main(app),
+        "dismiss" | "dismiss_alt" => crate::organs::dismiss(app),
+        "dnd" => {
+            crate::dnd::set_dnd(app.clone(), !crate::dnd::is_enabled());
+        }
+        _ => {
+            if let Some(id) = action.strip_prefix(ORGAN_PREFIX) {
+                if crate::presentation::is_active() {
+                    return;
+                }
+                if let Err(e) = crate::organs::open(app, id) {
+                    tracing::warn!("shortcut {action}: {e}");
+                }
+            } else {
+                // Canvas actions bound globally: let the canvas run them.
+                let _ = app.emit_to("main", "shortcuts://action", action);
+            }
+        }
+    }
+}
+
+/// Show the overlay while the chord is held, if it wasn't already up.
+fn peek(app: &tauri::AppHandle, held: bool) {
+    let Some(main) = app.get_webview_window("main") else { return };
+    if held {
+        if crate::presentation::is_active() || main.is_visible().unwrap_or(false) {
+            return;
+        }
+        PEEKING.store(true, Ordering::Relaxed);
+        crate::show_window(&main);
+    } else if PEEKING.swap(false, Ordering::Relaxed) {
+        crate::hide_window(&main);
+    }
+    crate::tray::refresh_tooltip(app);
+}
+
+/// Record while the chord is held.
+fn push_to_talk(app: &tauri::AppHandle, held: bool) {
+    if !held {
+        crate::voice::stop();
+        return;
+    }
+    match crate::voice::start(app) {
+        Ok(()) | Err(crate::voice::VoiceError::AlreadyActive) => {}
+        Err(e) => {
+            tracing::warn!("push to talk: {e}");
+            let _ = app.emit_to("main", "voice://error", e.to_string());
+        }
+    }
+}
+
+// ── Commands ───────────────────────────────────────────────────
+
+#[tauri::command]
+pub fn get_shortcuts(app: tauri::AppHandle) -> Result<Vec<ShortcutInfo>, String> {
+    resolve(&app, &crate::config::current(&app).shortcuts)
+}
+
+/// Rebind one action; an empty accelerator unbinds it.
+#[tauri::command]
+pub fn set_shortcut(app: tauri::AppHandle, action: String, accelerator: String) -> Result<Vec<ShortcutInfo>, String> {
+    if !known(&app, &action) {
+        return Err(format!("unknown action '{action}'"));
+    }
+    let mut overrides = crate::config::current(&app).shortcuts;
+    let scope = overrides.get(&action).and_then(|b| b.scope);
+    overrides.insert(action, Binding { keys: accelerator, scope });
+    replace(&app, overrides)
+}
+
+/// Rebind the show/hide chord. Unlike `set_shortcut` it won't unbind:
+/// losing the toggle leaves only the tray to bring Lexicon back.
+#[tauri::command]
+pub fn set_toggle_shortcut(app: tauri::AppHandle, accel: String) -> Result<Vec<ShortcutInfo>, String> {
+    if accel.trim().is_empty() {
+        return Err("the toggle needs a chord; use set_shortcut to unbind it".into());
+    }
+    set_shortcut(app, "toggle".into(), accel)
+}
+
+/// Which global chords are live and which the OS refused.
+#[tauri::command]
+pub fn get_shortcut_status(app: tauri::AppHandle) -> ShortcutStatus {
+    let manager = app.state::<ShortcutManager>();
+    let mut registered: Vec<_> =
+        manager.registered.lock().unwrap_or_else(|e| e.into_inner()).values().map(|(_, a)| a.clone()).collect();
+    registered.sort();
+    let refused = manager.refused.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    ShortcutStatus { registered, refused }
+}
+
+#[tauri::command]
+pub fn reset_shortcuts(app: tauri::AppHandle) -> Result<Vec<ShortcutInfo>, String> {
+    replace(&app, BTreeMap::new())
+}
+
+/// Run an action from the canvas — used when a Rust-side action such as
+/// `toggle` is bound with window scope. The canvas only reports presses,
+/// so a window-scoped `push_to_talk` toggles recording instead.
+#[tauri::command]
+pub fn run_shortcut(app: tauri::AppHandle, action: String) -> Result<(), String> {
+    if !known(&app, &action) {
+        return Err(format!("unknown action '{action}'"));
+    }
+    if action == "push_to_talk" && crate::voice::stop() {
+        return Ok(());
+    }
+    run(&app, &action, ShortcutState::Pressed);
+    Ok(())
+}
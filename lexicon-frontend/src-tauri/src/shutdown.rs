@@ -0,0 +1,157 @@
+//! Graceful exit — the one path every "quit" goes through.
+//!
+//! The tray's Quit, `quit_app` from the canvas, and anything else that
+//! asks the app to exit (closing the last window included) wind down in
+//! the same order: the relay stops taking sends, delivers what is queued
+//! and tells the Brain the organs are going away (see `relay`), the
+//! window layout is saved, the organ windows are destroyed one by one,
+//! and a Brain the app started is stopped (see `brain_supervisor`) before
+//! the process goes. However the Brain is doing, that takes a
+//! few seconds at most. A second quit while one is under way is ignored.
+//!
+//! An update restart goes through the same wind-down, and additionally
+//! records which organs were open so the next launch brings them back.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::{Deserialize, Serialize};
+use tauri::Manager;
+
+/// What a restart should put back.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+struct Session {
+    organs: Vec<String>,
+}
+
+/// Set once the app is on its way out.
+static EXITING: AtomicBool = AtomicBool::new(false);
+
+fn session_path(app: &tauri::AppHandle) -> Option<PathBuf> {
+    app.path().app_data_dir().ok().map(|dir| dir.join("session.json"))
+}
+
+/// Stop anything that could lose work, then close the organ windows.
+fn wind_down(app: &tauri::AppHandle) {
+    crate::voice::stop();
+    crate::audio::stop(app);
+    crate::relay::shutdown(app);
+    let queued = crate::ingest::flush(app);
+    if queued > 0 {
+        tracing::info!("{queued} queued file(s) kept for the next launch");
+    }
+    #[cfg(desktop)]
+    crate::window_state::save(app);
+    #[cfg(desktop)]
+    {
+        let manager = app.state::<crate::organs::OrganManager>();
+        for def in manager.defs() {
+            if let Some(window) = app.get_webview_window(&crate::organs::window_label(&def.id)) {
+                let _ = window.destroy();
+            }
+        }
+        for (label, window) in app.webview_windows() {
+            if label != "main" {
+                let _ = window.destroy();
+            }
+        }
+        crate::brain_supervisor::shutdown();
+    }
+}
+
+/// Tear the app down in order: work in flight, organ windows, then the
+/// process. Blocks for the few seconds that takes.
+pub fn graceful_exit(app: &tauri::AppHandle) {
+    exit_with(app, 0);
+}
+
+fn exit_with(app: &tauri::AppHandle, code: i32) {
+    if EXITING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    tracing::info!("shutting down");
+    wind_down(app);
+    app.exit(code);
+}
+
+/// An exit Tauri was asked for that didn't come through here (the last
+/// window closed, say) waits and goes through `graceful_exit` first.
+pub fn on_run_event(app: &tauri::AppHandle, event: &tauri::RunEvent) {
+    if let tauri::RunEvent::ExitRequested { code, api, .. } = event {
+        if EXITING.load(Ordering::SeqCst) {
+            return;
+        }
+        api.prevent_exit();
+        let (app, code) = (app.clone(), code.unwrap_or(0));
+        std::thread::spawn(move || exit_with(&app, code));
+    }
+}
+
+/// Quit the app, the same way the tray does.
+#[tauri::command]
+pub async fn quit_app(window: tauri::WebviewWindow, app: tauri::AppHandle) -> Result<(), String> {
+    if window.label() != "main" {
+        return Err("only the main window can quit the app".into());
+    }
+    tauri::async_runtime::spawn_blocking(move || graceful_exit(&app)).await.map_err(|e| e.to_string())
+}
+
+/// Wind down for an in-place restart. The caller hands over to whatever
+/// replaces the process (the updater) afterwards.
+#[cfg_attr(mobile, allow(dead_code))]
+pub fn prepare_restart(app: &tauri::AppHandle) -> Result<(), String> {
+    tracing::info!("preparing to restart");
+    let manager = app.state::<crate::organs::OrganManager>();
+    let organs = manager
+        .defs()
+        .iter()
+        .filter(|def| crate::organs::status(app, &def.id) != "closed" || crate::idle::suspended(&def.id))
+        .map(|def| def.id.clone())
+        .collect();
+    let path = session_path(app).ok_or("no data directory on this platform")?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("create {}: {e}", dir.display()))?;
+    }
+    let text = serde_json::to_string_pretty(&Session { organs }).map_err(|e| format!("serialize session: {e}"))?;
+    std::fs::write(&path, text).map_err(|e| format!("write {}: {e}", path.display()))?;
+    // The restart's own exit request has nothing left to wind down.
+    EXITING.store(true, Ordering::SeqCst);
+    wind_down(app);
+    Ok(())
+}
+
+/// Reopen what `prepare_restart` recorded, once. Organs come back in the
+/// background.
+pub fn restore_session(app: &tauri::AppHandle) {
+    let Some(path) = session_path(app) else { return };
+    let Ok(text) = std::fs::read_to_string(&path) else { return };
+    let _ = std::fs::remove_file(&path);
+    let session: Session = serde_json::from_str(&text).unwrap_or_default();
+    for id in session.organs {
+        match crate::organs::preload(app, &id) {
+            Ok(()) => tracing::info!("restoring organ {id}"),
+            Err(e) => tracing::warn!("could not restore organ {id}: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn sessions_round_trip(session in any::<Session>()) {
+            let text = serde_json::to_string(&session).unwrap();
+            prop_assert_eq!(serde_json::from_str::<Session>(&text).unwrap(), session);
+        }
+    }
+
+    #[test]
+    fn previous_session_format_still_loads() {
+        let session: Session = serde_json::from_str(include_str!("../fixtures/session-v1.json")).unwrap();
+        assert_eq!(session.organs, ["whatsapp"]);
+    }
+}
@@ -0,0 +1,258 @@
+//! Single-instance enforcement.
+//!
+//! The primary process holds an exclusive OS lock on
+//! `$XDG_RUNTIME_DIR/lexicon.lock` for its whole lifetime and writes the
+//! port of a loopback control socket (plus a per-session token) into it,
+//! readable by the user alone.
+//! A second launch finds the lock taken, forwards its arguments over
+//! that socket, prints the primary's reply and exits.
+//!
+//! Because the lock is released by the OS when a process dies, a lock
+//! file left behind by a crash is simply reclaimed by the next launch —
+//! its stale contents are overwritten.
+
+use std::collections::hash_map::RandomState;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, BufRead, BufReader, Read, Seek, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// How long a second launch waits for a just-started primary to publish
+/// its control port.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+/// How long the primary waits on a forwarded launch's request before it
+/// drops the connection and takes the next.
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub enum Instance {
+    /// We own the lock; call `serve` once the app is up.
+    Primary(PrimaryLock),
+    /// Another instance handled our arguments and sent back this reply.
+    Forwarded(String),
+}
+
+pub struct PrimaryLock {
+    // Held only to keep the OS lock alive.
+    _file: File,
+    listener: TcpListener,
+    token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+struct Request {
+    token: String,
+    args: Vec<String>,
+}
+
+/// Default lock location: the per-user runtime dir, else the temp dir.
+pub fn default_lock_path() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join("lexicon.lock"),
+        None => {
+            let user = std::env::var("USER").or_else(|_| std::env::var("USERNAME")).unwrap_or_default();
+            std::env::temp_dir().join(format!("lexicon-{user}.lock"))
+        }
+    }
+}
+
+fn random_token() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(std::process::id());
+    format!("{:016x}", hasher.finish())
+}
+
+/// Become the primary instance, or forward `args` to the one running.
+pub fn acquire(lock_path: &Path, args: &[String]) -> io::Result<Instance> {
+    let mut options = OpenOptions::new();
+    options.read(true).write(true).create(true).truncate(false);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(lock_path)?;
+
+    match file.try_lock() {
+        Ok(()) => {
+            // A lock file left behind may predate the mode above.
+            #[cfg(unix)]
+            file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+            let token = random_token();
+            file.set_len(0)?;
+            file.rewind()?;
+            writeln!(file, "{} {}", listener.local_addr()?.port(), token)?;
+            file.flush()?;
+            Ok(Instance::Primary(PrimaryLock {
+                _file: file,
+                listener,
+                token,
+            }))
+        }
+        Err(TryLockError::WouldBlock) => forward(lock_path, args).map(Instance::Forwarded),
+        Err(TryLockError::Error(e)) => Err(e),
+    }
+}
+
+/// Read `port token` from the lock file, retrying while the primary is
+/// still writing it.
+fn read_endpoint(lock_path: &Path) -> io::Result<(u16, String)> {
+    let deadline = Instant::now() + CONNECT_TIMEOUT;
+    loop {
+        let mut text = String::new();
+        File::open(lock_path)?.read_to_string(&mut text)?;
+        let mut parts = text.split_whitespace();
+        if let (Some(port), Some(token)) = (parts.next().and_then(|p| p.parse().ok()), parts.next()) {
+            return Ok((port, token.to_string()));
+        }
+        if Instant::now() >= deadline {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "running instance never published its port"));
+        }
+        std::thread::sleep(Duration::from_millis(25));
+    }
+}
+
+fn forward(lock_path: &Path, args: &[String]) -> io::Result<String> {
+    let (port, token) = read_endpoint(lock_path)?;
+    let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, port))?;
+    let request = Request {
+        token,
+        args: args.to_vec(),
+    };
+    let mut line = serde_json::to_string(&request).map_err(io::Error::other)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    Ok(reply.trim_end().to_string())
+}
+
+impl PrimaryLock {
+    /// Answer forwarded launches on a background thread. `handler` gets
+    /// the second process's arguments and returns the reply it prints.
+    pub fn serve(self, handler: impl Fn(Vec<String>) -> String + Send + 'static) {
+        std::thread::spawn(move || {
+            let _file = self._file;
+            for stream in self.listener.incoming().flatten() {
+                if stream.set_read_timeout(Some(READ_TIMEOUT)).is_err() {
+                    continue;
+                }
+                let mut reader = BufReader::new(&stream);
+                let mut line = String::new();
+                if reader.read_line(&mut line).is_err() {
+                    continue;
+                }
+                let reply = match serde_json::from_str::<Request>(&line) {
+                    Ok(req) if req.token == self.token => handler(req.args),
+                    _ => "error: rejected".to_string(),
+                };
+                let _ = (&stream).write_all(format!("{reply}\n").as_bytes());
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Barrier};
+
+    fn lock_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("lexicon-si-{name}-{}.lock", std::process::id()))
+    }
+
+    #[test]
+    fn stale_lock_file_is_reclaimed() {
+        let path = lock_path("stale");
+        std::fs::write(&path, "4242 deadbeef\n").unwrap();
+
+        let Instance::Primary(primary) = acquire(&path, &[]).unwrap() else {
+            panic!("stale lock should not block a new primary");
+        };
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(!text.contains("deadbeef"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+        drop(primary);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn second_launch_forwards_args() {
+        let path = lock_path("forward");
+        let Instance::Primary(primary) = acquire(&path, &[]).unwrap() else {
+            panic!("first launch must be primary");
+        };
+        primary.serve(|args| format!("ok {}", args.join(" ")));
+
+        match acquire(&path, &["toggle".into()]).unwrap() {
+            Instance::Forwarded(reply) => assert_eq!(reply, "ok toggle"),
+            Instance::Primary(_) => panic!("second launch must not become primary"),
+        }
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn a_silent_connection_does_not_hold_up_the_next() {
+        let path = lock_path("silent");
+        let Instance::Primary(primary) = acquire(&path, &[]).unwrap() else {
+            panic!("first launch must be primary");
+        };
+        let (port, _) = read_endpoint(&path).unwrap();
+        primary.serve(|_| "ok".into());
+
+        let _silent = TcpStream::connect((Ipv4Addr::LOCALHOST, port)).unwrap();
+        match acquire(&path, &[]).unwrap() {
+            Instance::Forwarded(reply) => assert_eq!(reply, "ok"),
+            Instance::Primary(_) => panic!("second launch must not become primary"),
+        }
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn simultaneous_launches_elect_one_primary() {
+        let path = lock_path("race");
+        let launches = 8;
+        let barrier = Arc::new(Barrier::new(launches));
+        let primaries = Arc::new(AtomicUsize::new(0));
+
+        let threads: Vec<_> = (0..launches)
+            .map(|_| {
+                let (path, barrier, primaries) = (path.clone(), barrier.clone(), primaries.clone());
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    match acquire(&path, &["toggle".into()]).unwrap() {
+                        Instance::Primary(primary) => {
+                            primaries.fetch_add(1, Ordering::SeqCst);
+                            primary.serve(|_| "ok".into());
+                            None
+                        }
+                        Instance::Forwarded(reply) => Some(reply),
+                    }
+                })
+            })
+            .collect();
+
+        let replies: Vec<_> = threads.into_iter().filter_map(|t| t.join().unwrap()).collect();
+        assert_eq!(primaries.load(Ordering::SeqCst), 1);
+        assert_eq!(replies.len(), launches - 1);
+        assert!(replies.iter().all(|r| r == "ok"));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn requests_round_trip(request in proptest::prelude::any::<Request>()) {
+            let line = serde_json::to_string(&request).unwrap();
+            proptest::prop_assert!(!line.contains('\n'), "requests are sent one per line");
+            proptest::prop_assert_eq!(serde_json::from_str::<Request>(&line).unwrap(), request);
+        }
+    }
+}
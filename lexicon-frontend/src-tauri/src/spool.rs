@@ -0,0 +1,268 @@
+//! Relay spool — sends the Brain never took, kept on disk until it does.
+//!
+//! `relay-spool.jsonl` in the app data dir holds one [`Entry`] per line,
+//! oldest first, and `relay-spool.head` the id of the last entry that is
+//! gone (delivered or evicted). Both removals take from the front, so one
+//! number covers them and the log itself is only ever appended to, until
+//! the dead lines outnumber the live ones and it is rewritten.
+//!
+//! The spool holds at most `max_entries` entries and `max_bytes` bytes of
+//! them; past either, the oldest go first. Delivery from it is at least
+//! once: a crash between a 2xx and the head moving sends that entry again.
+
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+/// Dead lines tolerated before a rewrite, whatever the live count.
+const COMPACT_SLACK: usize = 256;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct Entry {
+    pub id: u64,
+    /// Unix milliseconds it was spooled.
+    pub queued_ms: u64,
+    pub path: String,
+    pub body: serde_json::Value,
+    /// For a batch (`body` an array): where each item goes on its own if
+    /// the Brain has no batch route.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SpoolStats {
+    pub depth: usize,
+    pub bytes: u64,
+    /// Age of the oldest entry, in seconds.
+    pub oldest_age_secs: Option<u64>,
+    /// Entries dropped to stay under the caps since startup.
+    pub evicted: u64,
+}
+
+#[derive(Default)]
+struct Inner {
+    /// Live entries and the length of their line.
+    entries: VecDeque<(Entry, u64)>,
+    bytes: u64,
+    next_id: u64,
+    /// Lines in the log that are behind the head.
+    dead: usize,
+    evicted: u64,
+}
+
+pub struct Spool {
+    /// The log; `None` keeps the spool in memory only.
+    file: Option<PathBuf>,
+    max_entries: usize,
+    max_bytes: u64,
+    inner: Mutex<Inner>,
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+impl Spool {
+    /// Load what an earlier run left in `file`. Lines that don't parse are
+    /// skipped.
+    pub fn open(file: Option<PathBuf>, max_entries: usize, max_bytes: u64) -> Self {
+        let mut inner = Inner { next_id: 1, ..Inner::default() };
+        if let Some(file) = &file {
+            let head: u64 = std::fs::read_to_string(file.with_extension("head"))
+                .ok()
+                .and_then(|text| text.trim().parse().ok())
+                .unwrap_or(0);
+            inner.next_id = head + 1;
+            let text = std::fs::read_to_string(file).unwrap_or_default();
+            for line in text.lines().filter(|l| !l.trim().is_empty()) {
+                match serde_json::from_str::<Entry>(line) {
+                    Ok(entry) if entry.id > head => {
+                        inner.next_id = inner.next_id.max(entry.id + 1);
+                        inner.bytes += line.len() as u64 + 1;
+                        inner.entries.push_back((entry, line.len() as u64 + 1));
+                    }
+                    Ok(_) => inner.dead += 1,
+                    Err(e) => {
+                        tracing::warn!("relay spool: skipping a bad line ({e})");
+                        inner.dead += 1;
+                    }
+                }
+            }
+        }
+        let spool = Self { file, max_entries: max_entries.max(1), max_bytes, inner: Mutex::new(inner) };
+        let mut inner = spool.lock();
+        spool.evict(&mut inner);
+        drop(inner);
+        spool
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Keep a send for later.
+    pub fn push(&self, path: &str, body: serde_json::Value, fallback: Option<String>) {
+        let mut inner = self.lock();
+        let entry = Entry { id: inner.next_id, queued_ms: now_ms(), path: path.into(), body, fallback };
+        inner.next_id += 1;
+        let line = serde_json::to_string(&entry).unwrap_or_default();
+        if let Some(file) = &self.file {
+            let appended = file.parent().map_or(Ok(()), std::fs::create_dir_all).and_then(|()| {
+                std::fs::OpenOptions::new().create(true).append(true).open(file)?.write_all(format!("{line}\n").as_bytes())
+            });
+            if let Err(e) = appended {
+                tracing::warn!("relay spool: could not write {}: {e}", file.display());
+            }
+        }
+        let size = line.len() as u64 + 1;
+        inner.bytes += size;
+        inner.entries.push_back((entry, size));
+        self.evict(&mut inner);
+    }
+
+    /// The oldest entry, still in the spool.
+    pub fn front(&self) -> Option<Entry> {
+        self.lock().entries.front().map(|(entry, _)| entry.clone())
+    }
+
+    /// Drop the oldest entry, if it is `id`: it was delivered.
+    pub fn remove(&self, id: u64) {
+        let mut inner = self.lock();
+        if inner.entries.front().is_some_and(|(entry, _)| entry.id == id) {
+            self.pop(&mut inner);
+            self.advance(&mut inner);
+        }
+    }
+
+    pub fn stats(&self) -> SpoolStats {
+        let inner = self.lock();
+        SpoolStats {
+            depth: inner.entries.len(),
+            bytes: inner.bytes,
+            oldest_age_secs: inner.entries.front().map(|(e, _)| now_ms().saturating_sub(e.queued_ms) / 1000),
+            evicted: inner.evicted,
+        }
+    }
+
+    fn pop(&self, inner: &mut Inner) {
+        if let Some((_, size)) = inner.entries.pop_front() {
+            inner.bytes -= size;
+            inner.dead += 1;
+        }
+    }
+
+    fn evict(&self, inner: &mut Inner) {
+        let mut evicted = 0;
+        while inner.entries.len() > self.max_entries || (inner.bytes > self.max_bytes && !inner.entries.is_empty()) {
+            self.pop(inner);
+            evicted += 1;
+        }
+        if evicted > 0 {
+            inner.evicted += evicted;
+            tracing::warn!("relay spool full — dropped the {evicted} oldest");
+            self.advance(inner);
+        }
+    }
+
+    /// Record the head on disk; rewrite the log once it is mostly dead.
+    fn advance(&self, inner: &mut Inner) {
+        let Some(file) = &self.file else { return };
+        let head = inner.entries.front().map_or(inner.next_id, |(e, _)| e.id) - 1;
+        if let Err(e) = std::fs::write(file.with_extension("head"), head.to_string()) {
+            tracing::warn!("relay spool: could not write the head: {e}");
+        }
+        if inner.dead <= inner.entries.len().max(COMPACT_SLACK) {
+            return;
+        }
+        let mut text = String::new();
+        for (entry, _) in &inner.entries {
+            text.push_str(&serde_json::to_string(entry).unwrap_or_default());
+            text.push('\n');
+        }
+        let tmp = file.with_extension("jsonl.tmp");
+        match std::fs::write(&tmp, text).and_then(|()| std::fs::rename(&tmp, file)) {
+            Ok(()) => inner.dead = 0,
+            Err(e) => tracing::warn!("relay spool: could not compact {}: {e}", file.display()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::host::Host;
+    use crate::testing::TestHost;
+
+    fn file(host: &TestHost) -> Option<PathBuf> {
+        host.data_dir().map(|d| d.join("relay-spool.jsonl"))
+    }
+
+    #[test]
+    fn entries_survive_a_restart_in_order() {
+        let host = TestHost::new();
+        let spool = Spool::open(file(&host), 100, u64::MAX);
+        for n in 0..3 {
+            spool.push("/whatsapp/status", serde_json::json!({ "n": n }), None);
+        }
+        let first = spool.front().unwrap();
+        spool.remove(first.id);
+        drop(spool);
+
+        let spool = Spool::open(file(&host), 100, u64::MAX);
+        assert_eq!(spool.stats().depth, 2);
+        let next = spool.front().unwrap();
+        assert_eq!(next.body, serde_json::json!({ "n": 1 }));
+        assert!(next.id > first.id);
+
+        // Ids keep climbing past what was delivered.
+        spool.push("/whatsapp/status", serde_json::json!({ "n": 3 }), None);
+        spool.remove(next.id);
+        drop(spool);
+        let spool = Spool::open(file(&host), 100, u64::MAX);
+        let bodies: Vec<_> = std::iter::from_fn(|| spool.front().inspect(|e| spool.remove(e.id))).map(|e| e.body).collect();
+        assert_eq!(bodies, [serde_json::json!({ "n": 2 }), serde_json::json!({ "n": 3 })]);
+    }
+
+    #[test]
+    fn the_oldest_are_evicted_past_either_cap() {
+        let spool = Spool::open(None, 3, u64::MAX);
+        for n in 0..5 {
+            spool.push("/p", serde_json::json!(n), None);
+        }
+        assert_eq!(spool.front().unwrap().body, serde_json::json!(2));
+        assert_eq!((spool.stats().depth, spool.stats().evicted), (3, 2));
+
+        let spool = Spool::open(None, 100, 100);
+        for n in 0..10 {
+            spool.push("/p", serde_json::json!(n), None);
+        }
+        let stats = spool.stats();
+        assert!(stats.bytes <= 100 && stats.depth < 10 && stats.evicted > 0);
+        assert_eq!(spool.front().unwrap().body, serde_json::json!(10 - stats.depth));
+    }
+
+    #[test]
+    fn a_mostly_dead_log_is_compacted_and_bad_lines_skipped() {
+        let host = TestHost::new();
+        let spool = Spool::open(file(&host), 10_000, u64::MAX);
+        for n in 0..(COMPACT_SLACK + 10) {
+            spool.push("/p", serde_json::json!(n), None);
+        }
+        for _ in 0..(COMPACT_SLACK + 5) {
+            spool.remove(spool.front().unwrap().id);
+        }
+        let path = file(&host).unwrap();
+        assert!(std::fs::read_to_string(&path).unwrap().lines().count() < COMPACT_SLACK);
+
+        let mut log = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        writeln!(log, "{{ not json").unwrap();
+        drop(log);
+        assert_eq!(Spool::open(Some(path), 10_000, u64::MAX).stats().depth, 5);
+    }
+}
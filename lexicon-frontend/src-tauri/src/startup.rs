@@ -0,0 +1,261 @@
+//! Startup in two halves, with timings.
+//!
+//! `setup()` does only what the main webview needs to come up: managed
+//! state, window hooks, shortcuts and the single-instance socket, all
+//! from the config already in memory. Everything else (the tray, scheme
+//! registration, D-Bus, watchdogs, queue replay, warm-starting organs,
+//! the updater) is a deferred [`Phase`]. Phases wait for the canvas to
+//! call `frontend_ready` — or for [`READY_TIMEOUT`] if it never does —
+//! and then run in dependency order on a worker thread; the ones that
+//! build native UI hop onto the main thread.
+//!
+//! The main window is up while the canvas boots, and is hidden once the
+//! canvas calls `frontend_ready` with `connected` — its WebSocket to the
+//! Brain is open, so it won't miss the first events — or after
+//! [`CONNECT_TIMEOUT`] if it never does. `startup.hide_after_boot =
+//! false` leaves it up.
+//!
+//! Every step is timed; `get_startup_report` lists the spans.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// How long deferred phases wait for `frontend_ready`.
+const READY_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long the boot waits for the canvas to reach the Brain.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+#[serde(default)]
+pub struct StartupConfig {
+    /// Hide the main window once the canvas has booted; false leaves it up.
+    pub hide_after_boot: bool,
+}
+
+impl Default for StartupConfig {
+    fn default() -> Self {
+        Self { hide_after_boot: true }
+    }
+}
+
+pub struct Phase {
+    pub name: &'static str,
+    /// Phases that must have finished first.
+    pub after: &'static [&'static str],
+    /// Run on the main thread (native UI such as the tray menu).
+    pub main_thread: bool,
+    pub run: fn(&tauri::AppHandle),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Span {
+    pub name: &'static str,
+    /// Milliseconds since the process started.
+    pub started_ms: u64,
+    pub duration_ms: u64,
+    /// Ran after `frontend_ready` rather than inside `setup()`.
+    pub deferred: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StartupReport {
+    pub spans: Vec<Span>,
+    pub frontend_ready_ms: Option<u64>,
+    /// When the canvas said its WebSocket to the Brain was open.
+    pub brain_connected_ms: Option<u64>,
+    /// When the last deferred phase finished.
+    pub finished_ms: Option<u64>,
+}
+
+static BOOT: OnceLock<Instant> = OnceLock::new();
+static SPANS: Mutex<Vec<Span>> = Mutex::new(Vec::new());
+/// Milliseconds since boot, offset by one so 0 can mean "not yet".
+static READY_AT: AtomicU64 = AtomicU64::new(0);
+static FINISHED_AT: AtomicU64 = AtomicU64::new(0);
+static CONNECTED_AT: AtomicU64 = AtomicU64::new(0);
+static READY: Mutex<Option<mpsc::Sender<()>>> = Mutex::new(None);
+static CONNECTED: Mutex<Option<mpsc::Sender<()>>> = Mutex::new(None);
+
+/// Start the clock. Call first thing in `run()`.
+pub fn begin() {
+    BOOT.get_or_init(Instant::now);
+}
+
+/// How long since `begin`.
+pub fn uptime() -> Duration {
+    BOOT.get_or_init(Instant::now).elapsed()
+}
+
+fn elapsed_ms() -> u64 {
+    BOOT.get_or_init(Instant::now).elapsed().as_millis() as u64
+}
+
+fn stamp(at: &AtomicU64) -> Option<u64> {
+    at.load(Ordering::Relaxed).checked_sub(1)
+}
+
+fn timed<T>(name: &'static str, deferred: bool, f: impl FnOnce() -> T) -> T {
+    let started_ms = elapsed_ms();
+    let value = f();
+    let duration_ms = elapsed_ms() - started_ms;
+    SPANS.lock().unwrap_or_else(|e| e.into_inner()).push(Span { name, started_ms, duration_ms, deferred });
+    value
+}
+
+/// Time one step of `setup()`.
+pub fn span<T>(name: &'static str, f: impl FnOnce() -> T) -> T {
+    timed(name, false, f)
+}
+
+/// Indices of `phases` in an order where each comes after everything it
+/// names in `after`. Unknown names and cycles are errors.
+fn order(phases: &[(&'static str, &'static [&'static str])]) -> Result<Vec<usize>, String> {
+    for (name, after) in phases {
+        if let Some(missing) = after.iter().find(|dep| !phases.iter().any(|(n, _)| n == *dep)) {
+            return Err(format!("phase {name} waits for unknown phase {missing}"));
+        }
+    }
+    let mut done: Vec<usize> = Vec::with_capacity(phases.len());
+    while done.len() < phases.len() {
+        let next = (0..phases.len()).find(|i| {
+            !done.contains(i)
+                && phases[*i].1.iter().all(|dep| done.iter().any(|d| phases[*d].0 == *dep))
+        });
+        match next {
+            Some(i) => done.push(i),
+            None => {
+                let stuck: Vec<_> = (0..phases.len()).filter(|i| !done.contains(i)).map(|i| phases[i].0).collect();
+                return Err(format!("startup phases wait on each other: {}", stuck.join(", ")));
+            }
+        }
+    }
+    Ok(done)
+}
+
+fn run_phase(app: &tauri::AppHandle, phase: &Phase) {
+    timed(phase.name, true, || {
+        if !phase.main_thread {
+            (phase.run)(app);
+            return;
+        }
+        let (done_tx, done_rx) = mpsc::channel();
+        let handle = app.clone();
+        let run = phase.run;
+        let dispatched = app.run_on_main_thread(move || {
+            run(&handle);
+            let _ = done_tx.send(());
+        });
+        if dispatched.is_ok() {
+            let _ = done_rx.recv();
+        }
+    });
+}
+
+/// Run `phases` once the canvas is ready. Returns immediately.
+pub fn defer(app: &tauri::AppHandle, phases: Vec<Phase>) {
+    let plan: Vec<_> = phases.iter().map(|p| (p.name, p.after)).collect();
+    let order = match order(&plan) {
+        Ok(order) => order,
+        Err(e) => {
+            tracing::warn!("startup: {e} — running phases as listed");
+            (0..phases.len()).collect()
+        }
+    };
+    let (ready_tx, ready_rx) = mpsc::channel();
+    *READY.lock().unwrap_or_else(|e| e.into_inner()) = Some(ready_tx);
+
+    let app = app.clone();
+    std::thread::spawn(move || {
+        if ready_rx.recv_timeout(READY_TIMEOUT).is_err() {
+            tracing::warn!("startup: canvas not ready after {}s — continuing", READY_TIMEOUT.as_secs());
+        }
+        for i in order {
+            run_phase(&app, &phases[i]);
+        }
+        FINISHED_AT.store(elapsed_ms() + 1, Ordering::Relaxed);
+        tracing::info!("startup finished in {}ms", elapsed_ms());
+    });
+}
+
+/// Run `then` once the canvas has reached the Brain, or after
+/// `CONNECT_TIMEOUT`. Returns immediately.
+pub fn when_connected(then: impl FnOnce() + Send + 'static) {
+    let (connected_tx, connected_rx) = mpsc::channel();
+    *CONNECTED.lock().unwrap_or_else(|e| e.into_inner()) = Some(connected_tx);
+    std::thread::spawn(move || {
+        match connected_rx.recv_timeout(CONNECT_TIMEOUT) {
+            Ok(()) => tracing::info!("canvas booted and connected in {}ms", elapsed_ms()),
+            Err(_) => {
+                CONNECTED.lock().unwrap_or_else(|e| e.into_inner()).take();
+                tracing::warn!("startup: canvas not connected after {}s — continuing", CONNECT_TIMEOUT.as_secs());
+            }
+        }
+        then();
+    });
+}
+
+pub fn report() -> StartupReport {
+    StartupReport {
+        spans: SPANS.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+        frontend_ready_ms: stamp(&READY_AT),
+        brain_connected_ms: stamp(&CONNECTED_AT),
+        finished_ms: stamp(&FINISHED_AT),
+    }
+}
+
+// ── Commands ───────────────────────────────────────────────────
+
+/// The canvas has painted; release the deferred phases. With `connected`,
+/// its WebSocket to the Brain is open too, which ends the boot.
+#[tauri::command]
+pub fn frontend_ready(connected: Option<bool>) {
+    if let Some(ready) = READY.lock().unwrap_or_else(|e| e.into_inner()).take() {
+        READY_AT.store(elapsed_ms() + 1, Ordering::Relaxed);
+        let _ = ready.send(());
+    }
+    if connected == Some(true) {
+        if let Some(connected) = CONNECTED.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            CONNECTED_AT.store(elapsed_ms() + 1, Ordering::Relaxed);
+            let _ = connected.send(());
+        }
+    }
+}
+
+#[tauri::command]
+pub fn get_startup_report() -> StartupReport {
+    report()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(plan: &[(&'static str, &'static [&'static str])]) -> Vec<&'static str> {
+        order(plan).unwrap().into_iter().map(|i| plan[i].0).collect()
+    }
+
+    #[test]
+    fn phases_run_after_their_dependencies() {
+        let plan: [(&str, &[&str]); 4] =
+            [("ingest", &["network"]), ("tray", &[]), ("network", &["power"]), ("power", &[])];
+        assert_eq!(names(&plan), ["tray", "power", "network", "ingest"]);
+    }
+
+    #[test]
+    fn independent_phases_keep_their_order() {
+        let plan: [(&str, &[&str]); 3] = [("a", &[]), ("b", &[]), ("c", &[])];
+        assert_eq!(names(&plan), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn unknown_and_cyclic_dependencies_are_errors() {
+        let unknown: [(&str, &[&str]); 1] = [("a", &["nope"])];
+        assert!(order(&unknown).unwrap_err().contains("unknown phase nope"));
+        let cycle: [(&str, &[&str]); 3] = [("a", &["b"]), ("b", &["a"]), ("c", &[])];
+        assert!(order(&cycle).unwrap_err().contains("a, b"));
+    }
+}
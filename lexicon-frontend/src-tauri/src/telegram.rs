@@ -0,0 +1,163 @@
+//! Telegram — Web A in the `telegram` organ, relayed to the Brain like
+//! WhatsApp.
+//!
+//! Its status is watched like any organ's (see `organs::watch_status`) and
+//! goes to `/telegram/status`. Messages come from a monitor run on every
+//! page load: it watches the chat list, and when a chat's last message
+//! changes while it has unread ones, reports the chat, the text and the
+//! unread count back through `organs::REPORT_HOST`. Chats are only taken
+//! as changed from the second time they render, so loading the list
+//! doesn't replay what was already there. Each message goes through
+//! `relay::message` to `/telegram/message` as a [`TgMessage`],
+//! deduplicated and filtered like WhatsApp's, with an `id` of the chat and
+//! a digest of the text and the organ, account and relay time filled in.
+//!
+//! The main window can relay too, with `tg_relay_message` and
+//! `tg_relay_status`, for a canvas that reads Telegram some other way.
+
+use serde::{Deserialize, Serialize};
+
+use crate::lifecycle::Lifecycle;
+use crate::organs::{clean, now_ms};
+
+pub const ORGAN: &str = "telegram";
+const MESSAGE_PATH: &str = "/telegram/message";
+
+/// Longest message text relayed; the rest is cut.
+const MAX_TEXT_LEN: usize = 2000;
+/// Longest chat name or message id accepted.
+const MAX_NAME_LEN: usize = 256;
+
+/// A message the monitor or `tg_relay_message` reported, as relayed to
+/// `/telegram/message`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TgMessage {
+    /// The chat and a digest of the text, for one seen in the chat list.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub chat: String,
+    pub text: String,
+    /// Unread messages in the chat.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unread: Option<u64>,
+    /// The rest is the relay's, not the page's.
+    #[serde(default)]
+    pub organ: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    /// Unix milliseconds it was relayed.
+    #[serde(default)]
+    pub timestamp: u64,
+}
+
+/// The chat list scan, run by `organs::monitor`.
+const MONITOR: &str = r#"
+  var seen = {}, timer = null;
+  function text(el, selector) {
+    var node = el.querySelector(selector);
+    return node ? node.textContent.trim() : '';
+  }
+  function scan() {
+    document.querySelectorAll('.chat-list .ListItem.Chat').forEach(function (item) {
+      var link = item.querySelector('a.ListItem-button');
+      var peer = link ? (link.getAttribute('href') || '').replace('#', '') : '';
+      var preview = text(item, '.last-message');
+      if (!peer || !preview) return;
+      var unread = parseInt(text(item, '.ChatBadge'), 10) || 0;
+      var before = seen[peer];
+      seen[peer] = preview;
+      if (before === undefined || before === preview || unread === 0) return;
+      report('message', { id: peer + ':' + digest(preview), chat: text(item, '.fullName'), text: preview, unread: unread });
+    });
+  }
+  new MutationObserver(function () {
+    if (!timer) timer = setTimeout(function () { timer = null; scan(); }, 500);
+  }).observe(document.documentElement, { childList: true, subtree: true, characterData: true });
+  scan();"#;
+
+/// The body relayed for a reported message, or why it isn't one.
+fn parse_message(message: TgMessage, account: Option<&str>, now_ms: u64) -> Result<TgMessage, String> {
+    Ok(TgMessage {
+        id: message.id.and_then(|id| clean(&id, MAX_NAME_LEN)),
+        chat: clean(&message.chat, MAX_NAME_LEN).ok_or("a message needs a chat")?,
+        text: clean(&message.text, MAX_TEXT_LEN).ok_or("a message needs a text")?,
+        unread: message.unread,
+        organ: ORGAN.into(),
+        account: account.map(String::from),
+        timestamp: now_ms,
+    })
+}
+
+/// Install the message monitor in `id`'s page, if it is Telegram.
+pub fn watch(app: &tauri::AppHandle, id: &str) {
+    crate::organs::monitor(app, id, ORGAN, "__lexiconMessages", MONITOR);
+}
+
+/// Organ `id`'s monitor reported `raw`, a [`TgMessage`] as JSON.
+pub fn reported(app: &tauri::AppHandle, id: &str, raw: &str) {
+    let account = crate::organs::account(app, id);
+    let message = serde_json::from_str(raw).map_err(|e| e.to_string());
+    match message.and_then(|m| parse_message(m, account.as_deref(), now_ms())) {
+        Ok(message) => {
+            crate::relay::message(app, id, MESSAGE_PATH, &message);
+        }
+        Err(e) => tracing::warn!("organ {id} reported a message that isn't one ({e}) — dropped"),
+    }
+}
+
+/// Relay a Telegram message (`chat`, `text`, and optionally `id` and
+/// `unread`) to the Brain. False if the filters or dedup dropped it.
+#[tauri::command]
+pub fn tg_relay_message(window: tauri::WebviewWindow, app: tauri::AppHandle, message: TgMessage) -> Result<bool, String> {
+    if window.label() != "main" {
+        return Err("only the main window can relay messages".into());
+    }
+    let message = parse_message(message, None, now_ms())?;
+    Ok(crate::relay::message(&app, ORGAN, MESSAGE_PATH, &message))
+}
+
+/// Relay Telegram's connection status, as an organ would report it.
+#[tauri::command]
+pub fn tg_relay_status(window: tauri::WebviewWindow, app: tauri::AppHandle, status: String) -> Result<(), String> {
+    if window.label() != "main" {
+        return Err("only the main window can relay a status".into());
+    }
+    let state = Lifecycle::parse(&status).ok_or_else(|| format!("unknown status: '{status}'"))?;
+    crate::relay::report_state(&app, ORGAN, state);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    fn parse(report: serde_json::Value) -> Result<serde_json::Value, String> {
+        let message = serde_json::from_value(report).map_err(|e| e.to_string())?;
+        Ok(serde_json::to_value(parse_message(message, None, 5)?).unwrap())
+    }
+
+    #[test]
+    fn reported_messages_are_checked_and_cleaned() {
+        let message = json!({
+            "id": "777:hi",
+            "chat": " Ana ",
+            "text": "hi\u{7}\nthere",
+            "unread": 2,
+            "extra": true,
+            "organ": "whatsapp",
+            "timestamp": 1,
+        });
+        assert_eq!(
+            parse(message).unwrap(),
+            json!({ "id": "777:hi", "chat": "Ana", "text": "hi\nthere", "unread": 2, "organ": "telegram", "timestamp": 5 })
+        );
+        assert_eq!(parse(json!({ "chat": "Ana", "text": "hi" })).unwrap().get("id"), None);
+        assert!(parse(json!({ "chat": "Ana", "text": "  " })).is_err());
+        assert!(parse(json!({ "text": "hi" })).is_err());
+        let long = "x".repeat(MAX_TEXT_LEN + 10);
+        let body = parse(json!({ "chat": "Ana", "text": long })).unwrap();
+        assert_eq!(body["text"].as_str().unwrap().len(), MAX_TEXT_LEN);
+    }
+}
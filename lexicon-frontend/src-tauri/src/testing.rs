@@ -0,0 +1,432 @@
+//! Test harness: a [`Host`] without a Tauri app, a scriptable mock
+//! Brain, and generators for property tests. Nothing here opens a window,
+//! so `cargo test` runs headless.
+//!
+//! Every persisted or serialized type derives `proptest_derive::Arbitrary`
+//! under `cfg(test)`, so a new field joins its round-trip property without
+//! further work. Fields whose full range the on-disk format can't hold
+//! take one of the strategies below. Proptest shrinks failures and writes
+//! the minimal case to `proptest-regressions/`; commit those files so the
+//! case is replayed on every run.
+//!
+//! ```ignore
+//! let brain = MockBrain::start();
+//! brain.script("/ingest/file", [Reply::Drop, Reply::json(200, json!({"id": 1}))]);
+//! let host = TestHost::new().with_brain(&brain);
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use proptest::prelude::*;
+use serde::Serialize;
+
+use crate::brain::{BrainClient, BrainTransport};
+use crate::config::Config;
+use crate::dedup::Dedup;
+use crate::filter::Filters;
+use crate::host::Host;
+use crate::metrics::Metrics;
+use crate::ratelimit::RateLimiter;
+use crate::relay::{Announce, Delivery, Messages};
+
+// ── Host ───────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    /// The window it was sent to; `None` for a broadcast.
+    pub window: Option<String>,
+    pub name: String,
+    pub payload: serde_json::Value,
+}
+
+struct Dir(PathBuf);
+
+impl Drop for Dir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Config in memory, data and cache dirs under a scratch directory that
+/// is removed with the last handle, and a log of every emitted event.
+#[derive(Clone)]
+pub struct TestHost {
+    config: Arc<RwLock<Config>>,
+    dir: Arc<Dir>,
+    transport: Arc<dyn BrainTransport>,
+    events: Arc<Mutex<Vec<Event>>>,
+    presenting: Arc<AtomicBool>,
+}
+
+impl TestHost {
+    pub fn new() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "lexicon-test-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&dir).expect("scratch dir");
+        Self {
+            config: Arc::new(RwLock::new(Config::default())),
+            dir: Arc::new(Dir(dir)),
+            transport: BrainClient::default().transport(),
+            events: Arc::new(Mutex::new(Vec::new())),
+            presenting: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Point the config at a mock Brain.
+    pub fn with_brain(self, brain: &MockBrain) -> Self {
+        self.config.write().unwrap().brain.url = brain.url();
+        self
+    }
+
+    /// The same disk state with fresh memory, as after an app restart.
+    pub fn restart(&self) -> Self {
+        Self {
+            config: Arc::new(RwLock::new(self.config())),
+            dir: self.dir.clone(),
+            transport: self.transport.clone(),
+            events: Arc::new(Mutex::new(Vec::new())),
+            presenting: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Engage or release this host's presentation guard.
+    pub fn present(&self, on: bool) {
+        self.presenting.store(on, Ordering::SeqCst);
+    }
+
+    pub fn scratch(&self) -> PathBuf {
+        self.dir.0.clone()
+    }
+
+    pub fn events(&self, name: &str) -> Vec<Event> {
+        self.events.lock().unwrap().iter().filter(|e| e.name == name).cloned().collect()
+    }
+
+    fn record<S: Serialize>(&self, window: Option<&str>, name: &str, payload: S) {
+        let payload = serde_json::to_value(payload).expect("serializable payload");
+        self.events.lock().unwrap().push(Event { window: window.map(String::from), name: name.into(), payload });
+    }
+}
+
+impl Host for TestHost {
+    fn config(&self) -> Config {
+        self.config.read().unwrap().clone()
+    }
+
+    fn data_dir(&self) -> Option<PathBuf> {
+        Some(self.dir.0.join("data"))
+    }
+
+    fn cache_dir(&self) -> Option<PathBuf> {
+        Some(self.dir.0.join("cache"))
+    }
+
+    fn transport(&self) -> Arc<dyn BrainTransport> {
+        self.transport.clone()
+    }
+
+    fn publish<S: Serialize + Clone>(&self, event: &str, payload: S) {
+        self.record(None, event, payload);
+    }
+
+    fn publish_to<S: Serialize + Clone>(&self, window: &str, event: &str, payload: S) {
+        self.record(Some(window), event, payload);
+    }
+
+    fn presenting(&self) -> bool {
+        self.presenting.load(Ordering::SeqCst)
+    }
+}
+
+// ── Message relay ──────────────────────────────────────────────
+
+/// What `relay::Messages` borrows, owned, with delivery to a mock Brain.
+pub struct TestRelay {
+    pub host: TestHost,
+    pub delivery: Delivery,
+    filters: Filters,
+    dedup: Dedup,
+    limiter: RateLimiter,
+    metrics: Metrics,
+}
+
+impl TestRelay {
+    pub fn start(brain: &MockBrain) -> Self {
+        let host = TestHost::new().with_brain(brain);
+        let metrics = Metrics::default();
+        let delivery = Delivery::start(host.clone(), &Default::default(), metrics.clone());
+        Self {
+            host,
+            delivery,
+            filters: Filters::default(),
+            dedup: Dedup::default(),
+            limiter: RateLimiter::new(Default::default()),
+            metrics,
+        }
+    }
+
+    /// The pipeline, telling `announce` of each new message.
+    pub fn messages<'a>(&'a self, announce: Announce<'a>) -> Messages<'a> {
+        Messages {
+            filters: &self.filters,
+            dedup: &self.dedup,
+            limiter: &self.limiter,
+            delivery: &self.delivery,
+            metrics: &self.metrics,
+            announce,
+        }
+    }
+}
+
+// ── Mock Brain ─────────────────────────────────────────────────
+
+#[derive(Debug, Clone)]
+pub enum Reply {
+    Json(u16, serde_json::Value),
+    /// Answer after a pause.
+    Delay(Duration, Box<Reply>),
+    /// Read the request and hang up without answering.
+    Drop,
+}
+
+impl Reply {
+    pub fn json(status: u16, body: serde_json::Value) -> Self {
+        Reply::Json(status, body)
+    }
+
+    pub fn status(status: u16) -> Self {
+        Reply::Json(status, serde_json::json!({ "status": status }))
+    }
+
+    pub fn delay(by: Duration, reply: Reply) -> Self {
+        Reply::Delay(by, Box::new(reply))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    /// Names lowercased, values trimmed.
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+    }
+}
+
+#[derive(Default)]
+struct Script {
+    /// One-shot replies per path, used up in order.
+    queued: HashMap<String, VecDeque<Reply>>,
+    /// What a path answers once its queue is empty.
+    fallback: HashMap<String, Reply>,
+}
+
+struct Shared {
+    script: Mutex<Script>,
+    requests: Mutex<Vec<Request>>,
+    stopped: AtomicBool,
+}
+
+/// An HTTP/1.1 server on a free localhost port. `/health` answers 200
+/// until scripted otherwise; unscripted paths answer 404. Dropping it
+/// (or `stop`) closes the port, which looks like the Brain going away.
+pub struct MockBrain {
+    port: u16,
+    shared: Arc<Shared>,
+}
+
+impl MockBrain {
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind mock brain");
+        let port = listener.local_addr().unwrap().port();
+        let shared = Arc::new(Shared {
+            script: Mutex::new(Script::default()),
+            requests: Mutex::new(Vec::new()),
+            stopped: AtomicBool::new(false),
+        });
+        shared.script.lock().unwrap().fallback.insert("/health".into(), Reply::json(200, serde_json::json!({ "status": "ok" })));
+        let server = shared.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                if server.stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                let Ok(stream) = stream else { continue };
+                let server = server.clone();
+                std::thread::spawn(move || serve(&server, stream));
+            }
+        });
+        Self { port, shared }
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://127.0.0.1:{}", self.port)
+    }
+
+    /// Answer `path` with these replies, one per request, in order.
+    pub fn script(&self, path: &str, replies: impl IntoIterator<Item = Reply>) {
+        self.shared.script.lock().unwrap().queued.entry(path.into()).or_default().extend(replies);
+    }
+
+    /// Answer `path` with `reply` whenever nothing is scripted for it.
+    pub fn always(&self, path: &str, reply: Reply) {
+        self.shared.script.lock().unwrap().fallback.insert(path.into(), reply);
+    }
+
+    /// Requests received so far for `path`.
+    pub fn requests(&self, path: &str) -> Vec<Request> {
+        self.shared.requests.lock().unwrap().iter().filter(|r| r.path == path).cloned().collect()
+    }
+
+    pub fn stop(&self) {
+        if !self.shared.stopped.swap(true, Ordering::SeqCst) {
+            // Wake the accept loop so it sees the flag and drops the listener.
+            let _ = TcpStream::connect(("127.0.0.1", self.port));
+        }
+    }
+}
+
+impl Drop for MockBrain {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn serve(shared: &Shared, stream: TcpStream) {
+    let mut reader = BufReader::new(match stream.try_clone() {
+        Ok(s) => s,
+        Err(_) => return,
+    });
+    let mut line = String::new();
+    if reader.read_line(&mut line).is_err() {
+        return;
+    }
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().split('?').next().unwrap_or_default().to_string();
+
+    let mut length = 0;
+    let mut chunked = false;
+    let mut headers = Vec::new();
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).is_err() || header.trim().is_empty() {
+            break;
+        }
+        let lower = header.to_ascii_lowercase();
+        if let Some(value) = lower.strip_prefix("content-length:") {
+            length = value.trim().parse().unwrap_or(0);
+        }
+        if lower.starts_with("transfer-encoding:") && lower.contains("chunked") {
+            chunked = true;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+    let body = if chunked { read_chunked(&mut reader) } else { read_exact(&mut reader, length) };
+    shared.requests.lock().unwrap().push(Request { method, path: path.clone(), headers, body });
+
+    let reply = {
+        let mut script = shared.script.lock().unwrap();
+        let next = script.queued.get_mut(&path).and_then(VecDeque::pop_front);
+        next.or_else(|| script.fallback.get(&path).cloned()).unwrap_or(Reply::status(404))
+    };
+    respond(stream, reply);
+}
+
+fn read_exact(reader: &mut impl Read, length: usize) -> Vec<u8> {
+    let mut body = vec![0; length];
+    let _ = reader.read_exact(&mut body);
+    body
+}
+
+fn read_chunked(reader: &mut impl BufRead) -> Vec<u8> {
+    let mut body = Vec::new();
+    loop {
+        let mut size = String::new();
+        if reader.read_line(&mut size).is_err() {
+            break;
+        }
+        let size = usize::from_str_radix(size.trim(), 16).unwrap_or(0);
+        body.extend(read_exact(reader, size));
+        let mut crlf = [0; 2];
+        let _ = reader.read_exact(&mut crlf);
+        if size == 0 {
+            break;
+        }
+    }
+    body
+}
+
+fn respond(mut stream: TcpStream, reply: Reply) {
+    match reply {
+        Reply::Drop => {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+        Reply::Delay(by, reply) => {
+            std::thread::sleep(by);
+            respond(stream, *reply);
+        }
+        Reply::Json(status, body) => {
+            let body = body.to_string();
+            let _ = write!(
+                stream,
+                "HTTP/1.1 {status} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+        }
+    }
+}
+
+// ── Generators ─────────────────────────────────────────────────
+
+/// Integers TOML can hold. Larger ones can't come from a config file.
+pub fn toml_u64() -> impl Strategy<Value = u64> {
+    0..=i64::MAX as u64
+}
+
+pub fn toml_usize() -> impl Strategy<Value = usize> {
+    0..=i64::MAX as usize
+}
+
+/// Floats equal to themselves. NaN never survives a comparison.
+pub fn finite_f32() -> impl Strategy<Value = f32> {
+    use proptest::num::f32::*;
+    POSITIVE | NEGATIVE | NORMAL | SUBNORMAL | ZERO
+}
+
+/// Any JSON document, for feeding parsers.
+pub fn json() -> impl Strategy<Value = serde_json::Value> {
+    use serde_json::Value;
+    let leaf = prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::Bool),
+        any::<i64>().prop_map(Value::from),
+        any::<u64>().prop_map(Value::from),
+        (proptest::num::f64::NORMAL | proptest::num::f64::ZERO).prop_map(Value::from),
+        any::<String>().prop_map(Value::String),
+    ];
+    leaf.prop_recursive(4, 64, 8, |inner| {
+        prop_oneof![
+            proptest::collection::vec(inner.clone(), 0..8).prop_map(Value::Array),
+            proptest::collection::btree_map(any::<String>(), inner, 0..8)
+                .prop_map(|map| Value::Object(map.into_iter().collect())),
+        ]
+    })
+}
@@ -0,0 +1,159 @@
+//! Light/dark theme — follows the system unless overridden, and is pushed
+//! to organs that can switch their own.
+//!
+//! The system value comes from the main window (`ThemeChanged`). A
+//! `theme = "dark" | "light"` override in the config wins and is also
+//! applied to Lexicon's own windows. Organs with a `theme_hook` get it
+//! evaluated on every page load and whenever the effective theme changes.
+//! `theme://changed` carries the new [`ThemeState`], and the effective
+//! theme is part of `get_health` so the Brain can adapt its rendering.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Manager};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    Light,
+    Dark,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeOverride {
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ThemeState {
+    pub system: Theme,
+    #[serde(rename = "override")]
+    pub override_: ThemeOverride,
+    pub effective: Theme,
+}
+
+/// Last system theme seen; true for dark.
+static SYSTEM_DARK: AtomicBool = AtomicBool::new(false);
+/// Effective theme as last propagated, for `health::snapshot`.
+static EFFECTIVE_DARK: AtomicBool = AtomicBool::new(false);
+
+fn system() -> Theme {
+    if SYSTEM_DARK.load(Ordering::Relaxed) {
+        Theme::Dark
+    } else {
+        Theme::Light
+    }
+}
+
+fn from_tauri(theme: tauri::Theme) -> Theme {
+    match theme {
+        tauri::Theme::Dark => Theme::Dark,
+        _ => Theme::Light,
+    }
+}
+
+pub fn state(app: &tauri::AppHandle) -> ThemeState {
+    let override_ = crate::config::current(app).theme;
+    let system = system();
+    let effective = match override_ {
+        ThemeOverride::System => system,
+        ThemeOverride::Light => Theme::Light,
+        ThemeOverride::Dark => Theme::Dark,
+    };
+    ThemeState { system, override_, effective }
+}
+
+/// The theme organs and the Brain should use right now.
+pub fn effective(app: &tauri::AppHandle) -> Theme {
+    state(app).effective
+}
+
+/// `effective` as of the last change, without needing the app handle.
+pub fn current() -> Theme {
+    if EFFECTIVE_DARK.load(Ordering::Relaxed) {
+        Theme::Dark
+    } else {
+        Theme::Light
+    }
+}
+
+/// Run an organ's theme hook, if it has one.
+pub fn apply_to_organ(app: &tauri::AppHandle, id: &str) {
+    let manager = app.state::<crate::organs::OrganManager>();
+    let Some(hook) = manager.get(id).and_then(|def| def.theme_hook.as_deref()) else { return };
+    let Some(window) = app.get_webview_window(&crate::organs::window_label(id)) else { return };
+    let theme = serde_json::to_string(&effective(app)).unwrap_or_default();
+    let _ = window.eval(format!("({hook})({theme});"));
+}
+
+/// Push the current state everywhere it's used.
+fn propagate(app: &tauri::AppHandle) {
+    let state = state(app);
+    EFFECTIVE_DARK.store(state.effective == Theme::Dark, Ordering::Relaxed);
+    let ids: Vec<String> = app.state::<crate::organs::OrganManager>().defs().iter().map(|d| d.id.clone()).collect();
+    for id in ids {
+        apply_to_organ(app, &id);
+    }
+    let _ = app.emit("theme://changed", state);
+    crate::health::announce(app);
+}
+
+/// Force Lexicon's own windows to the override, or hand them back to the
+/// system.
+fn apply_override(app: &tauri::AppHandle, override_: ThemeOverride) {
+    app.set_theme(match override_ {
+        ThemeOverride::System => None,
+        ThemeOverride::Light => Some(tauri::Theme::Light),
+        ThemeOverride::Dark => Some(tauri::Theme::Dark),
+    });
+}
+
+pub fn init(app: &tauri::AppHandle) {
+    let Some(main) = app.get_webview_window("main") else { return };
+    if let Ok(theme) = main.theme() {
+        SYSTEM_DARK.store(from_tauri(theme) == Theme::Dark, Ordering::Relaxed);
+    }
+    apply_override(app, crate::config::current(app).theme);
+    EFFECTIVE_DARK.store(effective(app) == Theme::Dark, Ordering::Relaxed);
+
+    let handle = app.clone();
+    main.on_window_event(move |event| {
+        if let tauri::WindowEvent::ThemeChanged(theme) = event {
+            // With an override in place this is our own set_theme echoing
+            // back, not the system.
+            if crate::config::current(&handle).theme != ThemeOverride::System {
+                return;
+            }
+            let dark = from_tauri(*theme) == Theme::Dark;
+            if SYSTEM_DARK.swap(dark, Ordering::Relaxed) != dark {
+                tracing::info!("system theme is now {}", if dark { "dark" } else { "light" });
+                propagate(&handle);
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub fn get_system_theme(app: tauri::AppHandle) -> ThemeState {
+    state(&app)
+}
+
+/// Persist an override ("dark" | "light" | "system") and apply it.
+#[tauri::command]
+pub fn set_theme_override(app: tauri::AppHandle, theme: ThemeOverride) -> Result<ThemeState, String> {
+    crate::config::update(&app, |config| config.theme = theme)?;
+    apply_override(&app, theme);
+    if theme == ThemeOverride::System {
+        if let Some(Ok(current)) = app.get_webview_window("main").map(|w| w.theme()) {
+            SYSTEM_DARK.store(from_tauri(current) == Theme::Dark, Ordering::Relaxed);
+        }
+    }
+    propagate(&app);
+    Ok(state(&app))
+}
@@ -0,0 +1,110 @@
+//! Window transitions — one at a time, and saying where they left off.
+//!
+//! `toggle_window`, `open_organ`, `show_organ` and `close_organ` (and the
+//! tray, shortcuts and other callers of `toggle_main`) each run as one
+//! transition through the [`WindowManager`]. One that comes in while
+//! another is still running is turned away with "transition in
+//! progress", never queued: a button pressed twice in a hurry would
+//! otherwise undo itself once the first press landed. The commands
+//! answer with the [`Screen`] they left behind.
+//!
+//! Every change of screen, from these or from anything else that moves
+//! windows (deep links, notifications, the watchdog), is emitted as
+//! `window://state` with the new [`Screen`]. Those other callers aren't
+//! turned away; `organs` runs their switches one after another.
+
+use std::sync::{Mutex, MutexGuard, TryLockError};
+
+use serde::Serialize;
+use tauri::{Emitter, Manager};
+
+/// What is on screen, as far as the sidebar cares. In the split layout an
+/// organ beside the canvas counts as the organ.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum Screen {
+    MainVisible,
+    OrganVisible { label: String },
+    AllHidden,
+}
+
+#[derive(Default)]
+pub struct WindowManager {
+    busy: Mutex<()>,
+    /// The screen last emitted.
+    shown: Mutex<Option<Screen>>,
+}
+
+impl WindowManager {
+    /// Take the turn, unless a transition has it.
+    fn begin(&self) -> Result<MutexGuard<'_, ()>, String> {
+        match self.busy.try_lock() {
+            Ok(turn) => Ok(turn),
+            Err(TryLockError::Poisoned(e)) => Ok(e.into_inner()),
+            Err(TryLockError::WouldBlock) => Err("transition in progress".into()),
+        }
+    }
+
+    /// Run `change` as a transition and answer with the screen after it.
+    pub fn transition(&self, app: &tauri::AppHandle, change: impl FnOnce() -> Result<(), String>) -> Result<Screen, String> {
+        let turn = self.begin()?;
+        let result = change();
+        drop(turn);
+        let screen = changed(app);
+        result.map(|()| screen)
+    }
+}
+
+pub fn current(app: &tauri::AppHandle) -> Screen {
+    #[cfg(desktop)]
+    if let Some(id) = crate::organs::on_screen(app) {
+        return Screen::OrganVisible { label: crate::organs::window_label(&id) };
+    }
+    match app.get_webview_window("main") {
+        Some(main) if main.is_visible().unwrap_or(false) => Screen::MainVisible,
+        _ => Screen::AllHidden,
+    }
+}
+
+/// Emit `window://state` if the screen isn't the one last emitted, and
+/// return it.
+pub fn changed(app: &tauri::AppHandle) -> Screen {
+    let screen = current(app);
+    let Some(manager) = app.try_state::<WindowManager>() else { return screen };
+    let mut shown = manager.shown.lock().unwrap_or_else(|e| e.into_inner());
+    if shown.as_ref() != Some(&screen) {
+        let _ = app.emit("window://state", &screen);
+        *shown = Some(screen.clone());
+    }
+    screen
+}
+
+/// `transition`, on a blocking thread, for the async commands.
+pub async fn run(
+    app: tauri::AppHandle,
+    change: impl FnOnce(&tauri::AppHandle) -> Result<(), String> + Send + 'static,
+) -> Result<Screen, String> {
+    tauri::async_runtime::spawn_blocking(move || app.state::<WindowManager>().transition(&app, || change(&app)))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_second_transition_is_turned_away() {
+        let manager = WindowManager::default();
+        let turn = manager.begin().unwrap();
+        assert_eq!(manager.begin().unwrap_err(), "transition in progress");
+        drop(turn);
+        assert!(manager.begin().is_ok());
+
+        assert_eq!(
+            serde_json::to_value(Screen::OrganVisible { label: "whatsapp-organ".into() }).unwrap(),
+            serde_json::json!({ "state": "organ_visible", "label": "whatsapp-organ" })
+        );
+        assert_eq!(serde_json::to_value(Screen::AllHidden).unwrap(), serde_json::json!({ "state": "all_hidden" }));
+    }
+}
@@ -0,0 +1,207 @@
+//! System tray — Lexicon's presence while every window is hidden.
+//!
+//! The menu is built once at startup; afterwards individual items are
+//! updated from `dnd://changed` / `organ://changed` events instead of
+//! rebuilding the whole menu on every click. Quit goes through
+//! `shutdown::graceful_exit`, which destroys organ windows first.
+//!
+//! The first item (and the tooltip, when it isn't plain good news) says
+//! whether the Brain is reachable and the control channel open; "Reconnect
+//! to Brain" probes it and reopens the channel right away (see
+//! `brain::reconnect`).
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use tauri::menu::{CheckMenuItem, Menu, MenuEvent, MenuItem, PredefinedMenuItem, Submenu};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIcon, TrayIconBuilder, TrayIconEvent};
+use tauri::{Listener, Manager, Wry};
+
+use crate::brain::{BrainState, BrainStatus, ControlState};
+use crate::organs::{self, OrganManager};
+
+const BRAIN_ID: &str = "brain";
+const RECONNECT_ID: &str = "reconnect-brain";
+const TOGGLE_ID: &str = "toggle";
+const WHATSAPP_ID: &str = "open-whatsapp";
+const DND_ID: &str = "dnd";
+const QUIT_ID: &str = "quit";
+const ORGAN_PREFIX: &str = "organ:";
+
+pub struct TrayState {
+    icon: TrayIcon<Wry>,
+    brain: MenuItem<Wry>,
+    dnd: CheckMenuItem<Wry>,
+    organs: Submenu<Wry>,
+    organ_items: Mutex<HashMap<String, MenuItem<Wry>>>,
+}
+
+/// "Brain — online", or what is wrong with it.
+fn brain_text(status: &BrainStatus) -> String {
+    let state = match (status.state, status.control) {
+        (BrainState::Unknown, _) => "checking…",
+        (BrainState::Offline, _) => "offline",
+        (BrainState::Online, ControlState::Connecting | ControlState::Disconnected) => "online, not listening",
+        (BrainState::Online, _) => "online",
+    };
+    format!("Brain — {state}")
+}
+
+fn organ_item_text(app: &tauri::AppHandle, id: &str, title: &str) -> String {
+    format!("{title} — {}", organs::status(app, id))
+}
+
+/// Build the tray icon and wire up its events. No-op when disabled in config.
+pub fn init(app: &tauri::AppHandle) -> tauri::Result<()> {
+    if !crate::config::current(app).tray.enabled {
+        tracing::info!("tray disabled in config");
+        return Ok(());
+    }
+    if crate::headless::enabled() {
+        return Ok(());
+    }
+
+    let brain = MenuItem::with_id(app, BRAIN_ID, brain_text(&crate::brain::status()), false, None::<&str>)?;
+    let reconnect = MenuItem::with_id(app, RECONNECT_ID, "Reconnect to Brain", true, None::<&str>)?;
+    let toggle = MenuItem::with_id(app, TOGGLE_ID, "Show/Hide Lexicon", true, None::<&str>)?;
+    let whatsapp = MenuItem::with_id(app, WHATSAPP_ID, "Open WhatsApp", true, None::<&str>)?;
+    let organs = Submenu::new(app, "Organs", true)?;
+    let dnd = CheckMenuItem::with_id(app, DND_ID, "Do Not Disturb", true, crate::dnd::is_enabled(), None::<&str>)?;
+    let quit = MenuItem::with_id(app, QUIT_ID, "Quit", true, None::<&str>)?;
+    let menu = Menu::with_items(
+        app,
+        &[
+            &brain,
+            &reconnect,
+            &PredefinedMenuItem::separator(app)?,
+            &toggle,
+            &whatsapp,
+            &organs,
+            &dnd,
+            &PredefinedMenuItem::separator(app)?,
+            &quit,
+        ],
+    )?;
+
+    let mut builder = TrayIconBuilder::with_id("main")
+        .menu(&menu)
+        .show_menu_on_left_click(false)
+        .on_menu_event(on_menu_event)
+        .on_tray_icon_event(|tray, event| {
+            if let TrayIconEvent::Click {
+                button: MouseButton::Left,
+                button_state: MouseButtonState::Up,
+                ..
+            } = event
+            {
+                crate::toggle_main(tray.app_handle());
+            }
+        });
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    let icon = builder.build(app)?;
+
+    app.manage(TrayState {
+        icon,
+        brain,
+        dnd,
+        organs,
+        organ_items: Mutex::new(HashMap::new()),
+    });
+    sync_organs(app);
+    refresh_tooltip(app);
+
+    let handle = app.clone();
+    app.listen("dnd://changed", move |_| {
+        if let Some(state) = handle.try_state::<TrayState>() {
+            let _ = state.dnd.set_checked(crate::dnd::is_enabled());
+        }
+        refresh_tooltip(&handle);
+    });
+    let handle = app.clone();
+    app.listen("organ://changed", move |_| {
+        sync_organs(&handle);
+        refresh_tooltip(&handle);
+    });
+    Ok(())
+}
+
+fn on_menu_event(app: &tauri::AppHandle, event: MenuEvent) {
+    match event.id().as_ref() {
+        RECONNECT_ID => crate::brain::reconnect(app),
+        TOGGLE_ID => crate::toggle_main(app),
+        WHATSAPP_ID => open_organ(app, crate::whatsapp::ORGAN),
+        DND_ID => {
+            crate::dnd::set_dnd(app.clone(), !crate::dnd::is_enabled());
+        }
+        QUIT_ID => crate::shutdown::graceful_exit(app),
+        id => {
+            if let Some(organ) = id.strip_prefix(ORGAN_PREFIX) {
+                open_organ(app, organ);
+            }
+        }
+    }
+}
+
+fn open_organ(app: &tauri::AppHandle, id: &str) {
+    if let Err(e) = organs::open(app, id) {
+        tracing::warn!("tray: {e}");
+    }
+}
+
+/// Bring the organ submenu in line with the registry: update labels of
+/// existing entries, append new organs, drop removed ones.
+fn sync_organs(app: &tauri::AppHandle) {
+    let Some(state) = app.try_state::<TrayState>() else {
+        return;
+    };
+    let manager = app.state::<OrganManager>();
+    let mut items = state.organ_items.lock().unwrap_or_else(|e| e.into_inner());
+
+    items.retain(|id, item| {
+        let keep = manager.get(id).is_some();
+        if !keep {
+            let _ = state.organs.remove(item);
+        }
+        keep
+    });
+    for def in manager.defs() {
+        let text = organ_item_text(app, &def.id, &def.title);
+        if let Some(item) = items.get(&def.id) {
+            let _ = item.set_text(text);
+            continue;
+        }
+        let id = format!("{ORGAN_PREFIX}{}", def.id);
+        if let Ok(item) = MenuItem::with_id(app, id, text, true, None::<&str>) {
+            let _ = state.organs.append(&item);
+            items.insert(def.id.clone(), item);
+        }
+    }
+}
+
+/// Swap the tray icon image.
+pub fn set_icon(app: &tauri::AppHandle, icon: tauri::image::Image<'static>) -> tauri::Result<()> {
+    match app.try_state::<TrayState>() {
+        Some(state) => state.icon.set_icon(Some(icon)),
+        None => Ok(()),
+    }
+}
+
+/// Update the tooltip from the health summary and the Brain's status,
+/// and the Brain item with it. Blank while the presentation guard is on.
+pub fn refresh_tooltip(app: &tauri::AppHandle) {
+    let Some(state) = app.try_state::<TrayState>() else {
+        return;
+    };
+    let brain = crate::brain::status();
+    let _ = state.brain.set_text(brain_text(&brain));
+    let text = if crate::presentation::is_active() {
+        "Lexicon".to_string()
+    } else if brain.state == BrainState::Online && brain.control != ControlState::Disconnected {
+        crate::health::summary(&crate::health::snapshot())
+    } else {
+        format!("{} · {}", crate::health::summary(&crate::health::snapshot()), brain_text(&brain))
+    };
+    let _ = state.icon.set_tooltip(Some(text));
+}
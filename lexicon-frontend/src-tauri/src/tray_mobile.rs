@@ -0,0 +1,12 @@
+//! No system tray on mobile; the calls the rest of the app makes into
+//! `tray` are no-ops here.
+
+pub fn init(_app: &tauri::AppHandle) -> tauri::Result<()> {
+    Ok(())
+}
+
+pub fn set_icon(_app: &tauri::AppHandle, _icon: tauri::image::Image<'static>) -> tauri::Result<()> {
+    Ok(())
+}
+
+pub fn refresh_tooltip(_app: &tauri::AppHandle) {}
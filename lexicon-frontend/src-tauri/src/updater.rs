@@ -0,0 +1,226 @@
+//! Self-update from our own release server.
+//!
+//! `[updater]` lists the manifest endpoints, the release channel and the
+//! minisign public key the bundles are signed with. `{{channel}}` in an
+//! endpoint becomes the channel; the plugin's own `{{target}}`, `{{arch}}`
+//! and `{{current_version}}` work as usual.
+//!
+//! A background check runs every `check_interval_hours` and emits
+//! `update-available` for each new version it finds. It installs on its
+//! own only with `auto_install = true`. `apply_update` downloads and
+//! verifies the bundle first, then winds the app down through
+//! `shutdown::prepare_restart` and only then installs and restarts, so a
+//! failed download or a bad signature leaves everything running.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::Emitter;
+use tauri_plugin_updater::UpdaterExt;
+
+/// Delay before the first background check, so it doesn't compete with boot.
+const FIRST_CHECK: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[serde(rename_all = "lowercase")]
+pub enum Channel {
+    #[default]
+    Stable,
+    Beta,
+}
+
+impl Channel {
+    fn as_str(self) -> &'static str {
+        match self {
+            Channel::Stable => "stable",
+            Channel::Beta => "beta",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+#[serde(default)]
+pub struct UpdaterConfig {
+    /// Release manifest URLs, tried in order.
+    pub endpoints: Vec<String>,
+    pub channel: Channel,
+    /// Contents of the `.pub` file from `tauri signer generate`.
+    pub pubkey: String,
+    /// Hours between background checks; 0 turns them off.
+    #[cfg_attr(test, proptest(strategy = "crate::testing::toml_u64()"))]
+    pub check_interval_hours: u64,
+    /// Install updates found in the background without asking.
+    pub auto_install: bool,
+}
+
+impl Default for UpdaterConfig {
+    fn default() -> Self {
+        Self {
+            endpoints: Vec::new(),
+            channel: Channel::Stable,
+            pubkey: String::new(),
+            check_interval_hours: 6,
+            auto_install: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", content = "message", rename_all = "snake_case")]
+pub enum UpdateError {
+    /// No endpoints or no public key in `[updater]`.
+    NotConfigured,
+    /// The release server could not be reached.
+    Offline(String),
+    /// The bundle's signature did not verify against `pubkey`.
+    Signature(String),
+    /// `apply_update` found nothing newer to install.
+    UpToDate,
+    Guarded,
+    Failed(String),
+}
+
+impl std::fmt::Display for UpdateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UpdateError::NotConfigured => f.write_str("updater has no endpoints or public key configured"),
+            UpdateError::Offline(e) => write!(f, "release server unreachable: {e}"),
+            UpdateError::Signature(e) => write!(f, "update signature rejected: {e}"),
+            UpdateError::UpToDate => f.write_str("already up to date"),
+            UpdateError::Guarded => f.write_str("presentation guard is on"),
+            UpdateError::Failed(e) => write!(f, "update failed: {e}"),
+        }
+    }
+}
+
+impl From<tauri_plugin_updater::Error> for UpdateError {
+    fn from(e: tauri_plugin_updater::Error) -> Self {
+        use tauri_plugin_updater::Error;
+        match e {
+            Error::EmptyEndpoints => UpdateError::NotConfigured,
+            Error::Reqwest(ref r) if r.is_connect() || r.is_timeout() || r.is_request() => {
+                UpdateError::Offline(e.to_string())
+            }
+            Error::Minisign(_)
+            | Error::Base64(_)
+            | Error::SignatureUtf8(_)
+            | Error::SignedVersionMismatch { .. }
+            | Error::MissingSignedVersion => UpdateError::Signature(e.to_string()),
+            _ => UpdateError::Failed(e.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UpdateInfo {
+    pub version: String,
+    pub current_version: String,
+    pub channel: Channel,
+    /// Release notes from the manifest.
+    pub notes: Option<String>,
+    /// `pub_date` as published, RFC 3339.
+    pub date: Option<String>,
+}
+
+/// Last version announced by the background check, so each is emitted once.
+static ANNOUNCED: Mutex<Option<String>> = Mutex::new(None);
+
+fn updater(app: &tauri::AppHandle, config: &UpdaterConfig) -> Result<tauri_plugin_updater::Updater, UpdateError> {
+    if config.endpoints.is_empty() || config.pubkey.trim().is_empty() {
+        return Err(UpdateError::NotConfigured);
+    }
+    let endpoints = config
+        .endpoints
+        .iter()
+        .map(|e| {
+            let url = e.replace("{{channel}}", config.channel.as_str());
+            url.parse().map_err(|err| UpdateError::Failed(format!("bad endpoint {url}: {err}")))
+        })
+        .collect::<Result<Vec<tauri::Url>, _>>()?;
+    Ok(app.updater_builder().endpoints(endpoints)?.pubkey(config.pubkey.trim()).build()?)
+}
+
+async fn find(app: &tauri::AppHandle) -> Result<Option<(tauri_plugin_updater::Update, UpdateInfo)>, UpdateError> {
+    let config = crate::config::current(app).updater;
+    let Some(update) = updater(app, &config)?.check().await? else { return Ok(None) };
+    let info = UpdateInfo {
+        version: update.version.clone(),
+        current_version: update.current_version.clone(),
+        channel: config.channel,
+        notes: update.body.clone(),
+        date: update.raw_json.get("pub_date").and_then(|d| d.as_str()).map(String::from),
+    };
+    Ok(Some((update, info)))
+}
+
+/// Download and verify, wind down, install, restart.
+async fn install(app: &tauri::AppHandle, update: tauri_plugin_updater::Update) -> Result<(), UpdateError> {
+    if crate::presentation::is_active() {
+        return Err(UpdateError::Guarded);
+    }
+    tracing::info!("downloading update {}", update.version);
+    let bytes = update.download(|_, _| {}, || {}).await?;
+    crate::shutdown::prepare_restart(app).map_err(UpdateError::Failed)?;
+    update.install(bytes)?;
+    tracing::info!("update {} installed — restarting", update.version);
+    app.restart();
+}
+
+pub fn init(app: &tauri::AppHandle) {
+    let hours = crate::config::current(app).updater.check_interval_hours;
+    if hours == 0 {
+        return;
+    }
+    let app = app.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(FIRST_CHECK);
+        loop {
+            if !crate::power::is_suspended() {
+                tauri::async_runtime::block_on(background_check(&app));
+            }
+            std::thread::sleep(Duration::from_secs(hours * 3600));
+        }
+    });
+}
+
+async fn background_check(app: &tauri::AppHandle) {
+    let (update, info) = match find(app).await {
+        Ok(Some(found)) => found,
+        Ok(None) | Err(UpdateError::NotConfigured) => return,
+        Err(e) => {
+            tracing::warn!("update check failed: {e}");
+            return;
+        }
+    };
+    let previous = ANNOUNCED.lock().unwrap_or_else(|e| e.into_inner()).replace(info.version.clone());
+    if previous.as_deref() != Some(info.version.as_str()) {
+        tracing::info!("update {} available", info.version);
+        let _ = app.emit("update-available", &info);
+    }
+    if crate::config::current(app).updater.auto_install {
+        if let Err(e) = install(app, update).await {
+            tracing::warn!("automatic update failed: {e}");
+        }
+    }
+}
+
+// ── Commands ───────────────────────────────────────────────────
+
+/// The newer version on the configured channel, if there is one.
+#[tauri::command]
+pub async fn check_for_updates(app: tauri::AppHandle) -> Result<Option<UpdateInfo>, UpdateError> {
+    Ok(find(&app).await?.map(|(_, info)| info))
+}
+
+/// Install the newer version and restart into it. Only returns on error.
+#[tauri::command]
+pub async fn apply_update(window: tauri::WebviewWindow, app: tauri::AppHandle) -> Result<(), UpdateError> {
+    if window.label() != "main" {
+        return Err(UpdateError::Failed("updates can only be applied from the main window".into()));
+    }
+    let (update, _) = find(&app).await?.ok_or(UpdateError::UpToDate)?;
+    install(&app, update).await
+}
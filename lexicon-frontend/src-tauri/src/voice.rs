@@ -0,0 +1,360 @@
+//! Push-to-talk voice capture streamed to the Brain.
+//!
+//! While the `push_to_talk` shortcut is held (or between
+//! `start_voice_capture` and `stop_voice_capture`), the default input
+//! device is recorded, mixed down to 16 kHz mono 16-bit PCM and streamed
+//! to `{BRAIN_URL}/voice/stream` as one chunked POST. With `voice.upload =
+//! "wav"` it is kept until the capture ends instead, and POSTed as one WAV
+//! file to `{BRAIN_URL}/voice/audio`, for a Brain that transcribes whole
+//! utterances. The Brain's reply — the transcription — arrives as
+//! `voice://transcript`; `voice://level` carries an input level between 0
+//! and 1 for a meter while recording.
+//!
+//! A capture always ends: on release, on `stop_voice_capture`, when the
+//! presentation guard comes on, or after `voice.max_seconds`.
+
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use serde::{Deserialize, Serialize};
+use tauri::Emitter;
+
+const TARGET_RATE: f64 = 16_000.0;
+const PCM_TYPE: &str = "audio/L16; rate=16000; channels=1";
+
+/// Minimum gap between two `voice://level` events.
+const LEVEL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+#[serde(default)]
+pub struct VoiceConfig {
+    /// Longest single capture; recording stops on its own after this.
+    #[cfg_attr(test, proptest(strategy = "crate::testing::toml_u64()"))]
+    pub max_seconds: u64,
+    pub upload: VoiceUpload,
+}
+
+impl Default for VoiceConfig {
+    fn default() -> Self {
+        Self { max_seconds: 30, upload: VoiceUpload::default() }
+    }
+}
+
+/// How a capture goes to the Brain.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(proptest_derive::Arbitrary))]
+#[serde(rename_all = "lowercase")]
+pub enum VoiceUpload {
+    /// Raw PCM to `/voice/stream` while recording.
+    #[default]
+    Stream,
+    /// One WAV file to `/voice/audio` once it ends.
+    Wav,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", content = "message", rename_all = "snake_case")]
+pub enum VoiceError {
+    AlreadyActive,
+    Guarded,
+    NoDevice,
+    PermissionDenied(String),
+    Unsupported(String),
+    Device(String),
+}
+
+impl std::fmt::Display for VoiceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VoiceError::AlreadyActive => f.write_str("voice capture is already running"),
+            VoiceError::Guarded => f.write_str("presentation guard is on"),
+            VoiceError::NoDevice => f.write_str("no input device found"),
+            VoiceError::PermissionDenied(e) => write!(f, "microphone access denied: {e}"),
+            VoiceError::Unsupported(e) => write!(f, "input device not supported: {e}"),
+            VoiceError::Device(e) => write!(f, "input device error: {e}"),
+        }
+    }
+}
+
+impl From<cpal::Error> for VoiceError {
+    fn from(e: cpal::Error) -> Self {
+        use cpal::ErrorKind;
+        match e.kind() {
+            ErrorKind::DeviceNotAvailable | ErrorKind::HostUnavailable => VoiceError::NoDevice,
+            ErrorKind::PermissionDenied => VoiceError::PermissionDenied(e.to_string()),
+            ErrorKind::UnsupportedConfig | ErrorKind::UnsupportedOperation => VoiceError::Unsupported(e.to_string()),
+            _ => VoiceError::Device(e.to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+struct Stopped {
+    /// "released" | "max_duration"
+    reason: &'static str,
+}
+
+/// Dropping the sender ends the capture.
+static SESSION: Mutex<Option<mpsc::Sender<()>>> = Mutex::new(None);
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Whether the microphone is being recorded right now.
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::SeqCst)
+}
+
+// ── Sample pipeline ────────────────────────────────────────────
+
+/// Mixes down, resamples to 16 kHz and hands out PCM chunks.
+struct Chunker {
+    app: tauri::AppHandle,
+    channels: usize,
+    /// Input frames per output sample.
+    step: f64,
+    /// Position of the next output sample, in input frames.
+    next: f64,
+    frame: f64,
+    out: mpsc::Sender<Vec<u8>>,
+    last_level: Instant,
+}
+
+impl Chunker {
+    fn push(&mut self, samples: impl Iterator<Item = f32>) {
+        let mut pcm = Vec::new();
+        let mut energy = 0.0f32;
+        let mut frames = 0usize;
+        let mut mix = 0.0f32;
+        for (i, sample) in samples.enumerate() {
+            mix += sample;
+            if (i + 1) % self.channels != 0 {
+                continue;
+            }
+            let mono = (mix / self.channels as f32).clamp(-1.0, 1.0);
+            mix = 0.0;
+            energy += mono * mono;
+            frames += 1;
+            // Nearest-sample decimation is plenty for speech recognition.
+            if self.frame >= self.next {
+                pcm.extend_from_slice(&((mono * i16::MAX as f32) as i16).to_le_bytes());
+                self.next += self.step;
+            }
+            self.frame += 1.0;
+        }
+        if !pcm.is_empty() {
+            let _ = self.out.send(pcm);
+        }
+        if frames > 0 && self.last_level.elapsed() >= LEVEL_INTERVAL {
+            self.last_level = Instant::now();
+            let level = (energy / frames as f32).sqrt().min(1.0);
+            let _ = self.app.emit_to("main", "voice://level", level);
+        }
+    }
+}
+
+fn build<T>(device: &cpal::Device, config: cpal::StreamConfig, mut chunker: Chunker) -> Result<cpal::Stream, cpal::Error>
+where
+    T: cpal::SizedSample,
+    f32: cpal::FromSample<T>,
+{
+    device.build_input_stream::<T, _, _>(
+        config,
+        move |data: &[T], _| chunker.push(data.iter().map(|s| cpal::Sample::to_sample::<f32>(*s))),
+        |e| tracing::warn!("voice capture stream error: {e}"),
+        None,
+    )
+}
+
+/// Feeds the chunked upload from the capture channel; EOF when it closes.
+struct ChannelReader {
+    rx: mpsc::Receiver<Vec<u8>>,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+        while self.pos == self.buf.len() {
+            match self.rx.recv() {
+                Ok(chunk) => {
+                    self.buf = chunk;
+                    self.pos = 0;
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+        let n = out.len().min(self.buf.len() - self.pos);
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// `pcm` (16 kHz mono 16-bit little-endian) as a WAV file.
+fn wav(pcm: &[u8]) -> Vec<u8> {
+    let rate = TARGET_RATE as u32;
+    let len = pcm.len() as u32;
+    let mut file = Vec::with_capacity(44 + pcm.len());
+    file.extend_from_slice(b"RIFF");
+    file.extend_from_slice(&(36 + len).to_le_bytes());
+    file.extend_from_slice(b"WAVEfmt ");
+    file.extend_from_slice(&16u32.to_le_bytes());
+    // PCM, one channel, the rate, bytes per second, bytes per frame, bits.
+    file.extend_from_slice(&1u16.to_le_bytes());
+    file.extend_from_slice(&1u16.to_le_bytes());
+    file.extend_from_slice(&rate.to_le_bytes());
+    file.extend_from_slice(&(rate * 2).to_le_bytes());
+    file.extend_from_slice(&2u16.to_le_bytes());
+    file.extend_from_slice(&16u16.to_le_bytes());
+    file.extend_from_slice(b"data");
+    file.extend_from_slice(&len.to_le_bytes());
+    file.extend_from_slice(pcm);
+    file
+}
+
+fn upload(app: tauri::AppHandle, rx: mpsc::Receiver<Vec<u8>>, max: Duration) {
+    let request = match crate::config::current(&app).voice.upload {
+        VoiceUpload::Stream => {
+            let body = crate::brain::Body::Stream(Box::new(ChannelReader { rx, buf: Vec::new(), pos: 0 }));
+            crate::brain::Request::post("/voice/stream", PCM_TYPE, body)
+        }
+        VoiceUpload::Wav => {
+            // Everything until the capture ends.
+            let pcm: Vec<u8> = rx.iter().flatten().collect();
+            if pcm.is_empty() {
+                let _ = app.emit_to("main", "voice://error", "nothing was recorded");
+                return;
+            }
+            crate::brain::Request::post("/voice/audio", "audio/wav", crate::brain::Body::Bytes(wav(&pcm)))
+        }
+    };
+    let result = crate::brain::call(&app, request.timeout(max + Duration::from_secs(60)));
+    match result {
+        Ok(text) => {
+            let reply = serde_json::from_str(&text).unwrap_or(serde_json::Value::String(text));
+            let _ = app.emit_to("main", "voice://transcript", reply);
+        }
+        Err(e) => {
+            tracing::warn!("voice upload failed: {e}");
+            let _ = app.emit_to("main", "voice://error", e.to_string());
+        }
+    }
+}
+
+/// Capture thread: owns the (non-`Send`) stream until told to stop.
+fn record(app: tauri::AppHandle, stop: mpsc::Receiver<()>, ready: mpsc::SyncSender<Result<(), VoiceError>>) {
+    let setup = || -> Result<(cpal::Stream, mpsc::Receiver<Vec<u8>>), VoiceError> {
+        let device = cpal::default_host().default_input_device().ok_or(VoiceError::NoDevice)?;
+        let supported = device.default_input_config()?;
+        let (tx, rx) = mpsc::channel();
+        let chunker = Chunker {
+            app: app.clone(),
+            channels: supported.channels().max(1) as usize,
+            step: supported.sample_rate() as f64 / TARGET_RATE,
+            next: 0.0,
+            frame: 0.0,
+            out: tx,
+            last_level: Instant::now(),
+        };
+        let config = supported.config();
+        let stream = match supported.sample_format() {
+            cpal::SampleFormat::F32 => build::<f32>(&device, config, chunker),
+            cpal::SampleFormat::I16 => build::<i16>(&device, config, chunker),
+            cpal::SampleFormat::U16 => build::<u16>(&device, config, chunker),
+            cpal::SampleFormat::I32 => build::<i32>(&device, config, chunker),
+            other => return Err(VoiceError::Unsupported(format!("sample format {other}"))),
+        }?;
+        stream.play()?;
+        Ok((stream, rx))
+    };
+
+    let (stream, audio) = match setup() {
+        Ok(started) => started,
+        Err(e) => {
+            let _ = ready.send(Err(e));
+            return;
+        }
+    };
+    let max = Duration::from_secs(crate::config::current(&app).voice.max_seconds.max(1));
+    let uploader = app.clone();
+    std::thread::spawn(move || upload(uploader, audio, max));
+    let _ = ready.send(Ok(()));
+
+    let reason = match stop.recv_timeout(max) {
+        Err(mpsc::RecvTimeoutError::Timeout) => "max_duration",
+        _ => "released",
+    };
+    // Dropping the stream drops the chunker and its sender, ending the upload.
+    drop(stream);
+    SESSION.lock().unwrap_or_else(|e| e.into_inner()).take();
+    ACTIVE.store(false, Ordering::SeqCst);
+    crate::health::announce(&app);
+    crate::tray::refresh_tooltip(&app);
+    tracing::info!("voice capture stopped ({reason})");
+    let _ = app.emit("voice://stopped", Stopped { reason });
+}
+
+// ── Start / stop ───────────────────────────────────────────────
+
+pub fn start(app: &tauri::AppHandle) -> Result<(), VoiceError> {
+    if crate::presentation::is_active() {
+        return Err(VoiceError::Guarded);
+    }
+    let mut session = SESSION.lock().unwrap_or_else(|e| e.into_inner());
+    if session.is_some() {
+        return Err(VoiceError::AlreadyActive);
+    }
+    let (stop_tx, stop_rx) = mpsc::channel();
+    let (ready_tx, ready_rx) = mpsc::sync_channel(1);
+    let handle = app.clone();
+    std::thread::spawn(move || record(handle, stop_rx, ready_tx));
+    ready_rx.recv().unwrap_or_else(|_| Err(VoiceError::Device("capture thread exited".into())))?;
+
+    *session = Some(stop_tx);
+    ACTIVE.store(true, Ordering::SeqCst);
+    drop(session);
+    crate::health::announce(app);
+    crate::tray::refresh_tooltip(app);
+    tracing::info!("voice capture started");
+    let _ = app.emit("voice://started", ());
+    Ok(())
+}
+
+/// End the capture, if one is running. Returns whether one was.
+pub fn stop() -> bool {
+    SESSION.lock().unwrap_or_else(|e| e.into_inner()).take().is_some()
+}
+
+#[tauri::command]
+pub fn start_voice_capture(window: tauri::WebviewWindow, app: tauri::AppHandle) -> Result<(), VoiceError> {
+    if window.label() != "main" {
+        return Err(VoiceError::Device("voice capture can only be started by the main window".into()));
+    }
+    start(&app)
+}
+
+#[tauri::command]
+pub fn stop_voice_capture() -> bool {
+    stop()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recordings_are_wrapped_as_16_khz_mono_wav() {
+        let pcm = [1u8, 0, 255, 127];
+        let file = wav(&pcm);
+        assert_eq!(file.len(), 48);
+        assert_eq!((&file[..4], &file[8..16], &file[36..40]), (&b"RIFF"[..], &b"WAVEfmt "[..], &b"data"[..]));
+        let u16_at = |at: usize| u16::from_le_bytes([file[at], file[at + 1]]);
+        let u32_at = |at: usize| u32::from_le_bytes([file[at], file[at + 1], file[at + 2], file[at + 3]]);
+        assert_eq!((u32_at(4), u16_at(20), u16_at(22), u32_at(24), u16_at(34)), (40, 1, 1, 16_000, 16));
+        assert_eq!((u32_at(40), &file[44..]), (4, &pcm[..]));
+    }
+}
@@ -0,0 +1,222 @@
+//! Watchdog — brings back organs whose page has stopped reporting.
+//!
+//! A watched page (one with a status probe) reports at least every 30
+//! seconds; see `organs::watch_status`. A renderer crash, a hard reload
+//! that lost the injected scripts or a page stuck off its own site all
+//! look the same from here: silence. Once an open organ has been quiet
+//! for `organs.watchdog_secs` (2 minutes by default), the first attempt
+//! runs the page-load scripts again if the window is still on one of the
+//! organ's hosts; otherwise, and for every attempt after, the window is
+//! destroyed and built again — in the background if that is where it
+//! was, so recovery never takes focus.
+//!
+//! An attempt that hasn't brought a report within the same interval is
+//! announced as `{id}://recovery-failed`, and the next one waits twice as
+//! long as the last, up to half an hour. The first report after an
+//! attempt is announced as `{id}://recovered` (`whatsapp://recovered`,
+//! …) and starts the count over. Both go to the main window, with the
+//! attempt number and how it was made.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{Emitter, Manager};
+
+/// Longest wait between attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(30 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum How {
+    /// Run the page-load scripts again.
+    Reinject,
+    /// Destroy the window and build it again.
+    Recreate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    /// Nothing to do.
+    Wait,
+    Recover(How),
+    /// The last attempt brought no report.
+    Failed,
+    /// A report came after an attempt.
+    Recovered,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Recovery {
+    attempt: u32,
+    how: How,
+}
+
+/// One organ's recovery so far.
+#[derive(Debug, Clone)]
+struct Watch {
+    /// When the watchdog first saw the window.
+    since: Instant,
+    attempts: u32,
+    how: How,
+    tried: Option<Instant>,
+    failed: bool,
+}
+
+impl Watch {
+    fn new(now: Instant) -> Self {
+        Self { since: now, attempts: 0, how: How::Reinject, tried: None, failed: false }
+    }
+
+    /// What to do, given when the page last reported (`None` if never)
+    /// and whether it is still on its own site.
+    fn step(&mut self, heard: Option<Instant>, own_page: bool, now: Instant, interval: Duration) -> Step {
+        let Some(tried) = self.tried else {
+            if now.saturating_duration_since(heard.unwrap_or(self.since)) < interval {
+                return Step::Wait;
+            }
+            return self.attempt(if own_page { How::Reinject } else { How::Recreate }, now);
+        };
+        if heard.is_some_and(|heard| heard > tried) {
+            *self = Watch::new(now);
+            return Step::Recovered;
+        }
+        let since_tried = now.saturating_duration_since(tried);
+        if !self.failed && since_tried >= interval {
+            self.failed = true;
+            return Step::Failed;
+        }
+        let backoff = interval.saturating_mul(1 << (self.attempts - 1).min(16)).min(MAX_BACKOFF).max(interval);
+        if since_tried >= backoff {
+            return self.attempt(How::Recreate, now);
+        }
+        Step::Wait
+    }
+
+    fn attempt(&mut self, how: How, now: Instant) -> Step {
+        self.attempts += 1;
+        self.how = how;
+        self.tried = Some(now);
+        self.failed = false;
+        Step::Recover(how)
+    }
+
+    fn recovery(&self) -> Recovery {
+        Recovery { attempt: self.attempts, how: self.how }
+    }
+}
+
+fn recover(app: &tauri::AppHandle, id: &str, how: How) -> Result<(), String> {
+    match how {
+        How::Reinject => {
+            crate::organs::inject(app, id);
+            Ok(())
+        }
+        How::Recreate => {
+            let visible = crate::organs::status(app, id) == "visible";
+            crate::organs::close(app, id)?;
+            if visible {
+                crate::organs::open(app, id).map(|_| ())
+            } else {
+                crate::organs::warm(app, id)
+            }
+        }
+    }
+}
+
+/// One pass over the open organs.
+fn check(app: &tauri::AppHandle, watches: &mut BTreeMap<String, Watch>, interval: Duration) {
+    let manager = app.state::<crate::organs::OrganManager>();
+    let now = Instant::now();
+    for def in manager.defs().iter().filter(|d| d.status_probe.is_some()) {
+        let id = def.id.as_str();
+        // Mid-build (perhaps our own rebuild) or warming up: not yet.
+        if crate::organs::settling(id) {
+            continue;
+        }
+        let Some(window) = app.get_webview_window(&crate::organs::window_label(id)) else {
+            watches.remove(id);
+            continue;
+        };
+        let watch = watches.entry(id.to_string()).or_insert_with(|| Watch::new(now));
+        let own_page = window.url().is_ok_and(|url| def.owns(&url));
+        let step = watch.step(crate::organs::last_heard(id), own_page, now, interval);
+        let recovery = watch.recovery();
+        match step {
+            Step::Wait => {}
+            Step::Recover(how) => {
+                tracing::warn!("organ {id} went quiet — {how:?}, attempt {}", recovery.attempt);
+                if let Err(e) = recover(app, id, how) {
+                    tracing::warn!("organ {id}: recovery failed: {e}");
+                }
+            }
+            Step::Failed => {
+                tracing::warn!("organ {id} still quiet after attempt {}", recovery.attempt);
+                let _ = app.emit_to("main", &format!("{id}://recovery-failed"), recovery);
+            }
+            Step::Recovered => {
+                tracing::info!("organ {id} recovered");
+                let _ = app.emit_to("main", &format!("{id}://recovered"), recovery);
+            }
+        }
+    }
+}
+
+/// Watch the organs for as long as the app runs.
+pub fn start(app: &tauri::AppHandle) {
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let mut watches = BTreeMap::new();
+        loop {
+            let secs = crate::config::current(&app).organs.watchdog_secs;
+            let interval = Duration::from_secs(secs.max(1));
+            // A few checks per interval keep the reaction close to it.
+            std::thread::sleep((interval / 4).clamp(Duration::from_secs(5), Duration::from_secs(30)));
+            if secs == 0 {
+                watches.clear();
+                continue;
+            }
+            check(&app, &mut watches, interval);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERVAL: Duration = Duration::from_secs(120);
+
+    #[test]
+    fn a_quiet_page_is_reinjected_then_rebuilt_with_backoff() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut watch = Watch::new(start);
+        assert_eq!(watch.step(Some(start), true, at(60), INTERVAL), Step::Wait);
+        assert_eq!(watch.step(Some(start), true, at(120), INTERVAL), Step::Recover(How::Reinject));
+        assert_eq!(watch.step(Some(start), true, at(200), INTERVAL), Step::Wait);
+        assert_eq!(watch.step(Some(start), true, at(240), INTERVAL), Step::Failed);
+        assert_eq!(watch.step(Some(start), true, at(245), INTERVAL), Step::Recover(How::Recreate));
+        // The window was rebuilt: no report from it yet.
+        assert_eq!(watch.step(None, true, at(300), INTERVAL), Step::Wait);
+        assert_eq!(watch.step(None, true, at(365), INTERVAL), Step::Failed);
+        assert_eq!(watch.step(None, true, at(400), INTERVAL), Step::Wait);
+        assert_eq!(watch.step(None, true, at(485), INTERVAL), Step::Recover(How::Recreate));
+        assert_eq!(watch.recovery().attempt, 3);
+        assert_eq!(watch.step(None, true, at(605), INTERVAL), Step::Failed);
+        assert_eq!(watch.step(None, true, at(900), INTERVAL), Step::Wait);
+        assert_eq!(watch.step(None, true, at(965), INTERVAL), Step::Recover(How::Recreate));
+    }
+
+    #[test]
+    fn a_report_after_an_attempt_is_a_recovery() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut watch = Watch::new(start);
+        // Off its own site, the window is rebuilt straight away.
+        assert_eq!(watch.step(None, false, at(120), INTERVAL), Step::Recover(How::Recreate));
+        assert_eq!(watch.step(Some(at(130)), true, at(135), INTERVAL), Step::Recovered);
+        assert_eq!(watch.step(Some(at(130)), true, at(140), INTERVAL), Step::Wait);
+        assert_eq!(watch.step(Some(at(130)), true, at(250), INTERVAL), Step::Recover(How::Reinject));
+    }
+}
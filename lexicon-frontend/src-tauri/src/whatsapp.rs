@@ -0,0 +1,933 @@
+//! `whatsapp://send` and `wa.me/<number>` links, opened in the WhatsApp
+//! organ.
+//!
+//! Lexicon only claims the `whatsapp:` scheme with
+//! `deep_links.claim_whatsapp = true` (Linux and Windows register it at
+//! runtime; Android gets `https://wa.me` from the bundle's intent
+//! filter). Browsers turn wa.me into `whatsapp://send`, so that alone
+//! covers clicks on the desktop.
+//!
+//! A chat is opened by pointing the organ at WhatsApp Web's own `/send`
+//! route, which selects the chat and prefills the composer; nothing is
+//! sent. Without a signed-in session the link goes to the system browser
+//! instead, through `external` like any other link, and as a
+//! web.whatsapp.com URL so it can't bounce back to us.
+//!
+//! `wa_send_message` does send. It drives WhatsApp Web's own UI in the
+//! organ, hidden or not: it finds the chat in the list or through the
+//! search box, types the text (newlines as Shift+Enter) and presses
+//! send. The script answers through `organs::ask` once the composer has
+//! emptied, so the caller learns whether the message actually went out.
+//! The organ has to be open and connected already; a page that is loading
+//! or reloading answers `not-ready`, or not at all.
+//!
+//! A second login (or third) is an account in `organs.whatsapp_accounts`:
+//! "work" is the organ `whatsapp-work`, window `whatsapp-work-organ`, with
+//! a profile of its own so the logins stay apart. Its statuses, messages
+//! and media go to the same Brain routes as the first one's, with
+//! `"account": "work"` in the body. `open_whatsapp_organ` and
+//! `close_whatsapp_organ` take the account (none for the first) and
+//! `whatsapp_organ_status` lists them all. Links, sends and the login QR
+//! code are the first login's.
+//!
+//! Incoming messages come from a monitor run on every page load (see
+//! `watch`). In the open chat it reports each message that arrives after
+//! the ones already there, with WhatsApp's own message id and, in a group,
+//! the sender; in the chat list, a chat whose last message changes while
+//! it has unread ones. A chat's first render is taken as what was already
+//! there, so opening it doesn't replay its history. The reports come back
+//! through `organs::REPORT_HOST` as a [`WaMessage`] and go to the Brain at
+//! `/whatsapp/message`, with the organ, account and relay time filled in,
+//! batched to `/whatsapp/messages` when they come in a burst; one that
+//! doesn't parse is dropped. An incoming photo, video or voice note is
+//! reported once WhatsApp Web has it decrypted as a `blob:` URL (within a
+//! minute), and relayed as media (see `media`), its caption with it.
+//!
+//! `organ_action` runs `listUnreadChats` and `markRead` in an account's
+//! page (see `actions`).
+//!
+//! `whatsapp_status_history` returns an account's recent state changes
+//! (see `lifecycle`); each one is also emitted as
+//! `whatsapp://state-changed` (`whatsapp-work://state-changed`).
+
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{Manager, Url};
+
+use crate::organs::{clean, now_ms};
+use crate::transition::Screen;
+
+pub const ORGAN: &str = "whatsapp";
+pub const SCHEME: &str = "whatsapp";
+
+/// Longest prefilled message we pass on.
+const MAX_TEXT_LEN: usize = 2000;
+/// How long a freshly created organ gets to report its session.
+const SESSION_WAIT: Duration = Duration::from_secs(15);
+/// Longest message we send, in characters.
+const MAX_SEND_LEN: usize = 4096;
+/// Longest chat name we search for.
+const MAX_NAME_LEN: usize = 128;
+/// How long the organ gets to confirm a send.
+const SEND_TIMEOUT: Duration = Duration::from_secs(20);
+/// How long a reinjected page has to confirm.
+const REINJECT_TIMEOUT: Duration = Duration::from_secs(5);
+const MESSAGE_PATH: &str = "/whatsapp/message";
+/// Longest message id accepted.
+const MAX_ID_LEN: usize = 256;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatTarget {
+    /// Digits only, country code first.
+    pub phone: String,
+    pub text: Option<String>,
+}
+
+fn clean_phone(raw: &str) -> Option<String> {
+    let phone: String = raw.chars().filter(|c| !matches!(c, '+' | ' ' | '-' | '(' | ')' | '.')).collect();
+    (phone.len() >= 6 && phone.len() <= 15 && phone.chars().all(|c| c.is_ascii_digit())).then_some(phone)
+}
+
+fn clean_text(raw: &str) -> Option<String> {
+    clean(raw, MAX_TEXT_LEN)
+}
+
+impl ChatTarget {
+    pub fn new(phone: &str, text: Option<&str>) -> Result<Self, String> {
+        let phone = clean_phone(phone).ok_or_else(|| format!("not a phone number: '{phone}'"))?;
+        Ok(Self { phone, text: text.and_then(clean_text) })
+    }
+
+    /// `whatsapp://send?phone=…&text=…`, `https://wa.me/<phone>?text=…` or
+    /// `https://api.whatsapp.com/send?phone=…`. None for any other URL.
+    pub fn from_url(url: &Url) -> Option<Result<Self, String>> {
+        let param = |key: &str| url.query_pairs().find(|(k, _)| k == key).map(|(_, v)| v.into_owned());
+        let host = url.host_str().unwrap_or_default();
+        let phone = match (url.scheme(), host) {
+            (SCHEME, "send") => param("phone"),
+            ("https" | "http", "wa.me") => url.path_segments().and_then(|mut s| s.next()).map(String::from),
+            ("https" | "http", "api.whatsapp.com") if url.path().trim_end_matches('/') == "/send" => param("phone"),
+            _ => return None,
+        };
+        let Some(phone) = phone.filter(|p| !p.is_empty()) else {
+            return Some(Err("WhatsApp link has no phone number".into()));
+        };
+        Some(Self::new(&phone, param("text").as_deref()))
+    }
+
+    /// WhatsApp Web's route for this chat.
+    fn web_url(&self) -> Url {
+        let mut url = Url::parse("https://web.whatsapp.com/send").expect("static url");
+        url.query_pairs_mut().append_pair("phone", &self.phone);
+        if let Some(text) = &self.text {
+            url.query_pairs_mut().append_pair("text", text);
+        }
+        url
+    }
+}
+
+/// Whether a launch argument is a WhatsApp link we handle.
+pub fn is_link(arg: &str) -> bool {
+    Url::parse(arg).ok().is_some_and(|url| ChatTarget::from_url(&url).is_some())
+}
+
+/// Who `wa_send_message` writes to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Recipient {
+    /// Digits only, country code first; found through search.
+    Phone(String),
+    /// The chat's title as the chat list shows it.
+    Name(String),
+}
+
+impl Recipient {
+    /// A user JID (`<number>@c.us`, `<number>@s.whatsapp.net`) or a chat
+    /// name. Group JIDs can't be looked up; use the group's name.
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let raw = raw.trim();
+        if let Some((user, server)) = raw.split_once('@') {
+            return match server {
+                "c.us" | "s.whatsapp.net" => {
+                    clean_phone(user).map(Recipient::Phone).ok_or_else(|| format!("not a phone number: '{user}'"))
+                }
+                "g.us" => Err("group JIDs can't be looked up — use the group's name".into()),
+                _ => Err(format!("not a WhatsApp JID: '{raw}'")),
+            };
+        }
+        let name: String = raw.chars().filter(|c| !c.is_control()).collect();
+        match name.trim() {
+            "" => Err("no chat given".into()),
+            name if name.chars().count() > MAX_NAME_LEN => Err("chat name is too long".into()),
+            name => Ok(Recipient::Name(name.to_string())),
+        }
+    }
+}
+
+/// Message text as typed: line breaks kept, other control characters
+/// dropped. Too long is an error rather than a silent cut.
+fn clean_message(raw: &str) -> Result<String, String> {
+    let text: String = raw.chars().filter(|c| *c == '\n' || !c.is_control()).collect();
+    let text = text.trim();
+    if text.is_empty() {
+        return Err("message is empty".into());
+    }
+    if text.chars().count() > MAX_SEND_LEN {
+        return Err(format!("message is longer than {MAX_SEND_LEN} characters"));
+    }
+    Ok(text.to_string())
+}
+
+/// The script that sends `text` to `to` and calls `reply` with `sent:<chat
+/// title>`, `not-ready`, `no-chat`, `no-composer` or `not-sent`.
+fn send_script(to: &Recipient, text: &str, reply: &str) -> String {
+    let (query, by_phone) = match to {
+        Recipient::Phone(phone) => (phone.as_str(), true),
+        Recipient::Name(name) => (name.as_str(), false),
+    };
+    let lines: Vec<&str> = text.split('\n').collect();
+    format!(
+        r#"(function (query, byPhone, lines, reply) {{
+  function $(selector, root) {{ return (root || document).querySelector(selector); }}
+  function wait(test, then, fail, tries) {{
+    var found = test();
+    if (found) return then(found);
+    if (tries <= 0) return fail();
+    setTimeout(function () {{ wait(test, then, fail, tries - 1); }}, 100);
+  }}
+  function press(el, shift) {{
+    ['keydown', 'keyup'].forEach(function (type) {{
+      el.dispatchEvent(new KeyboardEvent(type, {{ key: 'Enter', code: 'Enter', keyCode: 13, shiftKey: shift, bubbles: true, cancelable: true }}));
+    }});
+  }}
+  function choose(el) {{
+    var row = el.closest('[role="listitem"], [role="row"]') || el;
+    ['mousedown', 'mouseup', 'click'].forEach(function (type) {{
+      row.dispatchEvent(new MouseEvent(type, {{ bubbles: true, cancelable: true }}));
+    }});
+  }}
+  function title() {{
+    var t = $('#main header span[title]');
+    return t ? t.getAttribute('title') : '';
+  }}
+  function named() {{
+    var want = query.toLowerCase();
+    return Array.prototype.find.call(document.querySelectorAll('#pane-side span[title]'), function (el) {{
+      return el.getAttribute('title').toLowerCase() === want;
+    }});
+  }}
+  function firstResult() {{ return $('#pane-side [role="listitem"] span[title], #pane-side [role="row"] span[title]'); }}
+  function compose() {{
+    wait(function () {{ return $({composer}); }}, function (box) {{
+      box.focus();
+      lines.forEach(function (line, i) {{
+        if (i > 0) press(box, true);
+        if (line) document.execCommand('insertText', false, line);
+      }});
+      var send = $('[data-icon="send"]');
+      if (send) choose(send); else press(box, false);
+      wait(function () {{ return !box.textContent.trim(); }}, function () {{ reply('sent:' + title()); }}, function () {{ reply('not-sent'); }}, 50);
+    }}, function () {{ reply('no-composer'); }}, 50);
+  }}
+  if (!$('#pane-side')) return reply('not-ready');
+  var listed = !byPhone && named();
+  if (listed) {{ choose(listed); return compose(); }}
+  var search = $('#side [contenteditable="true"]');
+  if (!search) return reply('not-ready');
+  search.focus();
+  document.execCommand('selectAll', false, null);
+  document.execCommand('insertText', false, query);
+  wait(byPhone ? firstResult : named, function (found) {{ choose(found); compose(); }}, function () {{ reply('no-chat'); }}, 30);
+}})({query}, {by_phone}, {lines}, {reply});"#,
+        composer = serde_json::to_string(crate::clipboard::WA_COMPOSER).unwrap_or_default(),
+        query = serde_json::to_string(query).unwrap_or_default(),
+        lines = serde_json::to_string(&lines).unwrap_or_default(),
+    )
+}
+
+/// Send `text` to `to` through the organ. Ok holds the title of the chat
+/// it went to. Blocks until the organ confirms; call it off the main thread.
+pub fn send_message(app: &tauri::AppHandle, to: &Recipient, text: &str) -> Result<String, String> {
+    if crate::idle::suspended(ORGAN) {
+        return Err("the whatsapp organ is suspended — resume it first".into());
+    }
+    if crate::organs::status(app, ORGAN) == "closed" {
+        return Err("the whatsapp organ is closed".into());
+    }
+    if crate::organs::signed_in(ORGAN) == Some(false) {
+        return Err("the whatsapp organ is not signed in".into());
+    }
+    let answer = crate::organs::ask(app, ORGAN, |reply| send_script(to, text, reply), SEND_TIMEOUT)
+        .map_err(|_| "whatsapp did not confirm the send — it may be reloading".to_string())?;
+    let (Recipient::Phone(who) | Recipient::Name(who)) = to;
+    match answer.split_once(':') {
+        Some(("sent", chat)) => Ok(chat.to_string()),
+        _ => Err(match answer.as_str() {
+            "not-ready" => "whatsapp is not connected yet".into(),
+            "no-chat" => format!("no chat found for {who}"),
+            "no-composer" => "the chat has no message box (a channel, or no longer a member?)".into(),
+            "not-sent" => "whatsapp did not send the message".into(),
+            other => format!("unexpected answer from the organ: {other}"),
+        }),
+    }
+}
+
+/// Whether the organ holds a session, creating it in the background and
+/// waiting for its first report if needed. Unknown counts as signed in:
+/// the organ then shows its own login screen.
+fn signed_in(app: &tauri::AppHandle) -> Result<bool, String> {
+    if crate::organs::status(app, ORGAN) == "closed" {
+        crate::organs::preload(app, ORGAN)?;
+        crate::organs::wait_ready(app, ORGAN, SESSION_WAIT)?;
+    } else {
+        // It may have been signed in since the last page load.
+        crate::organs::probe_session(app, ORGAN);
+    }
+    let started = Instant::now();
+    while started.elapsed() < SESSION_WAIT {
+        if let Some(signed_in) = crate::organs::signed_in(ORGAN) {
+            return Ok(signed_in);
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    Ok(true)
+}
+
+fn open_in_browser(app: &tauri::AppHandle, target: &ChatTarget) -> Result<(), String> {
+    crate::external::request(app, target.web_url().as_str(), ORGAN).map(|_| ())
+}
+
+/// Open the chat in the organ, or in the browser when the organ can't.
+/// Blocks while a new organ loads; call it off the main thread.
+pub fn open_chat(app: &tauri::AppHandle, target: &ChatTarget) -> Result<(), String> {
+    match signed_in(app) {
+        Ok(true) => {}
+        Ok(false) => {
+            tracing::info!("whatsapp organ is not signed in — opening the chat in the browser");
+            return open_in_browser(app, target);
+        }
+        Err(e) => {
+            tracing::warn!("whatsapp organ unavailable ({e}) — opening the chat in the browser");
+            return open_in_browser(app, target);
+        }
+    }
+    let window = app
+        .get_webview_window(&crate::organs::window_label(ORGAN))
+        .ok_or("whatsapp organ failed to open")?;
+    window.navigate(target.web_url()).map_err(|e| e.to_string())?;
+    crate::organs::open(app, ORGAN).map(|_| ())
+}
+
+/// Open a chat by phone number, optionally with a prefilled message.
+#[tauri::command]
+pub async fn wa_open_chat(
+    window: tauri::WebviewWindow,
+    app: tauri::AppHandle,
+    phone: String,
+    text: Option<String>,
+) -> Result<(), String> {
+    if window.label() != "main" {
+        return Err("only the main window can open chats".into());
+    }
+    if crate::presentation::is_active() {
+        return Err("presentation guard is on".into());
+    }
+    let target = ChatTarget::new(&phone, text.as_deref())?;
+    tauri::async_runtime::spawn_blocking(move || open_chat(&app, &target))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Send a message to a chat, by JID or by name, through the organ.
+/// Returns the title of the chat it went to once WhatsApp has taken it.
+#[tauri::command]
+pub async fn wa_send_message(
+    window: tauri::WebviewWindow,
+    app: tauri::AppHandle,
+    chat_id: String,
+    text: String,
+) -> Result<String, String> {
+    if window.label() != "main" {
+        return Err("only the main window can send messages".into());
+    }
+    tauri::async_runtime::spawn_blocking(move || send(&app, &chat_id, &text)).await.map_err(|e| e.to_string())?
+}
+
+/// `wa_send_message` without the window check, for the Brain's control
+/// channel. Blocks until the organ confirms.
+pub fn send(app: &tauri::AppHandle, chat_id: &str, text: &str) -> Result<String, String> {
+    if crate::presentation::is_active() {
+        return Err("presentation guard is on".into());
+    }
+    let to = Recipient::parse(chat_id)?;
+    let text = clean_message(text)?;
+    send_message(app, &to, &text)
+}
+
+/// Run the page-load scripts in the organ again (see `organs::inject`),
+/// for when it has stopped reporting. True once the page confirms its
+/// status watch is in; false if the page is not on WhatsApp Web or
+/// doesn't answer.
+#[tauri::command]
+pub async fn wa_reinject(app: tauri::AppHandle) -> Result<bool, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        if crate::organs::status(&app, ORGAN) == "closed" {
+            return Err("WhatsApp is not open".to_string());
+        }
+        if !crate::organs::inject(&app, ORGAN) {
+            return Ok(false);
+        }
+        let check = |reply: &str| format!("({reply})(window.__lexiconStatus ? 'yes' : 'no');");
+        match crate::organs::ask(&app, ORGAN, check, REINJECT_TIMEOUT) {
+            Ok(answer) => Ok(answer == "yes"),
+            Err(e) => {
+                tracing::warn!("reinject: {e}");
+                Ok(false)
+            }
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+// ── Messages ───────────────────────────────────────────────────
+
+/// A message the monitor reported, as relayed to `/whatsapp/message`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WaMessage {
+    /// WhatsApp's message id, or for one seen in the chat list the chat
+    /// and a digest of the text.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub chat: String,
+    /// Who wrote it, in a group.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    pub text: String,
+    /// Unread messages in the chat, for one seen in the chat list.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unread: Option<u64>,
+    /// The rest is the relay's, not the page's.
+    #[serde(default)]
+    pub organ: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    /// Unix milliseconds it was relayed.
+    #[serde(default)]
+    pub timestamp: u64,
+}
+
+/// The open chat and chat list scans, run by `organs::monitor`.
+const MONITOR: &str = r#"
+  var seen = {}, chats = {}, previews = {}, wanted = {}, timer = null;
+  function plain(node) {
+    var out = '';
+    node.childNodes.forEach(function (child) {
+      if (child.nodeType === 3) out += child.nodeValue;
+      else if (child.nodeName === 'BR') out += '\n';
+      else if (child.nodeName === 'IMG') out += child.getAttribute('alt') || '';
+      else out += plain(child);
+    });
+    return out;
+  }
+  function blob(row) {
+    var media = row.querySelector('img[src^="blob:"], video[src^="blob:"], audio[src^="blob:"]');
+    return media ? media.getAttribute('src') : '';
+  }
+  function scanChat() {
+    var header = document.querySelector('#main header span[title]');
+    var chat = header ? header.getAttribute('title') : '';
+    if (!chat) return;
+    var rows = Array.prototype.slice.call(document.querySelectorAll('#main [data-id]'));
+    var first = !chats[chat], last = -1;
+    chats[chat] = true;
+    rows.forEach(function (row, i) { if (seen[row.getAttribute('data-id')]) last = i; });
+    rows.forEach(function (row, i) {
+      var id = row.getAttribute('data-id');
+      var body = row.querySelector('span.selectable-text');
+      var media = row.querySelector('img[src^="blob:"], img[src^="data:image"], video, audio, [data-icon="audio-play"]');
+      if (seen[id] || !(body || media)) return;
+      seen[id] = true;
+      if (first || i < last || id.indexOf('false_') !== 0) return;
+      var pre = row.querySelector('[data-pre-plain-text]');
+      var from = pre ? pre.getAttribute('data-pre-plain-text').replace(/^\[[^\]]*\]\s*/, '').replace(/:\s*$/, '') : '';
+      var caption = body ? plain(body).slice(0, 5000) : undefined;
+      if (media) wanted[id] = { chat: chat, from: from || undefined, caption: caption, since: Date.now() };
+      else report('message', { id: id, chat: chat, from: from || undefined, text: caption });
+    });
+  }
+  function scanMedia() {
+    Object.keys(wanted).forEach(function (id) {
+      var want = wanted[id];
+      var row = document.querySelector('#main [data-id="' + CSS.escape(id) + '"]');
+      var url = row ? blob(row) : '';
+      if (!url && Date.now() - want.since < 60000) return;
+      delete wanted[id];
+      if (!url) return;
+      fetch(url).then(function (r) { return r.blob(); }).then(function (file) {
+        report('media', { id: id, chat: want.chat, from: want.from, caption: want.caption, mime: file.type, url: url });
+      }).catch(function () {});
+    });
+  }
+  function scanList() {
+    document.querySelectorAll('#pane-side [role="listitem"], #pane-side [role="row"]').forEach(function (row) {
+      var titles = row.querySelectorAll('span[title]');
+      var chat = titles[0] ? titles[0].getAttribute('title') : '';
+      var preview = titles[1] ? titles[1].getAttribute('title') : '';
+      if (!chat || !preview) return;
+      var badge = row.querySelector('span[aria-label*="unread" i]');
+      var unread = badge ? parseInt(badge.textContent, 10) || 1 : 0;
+      var before = previews[chat];
+      previews[chat] = preview;
+      if (before === undefined || before === preview || unread === 0) return;
+      report('message', { id: chat + ':' + digest(preview), chat: chat, text: preview, unread: unread });
+    });
+  }
+  new MutationObserver(function () {
+    if (!timer) timer = setTimeout(function () { timer = null; scanChat(); scanMedia(); scanList(); }, 500);
+  }).observe(document.documentElement, { childList: true, subtree: true, characterData: true, attributeFilter: ['src'] });
+  scanChat();
+  scanList();"#;
+
+/// The message relayed for a report from `account`'s page, or why it
+/// isn't one.
+fn parse_message(raw: &str, account: Option<&str>, now_ms: u64) -> Result<WaMessage, String> {
+    let message: WaMessage = serde_json::from_str(raw).map_err(|e| e.to_string())?;
+    let chat = clean(&message.chat, MAX_NAME_LEN).ok_or("a message needs a chat")?;
+    let text = clean(&message.text, MAX_SEND_LEN).ok_or("a message needs a text")?;
+    Ok(WaMessage {
+        id: message.id.and_then(|id| clean(&id, MAX_ID_LEN)),
+        chat,
+        from: message.from.and_then(|from| clean(&from, MAX_NAME_LEN)),
+        text,
+        unread: message.unread,
+        organ: ORGAN.into(),
+        account: account.map(String::from),
+        timestamp: now_ms,
+    })
+}
+
+/// Install the message monitor in `id`'s page, if it is a WhatsApp account.
+pub fn watch(app: &tauri::AppHandle, id: &str) {
+    crate::organs::monitor(app, id, ORGAN, "__lexiconMessages", MONITOR);
+}
+
+/// Relay `raw`, reported by organ `id` of `account`, through `relay`.
+/// False if it isn't a message or went nowhere.
+fn relay_report(
+    host: &impl crate::host::Host,
+    relay: &crate::relay::Messages,
+    id: &str,
+    account: Option<&str>,
+    raw: &str,
+    now_ms: u64,
+) -> bool {
+    match parse_message(raw, account, now_ms) {
+        Ok(message) => relay.relay(host, id, MESSAGE_PATH, &message),
+        Err(e) => {
+            tracing::warn!("organ {id} reported a message that isn't one ({e}) — dropped");
+            false
+        }
+    }
+}
+
+/// Organ `id`'s monitor reported `raw`, a [`WaMessage`] as JSON.
+pub fn reported(app: &tauri::AppHandle, id: &str, raw: &str) {
+    let account = crate::organs::account(app, id);
+    if relay_report(app, &crate::relay::Messages::of(app), id, account.as_deref(), raw, now_ms()) {
+        crate::relay::message_relayed(app, id);
+    }
+}
+
+// ── Actions ────────────────────────────────────────────────────
+
+/// Most chats `listUnreadChats` lists, and how many unless asked.
+const MAX_UNREAD: u64 = 100;
+const DEFAULT_UNREAD: u64 = 50;
+
+/// What `organ_action` may run in a WhatsApp organ; see `actions`.
+pub const ACTIONS: crate::actions::Actions = crate::actions::Actions {
+    service: ORGAN,
+    script: ACTIONS_SCRIPT,
+    allowed: &[
+        // `{ limit }` → `[{ chat, unread }]`, as far down the chat list as
+        // WhatsApp has rendered it.
+        crate::actions::Action { name: "listUnreadChats", args: list_unread_args },
+        // `{ chat }` (its name) → `{ chat }`, once opening it has cleared
+        // its unread count.
+        crate::actions::Action { name: "markRead", args: mark_read_args },
+    ],
+};
+
+fn list_unread_args(args: &serde_json::Value) -> Result<serde_json::Value, String> {
+    let limit = match args.get("limit") {
+        None | Some(serde_json::Value::Null) => DEFAULT_UNREAD,
+        Some(limit) => limit.as_u64().filter(|n| *n > 0).ok_or("limit is a count of chats")?.min(MAX_UNREAD),
+    };
+    Ok(serde_json::json!({ "limit": limit }))
+}
+
+fn mark_read_args(args: &serde_json::Value) -> Result<serde_json::Value, String> {
+    let chat = args.get("chat").and_then(|chat| chat.as_str()).ok_or("markRead takes a chat")?;
+    match Recipient::parse(chat)? {
+        Recipient::Name(name) => Ok(serde_json::json!({ "chat": name })),
+        Recipient::Phone(_) => Err("markRead takes the chat's name as the chat list shows it".into()),
+    }
+}
+
+const ACTIONS_SCRIPT: &str = r#"(function () {
+  function rows() {
+    return Array.prototype.slice.call(document.querySelectorAll('#pane-side [role="listitem"], #pane-side [role="row"]'));
+  }
+  function title(row) {
+    var t = row.querySelector('span[title]');
+    return t ? t.getAttribute('title') : '';
+  }
+  function unread(row) {
+    var badge = row.querySelector('span[aria-label*="unread" i]');
+    return badge ? parseInt(badge.textContent, 10) || 1 : 0;
+  }
+  function ready() {
+    if (!document.querySelector('#pane-side')) throw new Error('not connected yet');
+  }
+  function choose(el) {
+    ['mousedown', 'mouseup', 'click'].forEach(function (type) {
+      el.dispatchEvent(new MouseEvent(type, { bubbles: true, cancelable: true }));
+    });
+  }
+  window.__lexiconActions = Object.assign(window.__lexiconActions || {}, {
+    listUnreadChats: function (args) {
+      ready();
+      return rows()
+        .map(function (row) { return { chat: title(row), unread: unread(row) }; })
+        .filter(function (chat) { return chat.chat && chat.unread > 0; })
+        .slice(0, args.limit);
+    },
+    markRead: function (args) {
+      ready();
+      var want = args.chat.toLowerCase();
+      var row = rows().find(function (row) { return title(row).toLowerCase() === want; });
+      if (!row) throw new Error('no chat named ' + args.chat);
+      choose(row);
+      return new Promise(function (resolve, reject) {
+        var tries = 50;
+        (function check() {
+          var open = document.querySelector('#main header span[title]');
+          if (open && open.getAttribute('title').toLowerCase() === want && !unread(row)) {
+            return resolve({ chat: open.getAttribute('title') });
+          }
+          if (--tries <= 0) return reject(new Error('the chat opened but is still unread'));
+          setTimeout(check, 100);
+        })();
+      });
+    }
+  });
+})();"#;
+
+// ── Accounts ───────────────────────────────────────────────────
+
+/// The organ for WhatsApp `account`; `None` is the first login, `whatsapp`.
+pub fn organ_id(account: Option<&str>) -> String {
+    match account {
+        Some(account) => format!("{ORGAN}-{account}"),
+        None => ORGAN.into(),
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountState {
+    /// `None` for the first login.
+    pub account: Option<String>,
+    pub id: String,
+    #[serde(flatten)]
+    pub state: crate::organs::OrganState,
+}
+
+/// Open the organ for `account` (see `organs.whatsapp_accounts`), as
+/// `open_organ` would.
+#[tauri::command]
+pub async fn open_whatsapp_organ(app: tauri::AppHandle, account: Option<String>) -> Result<Screen, String> {
+    crate::organs::open_organ(app, organ_id(account.as_deref())).await
+}
+
+/// Close the organ for `account` only.
+#[tauri::command]
+pub async fn close_whatsapp_organ(app: tauri::AppHandle, account: Option<String>) -> Result<Screen, String> {
+    crate::organs::close_organ(app, organ_id(account.as_deref())).await
+}
+
+/// Every WhatsApp account's organ, the first login first.
+#[tauri::command]
+pub fn whatsapp_organ_status(app: tauri::AppHandle) -> Result<Vec<AccountState>, String> {
+    let manager = app.state::<crate::organs::OrganManager>();
+    manager
+        .defs()
+        .iter()
+        .filter(|def| def.service() == ORGAN)
+        .map(|def| {
+            let state = crate::organs::state(&app, &def.id)?;
+            Ok(AccountState { account: def.account.clone(), id: def.id.clone(), state })
+        })
+        .collect()
+}
+
+/// How the organ for `account` moved between states this session, the
+/// last 100 transitions, oldest first; see `lifecycle`.
+#[tauri::command]
+pub fn whatsapp_status_history(account: Option<String>) -> Vec<crate::lifecycle::Transition> {
+    crate::lifecycle::history(&organ_id(account.as_deref()))
+}
+
+/// Forget the WhatsApp Web session so the next open shows a fresh QR
+/// code. The organ has to be closed first.
+///
+/// Where the session lives differs by engine:
+/// - WebKitGTK (Linux): cookies, localStorage, IndexedDB and service
+///   workers all sit in the organ's data directory,
+///   `organs/whatsapp` under the app data dir, which is deleted.
+/// - WebView2 (Windows): the same directory is the user data folder. The
+///   browser process can hold it for a moment after the window goes;
+///   if the delete fails, try again shortly.
+/// - WKWebView (macOS): there is no directory. The session is in a data
+///   store keyed by a fixed id, which is removed through the system.
+#[tauri::command]
+pub async fn clear_whatsapp_session(app: tauri::AppHandle) -> Result<(), String> {
+    crate::organs::clear_session(&app, ORGAN).await
+}
+
+/// Warm the organ up at launch if `organs.autostart_whatsapp` is set.
+pub fn autostart(app: &tauri::AppHandle) {
+    if crate::headless::enabled() {
+        return crate::headless::start_organs(app);
+    }
+    if !crate::config::current(app).organs.autostart_whatsapp {
+        return;
+    }
+    match crate::organs::warm(app, ORGAN) {
+        Ok(()) => tracing::info!("starting the whatsapp organ in the background"),
+        Err(e) => tracing::warn!("could not start the whatsapp organ: {e}"),
+    }
+}
+
+/// Claim or release the `whatsapp:` scheme to match the config.
+pub fn init(app: &tauri::AppHandle) {
+    #[cfg(any(target_os = "linux", windows))]
+    {
+        use tauri_plugin_deep_link::DeepLinkExt;
+
+        let claim = crate::config::current(app).deep_links.claim_whatsapp;
+        let result = if claim {
+            app.deep_link().register(SCHEME)
+        } else if app.deep_link().is_registered(SCHEME).unwrap_or(false) {
+            app.deep_link().unregister(SCHEME)
+        } else {
+            Ok(())
+        };
+        if let Err(e) = result {
+            tracing::warn!("could not update the {SCHEME}:// handler: {e}");
+        }
+    }
+    #[cfg(not(any(target_os = "linux", windows)))]
+    let _ = app;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(raw: &str) -> Option<Result<ChatTarget, String>> {
+        ChatTarget::from_url(&Url::parse(raw).unwrap())
+    }
+
+    #[test]
+    fn parses_scheme_and_web_links() {
+        let expected = ChatTarget { phone: "4915112345678".into(), text: Some("hi there".into()) };
+        assert_eq!(parse("whatsapp://send?phone=%2B4915112345678&text=hi%20there"), Some(Ok(expected.clone())));
+        assert_eq!(parse("https://wa.me/4915112345678?text=hi+there"), Some(Ok(expected.clone())));
+        assert_eq!(parse("https://api.whatsapp.com/send/?phone=4915112345678&text=hi%20there"), Some(Ok(expected)));
+        assert_eq!(parse("https://wa.me/15551234567").unwrap().unwrap().text, None);
+    }
+
+    #[test]
+    fn rejects_bad_numbers_and_ignores_other_urls() {
+        assert!(matches!(parse("https://wa.me/"), Some(Err(_))));
+        assert!(matches!(parse("whatsapp://send?phone=abc"), Some(Err(_))));
+        assert!(matches!(parse("https://wa.me/1234567890123456"), Some(Err(_))));
+        assert_eq!(parse("https://example.com/4915112345678"), None);
+        assert_eq!(parse("lexicon://toggle"), None);
+    }
+
+    #[test]
+    fn reported_messages_survive_the_round_trip() {
+        for text in ["say \"hi\" to 'Ana'", r"C:\Users\ana \n not a newline", "🎉 ok 👍🏽", "first line\nsecond line\n\nfourth"] {
+            let raw = serde_json::json!({ "id": "false_491@c.us_3EB0", "chat": "Ana \"A\" \\ B", "text": text }).to_string();
+            let message = parse_message(&raw, Some("work"), 5).unwrap();
+            assert_eq!((message.text.as_str(), message.chat.as_str()), (text, "Ana \"A\" \\ B"));
+            let body = serde_json::to_value(&message).unwrap();
+            assert_eq!(body["text"], text);
+            assert_eq!((&body["organ"], &body["account"], &body["timestamp"]), (&"whatsapp".into(), &"work".into(), &5.into()));
+            assert_eq!(serde_json::from_value::<WaMessage>(body).unwrap(), message);
+        }
+        // The page can't choose the organ or the time.
+        let raw = r#"{"chat":" Ana ","text":"hi\u0007\n","from":"","organ":"telegram","timestamp":1}"#;
+        let message = parse_message(raw, None, 5).unwrap();
+        assert_eq!((message.chat.as_str(), message.text.as_str(), message.from.as_deref()), ("Ana", "hi", None));
+        assert_eq!((message.organ.as_str(), message.account.as_deref(), message.timestamp), (ORGAN, None, 5));
+        assert_eq!(serde_json::to_value(&message).unwrap().get("account"), None);
+    }
+
+    #[test]
+    fn malformed_reports_are_rejected() {
+        for raw in ["", "not json", "[]", r#"{"text":"hi"}"#, r#"{"chat":"Ana"}"#, r#"{"chat":"Ana","text":7}"#, r#"{"chat":"Ana","text":"  "}"#] {
+            assert!(parse_message(raw, None, 5).is_err(), "{raw}");
+        }
+    }
+
+    /// What the Brain was sent at `MESSAGE_PATH`, alone or batched, oldest
+    /// first.
+    fn relayed(brain: &crate::testing::MockBrain) -> Vec<serde_json::Value> {
+        let mut bodies = Vec::new();
+        for request in brain.requests(MESSAGE_PATH).into_iter().chain(brain.requests("/whatsapp/messages")) {
+            match serde_json::from_slice(&request.body).unwrap() {
+                serde_json::Value::Array(batch) => bodies.extend(batch),
+                body => bodies.push(body),
+            }
+        }
+        bodies.sort_by_key(|body| body["timestamp"].as_u64());
+        bodies
+    }
+
+    #[test]
+    fn a_message_relays_once_and_again_when_edited() {
+        use crate::testing::{MockBrain, Reply, TestRelay};
+        let brain = MockBrain::start();
+        brain.always(MESSAGE_PATH, Reply::status(200));
+        brain.always("/whatsapp/messages", Reply::status(200));
+        let test = TestRelay::start(&brain);
+        let relay = test.messages(Box::new(|_, _| ()));
+        let host = &test.host;
+        let report = |text: &str| serde_json::json!({ "id": "false_491@c.us_3EB0", "chat": "Ana", "text": text }).to_string();
+
+        assert!(relay_report(host, &relay, ORGAN, None, &report("see you at 5"), 1));
+        // Rendered again a second later: the same message.
+        assert!(!relay_report(host, &relay, ORGAN, None, &report("see you at 5"), 2));
+        assert!(relay_report(host, &relay, ORGAN, None, &report("see you at 6"), 3));
+        assert!(!relay_report(host, &relay, ORGAN, None, "{}", 4));
+        test.delivery.shutdown(Duration::from_secs(5));
+
+        // Both came within the batch window, so they went out together.
+        assert_eq!(brain.requests("/whatsapp/messages").len(), 1);
+        let bodies = relayed(&brain);
+        assert_eq!(bodies.len(), 2);
+        assert_eq!((&bodies[0]["text"], &bodies[0]["timestamp"], bodies[0].get("edited")), (&"see you at 5".into(), &1.into(), None));
+        assert_eq!((&bodies[1]["text"], &bodies[1]["edited"]), (&"see you at 6".into(), &true.into()));
+        assert!(bodies[1]["seq"].as_u64() > bodies[0]["seq"].as_u64());
+        // The main window heard of both, as WaMessages with the relay's seq.
+        let events = host.events("whatsapp://message");
+        assert!(events.iter().all(|e| e.window.as_deref() == Some("main") && e.payload["path"] == MESSAGE_PATH));
+        let heard: Vec<_> = events
+            .iter()
+            .map(|e| {
+                let message: WaMessage = serde_json::from_value(e.payload["message"].clone()).unwrap();
+                (e.payload["seq"].as_u64(), e.payload["edited"].as_bool(), message.text)
+            })
+            .collect();
+        let sent = |n: usize, edited: bool| (bodies[n]["seq"].as_u64(), Some(edited), bodies[n]["text"].as_str().unwrap().into());
+        assert_eq!(heard, [sent(0, false), sent(1, true)]);
+    }
+
+    #[test]
+    fn presenting_keeps_messages_off_the_screen_but_not_from_the_brain() {
+        use crate::testing::{MockBrain, Reply, TestRelay};
+        let brain = MockBrain::start();
+        brain.always(MESSAGE_PATH, Reply::status(200));
+        brain.always("/whatsapp/messages", Reply::status(200));
+        let test = TestRelay::start(&brain);
+        let relay = test.messages(Box::new(|_, _| ()));
+        test.host.present(true);
+        let report = serde_json::json!({ "id": "false_491@c.us_3EB2", "chat": "Ana", "text": "the figures" }).to_string();
+
+        assert!(relay_report(&test.host, &relay, ORGAN, None, &report, 1));
+        test.delivery.shutdown(Duration::from_secs(5));
+
+        assert_eq!(relayed(&brain).len(), 1);
+        assert!(test.host.events("whatsapp://message").is_empty());
+    }
+
+    #[test]
+    fn a_new_message_is_announced_once() {
+        use crate::testing::{MockBrain, Reply, TestRelay};
+        let brain = MockBrain::start();
+        brain.always(MESSAGE_PATH, Reply::status(200));
+        let test = TestRelay::start(&brain);
+        let announced = std::cell::RefCell::new(Vec::new());
+        let relay = test.messages(Box::new(|organ, body| announced.borrow_mut().push((organ.to_string(), body.clone()))));
+        let host = &test.host;
+        let report = |text: &str| {
+            serde_json::json!({ "id": "false_491@g.us_3EB1", "chat": "Family", "from": "Ana", "text": text }).to_string()
+        };
+
+        relay_report(host, &relay, "whatsapp-work", Some("work"), &report("dinner at eight?"), 1);
+        relay_report(host, &relay, "whatsapp-work", Some("work"), &report("dinner at eight?"), 2);
+        relay_report(host, &relay, "whatsapp-work", Some("work"), &report("dinner at nine?"), 3);
+        test.delivery.shutdown(Duration::from_secs(5));
+
+        // Neither the repeat nor the edit is news; what `notify` reads is there.
+        drop(relay);
+        let announced = announced.into_inner();
+        assert_eq!(announced.len(), 1);
+        let (organ, body) = &announced[0];
+        assert_eq!(organ, "whatsapp-work");
+        assert_eq!((&body["chat"], &body["from"], &body["text"]), (&"Family".into(), &"Ana".into(), &"dinner at eight?".into()));
+        assert_eq!(body["account"], "work");
+    }
+
+    #[test]
+    fn web_url_keeps_the_prefill() {
+        let target = ChatTarget::new("+49 151 1234", Some("a & b")).unwrap();
+        assert_eq!(target.web_url().as_str(), "https://web.whatsapp.com/send?phone=491511234&text=a+%26+b");
+    }
+
+    #[test]
+    fn recipients_are_jids_or_names() {
+        assert_eq!(Recipient::parse("4915112345678@c.us"), Ok(Recipient::Phone("4915112345678".into())));
+        assert_eq!(Recipient::parse("4915112345678@s.whatsapp.net"), Ok(Recipient::Phone("4915112345678".into())));
+        assert_eq!(Recipient::parse("  Mum \u{7}"), Ok(Recipient::Name("Mum".into())));
+        assert!(Recipient::parse("12345-678@g.us").unwrap_err().contains("group"));
+        assert!(Recipient::parse("abc@c.us").is_err());
+        assert!(Recipient::parse("someone@example.com").is_err());
+        assert!(Recipient::parse(" ").is_err());
+        assert!(Recipient::parse(&"x".repeat(MAX_NAME_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn messages_keep_their_lines_and_reject_overlong_text() {
+        assert_eq!(clean_message(" hi\r\nthere\n\nbye\u{0} ").unwrap(), "hi\nthere\n\nbye");
+        assert!(clean_message(" \n ").is_err());
+        assert!(clean_message(&"é".repeat(MAX_SEND_LEN)).is_ok());
+        assert!(clean_message(&"é".repeat(MAX_SEND_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn send_script_embeds_text_as_literals() {
+        let to = Recipient::Name("O'Brien \"Bob\"".into());
+        let script = send_script(&to, "line one\n</script> \"two\" 🎉", "reply");
+        assert!(script.contains(r#"})("O'Brien \"Bob\"", false, ["line one","</script> \"two\" 🎉"], reply);"#), "{script}");
+        let script = send_script(&Recipient::Phone("4915112345678".into()), "hi", "reply");
+        assert!(script.ends_with(r#"})("4915112345678", true, ["hi"], reply);"#));
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn any_chat_link_parses_or_is_rejected(
+            base in "(whatsapp://send|https://wa\\.me/|https://api\\.whatsapp\\.com/send)",
+            rest in "[ -~]{0,40}",
+        ) {
+            if let Ok(url) = Url::parse(&format!("{base}{rest}")) {
+                if let Some(Ok(target)) = ChatTarget::from_url(&url) {
+                    let _ = target.web_url();
+                }
+            }
+        }
+    }
+}
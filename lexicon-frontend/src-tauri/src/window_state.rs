@@ -0,0 +1,305 @@
+//! Window state — which window was on screen, so a relaunch comes back
+//! to it.
+//!
+//! `window-state.json` in the app data dir records the window on screen
+//! (the canvas, an organ, or nothing), whether it was fullscreen, which
+//! organs had a window, the canvas's last windowed geometry and each
+//! organ's as an ordinary window (see `layout`), and the layout and
+//! display mode. It is rewritten after every switch and toggle and once
+//! more on exit. The file is read at setup, before anything can overwrite
+//! it. Organs in the background are rebuilt from it right away, hidden,
+//! so relaying picks up before the canvas is even up; the window on
+//! screen comes back once the canvas has booted. A login launch
+//! (`--hidden`) only brings the organs back in the background; it never
+//! puts a window on screen.
+//!
+//! Each geometry keeps the name of the monitor it was on. One whose
+//! monitor is no longer connected is passed over, so a window last seen
+//! on an unplugged display opens where the system puts it rather than off
+//! screen. An organ built as an ordinary window starts at its geometry.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use tauri::Manager;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+pub struct Geometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+#[serde(default)]
+pub struct WindowState {
+    /// Label of the window on screen; `None` when everything was hidden.
+    pub visible: Option<String>,
+    pub fullscreen: bool,
+    /// Organs that had a window, on screen or in the background.
+    pub organs: Vec<String>,
+    /// The canvas's last position and size outside fullscreen.
+    pub geometry: Option<Geometry>,
+    /// The monitor `geometry` was on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub monitor: Option<String>,
+    /// Organs' last position and size as ordinary windows, by id.
+    pub organ_geometry: BTreeMap<String, Placement>,
+    pub layout: crate::layout::Layout,
+    pub display: crate::layout::DisplayMode,
+}
+
+/// A window's geometry and the monitor it was on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, proptest_derive::Arbitrary))]
+pub struct Placement {
+    #[serde(flatten)]
+    pub geometry: Geometry,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monitor: Option<String>,
+}
+
+impl Placement {
+    fn of(window: &tauri::WebviewWindow) -> Option<Self> {
+        let (pos, size) = (window.outer_position().ok()?, window.inner_size().ok()?);
+        Some(Self {
+            geometry: Geometry { x: pos.x, y: pos.y, width: size.width, height: size.height },
+            monitor: window.current_monitor().ok().flatten().and_then(|m| m.name().cloned()),
+        })
+    }
+
+    /// The geometry, unless its monitor isn't connected any more.
+    fn on_screen(&self, app: &tauri::AppHandle) -> Option<Geometry> {
+        let Some(name) = &self.monitor else { return Some(self.geometry) };
+        let monitors = app.available_monitors().unwrap_or_default();
+        let connected = monitors.is_empty() || monitors.iter().any(|m| m.name() == Some(name));
+        connected.then_some(self.geometry)
+    }
+}
+
+/// What setup read, waiting for `restore`.
+static PENDING: Mutex<Option<WindowState>> = Mutex::new(None);
+
+/// The last geometry seen, kept across saves while the canvas is
+/// fullscreen or hidden.
+static GEOMETRY: Mutex<Option<Placement>> = Mutex::new(None);
+
+/// Organs' geometry, as they were last moved or resized as ordinary windows.
+static ORGAN_GEOMETRY: Mutex<BTreeMap<String, Placement>> = Mutex::new(BTreeMap::new());
+
+fn path(app: &tauri::AppHandle) -> Option<PathBuf> {
+    app.path().app_data_dir().ok().map(|dir| dir.join("window-state.json"))
+}
+
+/// A file that doesn't parse is treated as absent.
+fn parse(text: &str) -> WindowState {
+    serde_json::from_str(text).unwrap_or_else(|e| {
+        tracing::warn!("window state unreadable ({e}) — starting fresh");
+        WindowState::default()
+    })
+}
+
+/// Read the saved state for `restore`.
+pub fn init(app: &tauri::AppHandle) {
+    let Some(text) = path(app).and_then(|p| std::fs::read_to_string(p).ok()) else { return };
+    let state = parse(&text);
+    crate::layout::restore(state.layout, state.display);
+    if let Some(main) = app.get_webview_window("main") {
+        crate::layout::dress(&main);
+    }
+    *GEOMETRY.lock().unwrap_or_else(|e| e.into_inner()) =
+        state.geometry.map(|geometry| Placement { geometry, monitor: state.monitor.clone() });
+    *ORGAN_GEOMETRY.lock().unwrap_or_else(|e| e.into_inner()) = state.organ_geometry.clone();
+    *PENDING.lock().unwrap_or_else(|e| e.into_inner()) = Some(state);
+}
+
+fn capture(app: &tauri::AppHandle) -> WindowState {
+    let manager = app.state::<crate::organs::OrganManager>();
+    let mut state =
+        WindowState { layout: crate::layout::current(), display: crate::layout::display(), ..WindowState::default() };
+    for def in manager.defs() {
+        let Some(window) = app.get_webview_window(&crate::organs::window_label(&def.id)) else {
+            // Suspended for now, but open as far as the next launch goes.
+            if crate::idle::suspended(&def.id) {
+                state.organs.push(def.id.clone());
+            }
+            continue;
+        };
+        state.organs.push(def.id.clone());
+        if crate::organs::status(app, &def.id) == "visible" {
+            state.visible = Some(window.label().to_string());
+            state.fullscreen = window.is_fullscreen().unwrap_or(false);
+        }
+    }
+
+    let mut geometry = GEOMETRY.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(main) = app.get_webview_window("main") {
+        if main.is_visible().unwrap_or(false) {
+            state.visible = Some("main".into());
+            state.fullscreen = main.is_fullscreen().unwrap_or(false);
+            // A split pane isn't the canvas's own geometry.
+            let paned = state.layout == crate::layout::Layout::Split
+                && state.display == crate::layout::DisplayMode::Overlay;
+            if !state.fullscreen && !paned {
+                if let Some(placement) = Placement::of(&main) {
+                    *geometry = Some(placement);
+                }
+            }
+        }
+    }
+    state.geometry = geometry.as_ref().map(|p| p.geometry);
+    state.monitor = geometry.as_ref().and_then(|p| p.monitor.clone());
+    state.organ_geometry = ORGAN_GEOMETRY.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    state
+}
+
+/// The canvas's last geometry outside fullscreen, if one was seen on a
+/// monitor that is still connected.
+pub fn geometry(app: &tauri::AppHandle) -> Option<Geometry> {
+    GEOMETRY.lock().unwrap_or_else(|e| e.into_inner()).as_ref()?.on_screen(app)
+}
+
+/// Move organ window `window` back to where it was last left as an
+/// ordinary window, if anywhere still on screen. A maximized one stays.
+pub fn put_back(window: &tauri::WebviewWindow) {
+    let Some(id) = crate::organs::id_from_label(window.label()) else { return };
+    let saved = ORGAN_GEOMETRY.lock().unwrap_or_else(|e| e.into_inner()).get(id).cloned();
+    let Some(g) = saved.and_then(|p| p.on_screen(window.app_handle())) else { return };
+    if !window.is_maximized().unwrap_or(false) {
+        let _ = window.set_position(tauri::PhysicalPosition::new(g.x, g.y));
+        let _ = window.set_size(tauri::PhysicalSize::new(g.width, g.height));
+    }
+}
+
+/// Organ `id`'s window moved or was resized: keep where it is if it is an
+/// ordinary window, for the next time it is shown.
+pub fn remember(app: &tauri::AppHandle, id: &str) {
+    let label = crate::organs::window_label(id);
+    if crate::headless::enabled() || !crate::layout::ordinary(app, &label) {
+        return;
+    }
+    let Some(window) = app.get_webview_window(&label) else { return };
+    let settled = window.is_visible().unwrap_or(false)
+        && !window.is_fullscreen().unwrap_or(false)
+        && !window.is_maximized().unwrap_or(false)
+        && !window.is_minimized().unwrap_or(false);
+    if let Some(placement) = Placement::of(&window).filter(|_| settled) {
+        ORGAN_GEOMETRY.lock().unwrap_or_else(|e| e.into_inner()).insert(id.to_string(), placement);
+    }
+}
+
+/// Record the windows as they are now. Problems are logged; losing the
+/// state only costs the next launch its restore.
+pub fn save(app: &tauri::AppHandle) {
+    let Some(path) = path(app) else { return };
+    let state = capture(app);
+    let result = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|()| std::fs::write(&path, serde_json::to_vec_pretty(&state).unwrap_or_default()));
+    if let Err(e) = result {
+        tracing::warn!("could not save window state to {}: {e}", path.display());
+    }
+}
+
+/// Rebuild, hidden, the organs `init` read that were in the background.
+/// The one on screen is left to `restore`.
+pub fn reopen(app: &tauri::AppHandle) {
+    let mut pending = PENDING.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(state) = pending.as_mut() else { return };
+    let front = state.visible.clone();
+    let (on_screen, background): (Vec<_>, Vec<_>) =
+        state.organs.drain(..).partition(|id| front.as_deref() == Some(crate::organs::window_label(id).as_str()));
+    state.organs = on_screen;
+    drop(pending);
+    for id in background {
+        match crate::organs::preload(app, &id) {
+            Ok(()) => tracing::info!("organ {id} reopened in the background"),
+            Err(e) => tracing::warn!("could not reopen organ {id}: {e}"),
+        }
+    }
+}
+
+/// Put back what `init` read, once. With `show` false, only organs are
+/// rebuilt, hidden.
+pub fn restore(app: &tauri::AppHandle, show: bool) {
+    let Some(state) = PENDING.lock().unwrap_or_else(|e| e.into_inner()).take() else { return };
+    let main = app.get_webview_window("main");
+    if let (Some(main), Some(g)) = (&main, geometry(app)) {
+        let _ = main.set_position(tauri::PhysicalPosition::new(g.x, g.y));
+        let _ = main.set_size(tauri::PhysicalSize::new(g.width, g.height));
+    }
+    let front = state.visible.filter(|_| show && !crate::presentation::is_active());
+    for id in &state.organs {
+        let on_screen = front.as_deref() == Some(crate::organs::window_label(id).as_str());
+        let result = if on_screen { crate::organs::open(app, id).map(|_| ()) } else { crate::organs::preload(app, id) };
+        if let Err(e) = result {
+            tracing::warn!("could not restore organ {id}: {e}");
+        }
+    }
+    if front.as_deref() == Some("main") {
+        if let Some(main) = &main {
+            crate::show_window(main);
+            if !state.fullscreen {
+                let _ = main.set_fullscreen(false);
+            }
+        }
+    }
+    tracing::info!("window state restored");
+}
+
+#[tauri::command]
+pub fn reset_window_state(window: tauri::WebviewWindow, app: tauri::AppHandle) -> Result<(), String> {
+    if window.label() != "main" {
+        return Err("only the main window can reset the window state".into());
+    }
+    PENDING.lock().unwrap_or_else(|e| e.into_inner()).take();
+    GEOMETRY.lock().unwrap_or_else(|e| e.into_inner()).take();
+    ORGAN_GEOMETRY.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    let Some(path) = path(&app) else { return Ok(()) };
+    match std::fs::remove_file(&path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(format!("remove {}: {e}", path.display())),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn window_states_round_trip(state in any::<WindowState>()) {
+            let text = serde_json::to_string(&state).unwrap();
+            prop_assert_eq!(parse(&text), state);
+        }
+    }
+
+    #[test]
+    fn a_corrupt_file_is_ignored() {
+        assert_eq!(parse("{\"visible\": 3"), WindowState::default());
+        assert_eq!(parse(""), WindowState::default());
+    }
+
+    #[test]
+    fn missing_fields_take_defaults() {
+        let state = parse(r#"{ "visible": "whatsapp-organ" }"#);
+        assert_eq!(state.visible.as_deref(), Some("whatsapp-organ"));
+        assert!(state.organs.is_empty() && state.geometry.is_none());
+    }
+
+    #[test]
+    fn placements_keep_their_monitor_when_known() {
+        let state = parse(r#"{ "organ_geometry": { "whatsapp": { "x": -3840, "y": 0, "width": 1280, "height": 2000 } } }"#);
+        let placement = &state.organ_geometry["whatsapp"];
+        assert_eq!((placement.geometry.x, placement.monitor.as_deref()), (-3840, None));
+        let text = serde_json::to_string(&Placement { monitor: Some("DP-2".into()), ..placement.clone() }).unwrap();
+        assert_eq!(text, r#"{"x":-3840,"y":0,"width":1280,"height":2000,"monitor":"DP-2"}"#);
+    }
+}
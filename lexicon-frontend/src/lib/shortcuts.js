@@ -0,0 +1,27 @@
+/**
+ * Window-scoped keyboard shortcuts.
+ * The map is owned by Rust (get_shortcuts); this only matches key events
+ * against it and formats hints for the dock.
+ */
+
+/** The window-scoped shortcut a keydown event triggers, if any. */
+export function match(shortcuts, e) {
+  for (var i = 0; i < shortcuts.length; i++) {
+    var s = shortcuts[i];
+    var c = s.chord;
+    if (s.scope !== 'window' || !c) continue;
+    if (e.code === c.code && e.ctrlKey === c.ctrl && e.altKey === c.alt &&
+        e.shiftKey === c.shift && e.metaKey === c.meta) {
+      return s;
+    }
+  }
+  return null;
+}
+
+/** Keys bound to an action, for hints; '' when unbound or unknown. */
+export function keysFor(shortcuts, action) {
+  for (var i = 0; i < shortcuts.length; i++) {
+    if (shortcuts[i].action === action) return shortcuts[i].keys;
+  }
+  return '';
+}